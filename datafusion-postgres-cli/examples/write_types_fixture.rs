@@ -0,0 +1,278 @@
+//! Writes the Arrow file served as the `types` table by the integration
+//! tests. Its columns carry `pg_type` hints to exercise postgres types with
+//! no direct arrow counterpart.
+//!
+//! Usage: `cargo run --example write_types_fixture -- <path>`
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    ArrayRef, BinaryArray, Decimal128Array, Decimal128Builder, FixedSizeBinaryArray,
+    FixedSizeBinaryBuilder, Int32Array, Int8Array, ListBuilder, MapBuilder, NullArray, StringArray,
+    StringBuilder, StringDictionaryBuilder, TimestampNanosecondArray, UInt32Array,
+};
+use datafusion::arrow::datatypes::{DataType, Field, Int32Type, Schema, TimeUnit};
+use datafusion::arrow::ipc::writer::FileWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion_postgres::PG_TYPE_METADATA_KEY;
+
+fn hinted_field(name: &str, data_type: DataType, pg_type: &str) -> Field {
+    Field::new(name, data_type, true).with_metadata(HashMap::from([(
+        PG_TYPE_METADATA_KEY.to_owned(),
+        pg_type.to_owned(),
+    )]))
+}
+
+fn extension_field(name: &str, data_type: DataType, extension: &str) -> Field {
+    Field::new(name, data_type, true).with_metadata(HashMap::from([(
+        "ARROW:extension:name".to_owned(),
+        extension.to_owned(),
+    )]))
+}
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .expect("Usage: write_types_fixture <path>");
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        hinted_field("price", DataType::Decimal128(10, 2), "money"),
+        hinted_field("grade", DataType::Int8, "char"),
+        Field::new("token", DataType::FixedSizeBinary(16), true),
+        Field::new_list(
+            "tokens",
+            Field::new_list_field(DataType::FixedSizeBinary(2), true),
+            true,
+        ),
+        Field::new(
+            "created",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            true,
+        ),
+        extension_field("uid", DataType::FixedSizeBinary(16), "arrow.uuid"),
+        extension_field("doc", DataType::Utf8, "arrow.json"),
+        Field::new_list(
+            "uids",
+            extension_field("item", DataType::FixedSizeBinary(16), "arrow.uuid"),
+            true,
+        ),
+        hinted_field("flags", DataType::Binary, "varbit"),
+        hinted_field("typname", DataType::Utf8, "name"),
+        hinted_field("typoid", DataType::Int32, "oid"),
+        hinted_field("typ", DataType::UInt32, "regtype"),
+        hinted_field("ip", DataType::Utf8, "inet"),
+        hinted_field("net", DataType::Utf8, "cidr"),
+        hinted_field("xdoc", DataType::Utf8, "xml"),
+        hinted_field("done", DataType::Null, "void"),
+        Field::new_list(
+            "tags",
+            Field::new_list_field(
+                DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+                true,
+            ),
+            true,
+        ),
+        Field::new_map(
+            "attrs",
+            "entries",
+            Field::new("keys", DataType::Utf8, false),
+            Field::new("values", DataType::Utf8, true),
+            false,
+            true,
+        ),
+        Field::new_list(
+            "amounts",
+            Field::new_list_field(DataType::Decimal128(12, 3), true),
+            true,
+        ),
+        hinted_field("code", DataType::Utf8, "bpchar(10)"),
+        hinted_field("rel", DataType::Int32, "regclass"),
+        hinted_field("func", DataType::UInt32, "regproc"),
+    ]));
+
+    let mut tokens = ListBuilder::new(FixedSizeBinaryBuilder::new(2));
+    tokens.values().append_value([0xca, 0xfe]).unwrap();
+    tokens.values().append_null();
+    tokens.append(true);
+    tokens.append(true);
+    tokens.append(false);
+    tokens.values().append_value([0x00, 0xff]).unwrap();
+    tokens.append(true);
+
+    let mut uids = ListBuilder::new(FixedSizeBinaryBuilder::new(16)).with_field(extension_field(
+        "item",
+        DataType::FixedSizeBinary(16),
+        "arrow.uuid",
+    ));
+    uids.values()
+        .append_value((0u8..16).collect::<Vec<_>>())
+        .unwrap();
+    uids.values().append_null();
+    uids.append(true);
+    uids.append(false);
+    uids.append(true);
+    uids.values().append_value([0xff; 16]).unwrap();
+    uids.append(true);
+
+    let mut tags = ListBuilder::new(StringDictionaryBuilder::<Int32Type>::new());
+    tags.values().append_value("red");
+    tags.values().append_value("blue");
+    tags.values().append_value("red");
+    tags.append(true);
+    tags.values().append_null();
+    tags.values().append_value("a \"quoted\" tag");
+    tags.append(true);
+    tags.append(false);
+    tags.append(true);
+
+    let mut attrs = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+    attrs.keys().append_value("b");
+    attrs.values().append_null();
+    attrs.keys().append_value("a");
+    attrs.values().append_value("1");
+    attrs.append(true).unwrap();
+    attrs.append(true).unwrap();
+    attrs.append(false).unwrap();
+    attrs.keys().append_value("q\"x");
+    attrs.values().append_value("back\\slash");
+    attrs.keys().append_value("a");
+    attrs.values().append_value("2");
+    attrs.keys().append_value("a");
+    attrs.values().append_value("3");
+    attrs.append(true).unwrap();
+
+    let mut amounts =
+        ListBuilder::new(Decimal128Builder::new().with_data_type(DataType::Decimal128(12, 3)));
+    amounts.values().append_value(1500);
+    amounts.values().append_null();
+    amounts.values().append_value(-12_345_678);
+    amounts.append(true);
+    amounts.append(true);
+    amounts.append(false);
+    amounts.values().append_value(0);
+    amounts.values().append_value(100_000_001);
+    amounts.append(true);
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(vec![1, 2, 3, 4])),
+        Arc::new(
+            Decimal128Array::from(vec![Some(123456), Some(-123450), None, Some(1)])
+                .with_precision_and_scale(10, 2)
+                .unwrap(),
+        ),
+        Arc::new(Int8Array::from(vec![
+            Some(b'A' as i8),
+            Some(b'B' as i8),
+            None,
+            Some(200u8 as i8),
+        ])),
+        Arc::new(
+            FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                vec![
+                    Some((0u8..16).collect::<Vec<_>>()),
+                    Some(vec![0xff; 16]),
+                    None,
+                    Some(vec![0; 16]),
+                ]
+                .into_iter(),
+                16,
+            )
+            .unwrap(),
+        ),
+        Arc::new(tokens.finish()),
+        // 2024-01-02 03:04:05.123456789, then the same truncated to microseconds
+        Arc::new(TimestampNanosecondArray::from(vec![
+            Some(1_704_164_645_123_456_789),
+            Some(1_704_164_645_123_456_000),
+            None,
+            Some(0),
+        ])),
+        Arc::new(
+            FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                vec![
+                    Some((0u8..16).collect::<Vec<_>>()),
+                    None,
+                    Some(vec![0xff; 16]),
+                    Some(vec![0; 16]),
+                ]
+                .into_iter(),
+                16,
+            )
+            .unwrap(),
+        ),
+        Arc::new(StringArray::from(vec![
+            Some(r#"{"a": [1, 2]}"#),
+            Some("null"),
+            None,
+            Some(r#""x""#),
+        ])),
+        Arc::new(uids.finish()),
+        Arc::new(BinaryArray::from_opt_vec(vec![
+            Some(&[0b1011_0000]),
+            Some(&[0xff, 0x00]),
+            None,
+            Some(&[]),
+        ])),
+        Arc::new(StringArray::from(vec![
+            Some("int4"),
+            Some("varchar"),
+            None,
+            Some("_int4"),
+        ])),
+        Arc::new(Int32Array::from(vec![Some(23), Some(1043), None, Some(-1)])),
+        Arc::new(UInt32Array::from(vec![
+            Some(23),
+            Some(1043),
+            None,
+            Some(1007),
+        ])),
+        Arc::new(StringArray::from(vec![
+            Some("192.168.0.1"),
+            Some("2001:db8::1:0:0:1/64"),
+            None,
+            Some("::ffff:1.2.3.4"),
+        ])),
+        Arc::new(StringArray::from(vec![
+            Some("10.0.0.0/8"),
+            Some("2001:db8::/32"),
+            None,
+            Some("1.2.3.4"),
+        ])),
+        Arc::new(StringArray::from(vec![
+            Some(r#"<a href="x">1 &amp; 2</a>"#),
+            Some("<?xml version=\"1.0\"?><b/>"),
+            None,
+            Some("text <c>&#233;</c>"),
+        ])),
+        Arc::new(NullArray::new(4)),
+        Arc::new(tags.finish()),
+        Arc::new(attrs.finish()),
+        Arc::new(amounts.finish()),
+        Arc::new(StringArray::from(vec![
+            Some("ab"),
+            Some("héllo"),
+            None,
+            Some(""),
+        ])),
+        Arc::new(Int32Array::from(vec![
+            Some(1259),
+            Some(16384),
+            None,
+            Some(0),
+        ])),
+        Arc::new(UInt32Array::from(vec![
+            Some(0),
+            Some(1242),
+            None,
+            Some(16500),
+        ])),
+    ];
+
+    let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+    let mut writer = FileWriter::try_new(File::create(&path).unwrap(), &schema).unwrap();
+    writer.write(&batch).unwrap();
+    writer.finish().unwrap();
+}
@@ -1,11 +1,15 @@
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
+use datafusion::arrow::datatypes::DataType;
 use datafusion::execution::options::{
     ArrowReadOptions, AvroReadOptions, CsvReadOptions, NdJsonReadOptions, ParquetReadOptions,
 };
 use datafusion::prelude::SessionContext;
-use datafusion_postgres::{DfSessionService, HandlerFactory};
-use pgwire::tokio::process_socket;
+use datafusion_postgres::{
+    serve, unsupported_types, DfSessionService, HandlerFactory, QueryStats, ServerOptions,
+};
 use structopt::StructOpt;
 use tokio::net::TcpListener;
 
@@ -36,6 +40,111 @@ struct Opt {
     /// Host address the server listens to, default to 127.0.0.1
     #[structopt(long("host"), default_value = "127.0.0.1")]
     host: String,
+    /// Always send integer and float columns as text, for clients that
+    /// mishandle their binary encoding
+    #[structopt(long("text-numbers"))]
+    text_numbers: bool,
+    /// Rename repeated column names in results with `_1`, `_2`... suffixes
+    #[structopt(long("unique-column-names"))]
+    unique_column_names: bool,
+    /// Fail on nanosecond timestamps that would be truncated to microseconds
+    #[structopt(long("strict-timestamps"))]
+    strict_timestamps: bool,
+    /// Convert parameters sent as text or varchar to the type inferred from
+    /// the query
+    #[structopt(long("inferred-parameter-types"))]
+    inferred_parameter_types: bool,
+    /// Reject xml parameters that aren't well-formed
+    #[structopt(long("validate-xml"))]
+    validate_xml: bool,
+    /// Send maps of strings to strings as hstore values, with this oid of the
+    /// hstore type
+    #[structopt(long("hstore-oid"))]
+    hstore_oid: Option<u32>,
+    /// Fail to execute statements bound with more parameters than this,
+    /// default to 65535
+    #[structopt(long("max-parameters"))]
+    max_parameters: Option<usize>,
+    /// Fail queries returning string or binary values larger than this many
+    /// bytes, default to 1 GB
+    #[structopt(long("max-field-size"))]
+    max_field_size: Option<usize>,
+    /// Flush the rows of a query to the client every this many bytes, rather
+    /// than when the 8 KB connection buffer is full or the query completes
+    #[structopt(long("flush-threshold"))]
+    flush_threshold: Option<usize>,
+    /// Fail queries holding more than this many bytes of memory at once,
+    /// with the out_of_memory error
+    #[structopt(long("query-memory-limit"))]
+    query_memory_limit: Option<usize>,
+    /// Fail COPY FROM STDIN statements whose rows hold more than this many
+    /// bytes, default to 1 GB
+    #[structopt(long("copy-memory-limit"))]
+    copy_memory_limit: Option<usize>,
+    /// Name of the database returned by current_database(), default to the
+    /// name of the default catalog
+    #[structopt(long("database-name"))]
+    database_name: Option<String>,
+    /// Follow the response to Describe with a notice of the number of rows
+    /// the statement is estimated to return
+    #[structopt(long("describe-estimates"))]
+    describe_estimates: bool,
+    /// Warn with a notice when values are sent despite a loss, like unsigned
+    /// integers wrapping around or timestamps truncated to microseconds
+    #[structopt(long("encode-warnings"))]
+    encode_warnings: bool,
+    /// Only serve columns of this arrow type, like `Int64` or `Utf8`, or
+    /// lists of it. Repeat to allow several types
+    #[structopt(long("allow-type"))]
+    allowed_types: Vec<DataType>,
+    /// Fail queries returning columns of this arrow type, like `Binary`, or
+    /// lists of it. Repeat to deny several types
+    #[structopt(long("deny-type"))]
+    denied_types: Vec<DataType>,
+    /// Close connections idle for this many seconds
+    #[structopt(long("idle-timeout"))]
+    idle_timeout: Option<u64>,
+    /// Close connections whose clients haven't started up within this many
+    /// seconds, default to 60
+    #[structopt(long("startup-timeout"), default_value = "60")]
+    startup_timeout: u64,
+    /// Seconds running queries are given to complete on shutdown, default to 30
+    #[structopt(long("shutdown-grace-period"), default_value = "30")]
+    shutdown_grace_period: u64,
+    /// Let Nagle's algorithm delay small responses, by not setting
+    /// TCP_NODELAY on connections
+    #[structopt(long("no-tcp-nodelay"))]
+    no_tcp_nodelay: bool,
+    /// Send TCP keepalive probes on connections idle for this many seconds
+    #[structopt(long("tcp-keepalive"))]
+    tcp_keepalive: Option<u64>,
+    /// Also listen to the Unix socket .s.PGSQL.<port> in this directory, like
+    /// /var/run/postgresql where psql looks for it by default
+    #[structopt(long("unix-socket-dir"))]
+    unix_socket_dir: Option<PathBuf>,
+    /// Refuse connections while this many are open
+    #[structopt(long("max-connections"))]
+    max_connections: Option<usize>,
+    /// Print the rows, bytes and duration of each query to stderr, then on
+    /// shutdown how many times each unsupported arrow type was returned
+    #[structopt(long("log-query-stats"))]
+    log_query_stats: bool,
+}
+
+/// Complete on ctrl-c, or SIGTERM on unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.unwrap();
 }
 
 fn parse_table_def(table_def: &str) -> (&str, &str) {
@@ -98,17 +207,68 @@ async fn main() {
         println!("Loaded {} as table {}", table_path, table_name);
     }
 
-    let factory = Arc::new(HandlerFactory(Arc::new(DfSessionService::new(
-        session_context,
-    ))));
+    let mut service = DfSessionService::new(session_context)
+        .with_text_numbers(opts.text_numbers)
+        .with_unique_column_names(opts.unique_column_names)
+        .with_strict_timestamps(opts.strict_timestamps)
+        .with_inferred_parameter_types(opts.inferred_parameter_types)
+        .with_xml_validation(opts.validate_xml)
+        .with_describe_estimates(opts.describe_estimates)
+        .with_encode_warnings(opts.encode_warnings)
+        .with_denied_types(opts.denied_types);
+    if let Some(max_field_size) = opts.max_field_size {
+        service = service.with_max_field_size(max_field_size);
+    }
+    if let Some(oid) = opts.hstore_oid {
+        service = service.with_hstore_oid(oid);
+    }
+    if let Some(max_parameters) = opts.max_parameters {
+        service = service.with_max_parameters(max_parameters);
+    }
+    if let Some(flush_threshold) = opts.flush_threshold {
+        service = service.with_flush_threshold(flush_threshold);
+    }
+    if let Some(query_memory_limit) = opts.query_memory_limit {
+        service = service.with_query_memory_limit(query_memory_limit);
+    }
+    if let Some(copy_memory_limit) = opts.copy_memory_limit {
+        service = service.with_copy_memory_limit(copy_memory_limit);
+    }
+    if let Some(database_name) = &opts.database_name {
+        service = service.with_database_name(database_name);
+    }
+    if !opts.allowed_types.is_empty() {
+        service = service.with_allowed_types(opts.allowed_types);
+    }
+    if opts.log_query_stats {
+        service = service.with_query_stats_callback(Arc::new(|stats: &QueryStats| {
+            eprintln!(
+                "rows={} bytes={} duration={:?} query={}",
+                stats.rows, stats.bytes, stats.duration, stats.query
+            )
+        }));
+    }
+    let factory = Arc::new(HandlerFactory(Arc::new(service)));
 
     let server_addr = format!("{}:{}", opts.host, opts.port);
     let listener = TcpListener::bind(&server_addr).await.unwrap();
     println!("Listening to {}", server_addr);
-    loop {
-        let incoming_socket = listener.accept().await.unwrap();
-        let factory_ref = factory.clone();
 
-        tokio::spawn(async move { process_socket(incoming_socket.0, None, factory_ref).await });
+    let server_options = ServerOptions::default()
+        .with_idle_timeout(opts.idle_timeout.map(Duration::from_secs))
+        .with_startup_timeout(Duration::from_secs(opts.startup_timeout))
+        .with_shutdown_grace_period(Duration::from_secs(opts.shutdown_grace_period))
+        .with_tcp_nodelay(!opts.no_tcp_nodelay)
+        .with_tcp_keepalive(opts.tcp_keepalive.map(Duration::from_secs))
+        .with_unix_socket_directory(opts.unix_socket_dir)
+        .with_max_connections(opts.max_connections);
+    serve(factory, listener, server_options, shutdown_signal())
+        .await
+        .unwrap();
+    if opts.log_query_stats {
+        for (data_type, count) in unsupported_types() {
+            eprintln!("unsupported type {data_type}: {count}");
+        }
     }
+    println!("Shut down");
 }
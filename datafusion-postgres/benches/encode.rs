@@ -0,0 +1,113 @@
+//! Throughput of `encode_dataframe`, for a column of each major type in
+//! text and binary formats.
+//!
+//! Run with `cargo bench -p datafusion-postgres --features bench --bench encode`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use datafusion::arrow::array::{
+    ArrayRef, Decimal128Array, Int64Array, Int64Builder, ListBuilder, StringArray,
+    TimestampMicrosecondArray,
+};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::{encode_dataframe, EncodeOptions, PG_TYPE_METADATA_KEY};
+use futures::StreamExt;
+use pgwire::api::portal::Format;
+
+const ROWS: usize = 100_000;
+
+fn batches() -> Vec<(&'static str, RecordBatch)> {
+    let column = |field: Field, array: ArrayRef| {
+        RecordBatch::try_new(Arc::new(Schema::new(vec![field])), vec![array]).unwrap()
+    };
+
+    let mut list = ListBuilder::new(Int64Builder::new());
+    for i in 0..ROWS as i64 {
+        list.values().append_slice(&[i, i + 1, i + 2]);
+        list.append(true);
+    }
+
+    vec![
+        (
+            "int64",
+            column(
+                Field::new("v", DataType::Int64, false),
+                Arc::new(Int64Array::from_iter_values(0..ROWS as i64)),
+            ),
+        ),
+        (
+            "utf8",
+            column(
+                Field::new("v", DataType::Utf8, false),
+                Arc::new(StringArray::from_iter_values(
+                    (0..ROWS).map(|i| format!("value {i}")),
+                )),
+            ),
+        ),
+        (
+            "timestamp",
+            column(
+                Field::new("v", DataType::Timestamp(TimeUnit::Microsecond, None), false),
+                Arc::new(TimestampMicrosecondArray::from_iter_values(
+                    (0..ROWS as i64).map(|i| 1_700_000_000_000_000 + i * 1_000_003),
+                )),
+            ),
+        ),
+        (
+            "list",
+            column(
+                Field::new_list("v", Field::new_list_field(DataType::Int64, true), false),
+                Arc::new(list.finish()),
+            ),
+        ),
+        (
+            // decimals are only encodable as money
+            "decimal",
+            column(
+                Field::new("v", DataType::Decimal128(18, 2), false).with_metadata(HashMap::from([
+                    (PG_TYPE_METADATA_KEY.to_owned(), "money".to_owned()),
+                ])),
+                Arc::new(
+                    Decimal128Array::from_iter_values((0..ROWS as i128).map(|i| i * 101))
+                        .with_precision_and_scale(18, 2)
+                        .unwrap(),
+                ),
+            ),
+        ),
+    ]
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let ctx = SessionContext::new();
+    let options = EncodeOptions::default();
+
+    let mut group = c.benchmark_group("encode_dataframe");
+    group.throughput(Throughput::Elements(ROWS as u64));
+    group.sample_size(20);
+    for (name, batch) in batches() {
+        for (format_name, format) in [
+            ("text", Format::UnifiedText),
+            ("binary", Format::UnifiedBinary),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, format_name), &batch, |b, batch| {
+                b.to_async(&runtime).iter(|| async {
+                    let df = ctx.read_batch(batch.clone()).unwrap();
+                    let resp = encode_dataframe(df, &format, &options).await.unwrap();
+                    let mut rows = resp.data_rows();
+                    while let Some(row) = rows.next().await {
+                        row.unwrap();
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode);
+criterion_main!(benches);
@@ -0,0 +1,114 @@
+//! Throughput of a client pipelining extended queries: a statement prepared
+//! once, then bound and executed many times before a single `Sync`, like
+//! drivers batching small queries do.
+//!
+//! Run with `cargo bench -p datafusion-postgres --bench pipeline`.
+
+use std::future;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::{serve, DfSessionService, HandlerFactory, ServerOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+
+const QUERY: &str = "SELECT 1";
+
+fn message(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = vec![kind];
+    message.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+    message.extend_from_slice(body);
+    message
+}
+
+/// Read messages until `ReadyForQuery`.
+async fn read_until_ready(socket: &mut TcpStream, buf: &mut Vec<u8>) {
+    loop {
+        while buf.len() >= 5 {
+            let length = i32::from_be_bytes(buf[1..5].try_into().unwrap()) as usize;
+            if buf.len() < 1 + length {
+                break;
+            }
+            let kind = buf[0];
+            buf.drain(..1 + length);
+            if kind == b'Z' {
+                return;
+            }
+        }
+        let mut chunk = [0; 65536];
+        let n = socket.read(&mut chunk).await.unwrap();
+        assert!(n > 0, "server closed the connection");
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+async fn connect(runtime: &Runtime) -> TcpStream {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let factory = Arc::new(HandlerFactory(Arc::new(DfSessionService::new(
+        SessionContext::new(),
+    ))));
+    runtime.spawn(serve(
+        factory,
+        listener,
+        ServerOptions::default(),
+        future::pending(),
+    ));
+
+    let mut socket = TcpStream::connect(addr).await.unwrap();
+    let mut startup = 196608i32.to_be_bytes().to_vec();
+    startup.extend_from_slice(b"user\0bench\0database\0bench\0\0");
+    socket
+        .write_all(&[&(startup.len() as i32 + 4).to_be_bytes()[..], &startup].concat())
+        .await
+        .unwrap();
+    let mut buf = vec![];
+    read_until_ready(&mut socket, &mut buf).await;
+
+    let parse = [b"s\0", QUERY.as_bytes(), b"\0\0\0"].concat();
+    socket
+        .write_all(&[message(b'P', &parse), message(b'S', &[])].concat())
+        .await
+        .unwrap();
+    read_until_ready(&mut socket, &mut buf).await;
+    socket
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut socket = runtime.block_on(connect(&runtime));
+    let mut buf = vec![];
+
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(20);
+    for queries in [1, 100] {
+        // unnamed portal of the `s` statement, without parameters, results
+        // in text
+        let mut pipeline = vec![];
+        for _ in 0..queries {
+            pipeline.extend(message(b'B', b"\0s\0\0\0\0\0\0\0"));
+            pipeline.extend(message(b'E', b"\0\0\0\0\0"));
+        }
+        pipeline.extend(message(b'S', &[]));
+
+        group.throughput(Throughput::Elements(queries));
+        group.bench_with_input(
+            BenchmarkId::new("bind_execute", queries),
+            &pipeline,
+            |b, pipeline| {
+                b.iter(|| {
+                    runtime.block_on(async {
+                        socket.write_all(pipeline).await.unwrap();
+                        read_until_ready(&mut socket, &mut buf).await;
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline);
+criterion_main!(benches);
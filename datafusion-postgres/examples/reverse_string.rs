@@ -0,0 +1,53 @@
+//! Serves an empty `SessionContext` with a custom `reverse_string` function:
+//!
+//! ```sql
+//! SELECT reverse_string('datafusion');
+//! ```
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::StringArray;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::cast::as_string_array;
+use datafusion::error::Result;
+use datafusion::logical_expr::{ColumnarValue, Volatility};
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::{DfSessionService, HandlerFactory};
+use pgwire::tokio::process_socket;
+use tokio::net::TcpListener;
+
+// the error type is imposed by DataFusion
+#[allow(clippy::result_large_err)]
+fn reverse_string(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let reversed = as_string_array(&args[0])?
+        .iter()
+        .map(|value| value.map(|value| value.chars().rev().collect::<String>()))
+        .collect::<StringArray>();
+    Ok(ColumnarValue::Array(Arc::new(reversed)))
+}
+
+#[tokio::main]
+async fn main() {
+    let service = DfSessionService::new(SessionContext::new());
+    service
+        .register_scalar_function(
+            "reverse_string",
+            vec![DataType::Utf8],
+            DataType::Utf8,
+            Volatility::Immutable,
+            Arc::new(reverse_string),
+        )
+        .expect("reverse_string returns a supported type");
+
+    let factory = Arc::new(HandlerFactory(Arc::new(service)));
+
+    let listener = TcpListener::bind("127.0.0.1:5432").await.unwrap();
+    println!("Listening to 127.0.0.1:5432");
+    loop {
+        let incoming_socket = listener.accept().await.unwrap();
+        let factory_ref = factory.clone();
+
+        tokio::spawn(async move { process_socket(incoming_socket.0, None, factory_ref).await });
+    }
+}
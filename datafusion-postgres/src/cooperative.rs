@@ -0,0 +1,92 @@
+//! Executing plans so that the task running them yields to the runtime, and
+//! notices being dropped, even while an operator computes without waiting.
+
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::error::Result;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+use datafusion::prelude::DataFrame;
+use futures::StreamExt;
+
+use crate::sleep;
+
+/// Like `DataFrame::execute_stream`, with each leaf of the physical plan
+/// consuming the task's coop budget for the batches it produces. Dropping the
+/// stream stops the execution at the next batch, an aggregate reading its
+/// whole input in one poll included, or a `pg_sleep` call.
+#[allow(clippy::result_large_err)]
+pub(crate) async fn execute_stream(df: DataFrame) -> Result<SendableRecordBatchStream> {
+    let task_ctx = Arc::new(df.task_ctx());
+    sleep::check_calls(df.logical_plan())?;
+    let plan = sleep::sleep_before_calls(df.create_physical_plan().await?)?
+        .transform_up(|plan| {
+            if plan.children().is_empty() {
+                Ok(Transformed::yes(
+                    Arc::new(Cooperative { input: plan }) as Arc<dyn ExecutionPlan>
+                ))
+            } else {
+                Ok(Transformed::no(plan))
+            }
+        })?
+        .data;
+    datafusion::physical_plan::execute_stream(plan, task_ctx)
+}
+
+/// Passes the batches of its input through, consuming a unit of the coop
+/// budget for each.
+#[derive(Debug)]
+struct Cooperative {
+    input: Arc<dyn ExecutionPlan>,
+}
+
+impl DisplayAs for Cooperative {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cooperative")
+    }
+}
+
+impl ExecutionPlan for Cooperative {
+    fn name(&self) -> &str {
+        "Cooperative"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let [input] = <[Arc<dyn ExecutionPlan>; 1]>::try_from(children)
+            .unwrap_or_else(|_| unreachable!("Cooperative has one input"));
+        Ok(Arc::new(Cooperative { input }))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context)?;
+        let schema = input.schema();
+        let batches = input.then(|batch| async move {
+            tokio::task::consume_budget().await;
+            batch
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, batches)))
+    }
+}
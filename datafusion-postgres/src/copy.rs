@@ -0,0 +1,635 @@
+//! `COPY ... TO STDOUT`, in the text, csv and binary formats, and
+//! `COPY ... FROM STDIN`, in the text and csv formats.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use datafusion::sql::sqlparser::ast::{
+    CopyLegacyCsvOption, CopyLegacyOption, CopyOption, CopySource, CopyTarget, Ident, Statement,
+};
+use pgwire::api::results::FieldInfo;
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use pgwire::messages::data::DataRow;
+
+use crate::rewrite::{parse_single_statement, starts_with_keyword};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CopyFormat {
+    Text,
+    Csv,
+    Binary,
+}
+
+/// Starts binary `COPY` data: the `PGCOPY\n\377\r\n\0` signature, no flags
+/// and an empty header extension.
+const BINARY_HEADER: &[u8] = b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0";
+
+/// Ends binary `COPY` data, as a field count of -1.
+const BINARY_TRAILER: &[u8] = b"\xff\xff";
+
+/// How rows are laid out in `COPY` data.
+#[derive(Debug, Clone)]
+struct CopyOptions {
+    format: CopyFormat,
+    delimiter: u8,
+    /// Written for NULL fields, and read as NULL when unquoted. NULL
+    /// elements of arrays are not affected, they are always written as
+    /// `NULL`, the only form array parsers read.
+    null: String,
+    header: bool,
+    quote: u8,
+    escape: u8,
+}
+
+/// A `COPY ... TO STDOUT` statement.
+#[derive(Debug, Clone)]
+pub(crate) struct CopyTo {
+    /// The query producing the copied rows.
+    pub(crate) query: String,
+    options: CopyOptions,
+}
+
+/// A `COPY ... FROM STDIN` statement.
+#[derive(Debug, Clone)]
+pub(crate) struct CopyFrom {
+    /// The table rows are inserted into, as written in the statement.
+    pub(crate) table_name: String,
+    /// The columns each row has a value for, all the columns of the table
+    /// when empty.
+    pub(crate) columns: Vec<Ident>,
+    options: CopyOptions,
+}
+
+impl CopyTo {
+    /// Parse a `COPY ... TO STDOUT` statement, returning `None` for any
+    /// other statement, including other forms of `COPY`.
+    pub(crate) fn parse(statement: &str) -> PgWireResult<Option<CopyTo>> {
+        if !starts_with_keyword(statement, "copy") {
+            return Ok(None);
+        }
+        let Some(Statement::Copy {
+            source,
+            to: true,
+            target: CopyTarget::Stdout,
+            options,
+            legacy_options,
+            ..
+        }) = parse_single_statement(statement)
+        else {
+            return Ok(None);
+        };
+
+        let query = match source {
+            CopySource::Table {
+                table_name,
+                columns,
+            } if columns.is_empty() => format!("SELECT * FROM {table_name}"),
+            CopySource::Table {
+                table_name,
+                columns,
+            } => format!(
+                "SELECT {} FROM {table_name}",
+                columns
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            CopySource::Query(query) => query.to_string(),
+        };
+
+        Ok(Some(CopyTo {
+            query,
+            options: CopyOptions::parse(options, legacy_options)?,
+        }))
+    }
+
+    /// Whether rows are copied in binary format, as encoded for binary
+    /// results.
+    pub(crate) fn is_binary(&self) -> bool {
+        self.options.format == CopyFormat::Binary
+    }
+
+    /// The header line, with the names of the columns, or the header of
+    /// binary data.
+    pub(crate) fn header_line(&self, fields: &[FieldInfo]) -> Option<Bytes> {
+        if self.is_binary() {
+            return Some(Bytes::from_static(BINARY_HEADER));
+        }
+        if !self.options.header {
+            return None;
+        }
+        let mut line = BytesMut::new();
+        for (idx, field) in fields.iter().enumerate() {
+            if idx > 0 {
+                line.put_u8(self.options.delimiter);
+            }
+            self.options.put_value(&mut line, field.name().as_bytes());
+        }
+        line.put_u8(b'\n');
+        Some(line.freeze())
+    }
+
+    /// The line of a row encoded in text format, or the tuple of a row
+    /// encoded in binary format. Tuples are laid out like data rows: the
+    /// field count, then the length and bytes of each field.
+    pub(crate) fn line(&self, row: &DataRow) -> Bytes {
+        if self.is_binary() {
+            let mut tuple = BytesMut::with_capacity(2 + row.data.len());
+            tuple.put_i16(row.field_count);
+            tuple.put_slice(&row.data);
+            return tuple.freeze();
+        }
+        let mut line = BytesMut::with_capacity(row.data.len());
+        let mut data = &row.data[..];
+        for idx in 0..row.field_count {
+            if idx > 0 {
+                line.put_u8(self.options.delimiter);
+            }
+            let len = i32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+            data = &data[4..];
+            if len < 0 {
+                line.put_slice(self.options.null.as_bytes());
+            } else {
+                let (value, rest) = data.split_at(len as usize);
+                self.options.put_value(&mut line, value);
+                data = rest;
+            }
+        }
+        line.put_u8(b'\n');
+        line.freeze()
+    }
+
+    /// What follows the last row, the trailer of binary data.
+    pub(crate) fn trailer(&self) -> Option<Bytes> {
+        self.is_binary().then(|| Bytes::from_static(BINARY_TRAILER))
+    }
+}
+
+impl CopyFrom {
+    /// Parse a `COPY ... FROM STDIN` statement, returning `None` for any
+    /// other statement, including other forms of `COPY`. Unlike psql
+    /// scripts, the statement is never followed by its data.
+    pub(crate) fn parse(statement: &str) -> PgWireResult<Option<CopyFrom>> {
+        if !starts_with_keyword(statement, "copy") {
+            return Ok(None);
+        }
+        let Some(Statement::Copy {
+            source:
+                CopySource::Table {
+                    table_name,
+                    columns,
+                },
+            to: false,
+            target: CopyTarget::Stdin,
+            options,
+            legacy_options,
+            ..
+        }) = parse_single_statement(&format!("{};", statement.trim_end_matches([' ', ';'])))
+        else {
+            return Ok(None);
+        };
+
+        let options = CopyOptions::parse(options, legacy_options)?;
+        if options.format == CopyFormat::Binary {
+            return Err(not_supported("COPY FROM STDIN in binary format"));
+        }
+        Ok(Some(CopyFrom {
+            table_name: table_name.to_string(),
+            columns,
+            options,
+        }))
+    }
+
+    /// A reader of the rows of the data copied into `columns`.
+    pub(crate) fn reader(&self, columns: Vec<Ident>) -> CopyReader {
+        CopyReader {
+            options: self.options.clone(),
+            header: self.options.header,
+            columns,
+            pending: vec![],
+            ended: false,
+        }
+    }
+}
+
+/// The rows of the data of a `COPY ... FROM STDIN`, read as it arrives in
+/// `CopyData` messages, which may end in the middle of a row.
+#[derive(Debug)]
+pub(crate) struct CopyReader {
+    options: CopyOptions,
+    /// Whether the header line is still to be skipped.
+    header: bool,
+    /// The columns each row has a field for.
+    columns: Vec<Ident>,
+    /// The data of the row that isn't complete yet.
+    pending: Vec<u8>,
+    /// Whether the `\.` end of data marker was read, the data after it is
+    /// ignored.
+    ended: bool,
+}
+
+impl CopyReader {
+    /// The rows completed by `data`. The rest of the data is kept until the
+    /// next data completes it.
+    pub(crate) fn read(&mut self, data: &[u8]) -> PgWireResult<Vec<Vec<Option<String>>>> {
+        if self.ended {
+            return Ok(vec![]);
+        }
+        self.pending.extend_from_slice(data);
+        let end = self.complete_rows_end();
+        let complete = self.pending.drain(..end).collect::<Vec<_>>();
+        if self.ended {
+            self.pending.clear();
+        }
+        self.rows(&complete)
+    }
+
+    /// The last rows, once the client sent all of its data. Data ends with
+    /// its last line, or with a `\.` line.
+    pub(crate) fn finish(&mut self) -> PgWireResult<Vec<Vec<Option<String>>>> {
+        let rest = std::mem::take(&mut self.pending);
+        self.rows(&rest)
+    }
+
+    /// The size of the data kept until it completes a row.
+    pub(crate) fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The end of the complete rows of the pending data, the start of the
+    /// end of data marker when it is there.
+    fn complete_rows_end(&mut self) -> usize {
+        let csv = self.options.format == CopyFormat::Csv;
+        let (quote, escape) = (self.options.quote, self.options.escape);
+        let data = &self.pending;
+        let mut end = 0;
+        let mut in_quotes = false;
+        let mut idx = 0;
+        while let Some(&byte) = data.get(idx) {
+            idx += 1;
+            if csv && in_quotes {
+                if byte == escape && data.get(idx) == Some(&quote) {
+                    idx += 1;
+                } else if byte == quote {
+                    in_quotes = false;
+                }
+            } else if csv && byte == quote {
+                in_quotes = true;
+            } else if byte == b'\n' {
+                let line = &data[end..idx - 1];
+                if line.strip_suffix(b"\r").unwrap_or(line) == b"\\." {
+                    self.ended = true;
+                    return end;
+                }
+                end = idx;
+            }
+        }
+        end
+    }
+
+    fn rows(&mut self, data: &[u8]) -> PgWireResult<Vec<Vec<Option<String>>>> {
+        let mut rows = match self.options.format {
+            CopyFormat::Text => self.options.text_rows(data)?,
+            CopyFormat::Csv => self.options.csv_rows(data)?,
+            CopyFormat::Binary => unreachable!("binary data isn't copied from clients"),
+        };
+        if self.header && !rows.is_empty() {
+            rows.remove(0);
+            self.header = false;
+        }
+        for row in &rows {
+            if row.len() > self.columns.len() {
+                return Err(bad_copy_format(
+                    "extra data after last expected column".to_owned(),
+                ));
+            }
+            if let Some(column) = self.columns.get(row.len()) {
+                return Err(bad_copy_format(format!("missing data for column {column}")));
+            }
+        }
+        Ok(rows)
+    }
+}
+
+impl CopyOptions {
+    fn parse(
+        options: Vec<CopyOption>,
+        legacy_options: Vec<CopyLegacyOption>,
+    ) -> PgWireResult<CopyOptions> {
+        let mut format = CopyFormat::Text;
+        let mut delimiter = None;
+        let mut null = None;
+        let mut header = false;
+        let mut quote = None;
+        let mut escape = None;
+        for option in options {
+            match option {
+                CopyOption::Format(name) => {
+                    format = match name.value.to_lowercase().as_str() {
+                        "text" => CopyFormat::Text,
+                        "csv" => CopyFormat::Csv,
+                        "binary" => CopyFormat::Binary,
+                        _ => {
+                            return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                                "ERROR".to_owned(),
+                                "22023".to_owned(),
+                                format!("COPY format \"{}\" not recognized", name.value),
+                            ))))
+                        }
+                    }
+                }
+                CopyOption::Delimiter(c) => delimiter = Some(c),
+                CopyOption::Null(s) => null = Some(s),
+                CopyOption::Header(h) => header = h,
+                CopyOption::Quote(c) => quote = Some(c),
+                CopyOption::Escape(c) => escape = Some(c),
+                option => return Err(not_supported(&format!("COPY option {option}"))),
+            }
+        }
+        for option in legacy_options {
+            match option {
+                CopyLegacyOption::Binary => format = CopyFormat::Binary,
+                CopyLegacyOption::Delimiter(c) => delimiter = Some(c),
+                CopyLegacyOption::Null(s) => null = Some(s),
+                CopyLegacyOption::Csv(csv_options) => {
+                    format = CopyFormat::Csv;
+                    for option in csv_options {
+                        match option {
+                            CopyLegacyCsvOption::Header => header = true,
+                            CopyLegacyCsvOption::Quote(c) => quote = Some(c),
+                            CopyLegacyCsvOption::Escape(c) => escape = Some(c),
+                            option => return Err(not_supported(&format!("COPY option {option}"))),
+                        }
+                    }
+                }
+            }
+        }
+
+        if format == CopyFormat::Binary {
+            for (option, given) in [
+                ("DELIMITER", delimiter.is_some()),
+                ("NULL", null.is_some()),
+                ("HEADER", header),
+            ] {
+                if given {
+                    return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                        "ERROR".to_owned(),
+                        "42601".to_owned(),
+                        format!("cannot specify {option} in BINARY mode"),
+                    ))));
+                }
+            }
+        }
+        if format != CopyFormat::Csv && (quote.is_some() || escape.is_some()) {
+            return Err(not_supported("COPY quote and escape outside of CSV mode"));
+        }
+        let quote = single_byte("quote", quote.unwrap_or('"'))?;
+        let (default_delimiter, default_null) = match format {
+            CopyFormat::Text | CopyFormat::Binary => ('\t', "\\N"),
+            CopyFormat::Csv => (',', ""),
+        };
+        Ok(CopyOptions {
+            format,
+            delimiter: single_byte("delimiter", delimiter.unwrap_or(default_delimiter))?,
+            null: null.unwrap_or_else(|| default_null.to_owned()),
+            header,
+            quote,
+            escape: escape.map_or(Ok(quote), |c| single_byte("escape", c))?,
+        })
+    }
+
+    /// The rows of text data: a line per row, fields separated by the
+    /// delimiter, and backslash escapes of the characters they can't hold.
+    /// Fields are NULL when they read as the null string before unescaping.
+    fn text_rows(&self, data: &[u8]) -> PgWireResult<Vec<Vec<Option<String>>>> {
+        let mut rows = vec![];
+        for line in lines(data) {
+            let mut row = vec![];
+            let mut value = vec![];
+            let mut start = 0;
+            let mut idx = 0;
+            loop {
+                if idx == line.len() || line[idx] == self.delimiter {
+                    let raw = &line[start..idx];
+                    let value = std::mem::take(&mut value);
+                    row.push(
+                        (raw != self.null.as_bytes())
+                            .then(|| utf8(value))
+                            .transpose()?,
+                    );
+                    if idx == line.len() {
+                        break;
+                    }
+                    idx += 1;
+                    start = idx;
+                    continue;
+                }
+                if line[idx] != b'\\' || idx + 1 == line.len() {
+                    value.push(line[idx]);
+                    idx += 1;
+                    continue;
+                }
+                idx += 1;
+                let (byte, digits) = match line[idx] {
+                    b'b' => (b'\x08', 1),
+                    b'f' => (b'\x0c', 1),
+                    b'n' => (b'\n', 1),
+                    b'r' => (b'\r', 1),
+                    b't' => (b'\t', 1),
+                    b'v' => (b'\x0b', 1),
+                    b'0'..=b'7' => number(&line[idx..], 8, 3),
+                    b'x' if line.get(idx + 1).is_some_and(u8::is_ascii_hexdigit) => {
+                        let (byte, digits) = number(&line[idx + 1..], 16, 2);
+                        (byte, digits + 1)
+                    }
+                    byte => (byte, 1),
+                };
+                value.push(byte);
+                idx += digits;
+            }
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// The rows of CSV data: a line per row, fields separated by the
+    /// delimiter, and quoted when they hold it, the quote or line breaks.
+    /// Unquoted fields are NULL when they read as the null string.
+    fn csv_rows(&self, data: &[u8]) -> PgWireResult<Vec<Vec<Option<String>>>> {
+        let mut rows = vec![];
+        let mut row = vec![];
+        let mut value = vec![];
+        let mut quoted = false;
+        let mut in_quotes = false;
+        let mut idx = 0;
+        loop {
+            let Some(&byte) = data.get(idx) else {
+                if in_quotes {
+                    return Err(bad_copy_format("unterminated CSV quoted field".to_owned()));
+                }
+                if !row.is_empty() || !value.is_empty() || quoted {
+                    row.push(self.csv_field(value, quoted)?);
+                    rows.push(row);
+                }
+                return Ok(rows);
+            };
+            idx += 1;
+            if in_quotes {
+                if byte == self.escape && data.get(idx) == Some(&self.quote) {
+                    value.push(self.quote);
+                    idx += 1;
+                } else if byte == self.quote {
+                    in_quotes = false;
+                } else {
+                    value.push(byte);
+                }
+                continue;
+            }
+            match byte {
+                b'\\' if row.is_empty() && value.is_empty() && !quoted => {
+                    // the end of data marker, alone on its line
+                    let rest = &data[idx..];
+                    let end = rest.strip_prefix(b".").is_some_and(|rest| {
+                        rest.is_empty() || rest.starts_with(b"\n") || rest.starts_with(b"\r\n")
+                    });
+                    if end {
+                        return Ok(rows);
+                    }
+                    value.push(byte);
+                }
+                byte if byte == self.quote => {
+                    quoted = true;
+                    in_quotes = true;
+                }
+                byte if byte == self.delimiter => {
+                    row.push(self.csv_field(std::mem::take(&mut value), quoted)?);
+                    quoted = false;
+                }
+                b'\r' if data.get(idx) == Some(&b'\n') => {}
+                b'\n' => {
+                    row.push(self.csv_field(std::mem::take(&mut value), quoted)?);
+                    rows.push(std::mem::take(&mut row));
+                    quoted = false;
+                }
+                byte => value.push(byte),
+            }
+        }
+    }
+
+    fn csv_field(&self, value: Vec<u8>, quoted: bool) -> PgWireResult<Option<String>> {
+        if !quoted && value == self.null.as_bytes() {
+            return Ok(None);
+        }
+        utf8(value).map(Some)
+    }
+
+    fn put_value(&self, out: &mut BytesMut, value: &[u8]) {
+        match self.format {
+            CopyFormat::Text => {
+                for &byte in value {
+                    let escaped = match byte {
+                        b'\\' => b'\\',
+                        b'\x08' => b'b',
+                        b'\x0c' => b'f',
+                        b'\n' => b'n',
+                        b'\r' => b'r',
+                        b'\t' => b't',
+                        b'\x0b' => b'v',
+                        byte if byte == self.delimiter => byte,
+                        byte => {
+                            out.put_u8(byte);
+                            continue;
+                        }
+                    };
+                    out.put_u8(b'\\');
+                    out.put_u8(escaped);
+                }
+            }
+            CopyFormat::Binary => unreachable!("binary values are copied as encoded"),
+            CopyFormat::Csv => {
+                // like postgres, a value that reads as the null string is
+                // quoted to tell them apart
+                let needs_quotes = value == self.null.as_bytes()
+                    || value.iter().any(|&byte| {
+                        byte == self.delimiter
+                            || byte == self.quote
+                            || byte == self.escape
+                            || byte == b'\n'
+                            || byte == b'\r'
+                    });
+                if !needs_quotes {
+                    out.put_slice(value);
+                    return;
+                }
+                out.put_u8(self.quote);
+                for &byte in value {
+                    if byte == self.quote || byte == self.escape {
+                        out.put_u8(self.escape);
+                    }
+                    out.put_u8(byte);
+                }
+                out.put_u8(self.quote);
+            }
+        }
+    }
+}
+
+/// The lines of text data, without their line break, up to a `\.` line.
+fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    (!data.is_empty())
+        .then(|| data.split(|byte| *byte == b'\n'))
+        .into_iter()
+        .flatten()
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .take_while(|line| *line != b"\\.")
+}
+
+/// The byte of up to `max_digits` digits in `radix` starting `digits`, and
+/// the number of digits read.
+fn number(digits: &[u8], radix: u32, max_digits: usize) -> (u8, usize) {
+    let digits = digits
+        .iter()
+        .take(max_digits)
+        .map_while(|digit| (*digit as char).to_digit(radix))
+        .collect::<Vec<_>>();
+    let value = digits
+        .iter()
+        .fold(0u32, |value, digit| value * radix + digit);
+    (value as u8, digits.len())
+}
+
+fn utf8(value: Vec<u8>) -> PgWireResult<String> {
+    String::from_utf8(value).map_err(|_| {
+        PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "22021".to_owned(),
+            "invalid byte sequence for encoding \"UTF8\"".to_owned(),
+        )))
+    })
+}
+
+fn bad_copy_format(message: String) -> PgWireError {
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_owned(),
+        "22P04".to_owned(),
+        message,
+    )))
+}
+
+fn single_byte(option: &str, c: char) -> PgWireResult<u8> {
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        Err(not_supported(&format!(
+            "COPY {option} other than a single one-byte character"
+        )))
+    }
+}
+
+fn not_supported(what: &str) -> PgWireError {
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_owned(),
+        "0A000".to_owned(),
+        format!("{what} is not supported"),
+    )))
+}
@@ -1,10 +1,15 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::{self, Debug};
 use std::iter;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+use bytes::{BufMut, BytesMut};
 use chrono::{DateTime, FixedOffset, TimeZone, Utc};
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use datafusion::arrow::array::*;
+use datafusion::arrow::compute::cast;
 use datafusion::arrow::datatypes::*;
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::common::{DFSchema, ParamValues};
@@ -12,17 +17,277 @@ use datafusion::prelude::*;
 use datafusion::scalar::ScalarValue;
 use futures::{stream, StreamExt};
 use pgwire::api::portal::{Format, Portal};
-use pgwire::api::results::{DataRowEncoder, FieldInfo, QueryResponse};
+use pgwire::api::results::{FieldFormat, FieldInfo, QueryResponse};
 use pgwire::api::Type;
 use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use pgwire::messages::data::DataRow;
+use pgwire::types::ToSqlText;
+use postgres_types::{FromSqlOwned, IsNull, ToSql};
 use timezone::Tz;
 
+use crate::pg_types::{
+    Bytea, Float, FromSqlText, Hstore, Inet, Interval, JsonPath, Money, Numeric, PgChar, Record,
+    RegOid, RegType, Timestamp, Uuid, VarBit, Void, Xml,
+};
+use crate::{cooperative, errors, metrics};
+
+/// Options controlling how result sets are described and encoded.
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    /// Send integer and float columns in text format even when the client
+    /// asks for binary.
+    pub(crate) text_numbers: bool,
+    /// Suffix repeated column names with `_1`, `_2`... so they are unique.
+    pub(crate) unique_column_names: bool,
+    /// Fail on nanosecond timestamps that can't be sent without truncating
+    /// them to microseconds, instead of truncating them.
+    pub(crate) strict_timestamps: bool,
+    /// Largest string or binary value sent, in bytes.
+    pub(crate) max_field_size: usize,
+    /// Arrow types columns may have, any type when `None`.
+    pub(crate) allowed_types: Option<Vec<DataType>>,
+    /// Arrow types columns may not have.
+    pub(crate) denied_types: Vec<DataType>,
+    /// The `extra_float_digits` of the session, see [`Float`].
+    pub(crate) extra_float_digits: i32,
+    /// Oid of the `hstore` type, which maps of strings to strings are sent
+    /// as when set.
+    pub(crate) hstore_oid: Option<u32>,
+    /// The `TimeZone` of the session, see [`timestamptz_zone`].
+    pub(crate) time_zone: Option<Tz>,
+    /// Applied to the schema of results before they are described.
+    pub(crate) schema_transform: Option<SchemaTransformFn>,
+    /// Collects warnings about values sent despite a loss, when set.
+    pub(crate) warnings: Option<EncodeWarnings>,
+}
+
+/// Called with the schema of each result, returning the schema its columns
+/// are described and encoded with instead. It may rename columns or change
+/// their metadata, like their [`PG_TYPE_METADATA_KEY`] hint, but must keep
+/// their number, order and arrow types.
+pub type SchemaTransform = Arc<dyn Fn(&DFSchema) -> DFSchema + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct SchemaTransformFn(pub(crate) SchemaTransform);
+
+impl Debug for SchemaTransformFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SchemaTransform")
+    }
+}
+
+/// Like postgres, values up to 1 GB can be sent by default.
+pub(crate) const DEFAULT_MAX_FIELD_SIZE: usize = (1 << 30) - 1;
+
+impl Default for EncodeOptions {
+    fn default() -> EncodeOptions {
+        EncodeOptions {
+            text_numbers: false,
+            unique_column_names: false,
+            strict_timestamps: false,
+            max_field_size: DEFAULT_MAX_FIELD_SIZE,
+            allowed_types: None,
+            denied_types: vec![],
+            extra_float_digits: 1,
+            hstore_oid: None,
+            time_zone: None,
+            schema_transform: None,
+            warnings: None,
+        }
+    }
+}
+
+/// Losses that values are sent despite, which clients can be warned about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EncodeWarning {
+    /// Unsigned integers beyond the range of the signed type of their width
+    /// wrap around to negative values.
+    UnsignedWrapped,
+    /// The nanoseconds of timestamps are truncated to microseconds.
+    NanosecondsTruncated,
+}
+
+/// The warnings raised while the rows of a result are encoded, each once per
+/// column, to be sent as notices along with the rows.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EncodeWarnings(Arc<Mutex<WarningsState>>);
+
+#[derive(Debug, Default)]
+struct WarningsState {
+    raised: Vec<(EncodeWarning, String)>,
+    pending: Vec<ErrorInfo>,
+}
+
+impl EncodeWarnings {
+    fn raise(&self, warning: EncodeWarning, column: &str, message: impl FnOnce() -> String) {
+        let mut state = self.0.lock().unwrap();
+        if state
+            .raised
+            .iter()
+            .any(|(raised, name)| *raised == warning && name == column)
+        {
+            return;
+        }
+        state.raised.push((warning, column.to_owned()));
+        let notice = ErrorInfo::new("WARNING".to_owned(), "01000".to_owned(), message());
+        state.pending.push(notice);
+    }
+
+    /// The warnings raised since the last call.
+    pub(crate) fn take(&self) -> Vec<ErrorInfo> {
+        std::mem::take(&mut self.0.lock().unwrap().pending)
+    }
+}
+
+/// Options controlling how parameters are decoded.
+#[derive(Debug, Clone)]
+pub(crate) struct ParameterOptions {
+    /// Convert parameters declared as `text` or `varchar` to the type
+    /// DataFusion inferred for them, when it isn't a string.
+    pub(crate) inferred_types: bool,
+    /// Most parameters a portal can be bound with.
+    pub(crate) max_parameters: usize,
+    /// Reject `xml` parameters that aren't well-formed XML content.
+    pub(crate) validate_xml: bool,
+}
+
+/// Like postgres, which counts parameters with 16 bits.
+pub(crate) const DEFAULT_MAX_PARAMETERS: usize = 65535;
+
+impl Default for ParameterOptions {
+    fn default() -> ParameterOptions {
+        ParameterOptions {
+            inferred_types: false,
+            max_parameters: DEFAULT_MAX_PARAMETERS,
+            validate_xml: false,
+        }
+    }
+}
+
+/// Field metadata key to request a specific postgres type for a column,
+/// instead of the one derived from its arrow type. The value is the name of
+/// the postgres type.
+///
+/// | value     | arrow type                                    |
+/// |-----------|-----------------------------------------------|
+/// | `money`   | `Decimal128`                                  |
+/// | `char`    | `Int8` or `UInt8`                             |
+/// | `varbit`  | `Binary`, `LargeBinary` or `FixedSizeBinary`  |
+/// | `name`    | `Utf8`, `LargeUtf8` or `Utf8View`             |
+/// | `oid`     | `Int32` or `UInt32`                           |
+/// | `regtype` | `Int32` or `UInt32`                           |
+/// | `regclass`| `Int32` or `UInt32`                           |
+/// | `regproc` | `Int32` or `UInt32`                           |
+/// | `inet`    | `Utf8`, `LargeUtf8` or `Utf8View`             |
+/// | `cidr`    | `Utf8`, `LargeUtf8` or `Utf8View`             |
+/// | `xml`     | `Utf8`, `LargeUtf8` or `Utf8View`             |
+/// | `bpchar`  | `Utf8`, `LargeUtf8` or `Utf8View`             |
+/// | `void`    | any                                           |
+///
+/// `varbit` bytes hold 8 bits each, the most significant bit first. `Int32`
+/// oids are read as unsigned, and `regtype` oids are written as type names
+/// in text format, while `regclass` and `regproc` ones are written as
+/// numbers. `inet` and `cidr` strings are parsed as such, and fail
+/// queries returning invalid ones. `xml` strings are sent as is. `bpchar`
+/// may be given a length, like `bpchar(10)`, to right pad strings with
+/// spaces to that many characters, like postgres does for `char(10)`
+/// columns. `void` values, like those of functions returning nothing, are
+/// sent empty.
+pub const PG_TYPE_METADATA_KEY: &str = "pg_type";
+
+/// Arrow field metadata key naming the extension type of a field.
+const EXTENSION_NAME_KEY: &str = "ARROW:extension:name";
+
+/// Postgres type of a field, honouring the [`PG_TYPE_METADATA_KEY`] hint,
+/// then the arrow extension type of the field or of its list items.
+///
+/// | extension type | arrow type                               | postgres type |
+/// |----------------|------------------------------------------|---------------|
+/// | `arrow.uuid`   | `FixedSizeBinary(16)`                    | `uuid`        |
+/// | `arrow.json`   | `Utf8`, `LargeUtf8` or `Utf8View`        | `json`        |
+///
+/// Other extension types are sent as their storage type.
+pub(crate) fn field_into_pg_type(field: &Field) -> PgWireResult<Type> {
+    let Some(hint) = field.metadata().get(PG_TYPE_METADATA_KEY) else {
+        if let Some(pg_type) = extension_pg_type(field) {
+            return Ok(pg_type);
+        }
+        if let DataType::List(item)
+        | DataType::FixedSizeList(item, _)
+        | DataType::LargeList(item)
+        | DataType::ListView(item)
+        | DataType::LargeListView(item) = field.data_type()
+        {
+            match extension_pg_type(item) {
+                Some(Type::UUID) => return Ok(Type::UUID_ARRAY),
+                Some(Type::JSON) => return Ok(Type::JSON_ARRAY),
+                _ => {}
+            }
+        }
+        return into_pg_type(field.data_type());
+    };
+
+    Ok(match (hint.as_str(), field.data_type()) {
+        ("money", DataType::Decimal128(_, _)) => Type::MONEY,
+        ("char", DataType::Int8 | DataType::UInt8) => Type::CHAR,
+        ("varbit", DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_)) => {
+            Type::VARBIT
+        }
+        ("name", DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) => Type::NAME,
+        ("oid", DataType::Int32 | DataType::UInt32) => Type::OID,
+        ("regtype", DataType::Int32 | DataType::UInt32) => Type::REGTYPE,
+        ("regclass", DataType::Int32 | DataType::UInt32) => Type::REGCLASS,
+        ("regproc", DataType::Int32 | DataType::UInt32) => Type::REGPROC,
+        ("inet", DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) => Type::INET,
+        ("cidr", DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) => Type::CIDR,
+        ("xml", DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) => Type::XML,
+        (hint, DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View)
+            if hint == "bpchar" || bpchar_length(field).is_some() =>
+        {
+            Type::BPCHAR
+        }
+        ("void", _) => Type::VOID,
+        (hint, df_type) => {
+            return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "XX000".to_owned(),
+                format!("Unsupported {PG_TYPE_METADATA_KEY} {hint} for Datatype {df_type}"),
+            ))));
+        }
+    })
+}
+
+/// The length of a `bpchar(n)` field, that its values are padded to.
+fn bpchar_length(field: &Field) -> Option<usize> {
+    field
+        .metadata()
+        .get(PG_TYPE_METADATA_KEY)?
+        .strip_prefix("bpchar(")?
+        .strip_suffix(')')?
+        .parse()
+        .ok()
+}
+
+fn extension_pg_type(field: &Field) -> Option<Type> {
+    match (
+        field.metadata().get(EXTENSION_NAME_KEY)?.as_str(),
+        field.data_type(),
+    ) {
+        ("arrow.uuid", DataType::FixedSizeBinary(16)) => Some(Type::UUID),
+        ("arrow.json", DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) => {
+            Some(Type::JSON)
+        }
+        _ => None,
+    }
+}
+
 pub(crate) fn into_pg_type(df_type: &DataType) -> PgWireResult<Type> {
     Ok(match df_type {
         DataType::Null => Type::UNKNOWN,
         DataType::Boolean => Type::BOOL,
-        DataType::Int8 | DataType::UInt8 => Type::CHAR,
-        DataType::Int16 | DataType::UInt16 => Type::INT2,
+        // `"char"` is a single character to clients, so bytes are served as
+        // the smallest integer type, unless hinted otherwise
+        DataType::Int8 | DataType::UInt8 | DataType::Int16 | DataType::UInt16 => Type::INT2,
         DataType::Int32 | DataType::UInt32 => Type::INT4,
         DataType::Int64 | DataType::UInt64 => Type::INT8,
         DataType::Timestamp(_, tz) => {
@@ -35,44 +300,60 @@ pub(crate) fn into_pg_type(df_type: &DataType) -> PgWireResult<Type> {
         DataType::Time32(_) | DataType::Time64(_) => Type::TIME,
         DataType::Date32 | DataType::Date64 => Type::DATE,
         DataType::Interval(_) => Type::INTERVAL,
+        DataType::Decimal128(_, _) => Type::NUMERIC,
         DataType::Binary | DataType::FixedSizeBinary(_) | DataType::LargeBinary => Type::BYTEA,
         DataType::Float16 | DataType::Float32 => Type::FLOAT4,
         DataType::Float64 => Type::FLOAT8,
         DataType::Utf8 => Type::VARCHAR,
         DataType::LargeUtf8 => Type::TEXT,
-        DataType::List(field) | DataType::FixedSizeList(field, _) | DataType::LargeList(field) => {
-            match field.data_type() {
-                DataType::Boolean => Type::BOOL_ARRAY,
-                DataType::Int8 | DataType::UInt8 => Type::CHAR_ARRAY,
-                DataType::Int16 | DataType::UInt16 => Type::INT2_ARRAY,
-                DataType::Int32 | DataType::UInt32 => Type::INT4_ARRAY,
-                DataType::Int64 | DataType::UInt64 => Type::INT8_ARRAY,
-                DataType::Timestamp(_, tz) => {
-                    if tz.is_some() {
-                        Type::TIMESTAMPTZ_ARRAY
-                    } else {
-                        Type::TIMESTAMP_ARRAY
-                    }
-                }
-                DataType::Time32(_) | DataType::Time64(_) => Type::TIME_ARRAY,
-                DataType::Date32 | DataType::Date64 => Type::DATE_ARRAY,
-                DataType::Interval(_) => Type::INTERVAL_ARRAY,
-                DataType::FixedSizeBinary(_) | DataType::Binary => Type::BYTEA_ARRAY,
-                DataType::Float16 | DataType::Float32 => Type::FLOAT4_ARRAY,
-                DataType::Float64 => Type::FLOAT8_ARRAY,
-                DataType::Utf8 => Type::VARCHAR_ARRAY,
-                DataType::LargeUtf8 => Type::TEXT_ARRAY,
-                list_type => {
-                    return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
-                        "ERROR".to_owned(),
-                        "XX000".to_owned(),
-                        format!("Unsupported List Datatype {list_type}"),
-                    ))));
+        DataType::List(field)
+        | DataType::FixedSizeList(field, _)
+        | DataType::LargeList(field)
+        | DataType::ListView(field)
+        | DataType::LargeListView(field) => match field.data_type() {
+            DataType::Boolean => Type::BOOL_ARRAY,
+            DataType::Int8 | DataType::UInt8 | DataType::Int16 | DataType::UInt16 => {
+                Type::INT2_ARRAY
+            }
+            DataType::Int32 | DataType::UInt32 => Type::INT4_ARRAY,
+            DataType::Int64 | DataType::UInt64 => Type::INT8_ARRAY,
+            DataType::Timestamp(_, tz) => {
+                if tz.is_some() {
+                    Type::TIMESTAMPTZ_ARRAY
+                } else {
+                    Type::TIMESTAMP_ARRAY
                 }
             }
-        }
+            DataType::Time32(_) | DataType::Time64(_) => Type::TIME_ARRAY,
+            DataType::Date32 | DataType::Date64 => Type::DATE_ARRAY,
+            DataType::Interval(_) => Type::INTERVAL_ARRAY,
+            DataType::Decimal128(_, _) => Type::NUMERIC_ARRAY,
+            DataType::FixedSizeBinary(_) | DataType::Binary => Type::BYTEA_ARRAY,
+            DataType::Float16 | DataType::Float32 => Type::FLOAT4_ARRAY,
+            DataType::Float64 => Type::FLOAT8_ARRAY,
+            DataType::Utf8 => Type::VARCHAR_ARRAY,
+            DataType::LargeUtf8 => Type::TEXT_ARRAY,
+            DataType::Dictionary(_, value) if is_string_type(value) => Type::TEXT_ARRAY,
+            DataType::Struct(fields) => {
+                check_record_fields(fields)?;
+                Record::array_type()
+            }
+            list_type => {
+                metrics::count_unsupported_type(df_type);
+                return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                    "ERROR".to_owned(),
+                    "XX000".to_owned(),
+                    format!("Unsupported List Datatype {list_type}"),
+                ))));
+            }
+        },
         DataType::Utf8View => Type::TEXT,
+        DataType::Struct(fields) => {
+            check_record_fields(fields)?;
+            Type::RECORD
+        }
         _ => {
+            metrics::count_unsupported_type(df_type);
             return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
                 "ERROR".to_owned(),
                 "XX000".to_owned(),
@@ -82,6 +363,14 @@ pub(crate) fn into_pg_type(df_type: &DataType) -> PgWireResult<Type> {
     })
 }
 
+/// Structs are sent as anonymous records, each field as its own type.
+fn check_record_fields(fields: &Fields) -> PgWireResult<()> {
+    for field in fields {
+        field_into_pg_type(field)?;
+    }
+    Ok(())
+}
+
 fn get_bool_value(arr: &Arc<dyn Array>, idx: usize) -> bool {
     arr.as_any()
         .downcast_ref::<BooleanArray>()
@@ -89,79 +378,102 @@ fn get_bool_value(arr: &Arc<dyn Array>, idx: usize) -> bool {
         .value(idx)
 }
 
+/// Whether `data_type` is a map of strings to strings, sent as `hstore` when
+/// the oid of the type is known.
+fn is_string_map(data_type: &DataType) -> bool {
+    let DataType::Map(entries, _) = data_type else {
+        return false;
+    };
+    let DataType::Struct(fields) = entries.data_type() else {
+        return false;
+    };
+    fields.len() == 2 && fields.iter().all(|f| is_string_type(f.data_type()))
+}
+
+fn is_string_type(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+    )
+}
+
+/// The items of the list at `idx` of a `List`, `LargeList`,
+/// `FixedSizeList`, `ListView` or `LargeListView` array. The items of views
+/// are sliced at their offset, in whatever order they are in the values.
+fn list_value(arr: &Arc<dyn Array>, idx: usize) -> ArrayRef {
+    match arr.data_type() {
+        DataType::LargeList(_) => arr.as_list::<i64>().value(idx),
+        DataType::FixedSizeList(_, _) => arr.as_fixed_size_list().value(idx),
+        DataType::ListView(_) => arr.as_list_view::<i32>().value(idx),
+        DataType::LargeListView(_) => arr.as_list_view::<i64>().value(idx),
+        _ => arr.as_list::<i32>().value(idx),
+    }
+}
+
 fn get_bool_list_value(arr: &Arc<dyn Array>, idx: usize) -> Vec<Option<bool>> {
-    let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
-    list_arr
-        .as_any()
-        .downcast_ref::<BooleanArray>()
-        .unwrap()
-        .iter()
-        .collect()
+    list_value(arr, idx).as_boolean().iter().collect()
 }
 
-macro_rules! get_primitive_value {
-    ($name:ident, $t:ty, $pt:ty) => {
-        fn $name(arr: &Arc<dyn Array>, idx: usize) -> $pt {
-            arr.as_any()
-                .downcast_ref::<PrimitiveArray<$t>>()
-                .unwrap()
-                .value(idx)
-        }
-    };
+/// How values of an arrow primitive type are sent to clients, alone or as
+/// list items. Postgres has no unsigned integers: they are sent as the signed
+/// type of their width, wrapping around beyond its range, unless `u32` ones
+/// are hinted as oids. Bytes are sent as `int2`, the smallest integer type.
+trait ArrowToPg: ArrowPrimitiveType {
+    type Pg;
+
+    fn to_pg(value: Self::Native) -> Self::Pg;
 }
 
-get_primitive_value!(get_i8_value, Int8Type, i8);
-get_primitive_value!(get_i16_value, Int16Type, i16);
-get_primitive_value!(get_i32_value, Int32Type, i32);
-get_primitive_value!(get_i64_value, Int64Type, i64);
-get_primitive_value!(get_u8_value, UInt8Type, u8);
-get_primitive_value!(get_u16_value, UInt16Type, u16);
-get_primitive_value!(get_u32_value, UInt32Type, u32);
-get_primitive_value!(get_u64_value, UInt64Type, u64);
-get_primitive_value!(get_f32_value, Float32Type, f32);
-get_primitive_value!(get_f64_value, Float64Type, f64);
-
-macro_rules! get_primitive_list_value {
-    ($name:ident, $t:ty, $pt:ty) => {
-        fn $name(arr: &Arc<dyn Array>, idx: usize) -> Vec<Option<$pt>> {
-            let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
-            list_arr
-                .as_any()
-                .downcast_ref::<PrimitiveArray<$t>>()
-                .unwrap()
-                .iter()
-                .collect()
-        }
-    };
+macro_rules! arrow_to_pg {
+    ($t:ty, $pg:ty) => {
+        impl ArrowToPg for $t {
+            type Pg = $pg;
 
-    ($name:ident, $t:ty, $pt:ty, $f:expr) => {
-        fn $name(arr: &Arc<dyn Array>, idx: usize) -> Vec<Option<$pt>> {
-            let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
-            list_arr
-                .as_any()
-                .downcast_ref::<PrimitiveArray<$t>>()
-                .unwrap()
-                .iter()
-                .map(|val| val.map($f))
-                .collect()
+            fn to_pg(value: Self::Native) -> $pg {
+                value as $pg
+            }
         }
     };
 }
 
-get_primitive_list_value!(get_i8_list_value, Int8Type, i8);
-get_primitive_list_value!(get_i16_list_value, Int16Type, i16);
-get_primitive_list_value!(get_i32_list_value, Int32Type, i32);
-get_primitive_list_value!(get_i64_list_value, Int64Type, i64);
-get_primitive_list_value!(get_u8_list_value, UInt8Type, i8, |val: u8| { val as i8 });
-get_primitive_list_value!(get_u16_list_value, UInt16Type, i16, |val: u16| {
-    val as i16
-});
-get_primitive_list_value!(get_u32_list_value, UInt32Type, u32);
-get_primitive_list_value!(get_u64_list_value, UInt64Type, i64, |val: u64| {
-    val as i64
-});
-get_primitive_list_value!(get_f32_list_value, Float32Type, f32);
-get_primitive_list_value!(get_f64_list_value, Float64Type, f64);
+arrow_to_pg!(Int8Type, i16);
+arrow_to_pg!(Int16Type, i16);
+arrow_to_pg!(Int32Type, i32);
+arrow_to_pg!(Int64Type, i64);
+arrow_to_pg!(UInt8Type, i16);
+arrow_to_pg!(UInt16Type, i16);
+arrow_to_pg!(UInt32Type, i32);
+arrow_to_pg!(UInt64Type, i64);
+arrow_to_pg!(Float32Type, f32);
+arrow_to_pg!(Float64Type, f64);
+
+/// Half floats are sent as `float4`, which has every value they have.
+impl ArrowToPg for Float16Type {
+    type Pg = f32;
+
+    fn to_pg(value: Self::Native) -> f32 {
+        value.to_f32()
+    }
+}
+
+fn pg_value<T: ArrowToPg>(arr: &Arc<dyn Array>, idx: usize) -> T::Pg {
+    T::to_pg(arr.as_primitive::<T>().value(idx))
+}
+
+fn pg_list_value<T: ArrowToPg>(arr: &Arc<dyn Array>, idx: usize) -> Vec<Option<T::Pg>> {
+    list_value(arr, idx)
+        .as_primitive::<T>()
+        .iter()
+        .map(|value| value.map(T::to_pg))
+        .collect()
+}
+
+fn float_list<T>(values: Vec<Option<T>>, extra_float_digits: i32) -> Vec<Option<Float<T>>> {
+    values
+        .into_iter()
+        .map(|value| value.map(|value| Float(value, extra_float_digits)))
+        .collect()
+}
 
 fn get_utf8_view_value(arr: &Arc<dyn Array>, idx: usize) -> &str {
     arr.as_any()
@@ -198,6 +510,13 @@ fn get_large_binary_value(arr: &Arc<dyn Array>, idx: usize) -> &[u8] {
         .value(idx)
 }
 
+fn get_fixed_size_binary_value(arr: &Arc<dyn Array>, idx: usize) -> &[u8] {
+    arr.as_any()
+        .downcast_ref::<FixedSizeBinaryArray>()
+        .unwrap()
+        .value(idx)
+}
+
 fn get_date32_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveDate> {
     arr.as_any()
         .downcast_ref::<Date32Array>()
@@ -212,84 +531,518 @@ fn get_date64_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveDate> {
         .value_as_date(idx)
 }
 
-fn get_time32_second_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveDateTime> {
+fn get_time32_second_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveTime> {
     arr.as_any()
         .downcast_ref::<Time32SecondArray>()
         .unwrap()
-        .value_as_datetime(idx)
+        .value_as_time(idx)
 }
 
-fn get_time32_millisecond_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveDateTime> {
+fn get_time32_millisecond_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveTime> {
     arr.as_any()
         .downcast_ref::<Time32MillisecondArray>()
         .unwrap()
-        .value_as_datetime(idx)
+        .value_as_time(idx)
 }
 
-fn get_time64_microsecond_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveDateTime> {
+fn get_time64_microsecond_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveTime> {
     arr.as_any()
         .downcast_ref::<Time64MicrosecondArray>()
         .unwrap()
-        .value_as_datetime(idx)
+        .value_as_time(idx)
 }
-fn get_time64_nanosecond_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveDateTime> {
+fn get_time64_nanosecond_value(arr: &Arc<dyn Array>, idx: usize) -> Option<NaiveTime> {
     arr.as_any()
         .downcast_ref::<Time64NanosecondArray>()
         .unwrap()
-        .value_as_datetime(idx)
+        .value_as_time(idx)
+}
+
+/// The entries of the map at `idx` of a map of strings to strings.
+fn get_hstore_value(arr: &Arc<dyn Array>, idx: usize) -> PgWireResult<Hstore> {
+    let entries = arr.as_map().value(idx);
+    let strings = |column: &ArrayRef| {
+        cast(column, &DataType::Utf8).map_err(|e| PgWireError::ApiError(Box::new(e)))
+    };
+    let (keys, values) = (strings(entries.column(0))?, strings(entries.column(1))?);
+    let pairs = keys
+        .as_string::<i32>()
+        .iter()
+        .zip(values.as_string::<i32>())
+        .map(|(key, value)| (key.unwrap_or_default().to_owned(), value.map(str::to_owned)))
+        .collect();
+    Ok(Hstore::new(pairs))
+}
+
+/// The interval at `idx` of an `Interval` array of any unit. Nanoseconds are
+/// truncated to microseconds.
+fn get_interval_value(arr: &Arc<dyn Array>, idx: usize) -> Option<Interval> {
+    if arr.is_null(idx) {
+        return None;
+    }
+    Some(match arr.data_type() {
+        DataType::Interval(IntervalUnit::YearMonth) => Interval {
+            months: arr.as_primitive::<IntervalYearMonthType>().value(idx),
+            days: 0,
+            microseconds: 0,
+        },
+        DataType::Interval(IntervalUnit::DayTime) => {
+            let value = arr.as_primitive::<IntervalDayTimeType>().value(idx);
+            Interval {
+                months: 0,
+                days: value.days,
+                microseconds: value.milliseconds as i64 * 1000,
+            }
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) => {
+            let value = arr.as_primitive::<IntervalMonthDayNanoType>().value(idx);
+            Interval {
+                months: value.months,
+                days: value.days,
+                microseconds: value.nanoseconds / 1000,
+            }
+        }
+        data_type => unreachable!("{data_type} is not an interval type"),
+    })
+}
+
+fn get_numeric_value(arr: &Arc<dyn Array>, idx: usize) -> Option<Numeric> {
+    let DataType::Decimal128(_, scale) = arr.data_type() else {
+        unreachable!("numeric values are read from Decimal128 arrays")
+    };
+    let arr = arr.as_primitive::<Decimal128Type>();
+    arr.is_valid(idx).then(|| Numeric {
+        value: arr.value(idx),
+        scale: *scale,
+    })
+}
+
+fn get_numeric_list_value(arr: &Arc<dyn Array>, idx: usize) -> Vec<Option<Numeric>> {
+    let values = list_value(arr, idx);
+    (0..values.len())
+        .map(|idx| get_numeric_value(&values, idx))
+        .collect()
+}
+
+fn get_interval_list_value(arr: &Arc<dyn Array>, idx: usize) -> Vec<Option<Interval>> {
+    let values = list_value(arr, idx);
+    (0..values.len())
+        .map(|idx| get_interval_value(&values, idx))
+        .collect()
+}
+
+fn get_time_list_value<T>(arr: &Arc<dyn Array>, idx: usize) -> Vec<Option<NaiveTime>>
+where
+    T: ArrowTemporalType,
+    i64: From<T::Native>,
+{
+    let list_arr = list_value(arr, idx);
+    let times = list_arr.as_primitive::<T>();
+    (0..times.len())
+        .map(|i| times.value_as_time(i).filter(|_| times.is_valid(i)))
+        .collect()
+}
+
+/// Arrow only has second and millisecond `Time32`, and microsecond and
+/// nanosecond `Time64`. Other units are rejected rather than leaving a
+/// value out of the row.
+fn unsupported_time_unit(data_type: &DataType) -> PgWireError {
+    metrics::count_unsupported_type(data_type);
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_owned(),
+        "XX000".to_owned(),
+        format!("Unsupported Datatype {data_type}"),
+    )))
+}
+
+/// Postgres timestamps have microsecond precision. Unless timestamps are
+/// strict, the nanoseconds of a timestamp are truncated, with a warning.
+fn check_timestamp_nanos(
+    nanos: i64,
+    encoder: &RowEncoder,
+    options: &EncodeOptions,
+) -> PgWireResult<()> {
+    if nanos % 1000 == 0 {
+        return Ok(());
+    }
+    let timestamp = DateTime::from_timestamp_nanos(nanos).naive_utc();
+    if options.strict_timestamps {
+        return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "22008".to_owned(),
+            format!(
+                "timestamp {timestamp} has sub-microsecond digits, which postgres can't represent"
+            ),
+        ))));
+    }
+    encoder.warn(options, EncodeWarning::NanosecondsTruncated, |column| {
+        format!("timestamp {timestamp} of column \"{column}\" has sub-microsecond digits, which are truncated")
+    });
+    Ok(())
+}
+
+/// Warn about an unsigned value sent as the negative value of the signed
+/// type of its width.
+fn warn_wrapped(encoder: &RowEncoder, options: &EncodeOptions, value: u64, wrapped: i64) {
+    encoder.warn(options, EncodeWarning::UnsignedWrapped, |column| {
+        format!(
+            "value {value} of column \"{column}\" is out of range for its type, sent as {wrapped}"
+        )
+    });
+}
+
+/// Fail on string and binary values larger than the maximum field size,
+/// rather than on the size limits of the protocol.
+fn check_field_size(
+    arr: &Arc<dyn Array>,
+    idx: usize,
+    field: &FieldInfo,
+    options: &EncodeOptions,
+) -> PgWireResult<()> {
+    let size = match arr.data_type() {
+        DataType::Utf8 => arr.as_string::<i32>().value(idx).len(),
+        DataType::LargeUtf8 => arr.as_string::<i64>().value(idx).len(),
+        DataType::Utf8View => arr.as_string_view().value(idx).len(),
+        DataType::Binary => arr.as_binary::<i32>().value(idx).len(),
+        DataType::LargeBinary => arr.as_binary::<i64>().value(idx).len(),
+        DataType::BinaryView => arr.as_binary_view().value(idx).len(),
+        DataType::FixedSizeBinary(size) => *size as usize,
+        _ => return Ok(()),
+    };
+    if size > options.max_field_size {
+        return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "54000".to_owned(),
+            format!(
+                "value of column \"{}\" is {size} bytes, more than the maximum field size of {} bytes",
+                field.name(),
+                options.max_field_size
+            ),
+        ))));
+    }
+    Ok(())
+}
+
+fn is_reg_oid(pg_type: &Type) -> bool {
+    matches!(*pg_type, Type::REGCLASS | Type::REGPROC)
+}
+
+fn is_inet(pg_type: &Type) -> bool {
+    matches!(*pg_type, Type::INET | Type::CIDR)
+}
+
+/// Right pad a `bpchar` value with spaces to the length of its column, in
+/// characters like postgres.
+fn pad_bpchar(text: &str, length: Option<usize>) -> Cow<'_, str> {
+    match length {
+        Some(length) => Cow::Owned(format!("{text:<length$}")),
+        None => Cow::Borrowed(text),
+    }
+}
+
+fn parse_inet(pg_type: &Type, text: &str) -> PgWireResult<Inet> {
+    Inet::parse(pg_type, text).map_err(|e| {
+        PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "22P02".to_owned(),
+            e.to_string(),
+        )))
+    })
+}
+
+/// Encodes the values of a row like pgwire's `DataRowEncoder`, but into a
+/// buffer shared by the rows of a batch, which each `DataRow` is split off
+/// of, rather than into a new buffer per row.
+struct RowEncoder<'a> {
+    fields: &'a [FieldInfo],
+    buffer: &'a mut BytesMut,
+    col_index: usize,
+}
+
+/// Rows are split off buffers of this size, which are only allocated again
+/// once full.
+const ROWS_BUFFER_CAPACITY: usize = 64 * 1024;
+
+impl RowEncoder<'_> {
+    fn new<'a>(fields: &'a [FieldInfo], buffer: &'a mut BytesMut) -> RowEncoder<'a> {
+        // drop what a row that failed to encode left
+        buffer.clear();
+        RowEncoder {
+            fields,
+            buffer,
+            col_index: 0,
+        }
+    }
+
+    /// The format of the next field.
+    fn format(&self) -> FieldFormat {
+        self.fields[self.col_index].format()
+    }
+
+    /// Warn about a loss the value of the next field is sent despite, when
+    /// warnings are collected.
+    fn warn(
+        &self,
+        options: &EncodeOptions,
+        warning: EncodeWarning,
+        message: impl FnOnce(&str) -> String,
+    ) {
+        if let Some(warnings) = &options.warnings {
+            let column = self.fields[self.col_index].name();
+            warnings.raise(warning, column, || message(column));
+        }
+    }
+
+    fn encode_field<T: ToSql + ToSqlText>(&mut self, value: &T) -> PgWireResult<()> {
+        let field = &self.fields[self.col_index];
+        // write the length as -1 for NULL, then overwrite it once the value
+        // is written
+        let length_index = self.buffer.len();
+        self.buffer.put_i32(-1);
+        let is_null = match field.format() {
+            FieldFormat::Text => value.to_sql_text(field.datatype(), self.buffer)?,
+            FieldFormat::Binary => value.to_sql(field.datatype(), self.buffer)?,
+        };
+        if let IsNull::No = is_null {
+            let length = (self.buffer.len() - length_index - 4) as i32;
+            self.buffer[length_index..length_index + 4].copy_from_slice(&length.to_be_bytes());
+        }
+        self.col_index += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> DataRow {
+        DataRow::new(self.buffer.split(), self.col_index as i16)
+    }
+}
+
+/// Encode the value at `idx` of `arr` on its own, like in a column of
+/// `pg_type` in `format`, or `None` for NULL.
+fn encode_single(
+    arr: &Arc<dyn Array>,
+    idx: usize,
+    pg_type: Type,
+    bpchar_length: Option<usize>,
+    format: FieldFormat,
+    options: &EncodeOptions,
+) -> PgWireResult<Option<Vec<u8>>> {
+    if arr.is_null(idx) {
+        return Ok(None);
+    }
+    let fields = [FieldInfo::new(String::new(), None, None, pg_type, format)];
+    let mut buffer = BytesMut::new();
+    let mut encoder = RowEncoder::new(&fields, &mut buffer);
+    encode_value(
+        &mut encoder,
+        arr,
+        idx,
+        fields[0].datatype(),
+        bpchar_length,
+        options,
+    )?;
+    // the length of the only value of the row, then its bytes
+    let row = encoder.finish();
+    let length = i32::from_be_bytes([row.data[0], row.data[1], row.data[2], row.data[3]]);
+    Ok((length >= 0).then(|| row.data[4..].to_vec()))
+}
+
+/// The fields of the struct at `idx` of `arr`, each encoded in `format` like
+/// a column of its type.
+fn get_record_value(
+    arr: &Arc<dyn Array>,
+    idx: usize,
+    format: FieldFormat,
+    options: &EncodeOptions,
+) -> PgWireResult<Record> {
+    let arr = arr.as_struct();
+    let mut fields = Vec::with_capacity(arr.num_columns());
+    for (field, column) in arr.fields().iter().zip(arr.columns()) {
+        let pg_type = field_into_pg_type(field)?;
+        let value = encode_single(
+            column,
+            idx,
+            pg_type.clone(),
+            bpchar_length(field),
+            format,
+            options,
+        )?;
+        fields.push((pg_type, value));
+    }
+    Ok(Record(fields))
+}
+
+/// Zone timestamptz values are written in: like postgres, the `TimeZone` of
+/// the session rather than the zone of their column, which is only used
+/// without a session.
+fn timestamptz_zone(column_zone: &str, options: &EncodeOptions) -> PgWireResult<Tz> {
+    match options.time_zone {
+        Some(tz) => Ok(tz),
+        None => Tz::from_str(column_zone).map_err(|e| PgWireError::ApiError(Box::new(e))),
+    }
 }
 
 fn encode_value(
-    encoder: &mut DataRowEncoder,
+    encoder: &mut RowEncoder,
     arr: &Arc<dyn Array>,
     idx: usize,
+    pg_type: &Type,
+    bpchar_length: Option<usize>,
+    options: &EncodeOptions,
 ) -> PgWireResult<()> {
     match arr.data_type() {
+        _ if *pg_type == Type::VOID => encoder.encode_field(&Void)?,
         DataType::Null => encoder.encode_field(&None::<i8>)?,
         DataType::Boolean => encoder.encode_field(&get_bool_value(arr, idx))?,
-        DataType::Int8 => encoder.encode_field(&get_i8_value(arr, idx))?,
-        DataType::Int16 => encoder.encode_field(&get_i16_value(arr, idx))?,
-        DataType::Int32 => encoder.encode_field(&get_i32_value(arr, idx))?,
-        DataType::Int64 => encoder.encode_field(&get_i64_value(arr, idx))?,
-        DataType::UInt8 => encoder.encode_field(&(get_u8_value(arr, idx) as i8))?,
-        DataType::UInt16 => encoder.encode_field(&(get_u16_value(arr, idx) as i16))?,
-        DataType::UInt32 => encoder.encode_field(&get_u32_value(arr, idx))?,
-        DataType::UInt64 => encoder.encode_field(&(get_u64_value(arr, idx) as i64))?,
-        DataType::Float32 => encoder.encode_field(&get_f32_value(arr, idx))?,
-        DataType::Float64 => encoder.encode_field(&get_f64_value(arr, idx))?,
+        DataType::Int8 if *pg_type == Type::CHAR => {
+            encoder.encode_field(&PgChar(arr.as_primitive::<Int8Type>().value(idx) as u8))?
+        }
+        DataType::Int8 => encoder.encode_field(&pg_value::<Int8Type>(arr, idx))?,
+        DataType::Int16 => encoder.encode_field(&pg_value::<Int16Type>(arr, idx))?,
+        DataType::Int32 if *pg_type == Type::OID => {
+            encoder.encode_field(&(pg_value::<Int32Type>(arr, idx) as u32))?
+        }
+        DataType::Int32 if *pg_type == Type::REGTYPE => {
+            encoder.encode_field(&RegType(pg_value::<Int32Type>(arr, idx) as u32))?
+        }
+        DataType::Int32 if is_reg_oid(pg_type) => {
+            encoder.encode_field(&RegOid(pg_value::<Int32Type>(arr, idx) as u32))?
+        }
+        DataType::Int32 => encoder.encode_field(&pg_value::<Int32Type>(arr, idx))?,
+        DataType::Int64 => encoder.encode_field(&pg_value::<Int64Type>(arr, idx))?,
+        DataType::UInt8 if *pg_type == Type::CHAR => {
+            encoder.encode_field(&PgChar(arr.as_primitive::<UInt8Type>().value(idx)))?
+        }
+        DataType::UInt8 => encoder.encode_field(&pg_value::<UInt8Type>(arr, idx))?,
+        DataType::UInt16 => {
+            let value = pg_value::<UInt16Type>(arr, idx);
+            if value < 0 {
+                warn_wrapped(encoder, options, value as u16 as u64, value as i64);
+            }
+            encoder.encode_field(&value)?
+        }
+        DataType::UInt32 if *pg_type == Type::OID => {
+            encoder.encode_field(&arr.as_primitive::<UInt32Type>().value(idx))?
+        }
+        DataType::UInt32 if *pg_type == Type::REGTYPE => {
+            encoder.encode_field(&RegType(arr.as_primitive::<UInt32Type>().value(idx)))?
+        }
+        DataType::UInt32 if is_reg_oid(pg_type) => {
+            encoder.encode_field(&RegOid(arr.as_primitive::<UInt32Type>().value(idx)))?
+        }
+        DataType::UInt32 => {
+            let value = pg_value::<UInt32Type>(arr, idx);
+            if value < 0 {
+                warn_wrapped(encoder, options, value as u32 as u64, value as i64);
+            }
+            encoder.encode_field(&value)?
+        }
+        DataType::UInt64 => {
+            let value = pg_value::<UInt64Type>(arr, idx);
+            if value < 0 {
+                warn_wrapped(encoder, options, value as u64, value);
+            }
+            encoder.encode_field(&value)?
+        }
+        DataType::Float16 => encoder.encode_field(&Float(
+            pg_value::<Float16Type>(arr, idx),
+            options.extra_float_digits,
+        ))?,
+        DataType::Float32 => encoder.encode_field(&Float(
+            pg_value::<Float32Type>(arr, idx),
+            options.extra_float_digits,
+        ))?,
+        DataType::Float64 => encoder.encode_field(&Float(
+            pg_value::<Float64Type>(arr, idx),
+            options.extra_float_digits,
+        ))?,
+        // strings are borrowed from the arrow value buffer, and copied once
+        // into the row buffer, in both text and binary format
+        DataType::Utf8 if is_inet(pg_type) => {
+            encoder.encode_field(&parse_inet(pg_type, get_utf8_value(arr, idx))?)?
+        }
+        DataType::Utf8View if is_inet(pg_type) => {
+            encoder.encode_field(&parse_inet(pg_type, get_utf8_view_value(arr, idx))?)?
+        }
+        DataType::LargeUtf8 if is_inet(pg_type) => {
+            encoder.encode_field(&parse_inet(pg_type, get_large_utf8_value(arr, idx))?)?
+        }
+        DataType::Utf8 if *pg_type == Type::BPCHAR => {
+            encoder.encode_field(&pad_bpchar(get_utf8_value(arr, idx), bpchar_length).as_ref())?
+        }
+        DataType::Utf8View if *pg_type == Type::BPCHAR => encoder
+            .encode_field(&pad_bpchar(get_utf8_view_value(arr, idx), bpchar_length).as_ref())?,
+        DataType::LargeUtf8 if *pg_type == Type::BPCHAR => encoder
+            .encode_field(&pad_bpchar(get_large_utf8_value(arr, idx), bpchar_length).as_ref())?,
         DataType::Utf8 => encoder.encode_field(&get_utf8_value(arr, idx))?,
         DataType::Utf8View => encoder.encode_field(&get_utf8_view_value(arr, idx))?,
         DataType::LargeUtf8 => encoder.encode_field(&get_large_utf8_value(arr, idx))?,
+        DataType::Binary if *pg_type == Type::VARBIT => {
+            encoder.encode_field(&VarBit(get_binary_value(arr, idx)))?
+        }
+        DataType::LargeBinary if *pg_type == Type::VARBIT => {
+            encoder.encode_field(&VarBit(get_large_binary_value(arr, idx)))?
+        }
+        DataType::FixedSizeBinary(_) if *pg_type == Type::VARBIT => {
+            encoder.encode_field(&VarBit(get_fixed_size_binary_value(arr, idx)))?
+        }
         DataType::Binary => encoder.encode_field(&get_binary_value(arr, idx))?,
         DataType::LargeBinary => encoder.encode_field(&get_large_binary_value(arr, idx))?,
+        DataType::FixedSizeBinary(_) if *pg_type == Type::UUID => {
+            encoder.encode_field(&Uuid(get_fixed_size_binary_value(arr, idx)))?
+        }
+        DataType::FixedSizeBinary(_) => {
+            encoder.encode_field(&get_fixed_size_binary_value(arr, idx))?
+        }
         DataType::Date32 => encoder.encode_field(&get_date32_value(arr, idx))?,
         DataType::Date64 => encoder.encode_field(&get_date64_value(arr, idx))?,
+        DataType::Decimal128(_, scale) if *pg_type == Type::MONEY => {
+            let value = arr
+                .as_any()
+                .downcast_ref::<Decimal128Array>()
+                .unwrap()
+                .value(idx);
+            let money = Money::from_decimal(value, *scale).ok_or_else(|| {
+                PgWireError::UserError(Box::new(ErrorInfo::new(
+                    "ERROR".to_owned(),
+                    "22003".to_owned(),
+                    "money out of range".to_owned(),
+                )))
+            })?;
+            encoder.encode_field(&money)?
+        }
+        DataType::Decimal128(_, _) => encoder.encode_field(&get_numeric_value(arr, idx))?,
+        DataType::Interval(_) => encoder.encode_field(&get_interval_value(arr, idx))?,
+        DataType::Map(_, _) if pg_type.name() == "hstore" => {
+            encoder.encode_field(&get_hstore_value(arr, idx)?)?
+        }
+        DataType::Struct(_) => {
+            encoder.encode_field(&get_record_value(arr, idx, encoder.format(), options)?)?
+        }
         DataType::Time32(unit) => match unit {
             TimeUnit::Second => encoder.encode_field(&get_time32_second_value(arr, idx))?,
             TimeUnit::Millisecond => {
                 encoder.encode_field(&get_time32_millisecond_value(arr, idx))?
             }
-            _ => {}
+            _ => return Err(unsupported_time_unit(arr.data_type())),
         },
         DataType::Time64(unit) => match unit {
             TimeUnit::Microsecond => {
                 encoder.encode_field(&get_time64_microsecond_value(arr, idx))?
             }
             TimeUnit::Nanosecond => encoder.encode_field(&get_time64_nanosecond_value(arr, idx))?,
-            _ => {}
+            _ => return Err(unsupported_time_unit(arr.data_type())),
         },
         DataType::Timestamp(unit, timezone) => match unit {
             TimeUnit::Second => {
                 let ts_array = arr.as_any().downcast_ref::<TimestampSecondArray>().unwrap();
                 if let Some(tz) = timezone {
-                    let tz = Tz::from_str(tz.as_ref())
-                        .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+                    let tz = timestamptz_zone(tz, options)?;
                     let value = ts_array
                         .value_as_datetime_with_tz(idx, tz)
-                        .map(|d| d.fixed_offset());
+                        .map(|d| Timestamp(d.fixed_offset()));
                     encoder.encode_field(&value)?;
                 } else {
-                    let value = ts_array.value_as_datetime(idx);
+                    let value = ts_array.value_as_datetime(idx).map(Timestamp);
                     encoder.encode_field(&value)?
                 }
             }
@@ -299,14 +1052,13 @@ fn encode_value(
                     .downcast_ref::<TimestampMillisecondArray>()
                     .unwrap();
                 if let Some(tz) = timezone {
-                    let tz = Tz::from_str(tz.as_ref())
-                        .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+                    let tz = timestamptz_zone(tz, options)?;
                     let value = ts_array
                         .value_as_datetime_with_tz(idx, tz)
-                        .map(|d| d.fixed_offset());
+                        .map(|d| Timestamp(d.fixed_offset()));
                     encoder.encode_field(&value)?;
                 } else {
-                    let value = ts_array.value_as_datetime(idx);
+                    let value = ts_array.value_as_datetime(idx).map(Timestamp);
                     encoder.encode_field(&value)?
                 }
             }
@@ -316,14 +1068,13 @@ fn encode_value(
                     .downcast_ref::<TimestampMicrosecondArray>()
                     .unwrap();
                 if let Some(tz) = timezone {
-                    let tz = Tz::from_str(tz.as_ref())
-                        .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+                    let tz = timestamptz_zone(tz, options)?;
                     let value = ts_array
                         .value_as_datetime_with_tz(idx, tz)
-                        .map(|d| d.fixed_offset());
+                        .map(|d| Timestamp(d.fixed_offset()));
                     encoder.encode_field(&value)?;
                 } else {
-                    let value = ts_array.value_as_datetime(idx);
+                    let value = ts_array.value_as_datetime(idx).map(Timestamp);
                     encoder.encode_field(&value)?
                 }
             }
@@ -332,36 +1083,68 @@ fn encode_value(
                     .as_any()
                     .downcast_ref::<TimestampNanosecondArray>()
                     .unwrap();
+                check_timestamp_nanos(ts_array.value(idx), encoder, options)?;
                 if let Some(tz) = timezone {
-                    let tz = Tz::from_str(tz.as_ref())
-                        .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+                    let tz = timestamptz_zone(tz, options)?;
                     let value = ts_array
                         .value_as_datetime_with_tz(idx, tz)
-                        .map(|d| d.fixed_offset());
+                        .map(|d| Timestamp(d.fixed_offset()));
                     encoder.encode_field(&value)?;
                 } else {
-                    let value = ts_array.value_as_datetime(idx);
+                    let value = ts_array.value_as_datetime(idx).map(Timestamp);
                     encoder.encode_field(&value)?
                 }
             }
         },
 
-        DataType::List(field) | DataType::FixedSizeList(field, _) | DataType::LargeList(field) => {
+        DataType::List(field)
+        | DataType::FixedSizeList(field, _)
+        | DataType::LargeList(field)
+        | DataType::ListView(field)
+        | DataType::LargeListView(field) => {
             match field.data_type() {
                 DataType::Null => encoder.encode_field(&None::<i8>)?,
                 DataType::Boolean => encoder.encode_field(&get_bool_list_value(arr, idx))?,
-                DataType::Int8 => encoder.encode_field(&get_i8_list_value(arr, idx))?,
-                DataType::Int16 => encoder.encode_field(&get_i16_list_value(arr, idx))?,
-                DataType::Int32 => encoder.encode_field(&get_i32_list_value(arr, idx))?,
-                DataType::Int64 => encoder.encode_field(&get_i64_list_value(arr, idx))?,
-                DataType::UInt8 => encoder.encode_field(&get_u8_list_value(arr, idx))?,
-                DataType::UInt16 => encoder.encode_field(&get_u16_list_value(arr, idx))?,
-                DataType::UInt32 => encoder.encode_field(&get_u32_list_value(arr, idx))?,
-                DataType::UInt64 => encoder.encode_field(&get_u64_list_value(arr, idx))?,
-                DataType::Float32 => encoder.encode_field(&get_f32_list_value(arr, idx))?,
-                DataType::Float64 => encoder.encode_field(&get_f64_list_value(arr, idx))?,
+                DataType::Int8 => encoder.encode_field(&pg_list_value::<Int8Type>(arr, idx))?,
+                DataType::Int16 => encoder.encode_field(&pg_list_value::<Int16Type>(arr, idx))?,
+                DataType::Int32 => encoder.encode_field(&pg_list_value::<Int32Type>(arr, idx))?,
+                DataType::Int64 => encoder.encode_field(&pg_list_value::<Int64Type>(arr, idx))?,
+                DataType::UInt8 => encoder.encode_field(&pg_list_value::<UInt8Type>(arr, idx))?,
+                DataType::UInt16 => {
+                    let value = pg_list_value::<UInt16Type>(arr, idx);
+                    if let Some(&wrapped) = value.iter().flatten().find(|item| **item < 0) {
+                        warn_wrapped(encoder, options, wrapped as u16 as u64, wrapped as i64);
+                    }
+                    encoder.encode_field(&value)?
+                }
+                DataType::UInt32 => {
+                    let value = pg_list_value::<UInt32Type>(arr, idx);
+                    if let Some(&wrapped) = value.iter().flatten().find(|item| **item < 0) {
+                        warn_wrapped(encoder, options, wrapped as u32 as u64, wrapped as i64);
+                    }
+                    encoder.encode_field(&value)?
+                }
+                DataType::UInt64 => {
+                    let value = pg_list_value::<UInt64Type>(arr, idx);
+                    if let Some(&wrapped) = value.iter().flatten().find(|item| **item < 0) {
+                        warn_wrapped(encoder, options, wrapped as u64, wrapped);
+                    }
+                    encoder.encode_field(&value)?
+                }
+                DataType::Float16 => encoder.encode_field(&float_list(
+                    pg_list_value::<Float16Type>(arr, idx),
+                    options.extra_float_digits,
+                ))?,
+                DataType::Float32 => encoder.encode_field(&float_list(
+                    pg_list_value::<Float32Type>(arr, idx),
+                    options.extra_float_digits,
+                ))?,
+                DataType::Float64 => encoder.encode_field(&float_list(
+                    pg_list_value::<Float64Type>(arr, idx),
+                    options.extra_float_digits,
+                ))?,
                 DataType::Utf8 => {
-                    let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
+                    let list_arr = list_value(arr, idx);
                     let value: Vec<_> = list_arr
                         .as_any()
                         .downcast_ref::<StringArray>()
@@ -371,28 +1154,52 @@ fn encode_value(
                     encoder.encode_field(&value)?
                 }
                 DataType::Binary => {
-                    let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
+                    let list_arr = list_value(arr, idx);
                     let value: Vec<_> = list_arr
                         .as_any()
                         .downcast_ref::<BinaryArray>()
                         .unwrap()
                         .iter()
+                        .map(|value| value.map(Bytea))
                         .collect();
                     encoder.encode_field(&value)?
                 }
                 DataType::LargeBinary => {
-                    let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
+                    let list_arr = list_value(arr, idx);
                     let value: Vec<_> = list_arr
                         .as_any()
                         .downcast_ref::<LargeBinaryArray>()
                         .unwrap()
                         .iter()
+                        .map(|value| value.map(Bytea))
+                        .collect();
+                    encoder.encode_field(&value)?
+                }
+                DataType::FixedSizeBinary(_) if *pg_type == Type::UUID_ARRAY => {
+                    let list_arr = list_value(arr, idx);
+                    let value: Vec<_> = list_arr
+                        .as_any()
+                        .downcast_ref::<FixedSizeBinaryArray>()
+                        .unwrap()
+                        .iter()
+                        .map(|value| value.map(Uuid))
+                        .collect();
+                    encoder.encode_field(&value)?
+                }
+                DataType::FixedSizeBinary(_) => {
+                    let list_arr = list_value(arr, idx);
+                    let value: Vec<_> = list_arr
+                        .as_any()
+                        .downcast_ref::<FixedSizeBinaryArray>()
+                        .unwrap()
+                        .iter()
+                        .map(|value| value.map(Bytea))
                         .collect();
                     encoder.encode_field(&value)?
                 }
 
                 DataType::Date32 => {
-                    let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
+                    let list_arr = list_value(arr, idx);
                     let value: Vec<_> = list_arr
                         .as_any()
                         .downcast_ref::<Date32Array>()
@@ -402,7 +1209,7 @@ fn encode_value(
                     encoder.encode_field(&value)?
                 }
                 DataType::Date64 => {
-                    let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
+                    let list_arr = list_value(arr, idx);
                     let value: Vec<_> = list_arr
                         .as_any()
                         .downcast_ref::<Date64Array>()
@@ -411,56 +1218,36 @@ fn encode_value(
                         .collect();
                     encoder.encode_field(&value)?
                 }
+                DataType::Interval(_) => {
+                    encoder.encode_field(&get_interval_list_value(arr, idx))?
+                }
+                DataType::Decimal128(_, _) => {
+                    encoder.encode_field(&get_numeric_list_value(arr, idx))?
+                }
                 DataType::Time32(unit) => match unit {
                     TimeUnit::Second => {
-                        let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
-                        let value: Vec<_> = list_arr
-                            .as_any()
-                            .downcast_ref::<Time32SecondArray>()
-                            .unwrap()
-                            .iter()
-                            .collect();
-                        encoder.encode_field(&value)?
+                        encoder.encode_field(&get_time_list_value::<Time32SecondType>(arr, idx))?
                     }
                     TimeUnit::Millisecond => {
-                        let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
-                        let value: Vec<_> = list_arr
-                            .as_any()
-                            .downcast_ref::<Time32MillisecondArray>()
-                            .unwrap()
-                            .iter()
-                            .collect();
-                        encoder.encode_field(&value)?
+                        encoder
+                            .encode_field(&get_time_list_value::<Time32MillisecondType>(arr, idx))?
                     }
-                    _ => {}
+                    _ => return Err(unsupported_time_unit(arr.data_type())),
                 },
                 DataType::Time64(unit) => match unit {
                     TimeUnit::Microsecond => {
-                        let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
-                        let value: Vec<_> = list_arr
-                            .as_any()
-                            .downcast_ref::<Time64MicrosecondArray>()
-                            .unwrap()
-                            .iter()
-                            .collect();
-                        encoder.encode_field(&value)?
+                        encoder
+                            .encode_field(&get_time_list_value::<Time64MicrosecondType>(arr, idx))?
                     }
                     TimeUnit::Nanosecond => {
-                        let list_arr = arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
-                        let value: Vec<_> = list_arr
-                            .as_any()
-                            .downcast_ref::<Time64NanosecondArray>()
-                            .unwrap()
-                            .iter()
-                            .collect();
-                        encoder.encode_field(&value)?
+                        encoder
+                            .encode_field(&get_time_list_value::<Time64NanosecondType>(arr, idx))?
                     }
-                    _ => {}
+                    _ => return Err(unsupported_time_unit(arr.data_type())),
                 },
                 DataType::Timestamp(unit, timezone) => match unit {
                     TimeUnit::Second => {
-                        let list_array =
-                            arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
+                        let list_array = list_value(arr, idx);
                         let array_iter = list_array
                             .as_any()
                             .downcast_ref::<TimestampSecondArray>()
@@ -468,15 +1255,16 @@ fn encode_value(
                             .iter();
 
                         if let Some(tz) = timezone {
-                            let tz = Tz::from_str(tz.as_ref())
-                                .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+                            let tz = timestamptz_zone(tz, options)?;
                             let value: Vec<_> = array_iter
                                 .map(|i| {
                                     i.and_then(|i| {
                                         DateTime::from_timestamp(i, 0).map(|dt| {
-                                            Utc.from_utc_datetime(&dt.naive_utc())
-                                                .with_timezone(&tz)
-                                                .fixed_offset()
+                                            Timestamp(
+                                                Utc.from_utc_datetime(&dt.naive_utc())
+                                                    .with_timezone(&tz)
+                                                    .fixed_offset(),
+                                            )
                                         })
                                     })
                                 })
@@ -486,7 +1274,8 @@ fn encode_value(
                             let value: Vec<_> = array_iter
                                 .map(|i| {
                                     i.and_then(|i| {
-                                        DateTime::from_timestamp(i, 0).map(|dt| dt.naive_utc())
+                                        DateTime::from_timestamp(i, 0)
+                                            .map(|dt| Timestamp(dt.naive_utc()))
                                     })
                                 })
                                 .collect();
@@ -494,8 +1283,7 @@ fn encode_value(
                         }
                     }
                     TimeUnit::Millisecond => {
-                        let list_array =
-                            arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
+                        let list_array = list_value(arr, idx);
                         let array_iter = list_array
                             .as_any()
                             .downcast_ref::<TimestampMillisecondArray>()
@@ -503,15 +1291,16 @@ fn encode_value(
                             .iter();
 
                         if let Some(tz) = timezone {
-                            let tz = Tz::from_str(tz.as_ref())
-                                .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+                            let tz = timestamptz_zone(tz, options)?;
                             let value: Vec<_> = array_iter
                                 .map(|i| {
                                     i.and_then(|i| {
                                         DateTime::from_timestamp_millis(i).map(|dt| {
-                                            Utc.from_utc_datetime(&dt.naive_utc())
-                                                .with_timezone(&tz)
-                                                .fixed_offset()
+                                            Timestamp(
+                                                Utc.from_utc_datetime(&dt.naive_utc())
+                                                    .with_timezone(&tz)
+                                                    .fixed_offset(),
+                                            )
                                         })
                                     })
                                 })
@@ -521,7 +1310,8 @@ fn encode_value(
                             let value: Vec<_> = array_iter
                                 .map(|i| {
                                     i.and_then(|i| {
-                                        DateTime::from_timestamp_millis(i).map(|dt| dt.naive_utc())
+                                        DateTime::from_timestamp_millis(i)
+                                            .map(|dt| Timestamp(dt.naive_utc()))
                                     })
                                 })
                                 .collect();
@@ -529,8 +1319,7 @@ fn encode_value(
                         }
                     }
                     TimeUnit::Microsecond => {
-                        let list_array =
-                            arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
+                        let list_array = list_value(arr, idx);
                         let array_iter = list_array
                             .as_any()
                             .downcast_ref::<TimestampMicrosecondArray>()
@@ -538,15 +1327,16 @@ fn encode_value(
                             .iter();
 
                         if let Some(tz) = timezone {
-                            let tz = Tz::from_str(tz.as_ref())
-                                .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+                            let tz = timestamptz_zone(tz, options)?;
                             let value: Vec<_> = array_iter
                                 .map(|i| {
                                     i.and_then(|i| {
                                         DateTime::from_timestamp_micros(i).map(|dt| {
-                                            Utc.from_utc_datetime(&dt.naive_utc())
-                                                .with_timezone(&tz)
-                                                .fixed_offset()
+                                            Timestamp(
+                                                Utc.from_utc_datetime(&dt.naive_utc())
+                                                    .with_timezone(&tz)
+                                                    .fixed_offset(),
+                                            )
                                         })
                                     })
                                 })
@@ -556,7 +1346,8 @@ fn encode_value(
                             let value: Vec<_> = array_iter
                                 .map(|i| {
                                     i.and_then(|i| {
-                                        DateTime::from_timestamp_micros(i).map(|dt| dt.naive_utc())
+                                        DateTime::from_timestamp_micros(i)
+                                            .map(|dt| Timestamp(dt.naive_utc()))
                                     })
                                 })
                                 .collect();
@@ -564,40 +1355,68 @@ fn encode_value(
                         }
                     }
                     TimeUnit::Nanosecond => {
-                        let list_array =
-                            arr.as_any().downcast_ref::<ListArray>().unwrap().value(idx);
-                        let array_iter = list_array
+                        let list_array = list_value(arr, idx);
+                        let ts_array = list_array
                             .as_any()
                             .downcast_ref::<TimestampNanosecondArray>()
-                            .unwrap()
-                            .iter();
+                            .unwrap();
+                        for nanos in ts_array.iter().flatten() {
+                            check_timestamp_nanos(nanos, encoder, options)?;
+                        }
+                        let array_iter = ts_array.iter();
 
                         if let Some(tz) = timezone {
-                            let tz = Tz::from_str(tz.as_ref())
-                                .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+                            let tz = timestamptz_zone(tz, options)?;
                             let value: Vec<_> = array_iter
                                 .map(|i| {
                                     i.map(|i| {
-                                        Utc.from_utc_datetime(
-                                            &DateTime::from_timestamp_nanos(i).naive_utc(),
+                                        Timestamp(
+                                            Utc.from_utc_datetime(
+                                                &DateTime::from_timestamp_nanos(i).naive_utc(),
+                                            )
+                                            .with_timezone(&tz)
+                                            .fixed_offset(),
                                         )
-                                        .with_timezone(&tz)
-                                        .fixed_offset()
                                     })
                                 })
                                 .collect();
                             encoder.encode_field(&value)?;
                         } else {
                             let value: Vec<_> = array_iter
-                                .map(|i| i.map(|i| DateTime::from_timestamp_nanos(i).naive_utc()))
+                                .map(|i| {
+                                    i.map(|i| {
+                                        Timestamp(DateTime::from_timestamp_nanos(i).naive_utc())
+                                    })
+                                })
                                 .collect();
                             encoder.encode_field(&value)?
                         }
                     }
                 },
 
+                // the values of dictionary encoded strings, like the lists
+                // of low-cardinality strings built by `array_agg`
+                DataType::Dictionary(_, value) if is_string_type(value) => {
+                    let items = cast(&list_value(arr, idx), &DataType::Utf8)
+                        .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+                    let value: Vec<_> = items.as_string::<i32>().iter().collect();
+                    encoder.encode_field(&value)?
+                }
+                DataType::Struct(_) => {
+                    let items = list_value(arr, idx);
+                    let value = (0..items.len())
+                        .map(|item| {
+                            (!items.is_null(item))
+                                .then(|| get_record_value(&items, item, encoder.format(), options))
+                                .transpose()
+                        })
+                        .collect::<PgWireResult<Vec<_>>>()?;
+                    encoder.encode_field(&value)?
+                }
+
                 // TODO: more types
                 list_type => {
+                    metrics::count_unsupported_type(arr.data_type());
                     return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
                         "ERROR".to_owned(),
                         "XX000".to_owned(),
@@ -605,11 +1424,12 @@ fn encode_value(
                             "Unsupported List Datatype {} and array {:?}",
                             list_type, &arr
                         ),
-                    ))))
+                    ))));
                 }
             }
         }
         _ => {
+            metrics::count_unsupported_type(arr.data_type());
             return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
                 "ERROR".to_owned(),
                 "XX000".to_owned(),
@@ -618,45 +1438,218 @@ fn encode_value(
                     arr.data_type(),
                     &arr
                 ),
-            ))))
+            ))));
+        }
+    }
+    Ok(())
+}
+
+fn is_number_type(pg_type: &Type) -> bool {
+    matches!(
+        *pg_type,
+        Type::INT2
+            | Type::INT4
+            | Type::INT8
+            | Type::FLOAT4
+            | Type::FLOAT8
+            | Type::INT2_ARRAY
+            | Type::INT4_ARRAY
+            | Type::INT8_ARRAY
+            | Type::FLOAT4_ARRAY
+            | Type::FLOAT8_ARRAY
+    )
+}
+
+/// Fail on columns of a type that `options` don't allow. Lists are judged
+/// by the type of their items.
+pub(crate) fn check_allowed_types(schema: &DFSchema, options: &EncodeOptions) -> PgWireResult<()> {
+    for field in schema.fields() {
+        let mut data_type = field.data_type();
+        while let DataType::List(item)
+        | DataType::FixedSizeList(item, _)
+        | DataType::LargeList(item)
+        | DataType::ListView(item)
+        | DataType::LargeListView(item) = data_type
+        {
+            data_type = item.data_type();
+        }
+
+        let allowed = options
+            .allowed_types
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(data_type));
+        if !allowed || options.denied_types.contains(data_type) {
+            return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "0A000".to_owned(),
+                format!(
+                    "column \"{}\" has type {data_type}, which is not allowed",
+                    field.name()
+                ),
+            ))));
         }
     }
     Ok(())
 }
 
+/// Check that every column of `schema` has a postgres type its values can
+/// be sent as, to find out when registering a table rather than when
+/// querying it. The error names the first column that hasn't.
+///
+/// Maps are reported as unsupported, as they are only sent as `hstore` when
+/// the server knows its oid.
+///
+/// ```
+/// use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+/// use datafusion::common::DFSchema;
+/// use datafusion_postgres::validate_pg_encodable;
+///
+/// let schema = Schema::new(vec![
+///     Field::new("id", DataType::Int64, false),
+///     Field::new_list("tags", Field::new_list_field(DataType::Utf8, true), true),
+/// ]);
+/// assert!(validate_pg_encodable(&DFSchema::try_from(schema).unwrap()).is_ok());
+///
+/// let schema = Schema::new(vec![Field::new_list(
+///     "elapsed",
+///     Field::new_list_field(DataType::Duration(TimeUnit::Second), true),
+///     true,
+/// )]);
+/// let error = validate_pg_encodable(&DFSchema::try_from(schema).unwrap()).unwrap_err();
+/// assert!(error.to_string().contains("column \"elapsed\""));
+/// ```
+pub fn validate_pg_encodable(schema: &DFSchema) -> PgWireResult<()> {
+    for field in schema.fields() {
+        field_into_pg_type(field).map_err(|e| match e {
+            PgWireError::UserError(mut info) => {
+                info.message = format!("column \"{}\": {}", field.name(), info.message);
+                PgWireError::UserError(info)
+            }
+            e => e,
+        })?;
+    }
+    Ok(())
+}
+
+/// The schema results of `schema` are described and encoded with, after the
+/// schema transform if any.
+fn output_schema<'a>(
+    schema: &'a DFSchema,
+    options: &EncodeOptions,
+) -> PgWireResult<Cow<'a, DFSchema>> {
+    let Some(SchemaTransformFn(transform)) = &options.schema_transform else {
+        return Ok(Cow::Borrowed(schema));
+    };
+    let transformed = transform(schema);
+    let same_columns = transformed.fields().len() == schema.fields().len()
+        && iter::zip(transformed.fields(), schema.fields())
+            .all(|(new, old)| new.data_type() == old.data_type());
+    if !same_columns {
+        return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "XX000".to_owned(),
+            "schema transform changed the number or types of the columns".to_owned(),
+        ))));
+    }
+    Ok(Cow::Owned(transformed))
+}
+
 pub(crate) fn df_schema_to_pg_fields(
     schema: &DFSchema,
     format: &Format,
+    options: &EncodeOptions,
 ) -> PgWireResult<Vec<FieldInfo>> {
+    let schema = output_schema(schema, options)?;
+    output_schema_to_pg_fields(&schema, format, options)
+}
+
+fn output_schema_to_pg_fields(
+    schema: &DFSchema,
+    format: &Format,
+    options: &EncodeOptions,
+) -> PgWireResult<Vec<FieldInfo>> {
+    check_allowed_types(schema, options)?;
+    check_result_formats(format, schema.fields().len())?;
+    let mut names = HashSet::new();
     schema
         .fields()
         .iter()
         .enumerate()
         .map(|(idx, f)| {
-            let pg_type = into_pg_type(f.data_type())?;
-            Ok(FieldInfo::new(
-                f.name().into(),
-                None,
-                None,
-                pg_type,
-                format.format_for(idx),
-            ))
+            let mut name = f.name().clone();
+            if options.unique_column_names {
+                let mut suffix = 1;
+                while !names.insert(name.clone()) {
+                    name = format!("{}_{suffix}", f.name());
+                    suffix += 1;
+                }
+            }
+            let pg_type = match options.hstore_oid {
+                Some(oid) if is_string_map(f.data_type()) => Hstore::pg_type(oid),
+                _ => field_into_pg_type(f)?,
+            };
+            let field_format = if options.text_numbers && is_number_type(&pg_type) {
+                FieldFormat::Text
+            } else {
+                format.format_for(idx)
+            };
+            Ok(FieldInfo::new(name, None, None, pg_type, field_format))
         })
         .collect::<PgWireResult<Vec<FieldInfo>>>()
 }
 
-pub(crate) async fn encode_dataframe<'a>(
+/// Like postgres, reject format codes given for each result column that
+/// aren't one per column, or that are neither text nor binary.
+#[allow(clippy::result_large_err)]
+fn check_result_formats(format: &Format, columns: usize) -> PgWireResult<()> {
+    let Format::Individual(codes) = format else {
+        return Ok(());
+    };
+    if codes.len() != columns {
+        return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "08P01".to_owned(),
+            format!(
+                "bind message has {} result formats but query has {columns} columns",
+                codes.len()
+            ),
+        ))));
+    }
+    match codes.iter().find(|code| !matches!(code, 0 | 1)) {
+        Some(code) => Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "22023".to_owned(),
+            format!("unsupported format code: {code}"),
+        )))),
+        None => Ok(()),
+    }
+}
+
+/// Encode the rows of `df` as a query response. Its rows are computed as the
+/// response is consumed, and dropping it stops the execution of the query.
+pub async fn encode_dataframe<'a>(
     df: DataFrame,
     format: &Format,
+    options: &EncodeOptions,
 ) -> PgWireResult<QueryResponse<'a>> {
-    let fields = Arc::new(df_schema_to_pg_fields(df.schema(), format)?);
+    let schema = output_schema(df.schema(), options)?;
+    let fields = Arc::new(output_schema_to_pg_fields(&schema, format, options)?);
+    // row descriptions have no type modifiers, so the lengths of `bpchar`
+    // columns are kept aside
+    let bpchar_lengths: Arc<Vec<_>> = Arc::new(
+        schema
+            .fields()
+            .iter()
+            .map(|field| bpchar_length(field))
+            .collect(),
+    );
 
-    let recordbatch_stream = df
-        .execute_stream()
+    let recordbatch_stream = cooperative::execute_stream(df)
         .await
-        .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+        .map_err(errors::datafusion_error)?;
 
     let fields_ref = fields.clone();
+    let options = options.clone();
     let pg_row_stream = recordbatch_stream
         .map(move |rb: datafusion::error::Result<RecordBatch>| {
             let row_stream: Box<dyn Iterator<Item = _> + Send> = match rb {
@@ -665,22 +1658,33 @@ pub(crate) async fn encode_dataframe<'a>(
                     let cols = rb.num_columns();
 
                     let fields = fields_ref.clone();
+                    let bpchar_lengths = bpchar_lengths.clone();
+                    let options = options.clone();
+                    let mut buffer = BytesMut::with_capacity(ROWS_BUFFER_CAPACITY);
 
                     let row_stream = (0..rows).map(move |row| {
-                        let mut encoder = DataRowEncoder::new(fields.clone());
+                        let mut encoder = RowEncoder::new(&fields, &mut buffer);
                         for col in 0..cols {
                             let array = rb.column(col);
                             if array.is_null(row) {
-                                encoder.encode_field(&None::<i8>).unwrap();
+                                encoder.encode_field(&None::<i8>)?;
                             } else {
-                                encode_value(&mut encoder, array, row).unwrap();
+                                check_field_size(array, row, &fields[col], &options)?;
+                                encode_value(
+                                    &mut encoder,
+                                    array,
+                                    row,
+                                    fields[col].datatype(),
+                                    bpchar_lengths[col],
+                                    &options,
+                                )?;
                             }
                         }
-                        encoder.finish()
+                        Ok(encoder.finish())
                     });
                     Box::new(row_stream)
                 }
-                Err(e) => Box::new(iter::once(Err(PgWireError::ApiError(e.into())))),
+                Err(e) => Box::new(iter::once(Err(errors::datafusion_error(e)))),
             };
 
             stream::iter(row_stream)
@@ -690,20 +1694,41 @@ pub(crate) async fn encode_dataframe<'a>(
     Ok(QueryResponse::new(fields, pg_row_stream))
 }
 
+/// The text of the value at `idx` of `arr`, as postgres writes it, or `None`
+/// for NULL. Values are written by the encoder of text columns, the one
+/// `COPY` text and CSV data is also written with, so none of them diverge.
+pub fn value_to_pg_text(arr: &ArrayRef, idx: usize) -> PgWireResult<Option<String>> {
+    let pg_type = into_pg_type(arr.data_type())?;
+    let options = EncodeOptions::default();
+    let Some(value) = encode_single(arr, idx, pg_type, None, FieldFormat::Text, &options)? else {
+        return Ok(None);
+    };
+    String::from_utf8(value)
+        .map(Some)
+        .map_err(|e| PgWireError::ApiError(Box::new(e)))
+}
+
 /// Deserialize client provided parameter data.
 ///
 /// First we try to use the type information from `pg_type_hint`, which is
 /// provided by the client.
 /// If the type is empty or unknown, we fallback to datafusion inferenced type
 /// from `inferenced_types`.
-/// An error will be raised when neither sources can provide type information.
+/// A NULL value without type information from either source is bound as an
+/// untyped `ScalarValue::Null`, like Postgres tolerates untyped NULLs.
+/// Other values without type information from either source are read as
+/// `text`, like Postgres does for parameters of unknown type.
+/// See [`ParameterOptions`] for how `options` change this.
 pub(crate) fn deserialize_parameters<S>(
     portal: &Portal<S>,
     inferenced_types: &[Option<&DataType>],
+    options: &ParameterOptions,
 ) -> PgWireResult<ParamValues>
 where
     S: Clone,
 {
+    // like postgres, parameters of unknown type that can't be inferred from
+    // the query either, like the argument of `octet_length($1)`, are text
     fn get_pg_type(
         pg_type_hint: Option<&Type>,
         inferenced_type: Option<&DataType>,
@@ -713,26 +1738,63 @@ where
         } else if let Some(infer_type) = inferenced_type {
             into_pg_type(infer_type)
         } else {
-            Err(PgWireError::UserError(Box::new(ErrorInfo::new(
-                "FATAL".to_string(),
-                "XX000".to_string(),
-                "Unknown parameter type".to_string(),
-            ))))
+            Ok(Type::TEXT)
         }
     }
 
     let param_len = portal.parameter_len();
+    if param_len > options.max_parameters {
+        return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "54000".to_owned(),
+            format!(
+                "bind message has {param_len} parameters, more than the maximum of {}",
+                options.max_parameters
+            ),
+        ))));
+    }
     let mut deserialized_params = Vec::with_capacity(param_len);
     for i in 0..param_len {
-        let pg_type = get_pg_type(
-            portal.statement.parameter_types.get(i),
-            inferenced_types.get(i).and_then(|v| v.to_owned()),
-        )?;
+        // clients send oid 0 for parameters they leave for the server to infer
+        let pg_type_hint = portal
+            .statement
+            .parameter_types
+            .get(i)
+            .filter(|ty| **ty != Type::UNKNOWN);
+        let inferenced_type = inferenced_types.get(i).and_then(|v| v.to_owned());
+
+        if pg_type_hint.is_none() && inferenced_type.is_none() && portal.parameters[i].is_none() {
+            deserialized_params.push(ScalarValue::Null);
+            continue;
+        }
+
+        if let Some(inferred) = inferenced_type.filter(|_| options.inferred_types) {
+            let generic_hint = pg_type_hint.filter(|ty| matches!(**ty, Type::TEXT | Type::VARCHAR));
+            let string_inferred = matches!(
+                inferred,
+                DataType::Null | DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+            );
+            if let Some(hint) = generic_hint.filter(|_| !string_inferred) {
+                // the text of a value, whatever the format it is sent in
+                let value = ScalarValue::Utf8(portal.parameter::<String>(i, hint)?);
+                let value = value.cast_to(inferred).map_err(|e| {
+                    PgWireError::UserError(Box::new(ErrorInfo::new(
+                        "ERROR".to_owned(),
+                        "22P02".to_owned(),
+                        format!("invalid value for parameter ${}: {e}", i + 1),
+                    )))
+                })?;
+                deserialized_params.push(value);
+                continue;
+            }
+        }
+
+        let pg_type = get_pg_type(pg_type_hint, inferenced_type)?;
         match pg_type {
             // enumerate all supported parameter types and deserialize the
             // type to ScalarValue
             Type::BOOL => {
-                let value = portal.parameter::<bool>(i, &pg_type)?;
+                let value = parameter::<bool, _>(portal, i, &pg_type)?;
                 deserialized_params.push(ScalarValue::Boolean(value));
             }
             Type::CHAR => {
@@ -751,12 +1813,16 @@ where
                 let value = portal.parameter::<i64>(i, &pg_type)?;
                 deserialized_params.push(ScalarValue::Int64(value));
             }
+            Type::OID => {
+                let value = parameter::<u32, _>(portal, i, &pg_type)?;
+                deserialized_params.push(ScalarValue::UInt32(value));
+            }
             Type::TEXT | Type::VARCHAR => {
                 let value = portal.parameter::<String>(i, &pg_type)?;
                 deserialized_params.push(ScalarValue::Utf8(value));
             }
             Type::BYTEA => {
-                let value = portal.parameter::<Vec<u8>>(i, &pg_type)?;
+                let value = parameter::<Vec<u8>, _>(portal, i, &pg_type)?;
                 deserialized_params.push(ScalarValue::Binary(value));
             }
 
@@ -783,10 +1849,77 @@ where
                 ));
             }
             Type::DATE => {
-                let value = portal.parameter::<NaiveDate>(i, &pg_type)?;
+                let value = parameter::<NaiveDate, _>(portal, i, &pg_type)
+                    .map_err(|e| invalid_parameter(e, i))?;
                 deserialized_params
                     .push(ScalarValue::Date32(value.map(Date32Type::from_naive_date)));
             }
+            Type::NUMERIC => {
+                let value = parameter::<Numeric, _>(portal, i, &pg_type)
+                    .map_err(|e| invalid_parameter(e, i))?;
+                deserialized_params.push(match value {
+                    Some(numeric) => ScalarValue::Decimal128(
+                        Some(numeric.value),
+                        DECIMAL128_MAX_PRECISION,
+                        numeric.scale,
+                    ),
+                    None => ScalarValue::Decimal128(None, DECIMAL128_MAX_PRECISION, 0),
+                });
+            }
+            Type::MONEY => {
+                let value = parameter::<Money, _>(portal, i, &pg_type)
+                    .map_err(|e| invalid_parameter(e, i))?;
+                deserialized_params.push(ScalarValue::Decimal128(
+                    value.map(|money| money.0 as i128),
+                    19,
+                    2,
+                ));
+            }
+            Type::VARBIT => {
+                let value = parameter::<VarBit<Vec<u8>>, _>(portal, i, &pg_type)
+                    .map_err(|e| invalid_parameter(e, i))?;
+                deserialized_params.push(ScalarValue::Binary(value.map(|bits| bits.0)));
+            }
+            Type::INET | Type::CIDR => {
+                let value = parameter::<Inet, _>(portal, i, &pg_type)
+                    .map_err(|e| invalid_parameter(e, i))?;
+                deserialized_params.push(ScalarValue::Utf8(value.map(|inet| inet.to_string())));
+            }
+            Type::XML => {
+                let value = parameter::<Xml, _>(portal, i, &pg_type)
+                    .map_err(|e| invalid_parameter(e, i))?;
+                if let Some(xml) = value.as_ref().filter(|_| options.validate_xml) {
+                    xml.check_well_formed().map_err(|e| {
+                        PgWireError::UserError(Box::new(ErrorInfo::new(
+                            "ERROR".to_owned(),
+                            "2200N".to_owned(),
+                            format!("invalid XML content in parameter ${}: {e}", i + 1),
+                        )))
+                    })?;
+                }
+                deserialized_params.push(ScalarValue::Utf8(value.map(|xml| xml.0)));
+            }
+            Type::JSONPATH => {
+                let value = parameter::<JsonPath, _>(portal, i, &pg_type)
+                    .map_err(|e| invalid_parameter(e, i))?;
+                deserialized_params.push(ScalarValue::Utf8(value.map(|path| path.0)));
+            }
+            Type::INTERVAL => {
+                let value = parameter::<Interval, _>(portal, i, &pg_type)
+                    .map_err(|e| invalid_parameter(e, i))?;
+                let unit = match inferenced_type {
+                    Some(DataType::Interval(unit)) => *unit,
+                    _ => IntervalUnit::MonthDayNano,
+                };
+                let value = interval_scalar(value, unit).ok_or_else(|| {
+                    PgWireError::UserError(Box::new(ErrorInfo::new(
+                        "ERROR".to_owned(),
+                        "22008".to_owned(),
+                        format!("interval out of range for parameter ${}", i + 1),
+                    )))
+                })?;
+                deserialized_params.push(value);
+            }
             // TODO: add more types
             _ => {
                 return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
@@ -800,3 +1933,73 @@ where
 
     Ok(ParamValues::List(deserialized_params))
 }
+
+/// An interval parameter in the layout DataFusion expects, `None` if it
+/// can't hold it. Like for results, `YearMonth` intervals are like postgres'
+/// `interval year to month`, dropping days and time, and `DayTime` ones like
+/// `interval day to second(3)`, rounding time to milliseconds, but without
+/// months.
+fn interval_scalar(value: Option<Interval>, unit: IntervalUnit) -> Option<ScalarValue> {
+    let Some(value) = value else {
+        return ScalarValue::try_from(DataType::Interval(unit)).ok();
+    };
+    Some(match unit {
+        IntervalUnit::YearMonth => ScalarValue::IntervalYearMonth(Some(value.months)),
+        IntervalUnit::DayTime => {
+            if value.months != 0 {
+                return None;
+            }
+            let half = 500 * value.microseconds.signum();
+            let milliseconds = value.microseconds.saturating_add(half) / 1000;
+            ScalarValue::IntervalDayTime(Some(IntervalDayTime::new(
+                value.days,
+                milliseconds.try_into().ok()?,
+            )))
+        }
+        IntervalUnit::MonthDayNano => {
+            ScalarValue::IntervalMonthDayNano(Some(IntervalMonthDayNano::new(
+                value.months,
+                value.days,
+                value.microseconds.checked_mul(1000)?,
+            )))
+        }
+    })
+}
+
+/// Decode a parameter sent in either format, unlike `Portal::parameter`
+/// which always decodes the binary format.
+fn parameter<T, S>(portal: &Portal<S>, idx: usize, pg_type: &Type) -> PgWireResult<Option<T>>
+where
+    T: FromSqlOwned + FromSqlText,
+    S: Clone,
+{
+    if portal.parameter_format.is_binary(idx) {
+        return portal.parameter(idx, pg_type);
+    }
+    let Some(param) = portal.parameters.get(idx).and_then(Option::as_ref) else {
+        return Ok(None);
+    };
+    T::from_sql_text(pg_type, param).map(Some).map_err(|e| {
+        PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "22P02".to_owned(),
+            format!("invalid value for parameter ${}: {e}", idx + 1),
+        )))
+    })
+}
+
+/// Values of a valid type that can't be converted, like numerics too large
+/// for a decimal, shouldn't cost the connection.
+fn invalid_parameter(e: PgWireError, idx: usize) -> PgWireError {
+    match e {
+        PgWireError::FailedToParseParameter(e) => PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "22P03".to_owned(),
+            format!("invalid value for parameter ${}: {e}", idx + 1),
+        ))),
+        e => e,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,338 @@
+//! Random record batches fed to `encode_dataframe` in text and binary
+//! formats, which must either encode them or fail with an error, and never
+//! panic.
+//!
+//! Each case is generated from its seed, printed when it panics. Run more
+//! cases than the default with
+//! `ENCODE_FUZZ_CASES=100000 cargo test -p datafusion-postgres encode_random_batches`.
+
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    new_empty_array, new_null_array, ArrayRef, FixedSizeBinaryArray, FixedSizeListArray,
+    Float64Array, Int64Array, IntervalDayTimeArray, IntervalMonthDayNanoArray,
+    IntervalYearMonthArray, LargeListArray, LargeListViewArray, ListArray, ListViewArray,
+    StringArray,
+};
+use datafusion::arrow::buffer::{NullBuffer, OffsetBuffer};
+use datafusion::arrow::compute::{cast_with_options, CastOptions};
+use datafusion::arrow::datatypes::{
+    DataType, Field, IntervalDayTime, IntervalMonthDayNano, IntervalUnit, Schema, TimeUnit,
+};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::prelude::SessionContext;
+use futures::{FutureExt, StreamExt};
+use pgwire::api::portal::Format;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::datatypes::{encode_dataframe, EncodeOptions};
+
+const DEFAULT_CASES: u64 = 500;
+const MAX_ROWS: usize = 8;
+const MAX_LIST_LENGTH: usize = 4;
+
+const INTEGERS: [i64; 9] = [
+    0,
+    1,
+    -1,
+    i8::MIN as i64,
+    i16::MAX as i64,
+    i32::MIN as i64,
+    i32::MAX as i64,
+    i64::MIN,
+    i64::MAX,
+];
+const FLOATS: [f64; 7] = [
+    0.0,
+    -0.0,
+    0.1,
+    f64::NAN,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+    1e300,
+];
+const CHARS: [char; 12] = ['a', 'Z', '0', ' ', ',', '"', '\\', '{', '}', 'é', '€', '\n'];
+
+fn random_type(rng: &mut StdRng, nested: bool) -> DataType {
+    let time_unit = [
+        TimeUnit::Second,
+        TimeUnit::Millisecond,
+        TimeUnit::Microsecond,
+        TimeUnit::Nanosecond,
+    ][rng.gen_range(0..4)];
+    let time_zone = [None, Some("UTC"), Some("+05:30")][rng.gen_range(0..3)].map(Into::into);
+    let item = |rng: &mut StdRng| Arc::new(Field::new_list_field(random_type(rng, true), true));
+    match rng.gen_range(0..if nested { 29 } else { 34 }) {
+        0 => DataType::Null,
+        1 => DataType::Boolean,
+        2 => DataType::Int8,
+        3 => DataType::Int16,
+        4 => DataType::Int32,
+        5 => DataType::Int64,
+        6 => DataType::UInt8,
+        7 => DataType::UInt16,
+        8 => DataType::UInt32,
+        9 => DataType::UInt64,
+        10 => DataType::Float16,
+        11 => DataType::Float32,
+        12 => DataType::Float64,
+        13 => DataType::Utf8,
+        14 => DataType::LargeUtf8,
+        15 => DataType::Utf8View,
+        16 => DataType::Binary,
+        17 => DataType::LargeBinary,
+        18 => DataType::BinaryView,
+        19 => DataType::FixedSizeBinary(rng.gen_range(1..17)),
+        20 => DataType::Date32,
+        21 => DataType::Date64,
+        22 => DataType::Time32([TimeUnit::Second, TimeUnit::Millisecond][rng.gen_range(0..2)]),
+        23 => DataType::Time64([TimeUnit::Microsecond, TimeUnit::Nanosecond][rng.gen_range(0..2)]),
+        24 => DataType::Timestamp(time_unit, time_zone),
+        25 => DataType::Duration(time_unit),
+        26 => DataType::Interval(
+            [
+                IntervalUnit::YearMonth,
+                IntervalUnit::DayTime,
+                IntervalUnit::MonthDayNano,
+            ][rng.gen_range(0..3)],
+        ),
+        27 => {
+            let precision = rng.gen_range(1..39);
+            DataType::Decimal128(precision, rng.gen_range(-2..=precision as i8))
+        }
+        28 => DataType::Dictionary(
+            Box::new(
+                [DataType::Int8, DataType::Int32, DataType::UInt16][rng.gen_range(0..3)].clone(),
+            ),
+            Box::new([DataType::Utf8, DataType::LargeUtf8][rng.gen_range(0..2)].clone()),
+        ),
+        29 => DataType::List(item(rng)),
+        30 => DataType::LargeList(item(rng)),
+        31 => DataType::ListView(item(rng)),
+        32 => DataType::LargeListView(item(rng)),
+        _ => DataType::FixedSizeList(item(rng), rng.gen_range(1..4)),
+    }
+}
+
+/// Edge values, small values and any values, in equal proportions.
+fn random_integer(rng: &mut StdRng) -> i64 {
+    match rng.gen_range(0..3) {
+        0 => INTEGERS[rng.gen_range(0..INTEGERS.len())],
+        1 => rng.gen_range(-100_000..100_000),
+        _ => rng.gen(),
+    }
+}
+
+fn random_nulls(rng: &mut StdRng, len: usize) -> Option<NullBuffer> {
+    rng.gen_bool(0.5)
+        .then(|| NullBuffer::from_iter((0..len).map(|_| rng.gen_bool(0.7))))
+}
+
+fn random_array(rng: &mut StdRng, data_type: &DataType, len: usize) -> ArrayRef {
+    match data_type {
+        DataType::Null => new_null_array(data_type, len),
+        DataType::List(item) => {
+            let lengths: Vec<_> = (0..len)
+                .map(|_| rng.gen_range(0..=MAX_LIST_LENGTH))
+                .collect();
+            let values = random_array(rng, item.data_type(), lengths.iter().sum());
+            let nulls = random_nulls(rng, len);
+            Arc::new(ListArray::new(
+                item.clone(),
+                OffsetBuffer::from_lengths(lengths),
+                values,
+                nulls,
+            ))
+        }
+        DataType::LargeList(item) => {
+            let lengths: Vec<_> = (0..len)
+                .map(|_| rng.gen_range(0..=MAX_LIST_LENGTH))
+                .collect();
+            let values = random_array(rng, item.data_type(), lengths.iter().sum());
+            let nulls = random_nulls(rng, len);
+            Arc::new(LargeListArray::new(
+                item.clone(),
+                OffsetBuffer::from_lengths(lengths),
+                values,
+                nulls,
+            ))
+        }
+        // views of items anywhere in their values, overlapping or not
+        DataType::ListView(item) | DataType::LargeListView(item) => {
+            let values_len = rng.gen_range(0..=MAX_LIST_LENGTH * 2);
+            let values = random_array(rng, item.data_type(), values_len);
+            let (offsets, sizes): (Vec<_>, Vec<_>) = (0..len)
+                .map(|_| {
+                    let size = rng.gen_range(0..=values_len.min(MAX_LIST_LENGTH));
+                    (rng.gen_range(0..=values_len - size), size)
+                })
+                .unzip();
+            let nulls = random_nulls(rng, len);
+            if matches!(data_type, DataType::ListView(_)) {
+                let offsets = offsets.into_iter().map(|offset| offset as i32).collect();
+                let sizes = sizes.into_iter().map(|size| size as i32).collect();
+                Arc::new(ListViewArray::new(
+                    item.clone(),
+                    offsets,
+                    sizes,
+                    values,
+                    nulls,
+                ))
+            } else {
+                let offsets = offsets.into_iter().map(|offset| offset as i64).collect();
+                let sizes = sizes.into_iter().map(|size| size as i64).collect();
+                Arc::new(LargeListViewArray::new(
+                    item.clone(),
+                    offsets,
+                    sizes,
+                    values,
+                    nulls,
+                ))
+            }
+        }
+        DataType::FixedSizeList(item, size) => {
+            let values = random_array(rng, item.data_type(), len * *size as usize);
+            let nulls = random_nulls(rng, len);
+            Arc::new(FixedSizeListArray::new(item.clone(), *size, values, nulls))
+        }
+        DataType::FixedSizeBinary(size) => {
+            let values = (0..len).map(|_| {
+                rng.gen_bool(0.8)
+                    .then(|| (0..*size).map(|_| rng.gen()).collect::<Vec<u8>>())
+            });
+            if len == 0 {
+                new_empty_array(data_type)
+            } else {
+                Arc::new(
+                    FixedSizeBinaryArray::try_from_sparse_iter_with_size(values, *size).unwrap(),
+                )
+            }
+        }
+        DataType::Utf8
+        | DataType::LargeUtf8
+        | DataType::Utf8View
+        | DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::Dictionary(_, _) => {
+            let strings: StringArray = (0..len)
+                .map(|_| {
+                    rng.gen_bool(0.8).then(|| {
+                        let length = rng.gen_range(0..8);
+                        (0..length)
+                            .map(|_| CHARS[rng.gen_range(0..CHARS.len())])
+                            .collect::<String>()
+                    })
+                })
+                .collect();
+            cast(&(Arc::new(strings) as ArrayRef), data_type)
+        }
+        DataType::Interval(unit) => {
+            let valid: Vec<_> = (0..len).map(|_| rng.gen_bool(0.8)).collect();
+            let values = valid.into_iter();
+            match unit {
+                IntervalUnit::YearMonth => Arc::new(IntervalYearMonthArray::from_iter(
+                    values.map(|valid| valid.then(|| random_integer(rng) as i32)),
+                )),
+                IntervalUnit::DayTime => {
+                    Arc::new(IntervalDayTimeArray::from_iter(values.map(|valid| {
+                        valid.then(|| {
+                            IntervalDayTime::new(
+                                random_integer(rng) as i32,
+                                random_integer(rng) as i32,
+                            )
+                        })
+                    })))
+                }
+                IntervalUnit::MonthDayNano => {
+                    Arc::new(IntervalMonthDayNanoArray::from_iter(values.map(|valid| {
+                        valid.then(|| {
+                            IntervalMonthDayNano::new(
+                                random_integer(rng) as i32,
+                                random_integer(rng) as i32,
+                                random_integer(rng),
+                            )
+                        })
+                    })))
+                }
+            }
+        }
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+            let floats: Float64Array = (0..len)
+                .map(|_| {
+                    rng.gen_bool(0.8).then(|| match rng.gen_range(0..2) {
+                        0 => FLOATS[rng.gen_range(0..FLOATS.len())],
+                        _ => rng.gen_range(-1e6..1e6),
+                    })
+                })
+                .collect();
+            cast(&(Arc::new(floats) as ArrayRef), data_type)
+        }
+        _ => {
+            let integers: Int64Array = (0..len)
+                .map(|_| rng.gen_bool(0.8).then(|| random_integer(rng)))
+                .collect();
+            let integers = Arc::new(integers) as ArrayRef;
+            // arrow casts some types only from 32 bit integers
+            match cast_with_options(&integers, data_type, &CAST_OPTIONS) {
+                Ok(array) => array,
+                Err(_) => cast(&cast(&integers, &DataType::Int32), data_type),
+            }
+        }
+    }
+}
+
+/// Values that don't fit the type are turned into nulls.
+const CAST_OPTIONS: CastOptions = CastOptions {
+    safe: true,
+    format_options: datafusion::arrow::util::display::FormatOptions::new(),
+};
+
+fn cast(array: &ArrayRef, data_type: &DataType) -> ArrayRef {
+    cast_with_options(array, data_type, &CAST_OPTIONS)
+        .unwrap_or_else(|e| panic!("cannot generate {data_type}: {e}"))
+}
+
+fn random_batch(rng: &mut StdRng) -> RecordBatch {
+    let rows = rng.gen_range(0..=MAX_ROWS);
+    let (fields, columns): (Vec<_>, Vec<_>) = (0..rng.gen_range(1..4))
+        .map(|i| {
+            let data_type = random_type(rng, false);
+            let column = random_array(rng, &data_type, rows);
+            (Field::new(format!("c{i}"), data_type, true), column)
+        })
+        .unzip();
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+}
+
+#[tokio::test]
+async fn encode_random_batches() {
+    let cases = std::env::var("ENCODE_FUZZ_CASES")
+        .map(|cases| cases.parse().unwrap())
+        .unwrap_or(DEFAULT_CASES);
+    let ctx = SessionContext::new();
+    let options = EncodeOptions::default();
+
+    for seed in 0..cases {
+        let batch = random_batch(&mut StdRng::seed_from_u64(seed));
+        for format in [Format::UnifiedText, Format::UnifiedBinary] {
+            let df = ctx.read_batch(batch.clone()).unwrap();
+            let encoded = AssertUnwindSafe(async {
+                // errors are fine, as long as they are returned
+                let Ok(response) = encode_dataframe(df, &format, &options).await else {
+                    return;
+                };
+                let mut rows = response.data_rows();
+                while let Some(Ok(_)) = rows.next().await {}
+            });
+            if encoded.catch_unwind().await.is_err() {
+                panic!(
+                    "encoding case {seed} in {format:?} panicked, with columns {:?}",
+                    batch.schema().fields()
+                );
+            }
+        }
+    }
+}
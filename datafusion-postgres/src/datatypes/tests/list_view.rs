@@ -0,0 +1,62 @@
+//! `ListView` and `LargeListView` columns are encoded like the `List` with
+//! the same items, whatever the order of their items in their values.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    ArrayRef, LargeListViewArray, ListArray, ListViewArray, StringArray,
+};
+use datafusion::arrow::buffer::{NullBuffer, OffsetBuffer, ScalarBuffer};
+use datafusion::arrow::datatypes::{DataType, Field};
+use pgwire::api::portal::Format;
+
+use super::encode_column;
+
+#[tokio::test]
+async fn encode_list_views_like_lists() {
+    // [a, b], [], NULL, [c, NULL]
+    let item = Arc::new(Field::new_list_field(DataType::Utf8, true));
+    let list: ArrayRef = Arc::new(ListArray::new(
+        item.clone(),
+        OffsetBuffer::from_lengths([2, 0, 0, 2]),
+        Arc::new(StringArray::from(vec![
+            Some("a"),
+            Some("b"),
+            Some("c"),
+            None,
+        ])),
+        Some(NullBuffer::from(vec![true, true, false, true])),
+    ));
+    // the same lists, with their items in another order
+    let values = Arc::new(StringArray::from(vec![
+        Some("c"),
+        None,
+        Some("a"),
+        Some("b"),
+    ]));
+    let nulls = Some(NullBuffer::from(vec![true, true, false, true]));
+    let list_view: ArrayRef = Arc::new(ListViewArray::new(
+        item.clone(),
+        ScalarBuffer::from(vec![2, 0, 0, 0]),
+        ScalarBuffer::from(vec![2, 0, 0, 2]),
+        values.clone(),
+        nulls.clone(),
+    ));
+    let large_list_view: ArrayRef = Arc::new(LargeListViewArray::new(
+        item,
+        ScalarBuffer::from(vec![2, 0, 0, 0]),
+        ScalarBuffer::from(vec![2, 0, 0, 2]),
+        values,
+        nulls,
+    ));
+
+    for format in [Format::UnifiedText, Format::UnifiedBinary] {
+        let expected = encode_column(list.clone(), &format).await;
+        assert_eq!(expected.0, 1015);
+        assert_eq!(encode_column(list_view.clone(), &format).await, expected);
+        assert_eq!(
+            encode_column(large_list_view.clone(), &format).await,
+            expected
+        );
+    }
+}
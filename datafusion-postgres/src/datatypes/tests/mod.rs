@@ -0,0 +1,70 @@
+//! Encoding of arrow columns into the rows sent to clients.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::ArrayRef;
+use datafusion::arrow::datatypes::{Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::prelude::SessionContext;
+use futures::StreamExt;
+use pgwire::api::portal::Format;
+use pgwire::api::results::FieldInfo;
+
+use crate::datatypes::{encode_dataframe, EncodeOptions};
+
+mod fuzz;
+mod list_view;
+mod numeric;
+mod primitive;
+mod records;
+mod result_formats;
+mod text_values;
+
+/// The values of a row, each with its length.
+fn fields(mut data: &[u8]) -> Vec<Option<Vec<u8>>> {
+    let mut fields = vec![];
+    while !data.is_empty() {
+        let length = i32::from_be_bytes(data[..4].try_into().unwrap());
+        data = &data[4..];
+        if length < 0 {
+            fields.push(None);
+            continue;
+        }
+        fields.push(Some(data[..length as usize].to_vec()));
+        data = &data[length as usize..];
+    }
+    fields
+}
+
+/// The fields of the response and the values of the rows of `columns`,
+/// encoded in `format`, `None` for NULL.
+async fn encode(
+    columns: Vec<ArrayRef>,
+    format: &Format,
+    options: &EncodeOptions,
+) -> (Vec<FieldInfo>, Vec<Vec<Option<Vec<u8>>>>) {
+    let schema = Schema::new(
+        columns
+            .iter()
+            .enumerate()
+            .map(|(idx, column)| Field::new(format!("c{idx}"), column.data_type().clone(), true))
+            .collect::<Vec<_>>(),
+    );
+    let batch = RecordBatch::try_new(Arc::new(schema), columns).unwrap();
+    let df = SessionContext::new().read_batch(batch).unwrap();
+    let response = encode_dataframe(df, format, options).await.unwrap();
+    let row_schema = response.row_schema().to_vec();
+    let rows = response
+        .data_rows()
+        .map(|row| fields(&row.unwrap().data))
+        .collect()
+        .await;
+    (row_schema, rows)
+}
+
+/// The oid and values of the single column `column`, encoded in `format`.
+async fn encode_column(column: ArrayRef, format: &Format) -> (u32, Vec<Option<Vec<u8>>>) {
+    let (fields, rows) = encode(vec![column], format, &EncodeOptions::default()).await;
+    let values = rows.into_iter().map(|mut row| row.remove(0)).collect();
+    (fields[0].datatype().oid(), values)
+}
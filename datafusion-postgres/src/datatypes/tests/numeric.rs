@@ -0,0 +1,139 @@
+//! Decimal columns are sent as `numeric`, in binary format as base-10000
+//! digits with a weight, a sign and the scale of the column.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, Decimal128Array};
+use datafusion::arrow::datatypes::DataType;
+use pgwire::api::portal::Format;
+
+use super::encode_column;
+
+/// A binary `numeric` value, split into its header and digits.
+#[derive(Debug, PartialEq)]
+struct BinaryNumeric {
+    weight: i16,
+    negative: bool,
+    dscale: u16,
+    digits: Vec<u16>,
+}
+
+impl BinaryNumeric {
+    fn parse(value: &[u8]) -> BinaryNumeric {
+        let word = |at: usize| u16::from_be_bytes([value[at], value[at + 1]]);
+        let ndigits = word(0) as usize;
+        assert_eq!(value.len(), 8 + ndigits * 2, "buffer size");
+        let sign = word(4);
+        assert!(sign == 0x0000 || sign == 0x4000, "sign {sign:#x}");
+        BinaryNumeric {
+            weight: word(2) as i16,
+            negative: sign == 0x4000,
+            dscale: word(6),
+            digits: (0..ndigits).map(|i| word(8 + i * 2)).collect(),
+        }
+    }
+}
+
+/// The values of a decimal column of `precision` and `scale`, in `format`.
+async fn encode(
+    values: Vec<Option<i128>>,
+    precision: u8,
+    scale: i8,
+    format: &Format,
+) -> Vec<Option<Vec<u8>>> {
+    // built without validation, so that scales above the precision can be
+    // sent as well
+    let column: ArrayRef = Arc::new(
+        Decimal128Array::from(values).with_data_type(DataType::Decimal128(precision, scale)),
+    );
+    let (oid, values) = encode_column(column, format).await;
+    assert_eq!(oid, 1700);
+    values
+}
+
+/// Check `value` is sent as `text`, and in binary format as `digits` with
+/// `weight`.
+async fn check(value: i128, precision: u8, scale: i8, text: &str, weight: i16, digits: &[u16]) {
+    let values = encode(vec![Some(value)], precision, scale, &Format::UnifiedText).await;
+    assert_eq!(values, vec![Some(text.as_bytes().to_vec())]);
+
+    let values = encode(vec![Some(value)], precision, scale, &Format::UnifiedBinary).await;
+    let expected = BinaryNumeric {
+        weight,
+        negative: value < 0,
+        dscale: scale.max(0) as u16,
+        digits: digits.to_vec(),
+    };
+    assert_eq!(
+        BinaryNumeric::parse(values[0].as_ref().unwrap()),
+        expected,
+        "{text}"
+    );
+}
+
+#[tokio::test]
+async fn encode_widest_decimals() {
+    let max = 10i128.pow(38) - 1;
+    let mut digits = vec![99];
+    digits.extend([9999; 9]);
+    check(max, 38, 0, &"9".repeat(38), 9, &digits).await;
+    check(-max, 38, 0, &format!("-{}", "9".repeat(38)), 9, &digits).await;
+
+    let mut digits = vec![9999; 9];
+    digits.push(9900);
+    check(-max, 38, 38, &format!("-0.{}", "9".repeat(38)), -1, &digits).await;
+
+    check(
+        max,
+        38,
+        19,
+        "9999999999999999999.9999999999999999999",
+        4,
+        &[999, 9999, 9999, 9999, 9999, 9999, 9999, 9999, 9999, 9990],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn encode_zero_digit_groups() {
+    // trailing zero groups of the integer part and of the fraction aren't
+    // sent, the scale keeps the zeros of the text
+    check(1_000_000, 10, 2, "10000.00", 1, &[1]).await;
+    check(
+        100_000_000_000_050,
+        20,
+        2,
+        "1000000000000.50",
+        3,
+        &[1, 0, 0, 0, 5000],
+    )
+    .await;
+    // neither are leading zero groups of the fraction
+    check(-123, 5, 5, "-0.00123", -1, &[12, 3000]).await;
+    check(1, 10, 10, "0.0000000001", -3, &[100]).await;
+    check(0, 10, 3, "0.000", 0, &[]).await;
+}
+
+#[tokio::test]
+async fn encode_scales_beyond_precision() {
+    check(123, 5, 8, "0.00000123", -2, &[123]).await;
+    check(-7, 1, 9, "-0.000000007", -3, &[7000]).await;
+    // negative scales multiply the value, without digits after the point
+    check(42, 5, -3, "42000", 1, &[4, 2000]).await;
+    check(-5, 5, -4, "-50000", 1, &[5]).await;
+}
+
+#[tokio::test]
+async fn encode_null_decimals() {
+    let values = encode(vec![None, Some(1)], 10, 2, &Format::UnifiedBinary).await;
+    assert_eq!(values[0], None);
+    assert_eq!(
+        BinaryNumeric::parse(values[1].as_ref().unwrap()),
+        BinaryNumeric {
+            weight: -1,
+            negative: false,
+            dscale: 2,
+            digits: vec![100],
+        }
+    );
+}
@@ -0,0 +1,293 @@
+//! Boolean, integer and float columns are sent as the postgres type of their
+//! width, with list items encoded exactly like the same values in a column.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    ArrayRef, BooleanArray, Float32Array, Float64Array, Int16Array, Int32Array, Int64Array,
+    Int8Array, ListArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+};
+use datafusion::arrow::buffer::{NullBuffer, OffsetBuffer};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Field};
+use pgwire::api::portal::Format;
+
+use super::{encode, encode_column};
+use crate::datatypes::EncodeOptions;
+
+/// The element oid and items of a one-dimensional array in binary format.
+fn binary_array_items(value: &[u8]) -> (u32, Vec<Option<Vec<u8>>>) {
+    let int = |at: usize| i32::from_be_bytes(value[at..at + 4].try_into().unwrap());
+    assert_eq!(int(0), 1, "dimensions");
+    let mut items = vec![];
+    let mut at = 20;
+    for _ in 0..int(12) {
+        let length = int(at);
+        at += 4;
+        if length < 0 {
+            items.push(None);
+        } else {
+            items.push(Some(value[at..at + length as usize].to_vec()));
+            at += length as usize;
+        }
+    }
+    (int(8) as u32, items)
+}
+
+/// Check `column` is sent with `oid`, its values with `text`, and that a list
+/// of the same values is sent as an array of them, in both formats.
+async fn check(column: ArrayRef, oid: u32, array_oid: u32, text: &[Option<&str>]) {
+    let (scalar_oid, values) = encode_column(column.clone(), &Format::UnifiedText).await;
+    assert_eq!(scalar_oid, oid, "{}", column.data_type());
+    let expected: Vec<_> = text
+        .iter()
+        .map(|v| v.map(|v| v.as_bytes().to_vec()))
+        .collect();
+    assert_eq!(values, expected, "{}", column.data_type());
+
+    let item = Arc::new(Field::new_list_field(column.data_type().clone(), true));
+    let list: ArrayRef = Arc::new(ListArray::new(
+        item,
+        OffsetBuffer::from_lengths([column.len()]),
+        column.clone(),
+        None,
+    ));
+
+    let (list_oid, values) = encode_column(list.clone(), &Format::UnifiedText).await;
+    assert_eq!(list_oid, array_oid, "{}", list.data_type());
+    let items = text.iter().map(|v| v.unwrap_or("NULL")).collect::<Vec<_>>();
+    let expected = format!("{{{}}}", items.join(","));
+    assert_eq!(
+        values,
+        vec![Some(expected.into_bytes())],
+        "{}",
+        list.data_type()
+    );
+
+    let (_, scalars) = encode_column(column.clone(), &Format::UnifiedBinary).await;
+    let (_, values) = encode_column(list, &Format::UnifiedBinary).await;
+    let (item_oid, items) = binary_array_items(values[0].as_ref().unwrap());
+    assert_eq!(item_oid, oid, "{}", column.data_type());
+    assert_eq!(items, scalars, "{}", column.data_type());
+}
+
+#[tokio::test]
+async fn encode_signed_integers() {
+    check(
+        Arc::new(Int8Array::from(vec![
+            Some(i8::MIN),
+            Some(-1),
+            None,
+            Some(i8::MAX),
+        ])),
+        21,
+        1005,
+        &[Some("-128"), Some("-1"), None, Some("127")],
+    )
+    .await;
+    check(
+        Arc::new(Int16Array::from(vec![Some(i16::MIN), None, Some(i16::MAX)])),
+        21,
+        1005,
+        &[Some("-32768"), None, Some("32767")],
+    )
+    .await;
+    check(
+        Arc::new(Int32Array::from(vec![Some(i32::MIN), None, Some(i32::MAX)])),
+        23,
+        1007,
+        &[Some("-2147483648"), None, Some("2147483647")],
+    )
+    .await;
+    check(
+        Arc::new(Int64Array::from(vec![Some(i64::MIN), None, Some(i64::MAX)])),
+        20,
+        1016,
+        &[
+            Some("-9223372036854775808"),
+            None,
+            Some("9223372036854775807"),
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn encode_unsigned_integers() {
+    check(
+        Arc::new(UInt8Array::from(vec![Some(0), None, Some(u8::MAX)])),
+        21,
+        1005,
+        &[Some("0"), None, Some("255")],
+    )
+    .await;
+    // unsigned values beyond the range of the signed type of their width
+    // wrap around
+    check(
+        Arc::new(UInt16Array::from(vec![
+            Some(0),
+            None,
+            Some(i16::MAX as u16),
+            Some(u16::MAX),
+        ])),
+        21,
+        1005,
+        &[Some("0"), None, Some("32767"), Some("-1")],
+    )
+    .await;
+    check(
+        Arc::new(UInt32Array::from(vec![
+            Some(0),
+            None,
+            Some(i32::MAX as u32),
+            Some(3_000_000_000),
+            Some(u32::MAX),
+        ])),
+        23,
+        1007,
+        &[
+            Some("0"),
+            None,
+            Some("2147483647"),
+            Some("-1294967296"),
+            Some("-1"),
+        ],
+    )
+    .await;
+    check(
+        Arc::new(UInt64Array::from(vec![
+            Some(0),
+            None,
+            Some(i64::MAX as u64),
+            Some(u64::MAX),
+        ])),
+        20,
+        1016,
+        &[Some("0"), None, Some("9223372036854775807"), Some("-1")],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn encode_floats() {
+    check(
+        Arc::new(Float32Array::from(vec![
+            Some(1.5),
+            None,
+            Some(f32::NAN),
+            Some(f32::NEG_INFINITY),
+        ])),
+        700,
+        1021,
+        &[Some("1.5"), None, Some("NaN"), Some("-Infinity")],
+    )
+    .await;
+    check(
+        Arc::new(Float64Array::from(vec![
+            Some(0.1),
+            None,
+            Some(f64::INFINITY),
+        ])),
+        701,
+        1022,
+        &[Some("0.1"), None, Some("Infinity")],
+    )
+    .await;
+    // half floats have no type of their own, they are sent as `float4`
+    let halves: ArrayRef = Arc::new(Float32Array::from(vec![
+        Some(1.5),
+        None,
+        Some(-0.25),
+        Some(65504.0),
+        Some(f32::INFINITY),
+    ]));
+    check(
+        cast(&halves, &DataType::Float16).unwrap(),
+        700,
+        1021,
+        &[
+            Some("1.5"),
+            None,
+            Some("-0.25"),
+            Some("65504"),
+            Some("Infinity"),
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn encode_shortest_floats() {
+    // like postgres, in exponent notation below 1e-4 and from 10 to the
+    // number of digits of the type on
+    let doubles: ArrayRef = Arc::new(Float64Array::from(vec![
+        1e20,
+        1e-7,
+        -1.5e300,
+        1e15,
+        123456789012345.0,
+        0.0001,
+        -0.0,
+    ]));
+    let reals: ArrayRef = Arc::new(Float32Array::from(vec![1e7, 123456.0, 1.2345678e-5]));
+    for extra_float_digits in [1, 3] {
+        let options = EncodeOptions {
+            extra_float_digits,
+            ..EncodeOptions::default()
+        };
+        let (_, rows) = encode(vec![doubles.clone()], &Format::UnifiedText, &options).await;
+        let values = rows.into_iter().map(|mut row| row.remove(0).unwrap());
+        assert_eq!(
+            values.collect::<Vec<_>>(),
+            [
+                &b"1e+20"[..],
+                b"1e-07",
+                b"-1.5e+300",
+                b"1e+15",
+                b"123456789012345",
+                b"0.0001",
+                b"-0",
+            ]
+        );
+        let (_, rows) = encode(vec![reals.clone()], &Format::UnifiedText, &options).await;
+        let values = rows.into_iter().map(|mut row| row.remove(0).unwrap());
+        assert_eq!(
+            values.collect::<Vec<_>>(),
+            [&b"1e+07"[..], b"123456", b"1.2345678e-05"]
+        );
+    }
+}
+
+#[tokio::test]
+async fn encode_booleans() {
+    check(
+        Arc::new(BooleanArray::from(vec![Some(true), None, Some(false)])),
+        16,
+        1000,
+        &[Some("t"), None, Some("f")],
+    )
+    .await;
+
+    // a NULL list is NULL, not an array of NULL items
+    // [true, NULL, false], NULL
+    let item = Arc::new(Field::new_list_field(DataType::Boolean, true));
+    let list: ArrayRef = Arc::new(ListArray::new(
+        item,
+        OffsetBuffer::from_lengths([3, 1]),
+        Arc::new(BooleanArray::from(vec![
+            Some(true),
+            None,
+            Some(false),
+            Some(true),
+        ])),
+        Some(NullBuffer::from(vec![true, false])),
+    ));
+    let (_, values) = encode_column(list.clone(), &Format::UnifiedText).await;
+    assert_eq!(values, vec![Some(b"{t,NULL,f}".to_vec()), None]);
+    let (_, values) = encode_column(list, &Format::UnifiedBinary).await;
+    assert_eq!(values[1], None);
+    assert_eq!(
+        binary_array_items(values[0].as_ref().unwrap()),
+        (16, vec![Some(vec![1]), None, Some(vec![0])])
+    );
+}
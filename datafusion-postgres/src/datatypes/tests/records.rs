@@ -0,0 +1,111 @@
+//! Struct columns are sent as records, and lists of structs as arrays of
+//! records, each field encoded like a column of its type.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Array, ArrayRef, Int32Array, ListArray, StringArray, StructArray};
+use datafusion::arrow::buffer::{NullBuffer, OffsetBuffer};
+use datafusion::arrow::datatypes::{DataType, Field, Fields};
+use pgwire::api::portal::Format;
+
+use super::encode_column;
+
+/// (1, 'a b'), NULL, (NULL, ''), (3, 'x"y')
+fn structs() -> StructArray {
+    let fields = Fields::from(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("name", DataType::Utf8, true),
+    ]);
+    StructArray::new(
+        fields,
+        vec![
+            Arc::new(Int32Array::from(vec![Some(1), Some(2), None, Some(3)])),
+            Arc::new(StringArray::from(vec![
+                Some("a b"),
+                Some("b"),
+                Some(""),
+                Some("x\"y"),
+            ])),
+        ],
+        Some(NullBuffer::from(vec![true, false, true, true])),
+    )
+}
+
+/// A binary record of an `int4` and a `varchar`.
+fn binary_record(id: Option<i32>, name: &str) -> Vec<u8> {
+    let mut record = 2i32.to_be_bytes().to_vec();
+    record.extend_from_slice(&23u32.to_be_bytes());
+    match id {
+        Some(id) => {
+            record.extend_from_slice(&4i32.to_be_bytes());
+            record.extend_from_slice(&id.to_be_bytes());
+        }
+        None => record.extend_from_slice(&(-1i32).to_be_bytes()),
+    }
+    record.extend_from_slice(&1043u32.to_be_bytes());
+    record.extend_from_slice(&(name.len() as i32).to_be_bytes());
+    record.extend_from_slice(name.as_bytes());
+    record
+}
+
+#[tokio::test]
+async fn encode_struct_as_record() {
+    let column: ArrayRef = Arc::new(structs());
+
+    let (oid, values) = encode_column(column.clone(), &Format::UnifiedText).await;
+    assert_eq!(oid, 2249);
+    assert_eq!(
+        values,
+        vec![
+            Some(b"(1,\"a b\")".to_vec()),
+            None,
+            Some(b"(,\"\")".to_vec()),
+            Some(b"(3,\"x\"\"y\")".to_vec()),
+        ]
+    );
+
+    let (_, values) = encode_column(column, &Format::UnifiedBinary).await;
+    assert_eq!(
+        values,
+        vec![
+            Some(binary_record(Some(1), "a b")),
+            None,
+            Some(binary_record(None, "")),
+            Some(binary_record(Some(3), "x\"y")),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn encode_list_of_structs_as_record_array() {
+    // [(1, 'a b'), NULL], [(NULL, ''), (3, 'x"y')]
+    let item = Arc::new(Field::new_list_field(structs().data_type().clone(), true));
+    let column: ArrayRef = Arc::new(ListArray::new(
+        item,
+        OffsetBuffer::from_lengths([2, 2]),
+        Arc::new(structs()),
+        None,
+    ));
+
+    let (oid, values) = encode_column(column.clone(), &Format::UnifiedText).await;
+    assert_eq!(oid, 2287);
+    assert_eq!(
+        values,
+        vec![
+            Some(br#"{"(1,\"a b\")",NULL}"#.to_vec()),
+            Some(br#"{"(,\"\")","(3,\"x\"\"y\")"}"#.to_vec()),
+        ]
+    );
+
+    // one dimension of records, the second array without NULL elements
+    let (_, values) = encode_column(column, &Format::UnifiedBinary).await;
+    let mut array = [1i32, 0, 2249, 2, 1]
+        .iter()
+        .flat_map(|v| v.to_be_bytes())
+        .collect::<Vec<_>>();
+    for record in [binary_record(None, ""), binary_record(Some(3), "x\"y")] {
+        array.extend_from_slice(&(record.len() as i32).to_be_bytes());
+        array.extend_from_slice(&record);
+    }
+    assert_eq!(values[1], Some(array));
+}
@@ -0,0 +1,76 @@
+//! Each result column is encoded in the format asked for it, which may
+//! differ from one column to the next.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, BooleanArray, Float64Array, Int32Array, StringArray};
+use pgwire::api::portal::Format;
+use pgwire::api::results::FieldFormat;
+
+use super::encode;
+use crate::datatypes::EncodeOptions;
+
+fn columns() -> Vec<ArrayRef> {
+    vec![
+        Arc::new(Int32Array::from(vec![Some(-2), None])),
+        Arc::new(StringArray::from(vec![Some("a"), Some("b")])),
+        Arc::new(Float64Array::from(vec![Some(0.5), Some(1.0)])),
+        Arc::new(BooleanArray::from(vec![Some(true), None])),
+    ]
+}
+
+fn value(value: impl AsRef<[u8]>) -> Option<Vec<u8>> {
+    Some(value.as_ref().to_vec())
+}
+
+/// The formats of the columns and the values of the rows of `columns()`,
+/// encoded with the result format codes `formats`.
+async fn encode_columns(formats: Vec<i16>) -> (Vec<FieldFormat>, Vec<Vec<Option<Vec<u8>>>>) {
+    let format = Format::Individual(formats);
+    let (fields, rows) = encode(columns(), &format, &EncodeOptions::default()).await;
+    (fields.iter().map(|field| field.format()).collect(), rows)
+}
+
+#[tokio::test]
+async fn encode_mixed_formats() {
+    let (formats, rows) = encode_columns(vec![1, 0, 1, 0]).await;
+    assert_eq!(
+        formats,
+        vec![
+            FieldFormat::Binary,
+            FieldFormat::Text,
+            FieldFormat::Binary,
+            FieldFormat::Text,
+        ]
+    );
+    assert_eq!(
+        rows,
+        vec![
+            vec![
+                value((-2i32).to_be_bytes()),
+                value("a"),
+                value(0.5f64.to_be_bytes()),
+                value("t"),
+            ],
+            vec![None, value("b"), value(1f64.to_be_bytes()), None],
+        ]
+    );
+
+    let (formats, rows) = encode_columns(vec![0, 1, 0, 1]).await;
+    assert_eq!(
+        formats,
+        vec![
+            FieldFormat::Text,
+            FieldFormat::Binary,
+            FieldFormat::Text,
+            FieldFormat::Binary,
+        ]
+    );
+    assert_eq!(
+        rows,
+        vec![
+            vec![value("-2"), value("a"), value("0.5"), value([1])],
+            vec![None, value("b"), value("1"), None],
+        ]
+    );
+}
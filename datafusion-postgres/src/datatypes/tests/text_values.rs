@@ -0,0 +1,70 @@
+//! The text of single values is the text they are sent with in results.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    ArrayRef, BinaryArray, BooleanArray, Date32Array, Decimal128Array, Float64Array, Int32Array,
+    IntervalMonthDayNanoArray, ListArray, StringArray, TimestampMicrosecondArray,
+};
+use datafusion::arrow::buffer::OffsetBuffer;
+use datafusion::arrow::datatypes::{DataType, Field, IntervalMonthDayNano};
+use pgwire::api::portal::Format;
+
+use super::encode_column;
+use crate::datatypes::value_to_pg_text;
+
+/// The values of the single column of `column` in text results.
+async fn text_results(column: ArrayRef) -> Vec<Option<String>> {
+    let (_, values) = encode_column(column, &Format::UnifiedText).await;
+    values
+        .into_iter()
+        .map(|value| value.map(|value| String::from_utf8(value).unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+async fn value_text_matches_text_results() {
+    let item = Arc::new(Field::new_list_field(DataType::Utf8, true));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(vec![Some(i32::MIN), None, Some(7)])),
+        Arc::new(Float64Array::from(vec![Some(0.1), Some(f64::NAN), None])),
+        Arc::new(BooleanArray::from(vec![Some(true), Some(false), None])),
+        Arc::new(StringArray::from(vec![Some("a,b"), Some("\"q\"\n"), None])),
+        Arc::new(BinaryArray::from(vec![Some(&b"\x00\xff"[..]), None])),
+        Arc::new(Date32Array::from(vec![Some(19782), None])),
+        Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(1_709_164_800_123_000),
+            None,
+        ])),
+        Arc::new(
+            Decimal128Array::from(vec![Some(-12345), None])
+                .with_precision_and_scale(10, 3)
+                .unwrap(),
+        ),
+        Arc::new(IntervalMonthDayNanoArray::from(vec![
+            Some(IntervalMonthDayNano::new(14, -3, 1_500_000_000)),
+            None,
+        ])),
+        Arc::new(ListArray::new(
+            item,
+            OffsetBuffer::from_lengths([2, 0]),
+            Arc::new(StringArray::from(vec![Some("x"), None])),
+            None,
+        )),
+    ];
+
+    for column in columns {
+        let values = (0..column.len())
+            .map(|idx| value_to_pg_text(&column, idx).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            text_results(column.clone()).await,
+            "{}",
+            column.data_type()
+        );
+        for (idx, value) in values.iter().enumerate() {
+            assert_eq!(value.is_none(), column.is_null(idx));
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! DataFusion errors sent to clients with the SQLSTATE postgres would use.
+
+use datafusion::arrow::error::ArrowError;
+use datafusion::common::SchemaError;
+use datafusion::error::DataFusionError;
+use pgwire::error::{ErrorInfo, PgWireError};
+
+/// `e` as an error with the SQLSTATE of its cause, when postgres has one for
+/// it. Other errors are sent with the generic `XX000`.
+pub(crate) fn datafusion_error(e: DataFusionError) -> PgWireError {
+    let Some(code) = sqlstate(e.find_root()) else {
+        return PgWireError::ApiError(Box::new(e));
+    };
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_owned(),
+        code.to_owned(),
+        e.to_string(),
+    )))
+}
+
+/// The SQLSTATE of the kind of `e`, if postgres has one for it.
+fn sqlstate(e: &DataFusionError) -> Option<&'static str> {
+    let code = match e {
+        // DataFusion finds unknown tables while planning
+        DataFusionError::Plan(message)
+            if message.starts_with("table '") && message.ends_with("' not found") =>
+        {
+            "42P01"
+        }
+        DataFusionError::SQL(..) | DataFusionError::Plan(_) => "42601",
+        DataFusionError::SchemaError(e, _) => match e {
+            SchemaError::FieldNotFound { .. } => "42703",
+            SchemaError::AmbiguousReference { .. } => "42702",
+            SchemaError::DuplicateQualifiedField { .. }
+            | SchemaError::DuplicateUnqualifiedField { .. } => "42701",
+        },
+        DataFusionError::ArrowError(ArrowError::DivideByZero, _) => "22012",
+        DataFusionError::ArrowError(ArrowError::CastError(_), _) => "22P02",
+        DataFusionError::Execution(_) => "58000",
+        DataFusionError::NotImplemented(_) => "0A000",
+        DataFusionError::ResourcesExhausted(_) => "53200",
+        _ => return None,
+    };
+    Some(code)
+}
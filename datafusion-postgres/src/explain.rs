@@ -0,0 +1,64 @@
+//! `EXPLAIN` results, sent like postgres sends them.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{AsArray, LargeStringArray, RecordBatch};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::common::{DFSchema, DFSchemaRef};
+use datafusion::error::Result;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::prelude::{DataFrame, SessionContext};
+
+/// Name of the single column of the result of `EXPLAIN`, a `LargeUtf8` one
+/// to be sent as `text`.
+const QUERY_PLAN: &str = "QUERY PLAN";
+
+/// Whether `plan` explains a query, with `EXPLAIN` or `EXPLAIN ANALYZE`.
+pub(crate) fn is_explain(plan: &LogicalPlan) -> bool {
+    matches!(plan, LogicalPlan::Explain(_) | LogicalPlan::Analyze(_))
+}
+
+fn query_plan_schema() -> Schema {
+    Schema::new(vec![Field::new(QUERY_PLAN, DataType::LargeUtf8, false)])
+}
+
+/// The schema of the rows `plan` returns once executed, a `QUERY PLAN`
+/// column for `EXPLAIN`.
+#[allow(clippy::result_large_err)]
+pub(crate) fn result_schema(plan: &LogicalPlan) -> Result<DFSchemaRef> {
+    if !is_explain(plan) {
+        return Ok(plan.schema().clone());
+    }
+    Ok(Arc::new(DFSchema::try_from(query_plan_schema())?))
+}
+
+/// The result of an `EXPLAIN`, executed into `dataframe`, reshaped like
+/// postgres.
+///
+/// DataFusion explains a query with a row per plan, of the plan type, like
+/// `logical_plan`, and of the plan's text. Postgres has a single text
+/// column, `QUERY PLAN`, with a row per line of the plan: each plan is sent
+/// as such lines, indented under a line with its type.
+pub(crate) async fn query_plan_rows(
+    context: &SessionContext,
+    dataframe: DataFrame,
+) -> Result<DataFrame> {
+    let mut lines = vec![];
+    for batch in dataframe.collect().await? {
+        let plan_types = batch.column(0).as_string::<i32>();
+        let plans = batch.column(1).as_string::<i32>();
+        for (plan_type, plan) in plan_types.iter().zip(plans) {
+            lines.push(format!("{}:", plan_type.unwrap_or_default()));
+            lines.extend(
+                plan.unwrap_or_default()
+                    .lines()
+                    .map(|line| format!("  {line}")),
+            );
+        }
+    }
+    let batch = RecordBatch::try_new(
+        Arc::new(query_plan_schema()),
+        vec![Arc::new(LargeStringArray::from(lines))],
+    )?;
+    context.read_batch(batch)
+}
@@ -1,31 +1,84 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::env::consts::{ARCH, OS};
+use std::fmt::Debug;
+use std::mem;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
-use datafusion::arrow::datatypes::DataType;
-use datafusion::logical_expr::LogicalPlan;
+use datafusion::arrow::array::{ArrayRef, RecordBatch, StringArray, UInt64Array};
+use datafusion::arrow::compute::{cast_with_options, CastOptions};
+use datafusion::arrow::datatypes::{DataType, SchemaRef};
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::catalog::{CatalogProvider, SchemaProvider};
+use datafusion::common::stats::Precision;
+use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
+use datafusion::common::{plan_datafusion_err, ParamValues, ScalarValue};
+use datafusion::datasource::{provider_as_source, MemTable};
+use datafusion::logical_expr::expr::Placeholder;
+use datafusion::logical_expr::{
+    create_udf, BinaryExpr, ColumnarValue, DdlStatement, Expr, LogicalPlan, LogicalPlanBuilder,
+    Operator, ScalarFunctionImplementation, ScalarUDF, Statement, Volatility, WriteOp,
+};
 use datafusion::prelude::*;
-use pgwire::api::auth::noop::NoopStartupHandler;
-use pgwire::api::copy::NoopCopyHandler;
+use datafusion::sql::sqlparser::ast::Ident;
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::tokenizer::{Location, Token, TokenWithSpan, Tokenizer};
+use datafusion::DATAFUSION_VERSION;
+use futures::stream::{self, BoxStream};
+use futures::{Sink, SinkExt, StreamExt};
+use pgwire::api::auth::{
+    finish_authentication, save_startup_parameters_to_metadata, ServerParameterProvider,
+    StartupHandler,
+};
+use pgwire::api::copy::{send_copy_in_response, send_copy_out_response, CopyHandler};
 use pgwire::api::portal::{Format, Portal};
-use pgwire::api::query::{ExtendedQueryHandler, SimpleQueryHandler};
-use pgwire::api::results::{DescribePortalResponse, DescribeStatementResponse, Response};
+use pgwire::api::query::{send_ready_for_query, ExtendedQueryHandler, SimpleQueryHandler};
+use pgwire::api::results::{
+    CopyResponse, DescribePortalResponse, DescribeResponse, DescribeStatementResponse, FieldInfo,
+    QueryResponse, Response, Tag,
+};
 use pgwire::api::stmt::QueryParser;
 use pgwire::api::stmt::StoredStatement;
-use pgwire::api::{ClientInfo, NoopErrorHandler, PgWireServerHandlers, Type};
-use pgwire::error::{PgWireError, PgWireResult};
+use pgwire::api::store::PortalStore;
+use pgwire::api::{
+    ClientInfo, ClientPortalStore, NoopErrorHandler, PgWireConnectionState, PgWireServerHandlers,
+    Type, DEFAULT_NAME,
+};
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use pgwire::messages::copy::{CopyData, CopyDone, CopyFail};
+use pgwire::messages::data::{DataRow, NoData, ParameterDescription, RowDescription};
+use pgwire::messages::extendedquery::{
+    Bind, BindComplete, Close, CloseComplete, Describe, Execute, Parse, ParseComplete,
+    PortalSuspended, TARGET_TYPE_BYTE_PORTAL, TARGET_TYPE_BYTE_STATEMENT,
+};
+use pgwire::messages::response::{EmptyQueryResponse, TransactionStatus};
+use pgwire::messages::simplequery::Query;
+use pgwire::messages::startup::ParameterStatus;
+use pgwire::messages::{PgWireBackendMessage, PgWireFrontendMessage};
 
-use crate::datatypes::{self, into_pg_type};
+use crate::copy::{CopyFrom, CopyReader, CopyTo};
+use crate::datatypes::{
+    self, into_pg_type, EncodeOptions, EncodeWarnings, ParameterOptions, SchemaTransform,
+    SchemaTransformFn, DEFAULT_MAX_PARAMETERS,
+};
+use crate::rewrite::{self, QueryRewriter, Rewrite};
+use crate::roles::{self, AuthorizeCallback};
+use crate::server::{Connections, Running};
+use crate::settings::{self, DEFAULT_SETTINGS, SERVER_VERSION};
+use crate::sleep::PgSleep;
+use crate::stats::{self, QueryStatsCallback};
+use crate::stub_functions::{StubFunction, DEFAULT_STUB_FUNCTIONS};
+use crate::{cooperative, errors, explain, memory, returning, search_path, timeout};
 
 pub struct HandlerFactory(pub Arc<DfSessionService>);
 
-impl NoopStartupHandler for DfSessionService {}
-
 impl PgWireServerHandlers for HandlerFactory {
     type StartupHandler = DfSessionService;
     type SimpleQueryHandler = DfSessionService;
     type ExtendedQueryHandler = DfSessionService;
-    type CopyHandler = NoopCopyHandler;
+    type CopyHandler = DfSessionService;
     type ErrorHandler = NoopErrorHandler;
 
     fn simple_query_handler(&self) -> Arc<Self::SimpleQueryHandler> {
@@ -41,7 +94,7 @@ impl PgWireServerHandlers for HandlerFactory {
     }
 
     fn copy_handler(&self) -> Arc<Self::CopyHandler> {
-        Arc::new(NoopCopyHandler)
+        self.0.clone()
     }
 
     fn error_handler(&self) -> Arc<Self::ErrorHandler> {
@@ -52,69 +105,1346 @@ impl PgWireServerHandlers for HandlerFactory {
 pub struct DfSessionService {
     session_context: Arc<SessionContext>,
     parser: Arc<Parser>,
+    query_rewriters: Arc<Vec<Arc<dyn QueryRewriter>>>,
+    server_parameters: Vec<(String, String)>,
+    encode_options: EncodeOptions,
+    suspended_portals: Mutex<Vec<SuspendedPortal>>,
+    copies_in: Mutex<HashMap<SocketAddr, CopyIn>>,
+    connections: Arc<Connections>,
+    query_stats: Option<QueryStatsCallback>,
+    parameter_options: ParameterOptions,
+    flush_threshold: Option<usize>,
+    version: Option<String>,
+    database_name: Option<String>,
+    describe_estimates: bool,
+    encode_warnings: bool,
+    query_memory_limit: Option<usize>,
+    copy_memory_limit: usize,
+    authorize: Option<AuthorizeCallback>,
+}
+
+/// Rows left in a portal whose last `Execute` reached its row limit.
+struct SuspendedPortal {
+    /// Dropped once the portal is closed, replaced or its connection gone.
+    portal: Weak<Portal<(String, Option<LogicalPlan>)>>,
+    command_tag: String,
+    rows: BoxStream<'static, PgWireResult<DataRow>>,
+    warnings: EncodeWarnings,
+}
+
+/// The memory the rows of a `COPY ... FROM STDIN` can hold by default.
+const DEFAULT_COPY_MEMORY_LIMIT: usize = 1 << 30;
+
+/// A `COPY ... FROM STDIN` waiting for the data of its client, read into
+/// batches as it arrives.
+struct CopyIn {
+    reader: CopyReader,
+    /// The `INSERT` of a row of parameters into the columns copied into.
+    insert: LogicalPlan,
+    /// The schema of the row of parameters, with the types of the columns.
+    schema: SchemaRef,
+    /// The rows read and not yet cast into a batch.
+    rows: Vec<Vec<Option<String>>>,
+    batches: Vec<RecordBatch>,
+    batch_size: usize,
+    /// The memory held by the batches and the data read, at most `limit`.
+    size: usize,
+    limit: usize,
+    /// The connection runs the statement until all of the data is sent, not
+    /// to time out while its client sends it.
+    _running: Option<Running>,
+}
+
+impl CopyIn {
+    /// Read the rows of `data`, casting them into a batch every
+    /// `batch_size` rows.
+    #[allow(clippy::result_large_err)]
+    fn read(&mut self, data: &[u8]) -> PgWireResult<()> {
+        let rows = self.reader.read(data)?;
+        self.rows.extend(rows);
+        while self.rows.len() >= self.batch_size {
+            let rest = self.rows.split_off(self.batch_size);
+            let rows = mem::replace(&mut self.rows, rest);
+            self.push_batch(rows)?;
+        }
+        self.check_size()
+    }
+
+    /// The `INSERT` of all of the rows copied, if any.
+    #[allow(clippy::result_large_err)]
+    fn finish(mut self) -> PgWireResult<Option<LogicalPlan>> {
+        let mut rows = mem::take(&mut self.rows);
+        rows.extend(self.reader.finish()?);
+        if !rows.is_empty() {
+            self.push_batch(rows)?;
+            self.check_size()?;
+        }
+        if self.batches.is_empty() {
+            return Ok(None);
+        }
+        scan_copied_rows(self.insert, self.schema, self.batches).map(Some)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn push_batch(&mut self, rows: Vec<Vec<Option<String>>>) -> PgWireResult<()> {
+        let batch = cast_copied_rows(&self.schema, &rows)?;
+        self.size += batch.get_array_memory_size();
+        self.batches.push(batch);
+        Ok(())
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn check_size(&self) -> PgWireResult<()> {
+        let text_size = self
+            .rows
+            .iter()
+            .flatten()
+            .flatten()
+            .map(String::len)
+            .sum::<usize>();
+        if self.size + text_size + self.reader.pending_len() > self.limit {
+            return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "54000".to_owned(),
+                format!("COPY data exceeds the limit of {} bytes", self.limit),
+            ))));
+        }
+        Ok(())
+    }
 }
 
 impl DfSessionService {
+    /// Serve `session_context`, with its `information_schema` enabled, as
+    /// clients expect one like postgres has.
     pub fn new(session_context: SessionContext) -> DfSessionService {
+        session_context
+            .state_ref()
+            .write()
+            .config_mut()
+            .options_mut()
+            .catalog
+            .information_schema = true;
         let session_context = Arc::new(session_context);
+        let query_rewriters = Arc::new(rewrite::default_rewriters());
         let parser = Arc::new(Parser {
             session_context: session_context.clone(),
+            query_rewriters: query_rewriters.clone(),
         });
-        DfSessionService {
+        let service = DfSessionService {
             session_context,
             parser,
+            query_rewriters,
+            server_parameters: vec![],
+            encode_options: EncodeOptions::default(),
+            suspended_portals: Mutex::new(vec![]),
+            copies_in: Mutex::default(),
+            connections: Arc::default(),
+            query_stats: None,
+            parameter_options: ParameterOptions::default(),
+            flush_threshold: None,
+            version: None,
+            database_name: None,
+            describe_estimates: false,
+            encode_warnings: false,
+            query_memory_limit: None,
+            copy_memory_limit: DEFAULT_COPY_MEMORY_LIMIT,
+            authorize: None,
+        };
+        service.register_version();
+        service.register_current_database();
+        for name in DEFAULT_STUB_FUNCTIONS {
+            service.register_stub_function(name, None);
+        }
+        service
+            .session_context
+            .register_udf(ScalarUDF::new_from_impl(PgSleep::new()));
+        service
+    }
+
+    /// Always send integer and float columns in text format, even when the
+    /// client asks for binary results.
+    ///
+    /// This is a workaround for legacy clients that mishandle binary integer
+    /// encoding. The actual format of each column is announced in the
+    /// `RowDescription`, so clients that ignore it and assume binary for
+    /// every column will misread these columns instead. Text output is also
+    /// larger and slower to parse.
+    pub fn with_text_numbers(mut self, text_numbers: bool) -> DfSessionService {
+        self.encode_options.text_numbers = text_numbers;
+        self
+    }
+
+    /// Rename repeated column names in results, like the ones of a self-join,
+    /// by appending `_1`, `_2`... to later occurrences. Some clients key
+    /// columns by name and would otherwise drop all but one of them.
+    pub fn with_unique_column_names(mut self, unique_column_names: bool) -> DfSessionService {
+        self.encode_options.unique_column_names = unique_column_names;
+        self
+    }
+
+    /// Fail queries returning nanosecond timestamps with sub-microsecond
+    /// digits, instead of truncating them to the microsecond precision of
+    /// postgres timestamps.
+    pub fn with_strict_timestamps(mut self, strict_timestamps: bool) -> DfSessionService {
+        self.encode_options.strict_timestamps = strict_timestamps;
+        self
+    }
+
+    /// Fail queries returning string or binary values larger than
+    /// `max_field_size` bytes, 1 GB by default like postgres.
+    pub fn with_max_field_size(mut self, max_field_size: usize) -> DfSessionService {
+        self.encode_options.max_field_size = max_field_size;
+        self
+    }
+
+    /// Only allow results with columns of these arrow types, or lists of
+    /// them. Queries returning other types fail when their columns are
+    /// described. Types are compared exactly, so `Timestamp(Nanosecond,
+    /// None)` doesn't allow timestamps with a time zone.
+    pub fn with_allowed_types(mut self, allowed_types: Vec<DataType>) -> DfSessionService {
+        self.encode_options.allowed_types = Some(allowed_types);
+        self
+    }
+
+    /// Fail queries returning columns of these arrow types, or lists of
+    /// them, like `Binary` to never send raw bytes to clients.
+    pub fn with_denied_types(mut self, denied_types: Vec<DataType>) -> DfSessionService {
+        self.encode_options.denied_types = denied_types;
+        self
+    }
+
+    /// Send maps of strings to strings as `hstore` values, with the oid the
+    /// type has in the database clients expect, as it comes from an
+    /// extension. They are otherwise not supported.
+    pub fn with_hstore_oid(mut self, oid: u32) -> DfSessionService {
+        self.encode_options.hstore_oid = Some(oid);
+        self
+    }
+
+    /// Convert parameters declared as `text` or `varchar` by the client to
+    /// the type DataFusion inferred from the query, like a number for
+    /// `meantemp + $1`, instead of failing to coerce the string later. Values
+    /// are parsed from their text, and rejected when they aren't valid for
+    /// the inferred type.
+    pub fn with_inferred_parameter_types(
+        mut self,
+        inferred_parameter_types: bool,
+    ) -> DfSessionService {
+        self.parameter_options.inferred_types = inferred_parameter_types;
+        self
+    }
+
+    /// Fail to execute portals bound with more than `max_parameters`
+    /// parameters, 65535 by default like postgres.
+    pub fn with_max_parameters(mut self, max_parameters: usize) -> DfSessionService {
+        self.parameter_options.max_parameters = max_parameters;
+        self
+    }
+
+    /// Reject `xml` parameters that aren't well-formed XML content, instead
+    /// of passing their text on unchecked.
+    pub fn with_xml_validation(mut self, validate_xml: bool) -> DfSessionService {
+        self.parameter_options.validate_xml = validate_xml;
+        self
+    }
+
+    /// Flush the rows of a query to the client every `flush_threshold` bytes,
+    /// so that the first rows of a slow query reach it without waiting for
+    /// the next ones.
+    ///
+    /// Responses are otherwise written when the client asks for them with
+    /// `Sync` or `Flush`, at the end of a simple query, or whenever the 8 KB
+    /// write buffer of the connection fills up, which also bounds how long
+    /// rows wait with larger thresholds.
+    pub fn with_flush_threshold(mut self, flush_threshold: usize) -> DfSessionService {
+        self.flush_threshold = Some(flush_threshold);
+        self
+    }
+
+    /// Follow the response to `Describe` with a notice carrying the number of
+    /// rows DataFusion estimates the statement returns, like `estimated rows:
+    /// 100`, with a detail telling whether the estimate is exact. Postgres
+    /// has no such notice, it is meant for clients preallocating buffers.
+    pub fn with_describe_estimates(mut self, describe_estimates: bool) -> DfSessionService {
+        self.describe_estimates = describe_estimates;
+        self
+    }
+
+    /// Send a warning notice along with the rows of a result the first time
+    /// a value of one of its columns is sent despite a loss: an unsigned
+    /// integer wrapping around to a negative value of the signed type of its
+    /// width, or a timestamp truncated to microseconds. The query still
+    /// succeeds, and columns are warned about once per result.
+    pub fn with_encode_warnings(mut self, encode_warnings: bool) -> DfSessionService {
+        self.encode_warnings = encode_warnings;
+        self
+    }
+
+    /// Fail queries whose operators, like sorts, joins and aggregates, hold
+    /// more than `query_memory_limit` bytes at once, with the `53200
+    /// out_of_memory` error of postgres, rather than letting one query
+    /// exhaust the memory of the server. Each query gets its own limit,
+    /// within the memory pool of the `SessionContext` that its memory is
+    /// still reserved from.
+    ///
+    /// Operators that can spill to disk do so before failing, when the
+    /// runtime of the `SessionContext` has a disk manager.
+    pub fn with_query_memory_limit(mut self, query_memory_limit: usize) -> DfSessionService {
+        self.query_memory_limit = Some(query_memory_limit);
+        self
+    }
+
+    /// Fail `COPY ... FROM STDIN` statements once the rows copied hold more
+    /// than `copy_memory_limit` bytes, 1 GB by default, with the `54000
+    /// program_limit_exceeded` error of postgres. Rows are kept in memory
+    /// until the client sends all of them, to insert them at once.
+    pub fn with_copy_memory_limit(mut self, copy_memory_limit: usize) -> DfSessionService {
+        self.copy_memory_limit = copy_memory_limit;
+        self
+    }
+
+    /// Describe and encode results with the schema `transform` makes of
+    /// theirs, to rename their columns or attach metadata to them, like a
+    /// [`PG_TYPE_METADATA_KEY`] hint or the `arrow.json` extension name for
+    /// strings holding JSON.
+    ///
+    /// [`PG_TYPE_METADATA_KEY`]: crate::PG_TYPE_METADATA_KEY
+    pub fn with_schema_transform(mut self, transform: SchemaTransform) -> DfSessionService {
+        self.encode_options.schema_transform = Some(SchemaTransformFn(transform));
+        self
+    }
+
+    /// Set a parameter reported to clients with `ParameterStatus` at startup,
+    /// overriding the default for known parameters like `server_version`.
+    ///
+    /// Parameters a client is allowed to `SET`, like `DateStyle`, still take
+    /// the value sent by the client in its startup message.
+    pub fn with_server_parameter(mut self, name: &str, value: &str) -> DfSessionService {
+        self.server_parameters
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.server_parameters
+            .push((name.to_owned(), value.to_owned()));
+        self.register_version();
+        self
+    }
+
+    /// Return `version` from `version()`, instead of `PostgreSQL` followed by
+    /// the `server_version` parameter and the platform. Drivers parse it to
+    /// detect the features of the server, so it should start like the one of
+    /// a postgres release.
+    pub fn with_version(mut self, version: &str) -> DfSessionService {
+        self.version = Some(version.to_owned());
+        self.register_version();
+        self
+    }
+
+    /// Return `name` from `current_database()`, instead of the name of the
+    /// default catalog. Drivers call it to describe the connection.
+    pub fn with_database_name(mut self, name: &str) -> DfSessionService {
+        self.database_name = Some(name.to_owned());
+        self.register_current_database();
+        self
+    }
+
+    /// Report the [`QueryStats`] of queries returning rows to `callback`.
+    ///
+    /// [`QueryStats`]: crate::QueryStats
+    pub fn with_query_stats_callback(mut self, callback: QueryStatsCallback) -> DfSessionService {
+        self.query_stats = Some(callback);
+        self
+    }
+
+    /// Authorize each statement planned by DataFusion with `callback`,
+    /// given the role set by `SET ROLE`, or the user of the connection. See
+    /// [`AuthorizeCallback`].
+    ///
+    /// [`AuthorizeCallback`]: crate::AuthorizeCallback
+    pub fn with_authorize_callback(mut self, callback: AuthorizeCallback) -> DfSessionService {
+        self.authorize = Some(callback);
+        self
+    }
+
+    /// Register a [`QueryRewriter`]. Rewriters registered later are consulted
+    /// first, and all of them before the built-in `SET`, `SHOW` and
+    /// transaction handlers.
+    pub fn with_query_rewriter(mut self, rewriter: Arc<dyn QueryRewriter>) -> DfSessionService {
+        let mut query_rewriters = self.query_rewriters.as_ref().clone();
+        query_rewriters.insert(0, rewriter);
+        self.query_rewriters = Arc::new(query_rewriters);
+        self.parser = Arc::new(Parser {
+            session_context: self.session_context.clone(),
+            query_rewriters: self.query_rewriters.clone(),
+        });
+        self
+    }
+
+    /// Register a scalar function implemented by a closure, callable from
+    /// SQL clients.
+    ///
+    /// Unlike registering it on the `SessionContext` directly, this fails
+    /// right away when results of `return_type` can't be sent to clients,
+    /// instead of on the first query calling the function.
+    pub fn register_scalar_function(
+        &self,
+        name: &str,
+        input_types: Vec<DataType>,
+        return_type: DataType,
+        volatility: Volatility,
+        fun: ScalarFunctionImplementation,
+    ) -> PgWireResult<()> {
+        into_pg_type(&return_type)?;
+        self.session_context.register_udf(create_udf(
+            name,
+            input_types,
+            return_type,
+            volatility,
+            fun,
+        ));
+        Ok(())
+    }
+
+    /// Register `name`, also callable as `pg_catalog.name`, as a function
+    /// taking any arguments and always returning `value` as `text`, or NULL.
+    ///
+    /// Introspection tools like pgAdmin and DBeaver call catalog functions
+    /// such as `pg_get_expr` or `pg_get_viewdef`, which are registered this
+    /// way returning NULL. This adds others, or changes what they return.
+    /// Registering a function of the same name replaces a stub.
+    pub fn register_stub_function(&self, name: &str, value: Option<&str>) {
+        self.session_context
+            .register_udf(ScalarUDF::new_from_impl(StubFunction::new(name, value)));
+    }
+
+    /// Register a catalog, whose tables clients name
+    /// `catalog.schema.table`. Returns the catalog of the same name it
+    /// replaces, if any.
+    pub fn register_catalog(
+        &self,
+        name: &str,
+        catalog: Arc<dyn CatalogProvider>,
+    ) -> Option<Arc<dyn CatalogProvider>> {
+        self.session_context.register_catalog(name, catalog)
+    }
+
+    /// Register a schema in the default catalog, whose tables clients name
+    /// `schema.table`, or just `table` when the schema is in the
+    /// `search_path` of their connection. Returns the schema of the same name
+    /// it replaces, if any.
+    pub fn register_schema(
+        &self,
+        name: &str,
+        schema: Arc<dyn SchemaProvider>,
+    ) -> PgWireResult<Option<Arc<dyn SchemaProvider>>> {
+        let state = self.session_context.state();
+        let default_catalog = &state.config_options().catalog.default_catalog;
+        let catalog = self
+            .session_context
+            .catalog(default_catalog)
+            .ok_or_else(|| {
+                PgWireError::ApiError(Box::new(plan_datafusion_err!(
+                    "default catalog {default_catalog} does not exist"
+                )))
+            })?;
+        catalog
+            .register_schema(name, schema)
+            .map_err(errors::datafusion_error)
+    }
+
+    pub(crate) fn connections(&self) -> &Arc<Connections> {
+        &self.connections
+    }
+
+    /// Drop the portals suspended by connections that are now closed, and
+    /// with them the execution of their queries. Portals are owned by their
+    /// connection, but the rows left in them are kept here.
+    pub(crate) fn release_closed_portals(&self) {
+        self.suspended_portals
+            .lock()
+            .unwrap()
+            .retain(|suspended| suspended.portal.strong_count() > 0);
+    }
+
+    /// Drop what is kept of a connection that is now closed.
+    pub(crate) fn release_connection(&self, addr: SocketAddr) {
+        self.release_closed_portals();
+        self.copies_in.lock().unwrap().remove(&addr);
+    }
+
+    /// Take the rows left in a suspended portal.
+    fn resume_portal(
+        &self,
+        portal: &Arc<Portal<(String, Option<LogicalPlan>)>>,
+    ) -> Option<SuspendedPortal> {
+        self.release_closed_portals();
+        let mut suspended_portals = self.suspended_portals.lock().unwrap();
+        let idx = suspended_portals
+            .iter()
+            .position(|suspended| Weak::ptr_eq(&suspended.portal, &Arc::downgrade(portal)))?;
+        Some(suspended_portals.swap_remove(idx))
+    }
+
+    /// Register `version()`, also callable as `pg_catalog.version()`, in
+    /// place of the DataFusion one.
+    // the error type of the function is imposed by DataFusion
+    #[allow(clippy::result_large_err)]
+    fn register_version(&self) {
+        let version = self.version.clone().unwrap_or_else(|| {
+            let server_version = self
+                .configured_parameter("server_version")
+                .unwrap_or(SERVER_VERSION);
+            format!(
+                "PostgreSQL {server_version} on {ARCH}-{OS}, served by DataFusion {DATAFUSION_VERSION}"
+            )
+        });
+        let fun: ScalarFunctionImplementation = Arc::new(move |_| {
+            Ok(ColumnarValue::Scalar(ScalarValue::Utf8(Some(
+                version.clone(),
+            ))))
+        });
+        let udf = create_udf("version", vec![], DataType::Utf8, Volatility::Stable, fun)
+            .with_aliases(["pg_catalog.version"]);
+        self.session_context.register_udf(udf);
+    }
+
+    /// Register `current_database()`, also callable as
+    /// `pg_catalog.current_database()`.
+    // the error type of the function is imposed by DataFusion
+    #[allow(clippy::result_large_err)]
+    fn register_current_database(&self) {
+        let name = self.database_name.clone().unwrap_or_else(|| {
+            let state = self.session_context.state();
+            state.config_options().catalog.default_catalog.clone()
+        });
+        let fun: ScalarFunctionImplementation =
+            Arc::new(move |_| Ok(ColumnarValue::Scalar(ScalarValue::Utf8(Some(name.clone())))));
+        let udf = create_udf(
+            "current_database",
+            vec![],
+            DataType::Utf8,
+            Volatility::Stable,
+            fun,
+        )
+        .with_aliases(["pg_catalog.current_database"]);
+        self.session_context.register_udf(udf);
+    }
+
+    fn configured_parameter(&self, name: &str) -> Option<&str> {
+        self.server_parameters
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Names of all parameters reported at startup.
+    fn reported_parameters(&self) -> impl Iterator<Item = &str> {
+        DEFAULT_SETTINGS.iter().map(|setting| setting.name).chain(
+            self.server_parameters
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .filter(|key| settings::find(key).is_none()),
+        )
+    }
+
+    /// Store the initial value of every reported parameter in the connection
+    /// settings, so `SHOW` agrees with what was reported at startup.
+    fn init_settings(&self, metadata: &mut HashMap<String, String>) -> PgWireResult<()> {
+        for name in self.reported_parameters() {
+            let read_only = settings::find(name).is_none_or(|setting| setting.read_only);
+            let from_client = metadata
+                .keys()
+                .find(|key| key.eq_ignore_ascii_case(name))
+                .cloned()
+                .and_then(|key| metadata.remove(&key));
+
+            let value = from_client
+                .filter(|_| !read_only)
+                .or_else(|| self.configured_parameter(name).map(str::to_owned))
+                .or_else(|| settings::find(name).map(|setting| setting.default.to_owned()))
+                .unwrap_or_default();
+            let value = settings::validate(name, &value).map_err(|e| match e {
+                PgWireError::UserError(mut info) => {
+                    // an unusable startup parameter fails the connection
+                    info.severity = "FATAL".to_owned();
+                    PgWireError::UserError(info)
+                }
+                e => e,
+            })?;
+            metadata.insert(name.to_lowercase(), value);
+        }
+        Ok(())
+    }
+
+    /// Run the query rewriters on the connection settings. Parameters changed
+    /// by them are reported to the client with `ParameterStatus`.
+    async fn rewrite_query<C>(&self, client: &mut C, query: &str) -> PgWireResult<Option<Rewrite>>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let before = self.server_parameters(client).unwrap_or_default();
+        let rewrite = apply_rewriters(&self.query_rewriters, query, client.metadata_mut())?;
+
+        if rewrite.is_some() {
+            for (name, value) in self.server_parameters(client).unwrap_or_default() {
+                if before.get(&name) != Some(&value) {
+                    client
+                        .feed(PgWireBackendMessage::ParameterStatus(ParameterStatus::new(
+                            name, value,
+                        )))
+                        .await?;
+                }
+            }
+        }
+
+        Ok(rewrite)
+    }
+
+    /// Respond to a rewriter outcome that doesn't need planning.
+    async fn rewrite_response<'a>(
+        &self,
+        rewrite: Rewrite,
+        format: &Format,
+        settings: &HashMap<String, String>,
+    ) -> PgWireResult<Response<'a>> {
+        let options = self.session_encode_options(settings);
+        match rewrite {
+            Rewrite::Query(sql) => {
+                let df = self.plan_sql(&sql, settings).await?;
+                let resp = datatypes::encode_dataframe(df, format, &options).await?;
+                Ok(Response::Query(resp))
+            }
+            Rewrite::Rows(batch) => {
+                let df = self
+                    .session_context
+                    .read_batch(batch)
+                    .map_err(errors::datafusion_error)?;
+                let resp = datatypes::encode_dataframe(df, format, &options).await?;
+                Ok(Response::Query(resp))
+            }
+            Rewrite::Command(tag) => Ok(Response::Execution(Tag::new(&tag))),
+            Rewrite::TransactionStart(tag) => Ok(Response::TransactionStart(Tag::new(&tag))),
+            Rewrite::TransactionEnd(tag) => Ok(Response::TransactionEnd(Tag::new(&tag))),
+        }
+    }
+
+    /// The encode options of the server, with the session settings of a
+    /// connection that change how values are written.
+    fn session_encode_options(&self, settings: &HashMap<String, String>) -> EncodeOptions {
+        let extra_float_digits = settings::get(settings, "extra_float_digits")
+            .and_then(|digits| digits.parse().ok())
+            .unwrap_or(self.encode_options.extra_float_digits);
+        let time_zone = settings::get(settings, "TimeZone").and_then(|zone| zone.parse().ok());
+        EncodeOptions {
+            extra_float_digits,
+            time_zone,
+            ..self.encode_options.clone()
+        }
+    }
+
+    /// The encode options of a query of a connection, raising the warnings
+    /// of its result into `warnings` when enabled.
+    fn query_encode_options(
+        &self,
+        settings: &HashMap<String, String>,
+        warnings: &EncodeWarnings,
+    ) -> EncodeOptions {
+        EncodeOptions {
+            warnings: self.encode_warnings.then(|| warnings.clone()),
+            ..self.session_encode_options(settings)
+        }
+    }
+
+    /// Run the authorization callback, if any, on a statement about to be
+    /// planned under the current role of a connection.
+    fn authorize(&self, settings: &HashMap<String, String>, query: &str) -> PgWireResult<()> {
+        match &self.authorize {
+            Some(callback) => callback(roles::current_role(settings), query),
+            None => Ok(()),
+        }
+    }
+
+    /// Plan and execute `sql` with the `search_path` of the connection.
+    async fn plan_sql(
+        &self,
+        sql: &str,
+        settings: &HashMap<String, String>,
+    ) -> PgWireResult<DataFrame> {
+        let plan = search_path::create_logical_plan(&self.session_context.state(), sql, settings)
+            .await
+            .map_err(errors::datafusion_error)?;
+        self.execute_logical_plan(plan).await
+    }
+
+    /// Fields described for an intercepted statement, given the settings of
+    /// the connection.
+    fn rewrite_fields(
+        &self,
+        query: &str,
+        settings: &HashMap<String, String>,
+        format: &Format,
+    ) -> PgWireResult<Vec<FieldInfo>> {
+        // describing must not change the connection state, work on a copy
+        let mut settings = settings.clone();
+        match apply_rewriters(&self.query_rewriters, query, &mut settings)? {
+            Some(Rewrite::Rows(batch)) => {
+                let df = self
+                    .session_context
+                    .read_batch(batch)
+                    .map_err(errors::datafusion_error)?;
+                datatypes::df_schema_to_pg_fields(df.schema(), format, &self.encode_options)
+            }
+            _ => Ok(vec![]),
         }
     }
 }
 
+/// Like postgres, reject the statements of a failed transaction block, other
+/// than the one ending it.
+fn check_transaction_not_failed<C: ClientInfo>(client: &C, query: &str) -> PgWireResult<()> {
+    if client.transaction_status() == TransactionStatus::Error && !rewrite::ends_transaction(query)
+    {
+        return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "25P02".to_owned(),
+            "current transaction is aborted, commands ignored until end of transaction block"
+                .to_owned(),
+        ))));
+    }
+    Ok(())
+}
+
+/// Queue the tag of a statement starting or ending a transaction block, and
+/// update the transaction status of the connection. Ending a failed block
+/// rolls it back, so its tag is `ROLLBACK` even for `COMMIT`.
+async fn feed_transaction_response<C>(client: &mut C, resp: Response<'_>) -> PgWireResult<()>
+where
+    C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+    C::Error: Debug,
+    PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+{
+    let status = client.transaction_status();
+    let (tag, status) = match resp {
+        Response::TransactionStart(tag) => (tag, status.to_in_transaction_state()),
+        Response::TransactionEnd(_) if status == TransactionStatus::Error => {
+            (Tag::new("ROLLBACK"), status.to_idle_state())
+        }
+        Response::TransactionEnd(tag) => (tag, status.to_idle_state()),
+        _ => unreachable!("statements respond with rows, a command tag or a transaction tag"),
+    };
+    client.set_transaction_status(status);
+    feed_execution_response(client, tag).await
+}
+
+/// Queue the `CommandComplete` of a statement.
+///
+/// Like in postgres, responses are queued rather than sent one by one, and
+/// written when the client sends `Sync` or `Flush`, or at the end of a
+/// simple query, so that a pipeline of statements is answered with as few
+/// writes as possible.
+async fn feed_execution_response<C>(client: &mut C, tag: Tag) -> PgWireResult<()>
+where
+    C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+    C::Error: Debug,
+    PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+{
+    client
+        .feed(PgWireBackendMessage::CommandComplete(tag.into()))
+        .await?;
+    Ok(())
+}
+
+/// Queue the `ParameterDescription` and `RowDescription` or `NoData` of a
+/// `Describe`.
+async fn feed_describe_response<C, R>(client: &mut C, resp: &R) -> PgWireResult<()>
+where
+    C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+    C::Error: Debug,
+    PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    R: DescribeResponse,
+{
+    if let Some(types) = resp.parameters() {
+        let oids = types.iter().map(Type::oid).collect();
+        client
+            .feed(PgWireBackendMessage::ParameterDescription(
+                ParameterDescription::new(oids),
+            ))
+            .await?;
+    }
+    if resp.is_no_data() {
+        client
+            .feed(PgWireBackendMessage::NoData(NoData::new()))
+            .await?;
+    } else {
+        client
+            .feed(PgWireBackendMessage::RowDescription(row_description(
+                resp.fields(),
+            )))
+            .await?;
+    }
+    Ok(())
+}
+
+fn row_description(fields: &[FieldInfo]) -> RowDescription {
+    RowDescription::new(fields.iter().map(Into::into).collect())
+}
+
+fn apply_rewriters(
+    rewriters: &[Arc<dyn QueryRewriter>],
+    query: &str,
+    settings: &mut HashMap<String, String>,
+) -> PgWireResult<Option<Rewrite>> {
+    for rewriter in rewriters {
+        if let Some(rewrite) = rewriter.rewrite(query, settings)? {
+            return Ok(Some(rewrite));
+        }
+    }
+    Ok(None)
+}
+
+/// Split a simple query into its `;` separated statements, leaving out empty
+/// ones. A single statement is returned as is, as well as a query that can't
+/// be tokenized, for DataFusion to report the error.
+fn split_statements(query: &str) -> Vec<String> {
+    let Ok(tokens) = Tokenizer::new(&PostgreSqlDialect {}, query).tokenize_with_location() else {
+        return vec![query.to_owned()];
+    };
+
+    let is_whitespace = |token: &TokenWithSpan| matches!(token.token, Token::Whitespace(_));
+    let statements = tokens
+        .split(|token| token.token == Token::SemiColon)
+        .filter_map(|tokens| {
+            // comments around a statement are dropped with the whitespace
+            let first = tokens.iter().find(|token| !is_whitespace(token))?;
+            let last = tokens.iter().rfind(|token| !is_whitespace(token))?;
+            Some(&query[byte_offset(query, first.span.start)..byte_offset(query, last.span.end)])
+        })
+        .collect::<Vec<_>>();
+    if statements.len() <= 1 {
+        return vec![query.to_owned()];
+    }
+
+    statements.into_iter().map(str::to_owned).collect()
+}
+
+/// Byte offset in `query` of a tokenizer location, which counts characters.
+fn byte_offset(query: &str, location: Location) -> usize {
+    let line_start = query
+        .split_inclusive('\n')
+        .take(location.line.saturating_sub(1) as usize)
+        .map(str::len)
+        .sum::<usize>();
+    let line = &query[line_start..];
+    line_start
+        + line
+            .char_indices()
+            .nth(location.column.saturating_sub(1) as usize)
+            .map_or(line.len(), |(offset, _)| offset)
+}
+
+#[async_trait]
+impl StartupHandler for DfSessionService {
+    async fn on_startup<C>(
+        &self,
+        client: &mut C,
+        message: PgWireFrontendMessage,
+    ) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        if let PgWireFrontendMessage::Startup(ref startup) = message {
+            save_startup_parameters_to_metadata(client, startup);
+            self.init_settings(client.metadata_mut())?;
+            finish_authentication(client, self).await?;
+            self.connections.started_up(client.socket_addr());
+        }
+
+        Ok(())
+    }
+}
+
+impl ServerParameterProvider for DfSessionService {
+    fn server_parameters<C>(&self, client: &C) -> Option<HashMap<String, String>>
+    where
+        C: ClientInfo,
+    {
+        Some(
+            self.reported_parameters()
+                .map(|name| {
+                    let value = settings::get(client.metadata(), name).unwrap_or_default();
+                    (name.to_owned(), value)
+                })
+                .collect(),
+        )
+    }
+}
+
 #[async_trait]
 impl SimpleQueryHandler for DfSessionService {
+    /// Like the default implementation, with the transaction status updated
+    /// by each statement as it completes, so that the statements following
+    /// `BEGIN` in the same query run in the transaction block.
+    async fn on_query<C>(&self, client: &mut C, query: Query) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        if !matches!(client.state(), PgWireConnectionState::ReadyForQuery) {
+            return Err(PgWireError::NotReadyForQuery);
+        }
+        client.set_state(PgWireConnectionState::QueryInProgress);
+
+        let trimmed = query.query.trim();
+        if trimmed.is_empty() || trimmed == ";" {
+            client
+                .feed(PgWireBackendMessage::EmptyQueryResponse(
+                    EmptyQueryResponse::new(),
+                ))
+                .await?;
+        } else {
+            for resp in SimpleQueryHandler::do_query(self, client, &query.query).await? {
+                let Response::Error(e) = resp else {
+                    unreachable!("statements of a simple query send their own responses")
+                };
+                client
+                    .feed(PgWireBackendMessage::ErrorResponse((*e).into()))
+                    .await?;
+                let status = client.transaction_status().to_error_state();
+                client.set_transaction_status(status);
+            }
+        }
+
+        if matches!(client.state(), PgWireConnectionState::CopyInProgress(_)) {
+            return Ok(());
+        }
+        client.set_state(PgWireConnectionState::ReadyForQuery);
+        send_ready_for_query(client, client.transaction_status()).await
+    }
+
     async fn do_query<'a, C>(
         &self,
-        _client: &mut C,
+        client: &mut C,
         query: &'a str,
     ) -> PgWireResult<Vec<Response<'a>>>
     where
-        C: ClientInfo + Unpin + Send + Sync,
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
     {
-        let ctx = &self.session_context;
-        let df = ctx
-            .sql(query)
+        let _running = self.connections.running(client.socket_addr());
+
+        // statements run in order, until one of them fails. Results are sent
+        // as soon as a statement completes, as `COPY` writes its data to the
+        // client directly.
+        let statements = split_statements(query);
+        if let Some((_, leading)) = statements.split_last() {
+            if leading
+                .iter()
+                .any(|statement| matches!(CopyFrom::parse(statement), Ok(Some(_))))
+            {
+                return Ok(vec![Response::Error(Box::new(ErrorInfo::new(
+                    "ERROR".to_owned(),
+                    "0A000".to_owned(),
+                    "COPY FROM STDIN followed by other statements is not supported".to_owned(),
+                )))]);
+            }
+        }
+        for statement in statements {
+            let warnings = EncodeWarnings::default();
+            match self.do_statement(client, &statement, &warnings).await {
+                Ok(Response::Query(resp)) => {
+                    self.feed_query_response(client, resp, &warnings).await?
+                }
+                Ok(Response::Execution(tag)) => feed_execution_response(client, tag).await?,
+                Ok(Response::CopyIn(resp)) => {
+                    // the query completes with the data, see `on_copy_done`
+                    send_copy_in_response(client, resp).await?;
+                    client.set_state(PgWireConnectionState::CopyInProgress(false));
+                }
+                Ok(resp) => feed_transaction_response(client, resp).await?,
+                Err(PgWireError::UserError(info)) => return Ok(vec![Response::Error(info)]),
+                Err(PgWireError::ApiError(e)) => {
+                    return Ok(vec![Response::Error(Box::new(ErrorInfo::new(
+                        "ERROR".to_owned(),
+                        "XX000".to_owned(),
+                        e.to_string(),
+                    )))])
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(vec![])
+    }
+}
+
+impl DfSessionService {
+    /// Run one statement of a simple query.
+    async fn do_statement<'a, C>(
+        &self,
+        client: &mut C,
+        query: &str,
+        warnings: &EncodeWarnings,
+    ) -> PgWireResult<Response<'a>>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        check_transaction_not_failed(client, query)?;
+        if let Some(rewrite) = self.rewrite_query(client, query).await? {
+            return self
+                .rewrite_response(rewrite, &Format::UnifiedText, client.metadata())
+                .await;
+        }
+        self.authorize(client.metadata(), query)?;
+        if let Some(copy) = CopyTo::parse(query)? {
+            return self.copy_to(client, copy).await;
+        }
+        if let Some(copy) = CopyFrom::parse(query)? {
+            return self.copy_from(client, copy).await;
+        }
+
+        let started = Instant::now();
+        let plan = search_path::create_logical_plan(
+            &self.session_context.state(),
+            query,
+            client.metadata(),
+        )
+        .await
+        .map_err(errors::datafusion_error)?;
+        let arrow_results = settings::get(client.metadata(), "result_format")
+            .is_some_and(|format| format == "arrow");
+        if arrow_results && command_tag(&plan).is_none() {
+            return self.copy_arrow(client, plan).await;
+        }
+        let options = self.query_encode_options(client.metadata(), warnings);
+        let timeout = settings::statement_timeout(client.metadata());
+        self.execute_plan(
+            plan,
+            &Format::UnifiedText,
+            &options,
+            query,
+            started,
+            timeout,
+        )
+        .await
+    }
+
+    /// Stream the result of a query as an Arrow IPC stream, in a binary
+    /// `COPY ... TO STDOUT` response with no columns. This is how queries
+    /// of the simple query protocol respond once `result_format` is set to
+    /// `arrow`, sparing clients that want arrow the conversion to postgres
+    /// rows and back.
+    ///
+    /// The first `CopyData` holds the schema, each following one the
+    /// messages of a record batch, and the last one the end of the stream.
+    /// Concatenated, they are a stream readable by any Arrow IPC reader.
+    async fn copy_arrow<'a, C>(
+        &self,
+        client: &mut C,
+        plan: LogicalPlan,
+    ) -> PgWireResult<Response<'a>>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        // not described as fields, but the same types are allowed
+        datatypes::check_allowed_types(plan.schema(), &self.encode_options)?;
+        let dataframe = self.execute_logical_plan(plan).await?;
+        let mut batches = cooperative::execute_stream(dataframe)
             .await
+            .map_err(errors::datafusion_error)?;
+        let mut writer = StreamWriter::try_new(Vec::new(), &batches.schema())
             .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
 
-        let resp = datatypes::encode_dataframe(df, &Format::UnifiedText).await?;
-        Ok(vec![Response::Query(resp)])
+        send_copy_out_response(client, CopyResponse::new(1, 0, vec![])).await?;
+        let schema = mem::take(writer.get_mut());
+        client
+            .feed(PgWireBackendMessage::CopyData(CopyData::new(schema.into())))
+            .await?;
+
+        let mut rows = 0;
+        while let Some(batch) = batches.next().await {
+            let batch = batch.map_err(errors::datafusion_error)?;
+            writer
+                .write(&batch)
+                .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+            let data = mem::take(writer.get_mut());
+            client
+                .feed(PgWireBackendMessage::CopyData(CopyData::new(data.into())))
+                .await?;
+            rows += batch.num_rows();
+        }
+
+        writer
+            .finish()
+            .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+        let end = mem::take(writer.get_mut());
+        client
+            .feed(PgWireBackendMessage::CopyData(CopyData::new(end.into())))
+            .await?;
+        client
+            .send(PgWireBackendMessage::CopyDone(CopyDone::new()))
+            .await?;
+
+        Ok(Response::Execution(Tag::new("COPY").with_rows(rows)))
+    }
+
+    /// Stream the rows of a `COPY ... TO STDOUT`, leaving the final
+    /// `CommandComplete` to the caller.
+    async fn copy_to<'a, C>(&self, client: &mut C, copy: CopyTo) -> PgWireResult<Response<'a>>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let df = self.plan_sql(&copy.query, client.metadata()).await?;
+        let (format, copy_format) = if copy.is_binary() {
+            (Format::UnifiedBinary, 1)
+        } else {
+            (Format::UnifiedText, 0)
+        };
+        let mut options = self.session_encode_options(client.metadata());
+        // every field of a binary copy is in binary format
+        options.text_numbers &= !copy.is_binary();
+        let resp = datatypes::encode_dataframe(df, &format, &options).await?;
+
+        let fields = resp.row_schema();
+        send_copy_out_response(
+            client,
+            CopyResponse::new(
+                copy_format,
+                fields.len(),
+                vec![copy_format.into(); fields.len()],
+            ),
+        )
+        .await?;
+        if let Some(header) = copy.header_line(&fields) {
+            client
+                .feed(PgWireBackendMessage::CopyData(CopyData::new(header)))
+                .await?;
+        }
+
+        let mut rows = 0;
+        let mut data_rows = resp.data_rows();
+        while let Some(row) = data_rows.next().await {
+            let line = copy.line(&row?);
+            client
+                .feed(PgWireBackendMessage::CopyData(CopyData::new(line)))
+                .await?;
+            rows += 1;
+        }
+        if let Some(trailer) = copy.trailer() {
+            client
+                .feed(PgWireBackendMessage::CopyData(CopyData::new(trailer)))
+                .await?;
+        }
+        client
+            .send(PgWireBackendMessage::CopyDone(CopyDone::new()))
+            .await?;
+
+        Ok(Response::Execution(Tag::new("COPY").with_rows(rows)))
+    }
+
+    /// Start a `COPY ... FROM STDIN`, planning the `INSERT` of its rows
+    /// before the client sends them, so that an unknown table or column
+    /// fails the statement right away. The rows are read into batches as
+    /// they arrive, and inserted once all of them are sent, in
+    /// [`CopyHandler::on_copy_done`]. Columns left out of
+    /// the statement get their default value, NULL unless the table has
+    /// one.
+    async fn copy_from<'a, C>(&self, client: &mut C, copy: CopyFrom) -> PgWireResult<Response<'a>>
+    where
+        C: ClientInfo,
+    {
+        let state = self.session_context.state();
+        let columns = if copy.columns.is_empty() {
+            let sql = format!("SELECT * FROM {}", copy.table_name);
+            search_path::create_logical_plan(&state, &sql, client.metadata())
+                .await
+                .map_err(errors::datafusion_error)?
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| Ident::with_quote('"', field.name()))
+                .collect()
+        } else {
+            copy.columns.clone()
+        };
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            copy.table_name,
+            columns
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            (1..=columns.len())
+                .map(|idx| format!("${idx}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let insert = search_path::create_logical_plan(&state, &sql, client.metadata())
+            .await
+            .map_err(errors::datafusion_error)?;
+
+        let schema = values_schema(&insert)?;
+        let response = CopyResponse::new(0, columns.len(), vec![0; columns.len()]);
+        let copy_in = CopyIn {
+            reader: copy.reader(columns),
+            insert,
+            schema,
+            rows: vec![],
+            batches: vec![],
+            batch_size: state.config().batch_size(),
+            size: 0,
+            limit: self.copy_memory_limit,
+            _running: self.connections.running(client.socket_addr()),
+        };
+        self.copies_in
+            .lock()
+            .unwrap()
+            .insert(client.socket_addr(), copy_in);
+        Ok(Response::CopyIn(response))
+    }
+
+    /// Insert the rows copied by a client, completing its `COPY`.
+    async fn insert_copied_rows(&self, copy_in: CopyIn) -> PgWireResult<Tag> {
+        let Some(plan) = copy_in.finish()? else {
+            return Ok(Tag::new("COPY").with_rows(0));
+        };
+        let batches = self
+            .execute_logical_plan(plan)
+            .await?
+            .collect()
+            .await
+            .map_err(errors::datafusion_error)?;
+        let rows = batches
+            .iter()
+            .filter_map(|batch| batch.column(0).as_any().downcast_ref::<UInt64Array>())
+            .flat_map(|counts| counts.iter().flatten())
+            .sum::<u64>();
+        Ok(Tag::new("COPY").with_rows(rows as usize))
+    }
+}
+
+#[async_trait]
+impl CopyHandler for DfSessionService {
+    async fn on_copy_data<C>(&self, client: &mut C, copy_data: CopyData) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        // the data is read out of the lock shared by all connections
+        let copy_in = self.copies_in.lock().unwrap().remove(&client.socket_addr());
+        let Some(mut copy_in) = copy_in else {
+            return Ok(());
+        };
+        if let Err(e) = copy_in.read(&copy_data.data) {
+            // pgwire ends the query, and ignores the rest of the data
+            let status = client.transaction_status().to_error_state();
+            client.set_transaction_status(status);
+            return Err(e);
+        }
+        self.copies_in
+            .lock()
+            .unwrap()
+            .insert(client.socket_addr(), copy_in);
+        Ok(())
+    }
+
+    /// Insert the rows copied, then let pgwire end the query with
+    /// `ReadyForQuery`.
+    async fn on_copy_done<C>(&self, client: &mut C, _done: CopyDone) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let _running = self.connections.running(client.socket_addr());
+        let copy_in = self.copies_in.lock().unwrap().remove(&client.socket_addr());
+        let Some(copy_in) = copy_in else {
+            return Ok(());
+        };
+        match self.insert_copied_rows(copy_in).await {
+            Ok(tag) => feed_execution_response(client, tag).await,
+            Err(e) => {
+                let status = client.transaction_status().to_error_state();
+                client.set_transaction_status(status);
+                Err(e)
+            }
+        }
+    }
+
+    async fn on_copy_fail<C>(&self, client: &mut C, fail: CopyFail) -> PgWireError
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        self.copies_in.lock().unwrap().remove(&client.socket_addr());
+        let status = client.transaction_status().to_error_state();
+        client.set_transaction_status(status);
+        PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_owned(),
+            "57014".to_owned(),
+            format!("COPY from stdin failed: {}", fail.message),
+        )))
     }
 }
 
 pub struct Parser {
     session_context: Arc<SessionContext>,
+    query_rewriters: Arc<Vec<Arc<dyn QueryRewriter>>>,
 }
 
 #[async_trait]
 impl QueryParser for Parser {
-    type Statement = LogicalPlan;
+    /// The query text, and its plan unless a [`QueryRewriter`] takes over
+    /// the query, in which case the rewriter runs again at execution.
+    type Statement = (String, Option<LogicalPlan>);
 
     async fn parse_sql(&self, sql: &str, _types: &[Type]) -> PgWireResult<Self::Statement> {
-        let context = &self.session_context;
-        let state = context.state();
+        self.parse_with_settings(sql, &HashMap::new()).await
+    }
+}
 
-        let logical_plan = state
-            .create_logical_plan(sql)
+impl Parser {
+    /// Plan a statement with the `search_path` in `settings`.
+    async fn parse_with_settings(
+        &self,
+        sql: &str,
+        settings: &HashMap<String, String>,
+    ) -> PgWireResult<(String, Option<LogicalPlan>)> {
+        let sql_to_plan = match apply_rewriters(&self.query_rewriters, sql, &mut settings.clone())?
+        {
+            None => sql.to_owned(),
+            Some(Rewrite::Query(rewritten)) => rewritten,
+            Some(_) => return Ok((sql.to_owned(), None)),
+        };
+
+        let state = self.session_context.state();
+        let logical_plan = search_path::create_logical_plan(&state, &sql_to_plan, settings)
             .await
-            .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+            .and_then(infer_boolean_parameters)
+            .map_err(errors::datafusion_error)?;
+        check_param_positions(&logical_plan)?;
         let optimised = state
             .optimize(&logical_plan)
-            .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+            .map_err(errors::datafusion_error)?;
 
-        Ok(optimised)
+        Ok((sql.to_owned(), Some(optimised)))
     }
 }
 
 #[async_trait]
 impl ExtendedQueryHandler for DfSessionService {
-    type Statement = LogicalPlan;
+    type Statement = (String, Option<LogicalPlan>);
 
     type QueryParser = Parser;
 
@@ -122,25 +1452,168 @@ impl ExtendedQueryHandler for DfSessionService {
         self.parser.clone()
     }
 
+    /// Like the default implementation, planning the statement with the
+    /// `search_path` of the connection.
+    async fn on_parse<C>(&self, client: &mut C, message: Parse) -> PgWireResult<()>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::PortalStore: PortalStore<Statement = Self::Statement>,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let statement = self
+            .parser
+            .parse_with_settings(&message.query, client.metadata())
+            .await?;
+        let parameter_types = message
+            .type_oids
+            .iter()
+            .map(|oid| Type::from_oid(*oid).unwrap_or(Type::UNKNOWN))
+            .collect();
+        let name = message.name.unwrap_or_else(|| DEFAULT_NAME.to_owned());
+        client
+            .portal_store()
+            .put_statement(Arc::new(StoredStatement::new(
+                name,
+                statement,
+                parameter_types,
+            )));
+        client
+            .feed(PgWireBackendMessage::ParseComplete(ParseComplete::new()))
+            .await?;
+        Ok(())
+    }
+
+    /// Like the default implementation, with `BindComplete` queued until the
+    /// client asks for responses, and portals with the wrong number of
+    /// parameters rejected right away.
+    async fn on_bind<C>(&self, client: &mut C, message: Bind) -> PgWireResult<()>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::PortalStore: PortalStore<Statement = Self::Statement>,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let name = message.statement_name.as_deref().unwrap_or(DEFAULT_NAME);
+        let Some(statement) = client.portal_store().get_statement(name) else {
+            return Err(PgWireError::StatementNotFound(name.to_owned()));
+        };
+        let portal = Portal::try_new(&message, statement)?;
+        if let (query, Some(plan)) = &portal.statement.statement {
+            check_parameter_count(&portal, query, plan)?;
+        }
+        client.portal_store().put_portal(Arc::new(portal));
+        client
+            .feed(PgWireBackendMessage::BindComplete(BindComplete::new()))
+            .await?;
+        Ok(())
+    }
+
+    /// Like the default implementation, with the description queued until
+    /// the client asks for responses.
+    async fn on_describe<C>(&self, client: &mut C, message: Describe) -> PgWireResult<()>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::PortalStore: PortalStore<Statement = Self::Statement>,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let name = message.name.as_deref().unwrap_or(DEFAULT_NAME);
+        match message.target_type {
+            TARGET_TYPE_BYTE_STATEMENT => {
+                let Some(statement) = client.portal_store().get_statement(name) else {
+                    return Err(PgWireError::StatementNotFound(name.to_owned()));
+                };
+                let resp = self.do_describe_statement(client, &statement).await?;
+                feed_describe_response(client, &resp).await?;
+                if let (_, Some(plan)) = &statement.statement {
+                    self.feed_row_estimate(client, plan).await?;
+                }
+                Ok(())
+            }
+            TARGET_TYPE_BYTE_PORTAL => {
+                let Some(portal) = client.portal_store().get_portal(name) else {
+                    return Err(PgWireError::PortalNotFound(name.to_owned()));
+                };
+                let resp = self.do_describe_portal(client, &portal).await?;
+                feed_describe_response(client, &resp).await?;
+                if let (query, Some(plan)) = &portal.statement.statement {
+                    let plan = bind_plan(&portal, query, plan, &self.parameter_options)?;
+                    self.feed_row_estimate(client, &plan).await?;
+                }
+                Ok(())
+            }
+            target_type => Err(PgWireError::InvalidTargetType(target_type)),
+        }
+    }
+
+    /// Like the default implementation, with `CloseComplete` queued until the
+    /// client asks for responses.
+    async fn on_close<C>(&self, client: &mut C, message: Close) -> PgWireResult<()>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::PortalStore: PortalStore<Statement = Self::Statement>,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let name = message.name.as_deref().unwrap_or(DEFAULT_NAME);
+        match message.target_type {
+            TARGET_TYPE_BYTE_STATEMENT => client.portal_store().rm_statement(name),
+            TARGET_TYPE_BYTE_PORTAL => client.portal_store().rm_portal(name),
+            _ => {}
+        }
+        client
+            .feed(PgWireBackendMessage::CloseComplete(CloseComplete::new()))
+            .await?;
+        Ok(())
+    }
+
     async fn do_describe_statement<C>(
         &self,
-        _client: &mut C,
+        client: &mut C,
         target: &StoredStatement<Self::Statement>,
     ) -> PgWireResult<DescribeStatementResponse>
     where
         C: ClientInfo + Unpin + Send + Sync,
     {
-        let plan = &target.statement;
+        let (query, plan) = &target.statement;
+        let Some(plan) = plan else {
+            let fields = self.rewrite_fields(query, client.metadata(), &Format::UnifiedBinary)?;
+            if fields.is_empty() {
+                return Ok(DescribeStatementResponse::no_data());
+            }
+            return Ok(DescribeStatementResponse::new(vec![], fields));
+        };
 
-        let schema = plan.schema();
-        let fields = datatypes::df_schema_to_pg_fields(schema.as_ref(), &Format::UnifiedBinary)?;
+        let fields = match command_tag(plan) {
+            Some(_) => vec![],
+            None => datatypes::df_schema_to_pg_fields(
+                explain::result_schema(plan)
+                    .map_err(errors::datafusion_error)?
+                    .as_ref(),
+                &Format::UnifiedBinary,
+                &self.encode_options,
+            )?,
+        };
         let params = plan
             .get_parameter_types()
-            .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+            .map_err(errors::datafusion_error)?;
 
         let mut param_types = Vec::with_capacity(params.len());
-        for param_type in ordered_param_types(&params).iter() {
-            if let Some(datatype) = param_type {
+        let names = named_params(query, &params);
+        for (idx, param_type) in ordered_param_types(&params, names.as_deref())
+            .iter()
+            .enumerate()
+        {
+            // types given by the client when preparing the statement win, as
+            // they are the ones the parameters will be sent with
+            let declared_type = target
+                .parameter_types
+                .get(idx)
+                .filter(|ty| **ty != Type::UNKNOWN);
+            if let Some(pgtype) = declared_type {
+                param_types.push(pgtype.clone());
+            } else if let Some(datatype) = param_type {
                 let pgtype = into_pg_type(datatype)?;
                 param_types.push(pgtype);
             } else {
@@ -153,59 +1626,636 @@ impl ExtendedQueryHandler for DfSessionService {
 
     async fn do_describe_portal<C>(
         &self,
-        _client: &mut C,
+        client: &mut C,
         target: &Portal<Self::Statement>,
     ) -> PgWireResult<DescribePortalResponse>
     where
         C: ClientInfo + Unpin + Send + Sync,
     {
-        let plan = &target.statement.statement;
+        let (query, plan) = &target.statement.statement;
         let format = &target.result_column_format;
-        let schema = plan.schema();
-        let fields = datatypes::df_schema_to_pg_fields(schema.as_ref(), format)?;
+        let Some(plan) = plan else {
+            let fields = self.rewrite_fields(query, client.metadata(), format)?;
+            return Ok(DescribePortalResponse::new(fields));
+        };
+        if command_tag(plan).is_some() {
+            return Ok(DescribePortalResponse::no_data());
+        }
+        // bound values can change the result types, like for `SELECT $1`
+        let plan = bind_plan(target, query, plan, &self.parameter_options)?;
+        let schema = explain::result_schema(&plan).map_err(errors::datafusion_error)?;
+        let fields =
+            datatypes::df_schema_to_pg_fields(schema.as_ref(), format, &self.encode_options)?;
 
         Ok(DescribePortalResponse::new(fields))
     }
 
     async fn do_query<'a, C>(
         &self,
-        _client: &mut C,
+        client: &mut C,
         portal: &'a Portal<Self::Statement>,
         _max_rows: usize,
     ) -> PgWireResult<Response<'a>>
     where
-        C: ClientInfo + Unpin + Send + Sync,
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
     {
-        let plan = &portal.statement.statement;
+        self.execute_portal(client, portal, &EncodeWarnings::default())
+            .await
+    }
 
-        let param_types = plan
-            .get_parameter_types()
-            .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+    /// Like the default implementation, with support for row limits: once
+    /// `max_rows` rows are sent, the portal is suspended and the next
+    /// `Execute` of the same portal resumes it, like in postgres.
+    async fn on_execute<C>(&self, client: &mut C, message: Execute) -> PgWireResult<()>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::PortalStore: PortalStore<Statement = Self::Statement>,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        if !matches!(client.state(), PgWireConnectionState::ReadyForQuery) {
+            return Err(PgWireError::NotReadyForQuery);
+        }
+        let _running = self.connections.running(client.socket_addr());
+        let portal_name = message.name.as_deref().unwrap_or(DEFAULT_NAME);
+        let Some(portal) = client.portal_store().get_portal(portal_name) else {
+            return Err(PgWireError::PortalNotFound(portal_name.to_owned()));
+        };
 
-        let param_values =
-            datatypes::deserialize_parameters(portal, &ordered_param_types(&param_types))?;
+        let (command_tag, mut rows, warnings) = match self.resume_portal(&portal) {
+            Some(suspended) => (suspended.command_tag, suspended.rows, suspended.warnings),
+            None => {
+                let warnings = EncodeWarnings::default();
+                match self.execute_portal(client, &portal, &warnings).await? {
+                    Response::Query(resp) => {
+                        (resp.command_tag().to_owned(), resp.data_rows(), warnings)
+                    }
+                    Response::Execution(tag) => return feed_execution_response(client, tag).await,
+                    resp => return feed_transaction_response(client, resp).await,
+                }
+            }
+        };
 
-        let plan = plan
-            .clone()
-            .replace_params_with_values(&param_values)
-            .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+        // the count of `CommandComplete` is the number of rows sent by this
+        // `Execute` only
+        let max_rows = usize::try_from(message.max_rows).unwrap_or(0);
+        let mut sent = 0;
+        let mut unflushed = 0;
+        while max_rows == 0 || sent < max_rows {
+            let Some(row) = rows.next().await else {
+                if max_rows > 0 {
+                    // like postgres, a completed portal returns no more rows
+                    self.suspended_portals
+                        .lock()
+                        .unwrap()
+                        .push(SuspendedPortal {
+                            portal: Arc::downgrade(&portal),
+                            command_tag: command_tag.clone(),
+                            rows: stream::empty().boxed(),
+                            warnings: warnings.clone(),
+                        });
+                }
+                let tag = Tag::new(&command_tag).with_rows(sent);
+                return feed_execution_response(client, tag).await;
+            };
+            self.feed_row(client, row?, &mut unflushed, &warnings)
+                .await?;
+            sent += 1;
+        }
 
-        let dataframe = self
-            .session_context
-            .execute_logical_plan(plan)
+        self.suspended_portals
+            .lock()
+            .unwrap()
+            .push(SuspendedPortal {
+                portal: Arc::downgrade(&portal),
+                command_tag,
+                rows,
+                warnings,
+            });
+        client
+            .feed(PgWireBackendMessage::PortalSuspended(PortalSuspended::new()))
+            .await?;
+        Ok(())
+    }
+}
+
+impl DfSessionService {
+    /// Queue the `RowDescription`, rows and `CommandComplete` of a statement
+    /// of a simple query, with the warnings raised while encoding its rows.
+    async fn feed_query_response<C>(
+        &self,
+        client: &mut C,
+        resp: QueryResponse<'_>,
+        warnings: &EncodeWarnings,
+    ) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let command_tag = resp.command_tag().to_owned();
+        client
+            .feed(PgWireBackendMessage::RowDescription(row_description(
+                &resp.row_schema(),
+            )))
+            .await?;
+        let mut rows = resp.data_rows();
+        let mut sent = 0;
+        let mut unflushed = 0;
+        while let Some(row) = rows.next().await {
+            self.feed_row(client, row?, &mut unflushed, warnings)
+                .await?;
+            sent += 1;
+        }
+        feed_execution_response(client, Tag::new(&command_tag).with_rows(sent)).await
+    }
+
+    /// Queue a row, after the notices of the warnings raised while encoding
+    /// it, flushing the rows queued so far once they reach the flush
+    /// threshold.
+    async fn feed_row<C>(
+        &self,
+        client: &mut C,
+        row: DataRow,
+        unflushed: &mut usize,
+        warnings: &EncodeWarnings,
+    ) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        for notice in warnings.take() {
+            client
+                .feed(PgWireBackendMessage::NoticeResponse(notice.into()))
+                .await?;
+        }
+        *unflushed += row.data.len();
+        client.feed(PgWireBackendMessage::DataRow(row)).await?;
+        if self
+            .flush_threshold
+            .is_some_and(|threshold| *unflushed >= threshold)
+        {
+            client.flush().await?;
+            *unflushed = 0;
+        }
+        Ok(())
+    }
+
+    /// Run the statement of a portal with its bound parameters.
+    async fn execute_portal<C>(
+        &self,
+        client: &mut C,
+        portal: &Portal<(String, Option<LogicalPlan>)>,
+        warnings: &EncodeWarnings,
+    ) -> PgWireResult<Response<'static>>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let (query, plan) = &portal.statement.statement;
+        check_transaction_not_failed(client, query)?;
+        let Some(plan) = plan else {
+            return match self.rewrite_query(client, query).await? {
+                Some(rewrite) => {
+                    self.rewrite_response(rewrite, &portal.result_column_format, client.metadata())
+                        .await
+                }
+                None => Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                    "ERROR".to_owned(),
+                    "XX000".to_owned(),
+                    format!("Statement is no longer handled by any rewriter: {query}"),
+                )))),
+            };
+        };
+
+        self.authorize(client.metadata(), query)?;
+        let started = Instant::now();
+        let plan = bind_plan(portal, query, plan, &self.parameter_options)?;
+        let options = self.query_encode_options(client.metadata(), warnings);
+        let timeout = settings::statement_timeout(client.metadata());
+        self.execute_plan(
+            plan,
+            &portal.result_column_format,
+            &options,
+            query,
+            started,
+            timeout,
+        )
+        .await
+    }
+
+    /// Execute a plan, responding with its rows, or with the tag postgres
+    /// would send for a statement that doesn't return rows. The statement
+    /// is cancelled once it runs for longer than `timeout` since `started`.
+    async fn execute_plan(
+        &self,
+        plan: LogicalPlan,
+        format: &Format,
+        options: &EncodeOptions,
+        query: &str,
+        started: Instant,
+        timeout: Option<Duration>,
+    ) -> PgWireResult<Response<'static>> {
+        let deadline = timeout.map(|timeout| started + timeout);
+        let tag = command_tag(&plan);
+        let returning = returning::insert_returning(&plan).is_some();
+        let dataframe = timeout::run_until(deadline, self.execute_logical_plan(plan)).await?;
+
+        let Some((tag, counted)) = tag else {
+            let mut resp = timeout::run_until(
+                deadline,
+                datatypes::encode_dataframe(dataframe, format, options),
+            )
+            .await?;
+            if returning {
+                // completed with the number of rows returned, which are the
+                // rows inserted
+                resp.set_command_tag("INSERT 0");
+            }
+            let resp = timeout::rows_until(resp, deadline);
+            return Ok(Response::Query(self.record_stats(resp, query, started)));
+        };
+        // DDL runs when planned, DML and `COPY` when collected into the
+        // number of rows they affected
+        let batches = timeout::run_until(deadline, async {
+            dataframe.collect().await.map_err(errors::datafusion_error)
+        })
+        .await?;
+        if !counted {
+            return Ok(Response::Execution(tag));
+        }
+        let rows = batches
+            .iter()
+            .filter_map(|batch| batch.column(0).as_any().downcast_ref::<UInt64Array>())
+            .flat_map(|counts| counts.iter().flatten())
+            .sum::<u64>();
+        Ok(Response::Execution(tag.with_rows(rows as usize)))
+    }
+
+    /// Queue a notice with the number of rows DataFusion expects `plan` to
+    /// return, when enabled and the statistics of the plan have one.
+    /// Statements whose parameters aren't bound yet can't be planned, and
+    /// get no notice.
+    async fn feed_row_estimate<C>(&self, client: &mut C, plan: &LogicalPlan) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        if !self.describe_estimates || command_tag(plan).is_some() {
+            return Ok(());
+        }
+        let state = self.session_context.state();
+        let Ok(physical_plan) = state.create_physical_plan(plan).await else {
+            return Ok(());
+        };
+        let (rows, detail) = match physical_plan.statistics().map(|stats| stats.num_rows) {
+            Ok(Precision::Exact(rows)) => (rows, "The estimate is exact."),
+            Ok(Precision::Inexact(rows)) => (rows, "The estimate is approximate."),
+            _ => return Ok(()),
+        };
+        let mut notice = ErrorInfo::new(
+            "NOTICE".to_owned(),
+            "00000".to_owned(),
+            format!("estimated rows: {rows}"),
+        );
+        notice.detail = Some(detail.to_owned());
+        client
+            .feed(PgWireBackendMessage::NoticeResponse(notice.into()))
+            .await?;
+        Ok(())
+    }
+
+    /// Execute a plan into the dataframe of its result, once the rows of an
+    /// `INSERT ... RETURNING` are inserted.
+    async fn execute_logical_plan(&self, plan: LogicalPlan) -> PgWireResult<DataFrame> {
+        let explain = explain::is_explain(&plan);
+        let mut dataframe = match returning::insert_returning(&plan) {
+            Some(insert) => returning::execute(&self.session_context, insert).await,
+            None => self.session_context.execute_logical_plan(plan).await,
+        }
+        .map_err(errors::datafusion_error)?;
+        if let Some(limit) = self.query_memory_limit {
+            dataframe = memory::with_memory_limit(dataframe, limit);
+        }
+        if !explain {
+            return Ok(dataframe);
+        }
+        explain::query_plan_rows(&self.session_context, dataframe)
             .await
-            .map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+            .map_err(errors::datafusion_error)
+    }
+
+    fn record_stats<'a>(
+        &self,
+        resp: QueryResponse<'a>,
+        query: &str,
+        started: Instant,
+    ) -> QueryResponse<'a> {
+        match &self.query_stats {
+            Some(callback) => stats::record(resp, query, started, callback.clone()),
+            None => resp,
+        }
+    }
+}
+
+/// The schema of the row of parameters of the `INSERT` of a `COPY ... FROM
+/// STDIN`, with the types of the columns they are inserted into.
+#[allow(clippy::result_large_err)]
+fn values_schema(insert: &LogicalPlan) -> PgWireResult<SchemaRef> {
+    let mut schema = None;
+    insert
+        .apply(|plan| {
+            Ok(match plan {
+                LogicalPlan::Values(values) => {
+                    schema = Some(values.schema.inner().clone());
+                    TreeNodeRecursion::Stop
+                }
+                _ => TreeNodeRecursion::Continue,
+            })
+        })
+        .map_err(errors::datafusion_error)?;
+    schema
+        .ok_or_else(|| plan_datafusion_err!("COPY without a row of values to insert"))
+        .map_err(errors::datafusion_error)
+}
+
+/// A batch of copied rows, their fields cast from their text to the type of
+/// the column they are inserted into.
+#[allow(clippy::result_large_err)]
+fn cast_copied_rows(schema: &SchemaRef, rows: &[Vec<Option<String>>]) -> PgWireResult<RecordBatch> {
+    let cast_options = CastOptions {
+        safe: false,
+        ..CastOptions::default()
+    };
+    let columns = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let text: ArrayRef = Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row[idx].as_deref()),
+            ));
+            cast_with_options(&text, field.data_type(), &cast_options).map_err(|e| {
+                PgWireError::UserError(Box::new(ErrorInfo::new(
+                    "ERROR".to_owned(),
+                    "22P02".to_owned(),
+                    e.to_string(),
+                )))
+            })
+        })
+        .collect::<PgWireResult<Vec<_>>>()?;
+    RecordBatch::try_new(schema.clone(), columns).map_err(|e| errors::datafusion_error(e.into()))
+}
+
+/// The `INSERT` of a `COPY ... FROM STDIN`, its row of parameters replaced
+/// with a scan of the batches of rows copied.
+#[allow(clippy::result_large_err)]
+fn scan_copied_rows(
+    insert: LogicalPlan,
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+) -> PgWireResult<LogicalPlan> {
+    let table = MemTable::try_new(schema, vec![batches]).map_err(errors::datafusion_error)?;
+    let scan = LogicalPlanBuilder::scan("copy", provider_as_source(Arc::new(table)), None)
+        .and_then(LogicalPlanBuilder::build)
+        .map_err(errors::datafusion_error)?;
+    insert
+        .transform_up(|plan| {
+            Ok(match plan {
+                LogicalPlan::Values(_) => Transformed::yes(scan.clone()),
+                plan => Transformed::no(plan),
+            })
+        })
+        .map(|transformed| transformed.data)
+        .map_err(errors::datafusion_error)
+}
+
+/// Fail for portals bound with another number of parameters than their
+/// statement takes: like postgres, the parameters it uses, and those its
+/// client gave types for when preparing it.
+#[allow(clippy::result_large_err)]
+fn check_parameter_count<S: Clone>(
+    portal: &Portal<S>,
+    query: &str,
+    plan: &LogicalPlan,
+) -> PgWireResult<()> {
+    let param_types = plan
+        .get_parameter_types()
+        .map_err(errors::datafusion_error)?;
+    let names = named_params(query, &param_types);
+    let required = ordered_param_types(&param_types, names.as_deref())
+        .len()
+        .max(portal.statement.parameter_types.len());
+    if portal.parameter_len() == required {
+        return Ok(());
+    }
+    let name = match portal.statement.id.as_str() {
+        DEFAULT_NAME => "",
+        name => name,
+    };
+    Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_owned(),
+        "08P01".to_owned(),
+        format!(
+            "bind message supplies {} parameters, but prepared statement \"{name}\" requires {required}",
+            portal.parameter_len()
+        ),
+    ))))
+}
+
+/// Replace the parameters of a statement's plan with the values bound to the
+/// portal.
+#[allow(clippy::result_large_err)]
+fn bind_plan<S: Clone>(
+    portal: &Portal<S>,
+    query: &str,
+    plan: &LogicalPlan,
+    options: &ParameterOptions,
+) -> PgWireResult<LogicalPlan> {
+    let param_types = plan
+        .get_parameter_types()
+        .map_err(errors::datafusion_error)?;
+
+    let names = named_params(query, &param_types);
+    let param_types = ordered_param_types(&param_types, names.as_deref());
+    let mut param_values = datatypes::deserialize_parameters(portal, &param_types, options)?;
+    if let (Some(names), ParamValues::List(values)) = (names, &param_values) {
+        // named placeholders are looked up by their name without the `$`
+        param_values = ParamValues::Map(
+            names
+                .iter()
+                .map(|name| name.trim_start_matches('$').to_owned())
+                .zip(values.iter().cloned())
+                .collect(),
+        );
+    }
+
+    let plan = plan
+        .clone()
+        .replace_params_with_values(&param_values)
+        .map_err(errors::datafusion_error)?;
+
+    // schemas still have the types the parameters had when planning
+    plan.transform_up(|plan| plan.recompute_schema().map(Transformed::yes))
+        .map(|transformed| transformed.data)
+        .map_err(errors::datafusion_error)
+}
 
-        let resp = datatypes::encode_dataframe(dataframe, &portal.result_column_format).await?;
-        Ok(Response::Query(resp))
+/// Type the parameters DataFusion leaves untyped where a boolean is
+/// expected, like in `WHERE $1`, `$1 OR x > 0` or `NOT $1`, as `Boolean`, so
+/// that their values are decoded as such.
+#[allow(clippy::result_large_err)]
+fn infer_boolean_parameters(plan: LogicalPlan) -> datafusion::error::Result<LogicalPlan> {
+    fn as_boolean(expr: Expr) -> Expr {
+        match expr {
+            Expr::Placeholder(Placeholder {
+                id,
+                data_type: None,
+            }) => Expr::Placeholder(Placeholder::new(id, Some(DataType::Boolean))),
+            expr => expr,
+        }
     }
+
+    plan.transform_up(|plan| {
+        let filter = matches!(plan, LogicalPlan::Filter(_));
+        plan.map_expressions(|expr| {
+            let expr = if filter { as_boolean(expr) } else { expr };
+            expr.transform_up(|expr| {
+                Ok(match expr {
+                    Expr::BinaryExpr(BinaryExpr {
+                        left,
+                        op: op @ (Operator::And | Operator::Or),
+                        right,
+                    }) => Transformed::yes(Expr::BinaryExpr(BinaryExpr::new(
+                        Box::new(as_boolean(*left)),
+                        op,
+                        Box::new(as_boolean(*right)),
+                    ))),
+                    Expr::Not(expr) => Transformed::yes(Expr::Not(Box::new(as_boolean(*expr)))),
+                    expr => Transformed::no(expr),
+                })
+            })
+        })
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// The `CommandComplete` tag of plans that don't return rows, and whether
+/// the number of rows they affected is appended to it.
+fn command_tag(plan: &LogicalPlan) -> Option<(Tag, bool)> {
+    let tag = match plan {
+        LogicalPlan::Ddl(ddl) => match ddl {
+            DdlStatement::CreateExternalTable(_) | DdlStatement::CreateMemoryTable(_) => {
+                "CREATE TABLE"
+            }
+            DdlStatement::CreateView(_) => "CREATE VIEW",
+            DdlStatement::CreateCatalogSchema(_) => "CREATE SCHEMA",
+            DdlStatement::CreateCatalog(_) => "CREATE DATABASE",
+            DdlStatement::CreateIndex(_) => "CREATE INDEX",
+            DdlStatement::DropTable(_) => "DROP TABLE",
+            DdlStatement::DropView(_) => "DROP VIEW",
+            DdlStatement::DropCatalogSchema(_) => "DROP SCHEMA",
+            DdlStatement::CreateFunction(_) => "CREATE FUNCTION",
+            DdlStatement::DropFunction(_) => "DROP FUNCTION",
+        },
+        LogicalPlan::Statement(statement) => match statement {
+            Statement::SetVariable(_) => "SET",
+            Statement::Prepare(_) => "PREPARE",
+            Statement::Deallocate(_) => "DEALLOCATE",
+            _ => return None,
+        },
+        LogicalPlan::Dml(dml) => {
+            let tag = match dml.op {
+                // the oid of the inserted row, always 0 since postgres 12
+                WriteOp::Insert(_) => Tag::new("INSERT").with_oid(0),
+                WriteOp::Update => Tag::new("UPDATE"),
+                WriteOp::Delete => Tag::new("DELETE"),
+                WriteOp::Ctas => Tag::new("SELECT"),
+            };
+            return Some((tag, true));
+        }
+        LogicalPlan::Copy(_) => return Some((Tag::new("COPY"), true)),
+        _ => return None,
+    };
+    Some((Tag::new(tag), false))
+}
+
+/// Fail for `$n` parameters no `Bind` can supply a value for, as it counts
+/// its parameters with 16 bits, before their types are laid out by position.
+#[allow(clippy::result_large_err)]
+fn check_param_positions(plan: &LogicalPlan) -> PgWireResult<()> {
+    let param_types = plan
+        .get_parameter_types()
+        .map_err(errors::datafusion_error)?;
+    let Some(name) = param_types.keys().find(|name| {
+        param_position(name).is_some_and(|position| position > DEFAULT_MAX_PARAMETERS)
+    }) else {
+        return Ok(());
+    };
+    Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_owned(),
+        "42P02".to_owned(),
+        format!("there is no parameter {name}"),
+    ))))
 }
 
-fn ordered_param_types(types: &HashMap<String, Option<DataType>>) -> Vec<Option<&DataType>> {
+fn ordered_param_types<'a>(
+    types: &'a HashMap<String, Option<DataType>>,
+    names: Option<&[String]>,
+) -> Vec<Option<&'a DataType>> {
+    // named parameters are bound in the order they appear in the query
+    if let Some(names) = names {
+        return names
+            .iter()
+            .map(|name| types.get(name).and_then(Option::as_ref))
+            .collect();
+    }
+
     // Datafusion stores the parameters as a map.  In our case, the keys will be
-    // `$1`, `$2` etc.  The values will be the parameter types.
+    // `$1`, `$2` etc.  The values will be the parameter types. A parameter
+    // used several times has a single entry, and one that isn't used, like
+    // `$2` in `$1 + $3`, none, so its type is unknown.
+    let mut ordered = vec![];
+    for (name, data_type) in types {
+        let Some(position) = param_position(name) else {
+            continue;
+        };
+        if ordered.len() < position {
+            ordered.resize(position, None);
+        }
+        ordered[position - 1] = data_type.as_ref();
+    }
+    ordered
+}
 
-    let mut types = types.iter().collect::<Vec<_>>();
-    types.sort_by(|a, b| a.0.cmp(b.0));
-    types.into_iter().map(|pt| pt.1.as_ref()).collect()
+/// The position of a `$n` parameter, starting at 1.
+fn param_position(name: &str) -> Option<usize> {
+    name.strip_prefix('$')
+        .and_then(|position| position.parse::<usize>().ok())
+        .filter(|position| *position > 0)
+}
+
+/// The parameters of a statement using `$name` placeholders, in the order
+/// they first appear in its query, or `None` if they are all positional.
+fn named_params(query: &str, types: &HashMap<String, Option<DataType>>) -> Option<Vec<String>> {
+    if types.keys().all(|name| param_position(name).is_some()) {
+        return None;
+    }
+    let tokens = Tokenizer::new(&PostgreSqlDialect {}, query)
+        .tokenize()
+        .ok()?;
+    let mut names: Vec<String> = vec![];
+    for token in tokens {
+        if let Token::Placeholder(name) = token {
+            if types.contains_key(&name) && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    Some(names)
 }
@@ -1,4 +1,34 @@
+mod cooperative;
+mod copy;
 mod datatypes;
+mod errors;
+mod explain;
 mod handlers;
+mod memory;
+mod metrics;
+mod pg_types;
+mod returning;
+mod rewrite;
+mod roles;
+mod search_path;
+mod server;
+mod settings;
+mod sleep;
+mod stats;
+mod stub_functions;
+mod timeout;
 
+pub use datatypes::{
+    validate_pg_encodable, value_to_pg_text, SchemaTransform, PG_TYPE_METADATA_KEY,
+};
 pub use handlers::{DfSessionService, HandlerFactory, Parser};
+pub use metrics::unsupported_types;
+pub use rewrite::{QueryRewriter, Rewrite};
+pub use roles::AuthorizeCallback;
+pub use server::{serve, ServerOptions};
+pub use stats::{QueryStats, QueryStatsCallback};
+
+// for benchmarks, not part of the API
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub use datatypes::{encode_dataframe, EncodeOptions};
@@ -0,0 +1,85 @@
+//! Per-query memory limits, within the memory pool of the session.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use datafusion::common::{resources_datafusion_err, Result};
+use datafusion::execution::memory_pool::{MemoryConsumer, MemoryPool, MemoryReservation};
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::execution::SessionStateBuilder;
+use datafusion::prelude::DataFrame;
+
+/// `dataframe`, with its operators holding at most `limit` bytes at once.
+/// Their memory is still reserved from the pool of the session, which may
+/// run out first.
+pub(crate) fn with_memory_limit(dataframe: DataFrame, limit: usize) -> DataFrame {
+    let (state, plan) = dataframe.into_parts();
+    let runtime = state.runtime_env();
+    let runtime = Arc::new(RuntimeEnv {
+        memory_pool: Arc::new(QueryMemoryPool {
+            pool: runtime.memory_pool.clone(),
+            limit,
+            reserved: AtomicUsize::new(0),
+        }),
+        disk_manager: runtime.disk_manager.clone(),
+        cache_manager: runtime.cache_manager.clone(),
+        object_store_registry: runtime.object_store_registry.clone(),
+    });
+    let state = SessionStateBuilder::new_from_existing(state)
+        .with_runtime_env(runtime)
+        .build();
+    DataFrame::new(state, plan)
+}
+
+/// The memory reserved by a query in `pool`, limited to `limit` bytes.
+#[derive(Debug)]
+struct QueryMemoryPool {
+    pool: Arc<dyn MemoryPool>,
+    limit: usize,
+    reserved: AtomicUsize,
+}
+
+impl MemoryPool for QueryMemoryPool {
+    fn register(&self, consumer: &MemoryConsumer) {
+        self.pool.register(consumer);
+    }
+
+    fn unregister(&self, consumer: &MemoryConsumer) {
+        self.pool.unregister(consumer);
+    }
+
+    fn grow(&self, reservation: &MemoryReservation, additional: usize) {
+        self.pool.grow(reservation, additional);
+        self.reserved.fetch_add(additional, Ordering::Relaxed);
+    }
+
+    fn shrink(&self, reservation: &MemoryReservation, shrink: usize) {
+        self.pool.shrink(reservation, shrink);
+        self.reserved.fetch_sub(shrink, Ordering::Relaxed);
+    }
+
+    fn try_grow(&self, reservation: &MemoryReservation, additional: usize) -> Result<()> {
+        self.reserved
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |reserved| {
+                reserved
+                    .checked_add(additional)
+                    .filter(|reserved| *reserved <= self.limit)
+            })
+            .map_err(|reserved| {
+                resources_datafusion_err!(
+                    "Failed to allocate additional {additional} bytes for {} with {reserved} bytes already allocated for this query - {} bytes remain available for the query",
+                    reservation.consumer().name(),
+                    self.limit.saturating_sub(reserved)
+                )
+            })?;
+        self.pool
+            .try_grow(reservation, additional)
+            .inspect_err(|_| {
+                self.reserved.fetch_sub(additional, Ordering::Relaxed);
+            })
+    }
+
+    fn reserved(&self) -> usize {
+        self.reserved.load(Ordering::Relaxed)
+    }
+}
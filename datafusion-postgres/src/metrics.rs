@@ -0,0 +1,24 @@
+//! Counters of the arrow types the server couldn't serve, to tell which ones
+//! clients need.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use datafusion::arrow::datatypes::DataType;
+
+static UNSUPPORTED_TYPES: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+/// Number of times a value of each arrow type, by name, couldn't be described
+/// or encoded as a postgres type since the process started.
+pub fn unsupported_types() -> BTreeMap<String, u64> {
+    UNSUPPORTED_TYPES.lock().unwrap().clone()
+}
+
+/// Count a failure to describe or encode a value of `data_type`.
+pub(crate) fn count_unsupported_type(data_type: &DataType) {
+    *UNSUPPORTED_TYPES
+        .lock()
+        .unwrap()
+        .entry(data_type.to_string())
+        .or_default() += 1;
+}
@@ -0,0 +1,1814 @@
+//! Wire encodings for postgres types that have no Rust counterpart in
+//! `postgres-types`.
+
+use std::error::Error;
+use std::fmt::{self, Debug, Display, LowerExp};
+use std::net::{IpAddr, Ipv4Addr};
+
+use bytes::{BufMut, BytesMut};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Timelike};
+use datafusion::arrow::datatypes::DECIMAL128_MAX_PRECISION;
+use pgwire::api::Type;
+use pgwire::types::ToSqlText;
+use postgres_types::{to_sql_checked, FromSql, IsNull, Kind, ToSql};
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Decoding of values in text format, the counterpart of [`ToSqlText`] for
+/// parameters.
+pub(crate) trait FromSqlText: Sized {
+    fn from_sql_text(ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>>;
+}
+
+/// A `money` value, in cents.
+///
+/// Postgres formats money according to `lc_monetary`. We always use the `C`
+/// locale, with two fractional digits, `$` as currency symbol and `,` as
+/// thousands separator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Money(pub(crate) i64);
+
+impl Money {
+    /// Convert a decimal with the given scale to money, rounding half away
+    /// from zero like postgres does. Returns `None` when out of range.
+    pub(crate) fn from_decimal(value: i128, scale: i8) -> Option<Money> {
+        let cents = if scale > 2 {
+            let divisor = 10i128.checked_pow((scale - 2) as u32)?;
+            let quotient = value / divisor;
+            let remainder = value % divisor;
+            if remainder.unsigned_abs() * 2 >= divisor.unsigned_abs() {
+                quotient + value.signum()
+            } else {
+                quotient
+            }
+        } else {
+            value.checked_mul(10i128.checked_pow((2 - scale as i32) as u32)?)?
+        };
+        i64::try_from(cents).ok().map(Money)
+    }
+}
+
+impl ToSql for Money {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        out.put_i64(self.0);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::MONEY
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Money {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        if self.0 < 0 {
+            out.put_u8(b'-');
+        }
+        out.put_u8(b'$');
+
+        let cents = self.0.unsigned_abs();
+        let units = (cents / 100).to_string();
+        for (i, digit) in units.bytes().enumerate() {
+            if i > 0 && (units.len() - i) % 3 == 0 {
+                out.put_u8(b',');
+            }
+            out.put_u8(digit);
+        }
+        out.put_slice(format!(".{:02}", cents % 100).as_bytes());
+
+        Ok(IsNull::No)
+    }
+}
+
+impl<'a> FromSql<'a> for Money {
+    fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let bytes: [u8; 8] = raw
+            .try_into()
+            .map_err(|_| "invalid buffer size for money")?;
+        Ok(Money(i64::from_be_bytes(bytes)))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::MONEY
+    }
+}
+
+/// Like postgres' cash_in in the `C` locale, accepts a sign or parentheses
+/// for negative values, an optional `$`, `,` as thousands separator, and
+/// fractions of cents rounded half away from zero.
+impl FromSqlText for Money {
+    fn from_sql_text(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let text = std::str::from_utf8(raw)?.trim_matches(|c: char| c.is_ascii_whitespace());
+        let invalid = || format!("invalid input syntax for type money: \"{text}\"");
+        let (mut negative, mut rest) = match text.strip_prefix('(') {
+            Some(inner) => (true, inner.strip_suffix(')').ok_or_else(invalid)?),
+            None => (false, text),
+        };
+        // the sign may come before or after the currency symbol
+        for symbol in ["-", "+", "$", "-", "+"] {
+            if let Some(tail) = rest.strip_prefix(symbol) {
+                negative ^= symbol == "-";
+                rest = tail;
+            }
+        }
+        let (integer, fraction) = rest.split_once('.').unwrap_or((rest, ""));
+        let integer = integer.replace(',', "");
+        let digits = integer.bytes().chain(fraction.bytes());
+        if integer.is_empty() && fraction.is_empty() || !digits.clone().all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid().into());
+        }
+        // like postgres, only the digit after the cents is rounded
+        let fraction = &fraction[..fraction.len().min(3)];
+        let out_of_range = || format!("value \"{text}\" is out of range for type money");
+        let mut value: i128 = 0;
+        for digit in integer.bytes().chain(fraction.bytes()) {
+            value = value
+                .checked_mul(10)
+                .and_then(|value| value.checked_add((digit - b'0') as i128))
+                .ok_or_else(out_of_range)?;
+        }
+        let value = if negative { -value } else { value };
+        Money::from_decimal(value, fraction.len() as i8).ok_or_else(|| out_of_range().into())
+    }
+}
+
+/// A `"char"` value, postgres' internal single-byte type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PgChar(pub(crate) u8);
+
+impl ToSql for PgChar {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        out.put_u8(self.0);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::CHAR
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for PgChar {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        // like postgres' charout: non-ASCII bytes are written in octal, as
+        // they are not valid UTF-8 on their own, and NUL as an empty string
+        match self.0 {
+            0 => {}
+            byte if byte.is_ascii() => out.put_u8(byte),
+            byte => out.put_slice(format!("\\{byte:03o}").as_bytes()),
+        }
+        Ok(IsNull::No)
+    }
+}
+
+/// A `real` or `double precision` value, with the `extra_float_digits` of
+/// the session. Infinities are written as `Infinity` and `-Infinity` in text
+/// format, like postgres does, rather than Rust's `inf`.
+///
+/// Like postgres, text values are the shortest that read back as the same
+/// value when `extra_float_digits` is above 0, and are rounded to
+/// [`Digits::DIGITS`] plus `extra_float_digits` significant digits otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Float<T>(pub(crate) T, pub(crate) i32);
+
+/// Significant digits of a float type that always survive a round trip
+/// through text, C's `FLT_DIG` and `DBL_DIG`.
+pub(crate) trait Digits {
+    const DIGITS: i32;
+}
+
+impl Digits for f32 {
+    const DIGITS: i32 = f32::DIGITS as i32;
+}
+
+impl Digits for f64 {
+    const DIGITS: i32 = f64::DIGITS as i32;
+}
+
+impl<T: ToSql> ToSql for Float<T> {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        self.0.to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        T::accepts(ty)
+    }
+
+    to_sql_checked!();
+}
+
+impl<T: ToSqlText + Into<f64> + Copy + Digits + Display + LowerExp> ToSqlText for Float<T> {
+    fn to_sql_text(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match self.0.into() {
+            f64::INFINITY => out.put_slice(b"Infinity"),
+            f64::NEG_INFINITY => out.put_slice(b"-Infinity"),
+            value if value.is_nan() => return self.0.to_sql_text(ty, out),
+            value if self.1 <= 0 => {
+                let precision = (T::DIGITS + self.1).max(1) as usize;
+                out.put_slice(format_general(value, precision).as_bytes())
+            }
+            _ => out.put_slice(format_shortest(self.0, T::DIGITS).as_bytes()),
+        }
+        Ok(IsNull::No)
+    }
+}
+
+/// A finite `value` rounded to `precision` significant digits, written like
+/// C's `%.*g`: in exponent notation for exponents below -4 or from
+/// `precision` on, and without trailing zeros.
+fn format_general(value: f64, precision: usize) -> String {
+    let scientific = format!("{:.*e}", precision - 1, value);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("exponent notation has an exponent");
+    let exponent: i32 = exponent.parse().expect("the exponent is an integer");
+
+    if exponent < -4 || exponent >= precision as i32 {
+        exponent_notation(trim_fraction(mantissa), exponent)
+    } else {
+        let decimals = (precision as i32 - 1 - exponent) as usize;
+        trim_fraction(&format!("{value:.decimals$}")).to_owned()
+    }
+}
+
+/// The shortest digits of a finite `value` that read back as the same
+/// value, written like postgres does: in exponent notation for exponents
+/// below -4 or from `digits` on, [`Digits::DIGITS`] of its type.
+fn format_shortest<T: Display + LowerExp>(value: T, digits: i32) -> String {
+    let scientific = format!("{value:e}");
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("exponent notation has an exponent");
+    let exponent: i32 = exponent.parse().expect("the exponent is an integer");
+
+    if exponent < -4 || exponent >= digits {
+        exponent_notation(mantissa, exponent)
+    } else {
+        value.to_string()
+    }
+}
+
+/// `mantissa` times 10 to the `exponent`, like `1.5e+20`, the exponent
+/// having at least two digits like in C.
+fn exponent_notation(mantissa: &str, exponent: i32) -> String {
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{mantissa}e{sign}{:02}", exponent.abs())
+}
+
+/// `number` without the trailing zeros of its fraction, nor its decimal
+/// point when the fraction is all zeros.
+fn trim_fraction(number: &str) -> &str {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    }
+}
+
+/// A `numeric` value, as the unscaled integer and scale of an arrow decimal.
+///
+/// Text values keep the trailing zeros of the scale, like `1.50` for a
+/// scale of 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Numeric {
+    pub(crate) value: i128,
+    pub(crate) scale: i8,
+}
+
+impl<'a> FromSql<'a> for Numeric {
+    fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        // header: ndigits, weight, sign and dscale, followed by ndigits
+        // base-10000 digits, the first one being multiplied by
+        // 10000^weight
+        let header = |i: usize| u16::from_be_bytes([raw[i], raw[i + 1]]);
+        if raw.len() < 8 {
+            return Err("invalid buffer size for numeric".into());
+        }
+        let ndigits = header(0) as usize;
+        let weight = header(2) as i16;
+        let sign = header(4);
+        let dscale = header(6);
+        if raw.len() != 8 + ndigits * 2 {
+            return Err("invalid buffer size for numeric".into());
+        }
+
+        let negative = match sign {
+            0x0000 => false,
+            0x4000 => true,
+            0xC000 => return Err("NaN can't be stored as a decimal".into()),
+            0xD000 | 0xF000 => return Err("Infinity can't be stored as a decimal".into()),
+            _ => return Err("invalid sign for numeric".into()),
+        };
+        // a zero has no digits and any scale, the largest one of a decimal
+        // keeps as many zeros as it can
+        if ndigits == 0 {
+            return Ok(Numeric {
+                value: 0,
+                scale: dscale.min(DECIMAL128_MAX_PRECISION as u16) as i8,
+            });
+        }
+        if dscale as u32 > DECIMAL128_MAX_PRECISION as u32 {
+            return Err("numeric scale out of range for a decimal".into());
+        }
+
+        let out_of_range = "numeric value out of range for a decimal";
+        let mut value: i128 = 0;
+        for digit in raw[8..].chunks_exact(2) {
+            let digit = u16::from_be_bytes([digit[0], digit[1]]);
+            if digit >= 10000 {
+                return Err("invalid digit for numeric".into());
+            }
+            value = value
+                .checked_mul(10000)
+                .and_then(|value| value.checked_add(digit as i128))
+                .ok_or(out_of_range)?;
+        }
+
+        // `value` has 4 decimal digits for each base-10000 digit after the
+        // decimal point, rescale it to dscale digits
+        let fraction_digits = 4 * (ndigits as i32 - 1 - weight as i32);
+        let shift = dscale as i32 - fraction_digits;
+        let value = if shift >= 0 {
+            10i128
+                .checked_pow(shift as u32)
+                .and_then(|factor| value.checked_mul(factor))
+                .ok_or(out_of_range)?
+        } else {
+            // postgres doesn't send digits beyond dscale, these are zeros
+            10i128
+                .checked_pow(-shift as u32)
+                .map_or(0, |divisor| value / divisor)
+        };
+        if value >= 10i128.pow(DECIMAL128_MAX_PRECISION as u32) {
+            return Err(out_of_range.into());
+        }
+
+        Ok(Numeric {
+            value: if negative { -value } else { value },
+            scale: dscale as i8,
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+}
+
+/// Like postgres' numeric_in, accepts a sign, digits with a decimal point
+/// and an exponent, like `-1.50e3`. The scale is the number of digits after
+/// the point, less the exponent.
+impl FromSqlText for Numeric {
+    fn from_sql_text(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let text = std::str::from_utf8(raw)?.trim_matches(|c: char| c.is_ascii_whitespace());
+        let invalid = || format!("invalid input syntax for type numeric: \"{text}\"");
+        let lower = text.to_ascii_lowercase();
+        let (negative, unsigned) = match lower.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, lower.strip_prefix('+').unwrap_or(&lower)),
+        };
+        match unsigned {
+            "nan" => return Err("NaN can't be stored as a decimal".into()),
+            "inf" | "infinity" => return Err("Infinity can't be stored as a decimal".into()),
+            _ => {}
+        }
+        let (mantissa, exponent) = match unsigned.split_once('e') {
+            Some((mantissa, exponent)) => {
+                (mantissa, exponent.parse::<i32>().map_err(|_| invalid())?)
+            }
+            None => (unsigned, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = integer.bytes().chain(fraction.bytes());
+        if integer.is_empty() && fraction.is_empty() || !digits.clone().all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid().into());
+        }
+
+        let out_of_range = "numeric value out of range for a decimal";
+        let mut value: i128 = 0;
+        for digit in digits {
+            value = value
+                .checked_mul(10)
+                .and_then(|value| value.checked_add((digit - b'0') as i128))
+                .ok_or(out_of_range)?;
+        }
+        let mut scale = fraction.len() as i64 - exponent as i64;
+        if scale < 0 {
+            value = u32::try_from(-scale)
+                .ok()
+                .and_then(|shift| 10i128.checked_pow(shift))
+                .and_then(|factor| value.checked_mul(factor))
+                .ok_or(out_of_range)?;
+            scale = 0;
+        }
+        // a zero keeps as many zeros as a decimal can, like in binary format
+        if value == 0 {
+            scale = scale.min(DECIMAL128_MAX_PRECISION as i64);
+        }
+        if scale > DECIMAL128_MAX_PRECISION as i64 {
+            return Err("numeric scale out of range for a decimal".into());
+        }
+        if value >= 10i128.pow(DECIMAL128_MAX_PRECISION as u32) {
+            return Err(out_of_range.into());
+        }
+
+        Ok(Numeric {
+            value: if negative { -value } else { value },
+            scale: scale as i8,
+        })
+    }
+}
+
+impl Numeric {
+    /// The digits before and after the decimal point, without sign. Negative
+    /// scales multiply the value by a power of ten.
+    fn digits(&self) -> (String, String) {
+        let digits = self.value.unsigned_abs().to_string();
+        if self.scale <= 0 {
+            if digits == "0" {
+                return (digits, String::new());
+            }
+            let zeros = "0".repeat(-(self.scale as i32) as usize);
+            return (digits + &zeros, String::new());
+        }
+        let scale = self.scale as usize;
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        (integer.to_owned(), fraction.to_owned())
+    }
+}
+
+impl ToSql for Numeric {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        // base-10000 digits of the integer part, aligned on the decimal
+        // point, then of the fraction, padded with zeros on the right
+        let (integer, fraction) = self.digits();
+        let integer = integer.trim_start_matches('0');
+        let integer = format!("{integer:0>width$}", width = integer.len().div_ceil(4) * 4);
+        let fraction = format!(
+            "{fraction:0<width$}",
+            width = fraction.len().div_ceil(4) * 4
+        );
+        let mut digits: Vec<u16> = integer
+            .as_bytes()
+            .chunks(4)
+            .chain(fraction.as_bytes().chunks(4))
+            .map(|group| group.iter().fold(0, |acc, d| acc * 10 + (d - b'0') as u16))
+            .collect();
+        let mut weight = (integer.len() / 4) as i16 - 1;
+
+        // leading and trailing zero digits aren't sent
+        let leading = digits.iter().take_while(|digit| **digit == 0).count();
+        digits.drain(..leading);
+        weight -= leading as i16;
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        if digits.is_empty() {
+            weight = 0;
+        }
+
+        out.put_u16(digits.len() as u16);
+        out.put_i16(weight);
+        out.put_u16(if self.value < 0 { 0x4000 } else { 0x0000 });
+        out.put_u16(self.scale.max(0) as u16);
+        for digit in digits {
+            out.put_u16(digit);
+        }
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Numeric {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let (integer, fraction) = self.digits();
+        if self.value < 0 {
+            out.put_u8(b'-');
+        }
+        out.put_slice(integer.as_bytes());
+        if !fraction.is_empty() {
+            out.put_u8(b'.');
+            out.put_slice(fraction.as_bytes());
+        }
+        Ok(IsNull::No)
+    }
+}
+
+/// A `bytea` value. Inside arrays, the hex format is quoted with its
+/// backslash escaped, as postgres does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Bytea<'a>(pub(crate) &'a [u8]);
+
+impl ToSql for Bytea<'_> {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        self.0.to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::BYTEA
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Bytea<'_> {
+    fn to_sql_text(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let in_array = matches!(ty.kind(), Kind::Array(_));
+        if in_array {
+            out.put_slice(b"\"\\\\x");
+        } else {
+            out.put_slice(b"\\x");
+        }
+        for byte in self.0 {
+            out.put_u8(HEX_DIGITS[(byte >> 4) as usize]);
+            out.put_u8(HEX_DIGITS[(byte & 0x0f) as usize]);
+        }
+        if in_array {
+            out.put_u8(b'"');
+        }
+        Ok(IsNull::No)
+    }
+}
+
+/// Like postgres' byteain, accepts both the hex format, `\x` followed by
+/// pairs of hex digits optionally separated by whitespace, and the escape
+/// format, where backslashes start either an octal escape or another
+/// backslash.
+impl FromSqlText for Vec<u8> {
+    fn from_sql_text(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        if let Some(hex) = raw.strip_prefix(b"\\x") {
+            let digit = |c: u8| {
+                (c as char)
+                    .to_digit(16)
+                    .map(|d| d as u8)
+                    .ok_or_else(|| format!("invalid hexadecimal digit: \"{}\"", c as char))
+            };
+            let mut bytes = Vec::with_capacity(hex.len() / 2);
+            let mut digits = hex.iter().filter(|c| !c.is_ascii_whitespace());
+            while let Some(&high) = digits.next() {
+                let low = *digits
+                    .next()
+                    .ok_or("invalid hexadecimal data: odd number of digits")?;
+                bytes.push(digit(high)? << 4 | digit(low)?);
+            }
+            return Ok(bytes);
+        }
+
+        let mut bytes = Vec::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some((&c, tail)) = rest.split_first() {
+            if c != b'\\' {
+                bytes.push(c);
+                rest = tail;
+            } else if tail.first() == Some(&b'\\') {
+                bytes.push(b'\\');
+                rest = &tail[1..];
+            } else {
+                match tail {
+                    [a @ b'0'..=b'3', b @ b'0'..=b'7', c @ b'0'..=b'7', ..] => {
+                        bytes.push((a - b'0') << 6 | (b - b'0') << 3 | (c - b'0'));
+                        rest = &tail[3..];
+                    }
+                    _ => return Err("invalid input syntax for type bytea".into()),
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// A `uuid` value, as the 16 bytes of an arrow `arrow.uuid` extension value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Uuid<'a>(pub(crate) &'a [u8]);
+
+impl ToSql for Uuid<'_> {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        out.put_slice(self.0);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::UUID
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Uuid<'_> {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        for (idx, byte) in self.0.iter().enumerate() {
+            if matches!(idx, 4 | 6 | 8 | 10) {
+                out.put_u8(b'-');
+            }
+            out.put_u8(HEX_DIGITS[(byte >> 4) as usize]);
+            out.put_u8(HEX_DIGITS[(byte & 0x0f) as usize]);
+        }
+        Ok(IsNull::No)
+    }
+}
+
+/// An `interval`, in the fields postgres stores it in. The fields of an
+/// arrow interval depend on its unit, so `YearMonth` intervals are like
+/// postgres' `interval year to month`, and `DayTime` ones like `interval day
+/// to second(3)`.
+///
+/// Text values are written in the `postgres` interval style, like
+/// `1 year 2 mons 3 days 04:05:06.7`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Interval {
+    pub(crate) months: i32,
+    pub(crate) days: i32,
+    pub(crate) microseconds: i64,
+}
+
+impl ToSql for Interval {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        out.put_i64(self.microseconds);
+        out.put_i32(self.days);
+        out.put_i32(self.months);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::INTERVAL
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Interval {
+    fn to_sql_text(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        // like postgres, only the fields that aren't zero are written, and
+        // positive ones following a negative one are signed
+        let mut text = String::new();
+        let mut after_negative = false;
+        for (value, unit) in [
+            (self.months / 12, "year"),
+            (self.months % 12, "mon"),
+            (self.days, "day"),
+        ] {
+            if value == 0 {
+                continue;
+            }
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            if after_negative && value > 0 {
+                text.push('+');
+            }
+            let plural = if value == 1 { "" } else { "s" };
+            text.push_str(&format!("{value} {unit}{plural}"));
+            after_negative = value < 0;
+        }
+
+        if text.is_empty() || self.microseconds != 0 {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            if self.microseconds < 0 {
+                text.push('-');
+            } else if after_negative {
+                text.push('+');
+            }
+            let micros = self.microseconds.unsigned_abs();
+            text.push_str(&format!(
+                "{:02}:{:02}:{:02}",
+                micros / 3_600_000_000,
+                micros / 60_000_000 % 60,
+                micros / 1_000_000 % 60
+            ));
+            let fraction = micros % 1_000_000;
+            if fraction != 0 {
+                text.push_str(format!(".{fraction:06}").trim_end_matches('0'));
+            }
+        }
+
+        // quoted as an array element, as it may have spaces
+        text.as_str().to_sql_text(ty, out)
+    }
+}
+
+impl<'a> FromSql<'a> for Interval {
+    fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let bytes: [u8; 16] = raw
+            .try_into()
+            .map_err(|_| "invalid buffer size for interval")?;
+        let (microseconds, rest) = bytes.split_at(8);
+        let (days, months) = rest.split_at(4);
+        Ok(Interval {
+            months: i32::from_be_bytes(months.try_into()?),
+            days: i32::from_be_bytes(days.try_into()?),
+            microseconds: i64::from_be_bytes(microseconds.try_into()?),
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::INTERVAL
+    }
+}
+
+/// Like postgres' interval_in, accepts numbers followed by their unit, like
+/// `1 year 2 mons 3 days`, times like `-04:05:06.7`, either optionally
+/// followed by `ago`, and the ISO 8601 format, like `P1Y2M3DT4H5M6.7S`. A
+/// number without unit is a number of seconds. Fractions of months and days
+/// are carried to days, at 30 days a month, and time.
+impl FromSqlText for Interval {
+    fn from_sql_text(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let text = std::str::from_utf8(raw)?.trim_matches(|c: char| c.is_ascii_whitespace());
+        let lower = text.to_ascii_lowercase();
+        let fields = match lower.strip_prefix('p') {
+            Some(iso) => IntervalFields::parse_iso(iso),
+            None => IntervalFields::parse(&lower),
+        };
+        fields
+            .and_then(IntervalFields::interval)
+            .ok_or_else(|| format!("invalid input syntax for type interval: \"{text}\"").into())
+    }
+}
+
+/// The fields of an interval being read, wide enough not to overflow before
+/// they are checked.
+#[derive(Default)]
+struct IntervalFields {
+    months: i128,
+    days: i128,
+    microseconds: i128,
+}
+
+/// A unit of an interval, with its length in months, days or microseconds.
+enum IntervalUnit {
+    Months(i128),
+    Days(i128),
+    Microseconds(i128),
+}
+
+impl IntervalUnit {
+    fn parse(word: &str) -> Option<IntervalUnit> {
+        Some(match word {
+            "microsecond" | "microseconds" | "us" | "usec" | "usecs" => {
+                IntervalUnit::Microseconds(1)
+            }
+            "millisecond" | "milliseconds" | "ms" | "msec" | "msecs" => {
+                IntervalUnit::Microseconds(1_000)
+            }
+            "second" | "seconds" | "s" | "sec" | "secs" => IntervalUnit::Microseconds(1_000_000),
+            "minute" | "minutes" | "m" | "min" | "mins" => IntervalUnit::Microseconds(60_000_000),
+            "hour" | "hours" | "h" | "hr" | "hrs" => IntervalUnit::Microseconds(3_600_000_000),
+            "day" | "days" | "d" => IntervalUnit::Days(1),
+            "week" | "weeks" | "w" => IntervalUnit::Days(7),
+            "month" | "months" | "mon" | "mons" => IntervalUnit::Months(1),
+            "year" | "years" | "y" | "yr" | "yrs" => IntervalUnit::Months(12),
+            "decade" | "decades" => IntervalUnit::Months(120),
+            "century" | "centuries" => IntervalUnit::Months(1_200),
+            "millennium" | "millennia" => IntervalUnit::Months(12_000),
+            _ => return None,
+        })
+    }
+}
+
+impl IntervalFields {
+    /// Read the postgres format, with its units in words.
+    fn parse(text: &str) -> Option<IntervalFields> {
+        let mut fields = IntervalFields::default();
+        let text = text.strip_prefix('@').unwrap_or(text);
+        if text.trim().is_empty() {
+            return None;
+        }
+        let mut words = text
+            .split(|c: char| c.is_ascii_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .peekable();
+        let mut ago = false;
+        while let Some(word) = words.next() {
+            if ago {
+                return None;
+            }
+            if word == "ago" {
+                ago = true;
+            } else if word.contains(':') {
+                fields.microseconds += time_microseconds(word)?;
+            } else {
+                // the unit may follow the number right away, like `10s`
+                let split = word
+                    .find(|c: char| c.is_ascii_alphabetic())
+                    .unwrap_or(word.len());
+                let (number, unit) = word.split_at(split);
+                let unit = match unit {
+                    "" => match words.peek().and_then(|word| IntervalUnit::parse(word)) {
+                        Some(unit) => {
+                            words.next();
+                            unit
+                        }
+                        None => IntervalUnit::Microseconds(1_000_000),
+                    },
+                    unit => IntervalUnit::parse(unit)?,
+                };
+                fields.add(micro_units(number)?, unit);
+            }
+        }
+        if ago {
+            fields.months = -fields.months;
+            fields.days = -fields.days;
+            fields.microseconds = -fields.microseconds;
+        }
+        Some(fields)
+    }
+
+    /// Read the ISO 8601 format, following its `P`, where `M` stands for
+    /// months before `T` and minutes after it.
+    fn parse_iso(text: &str) -> Option<IntervalFields> {
+        let mut fields = IntervalFields::default();
+        let mut time = false;
+        let mut rest = text;
+        if rest.is_empty() {
+            return None;
+        }
+        while !rest.is_empty() {
+            if let Some(tail) = rest.strip_prefix('t') {
+                time = true;
+                rest = tail;
+                continue;
+            }
+            let split = rest.find(|c: char| c.is_ascii_alphabetic())?;
+            let (number, tail) = rest.split_at(split);
+            let unit = match (tail.as_bytes()[0], time) {
+                (b'y', false) => IntervalUnit::Months(12),
+                (b'm', false) => IntervalUnit::Months(1),
+                (b'w', false) => IntervalUnit::Days(7),
+                (b'd', false) => IntervalUnit::Days(1),
+                (b'h', true) => IntervalUnit::Microseconds(3_600_000_000),
+                (b'm', true) => IntervalUnit::Microseconds(60_000_000),
+                (b's', true) => IntervalUnit::Microseconds(1_000_000),
+                _ => return None,
+            };
+            fields.add(micro_units(number)?, unit);
+            rest = &tail[1..];
+        }
+        Some(fields)
+    }
+
+    /// Add `value` millionths of `unit`, carrying fractions of months to
+    /// days and fractions of days to time.
+    fn add(&mut self, value: i128, unit: IntervalUnit) {
+        let (days, unit) = match unit {
+            IntervalUnit::Months(months) => {
+                let months = value * months;
+                self.months += months / 1_000_000;
+                (months % 1_000_000 * 30, 1)
+            }
+            IntervalUnit::Days(days) => (value, days),
+            IntervalUnit::Microseconds(microseconds) => {
+                self.microseconds += value * microseconds / 1_000_000;
+                return;
+            }
+        };
+        let days = days * unit;
+        self.days += days / 1_000_000;
+        self.microseconds += days % 1_000_000 * 86_400;
+    }
+
+    fn interval(self) -> Option<Interval> {
+        Some(Interval {
+            months: self.months.try_into().ok()?,
+            days: self.days.try_into().ok()?,
+            microseconds: self.microseconds.try_into().ok()?,
+        })
+    }
+}
+
+/// The microseconds of a signed time like `-04:05:06.7`, or of hours and
+/// minutes like `04:05`.
+fn time_microseconds(text: &str) -> Option<i128> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let mut parts = text.split(':');
+    let hours = parts.next()?;
+    let minutes = parts.next()?;
+    let seconds = parts.next().unwrap_or("0");
+    if parts.next().is_some() || [hours, minutes].iter().any(|part| part.contains('.')) {
+        return None;
+    }
+    let microseconds =
+        micro_units(hours)? * 3_600 + micro_units(minutes)? * 60 + micro_units(seconds)?;
+    Some(if negative {
+        -microseconds
+    } else {
+        microseconds
+    })
+}
+
+/// The millionths of a signed decimal number like `-1.5`, rounded half away
+/// from zero.
+fn micro_units(text: &str) -> Option<i128> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let digits = integer.bytes().chain(fraction.bytes());
+    if integer.is_empty() && fraction.is_empty()
+        || integer.len() > 18
+        || !digits.clone().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let mut value: i128 = integer.parse::<i128>().unwrap_or(0) * 1_000_000;
+    let mut scale = 100_000;
+    for digit in fraction.bytes().take(6) {
+        value += (digit - b'0') as i128 * scale;
+        scale /= 10;
+    }
+    if fraction
+        .as_bytes()
+        .get(6)
+        .is_some_and(|digit| *digit >= b'5')
+    {
+        value += 1;
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// A `record` value: the type of each of its fields and their value, already
+/// encoded in the format of the record, `None` for NULL.
+///
+/// Text values are written like postgres does, in parentheses with nothing
+/// for NULL fields, and fields quoted when empty or when they have a
+/// delimiter, a quote, a backslash or whitespace, like `(1,"a b",)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Record(pub(crate) Vec<(Type, Option<Vec<u8>>)>);
+
+impl Record {
+    /// `_record`, as an array of records. `Type::RECORD_ARRAY` is a pseudo
+    /// type, which arrays are neither written nor quoted as.
+    pub(crate) fn array_type() -> Type {
+        Type::new(
+            "_record".to_owned(),
+            Type::RECORD_ARRAY.oid(),
+            Kind::Array(Type::RECORD),
+            "pg_catalog".to_owned(),
+        )
+    }
+}
+
+impl ToSql for Record {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        out.put_i32(self.0.len() as i32);
+        for (ty, value) in &self.0 {
+            out.put_u32(ty.oid());
+            match value {
+                Some(value) => {
+                    out.put_i32(value.len() as i32);
+                    out.put_slice(value);
+                }
+                None => out.put_i32(-1),
+            }
+        }
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::RECORD
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Record {
+    fn to_sql_text(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let mut text = String::from("(");
+        for (idx, (_, value)) in self.0.iter().enumerate() {
+            if idx > 0 {
+                text.push(',');
+            }
+            let Some(value) = value else {
+                continue;
+            };
+            let value = std::str::from_utf8(value)?;
+            let quote = value.is_empty()
+                || value
+                    .chars()
+                    .any(|c| matches!(c, '"' | '\\' | '(' | ')' | ',') || c.is_ascii_whitespace());
+            if !quote {
+                text.push_str(value);
+                continue;
+            }
+            text.push('"');
+            for c in value.chars() {
+                if matches!(c, '"' | '\\') {
+                    text.push(c);
+                }
+                text.push(c);
+            }
+            text.push('"');
+        }
+        text.push(')');
+
+        // quoted as an array element, as it has parentheses
+        text.as_str().to_sql_text(ty, out)
+    }
+}
+
+/// A `timestamp` or `timestamptz` value, a `NaiveDateTime` or a
+/// `DateTime<FixedOffset>` in the time zone it is shown in.
+///
+/// Text values are written like postgres does, with at most six fractional
+/// digits of seconds and none of their trailing zeros, and the offset of
+/// `timestamptz` in hours, with minutes only if it has some, like
+/// `2024-01-02 03:04:05.1+05:30`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Timestamp<T>(pub(crate) T);
+
+impl<T: ToSql> ToSql for Timestamp<T> {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        self.0.to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        T::accepts(ty)
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Timestamp<NaiveDateTime> {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        out.put_slice(timestamp_text(&self.0).as_bytes());
+        Ok(IsNull::No)
+    }
+}
+
+impl ToSqlText for Timestamp<DateTime<FixedOffset>> {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let mut text = timestamp_text(&self.0.naive_local());
+        let offset = self.0.offset().local_minus_utc();
+        let seconds = offset.unsigned_abs();
+        let sign = if offset < 0 { '-' } else { '+' };
+        text.push_str(&format!("{sign}{:02}", seconds / 3600));
+        if seconds % 3600 != 0 {
+            text.push_str(&format!(":{:02}", seconds / 60 % 60));
+        }
+        if seconds % 60 != 0 {
+            text.push_str(&format!(":{:02}", seconds % 60));
+        }
+        out.put_slice(text.as_bytes());
+        Ok(IsNull::No)
+    }
+}
+
+fn timestamp_text(datetime: &NaiveDateTime) -> String {
+    let mut text = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
+    let micros = datetime.nanosecond() % 1_000_000_000 / 1000;
+    if micros != 0 {
+        let fraction = format!("{micros:06}");
+        text.push('.');
+        text.push_str(fraction.trim_end_matches('0'));
+    }
+    text
+}
+
+/// An `inet` or `cidr` value: an address and the length of its network mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Inet {
+    addr: IpAddr,
+    bits: u8,
+    cidr: bool,
+}
+
+impl Inet {
+    /// Parse the text form of a value of type `ty`, `inet` or `cidr`. Unlike
+    /// postgres, abbreviated IPv4 networks like `10/8` aren't accepted.
+    pub(crate) fn parse(ty: &Type, text: &str) -> Result<Inet, Box<dyn Error + Sync + Send>> {
+        let invalid = || format!("invalid input syntax for type {}: \"{text}\"", ty.name());
+        let (addr, bits) = match text.split_once('/') {
+            Some((addr, bits)) => (addr, Some(bits)),
+            None => (text, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max_bits = max_bits(&addr);
+        let bits = match bits {
+            Some(bits) => bits
+                .parse()
+                .ok()
+                .filter(|bits| *bits <= max_bits)
+                .ok_or_else(invalid)?,
+            None => max_bits,
+        };
+        Inet::new(ty, addr, bits).map_err(|e| format!("invalid cidr value: \"{text}\": {e}").into())
+    }
+
+    fn new(ty: &Type, addr: IpAddr, bits: u8) -> Result<Inet, Box<dyn Error + Sync + Send>> {
+        let cidr = *ty == Type::CIDR;
+        let host_bits = (max_bits(&addr) - bits) as u32;
+        let value = match addr {
+            IpAddr::V4(addr) => u32::from(addr) as u128,
+            IpAddr::V6(addr) => u128::from(addr),
+        };
+        if cidr && host_bits > 0 && value & (u128::MAX >> (128 - host_bits)) != 0 {
+            return Err("value has bits set to right of mask".into());
+        }
+        Ok(Inet { addr, bits, cidr })
+    }
+}
+
+fn max_bits(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+impl Display for Inet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.addr {
+            // like postgres, IPv4-compatible addresses keep their dotted
+            // form, which Rust only uses for IPv4-mapped addresses
+            IpAddr::V6(addr) if addr.segments()[..6] == [0; 6] && u128::from(addr) > 0xffff => {
+                let [.., a, b, c, d] = addr.octets();
+                write!(f, "::{}", Ipv4Addr::new(a, b, c, d))?
+            }
+            addr => write!(f, "{addr}")?,
+        }
+        if self.cidr || self.bits != max_bits(&self.addr) {
+            write!(f, "/{}", self.bits)?;
+        }
+        Ok(())
+    }
+}
+
+impl ToSql for Inet {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        // family, as PGSQL_AF_INET or PGSQL_AF_INET6, mask length, whether
+        // it is a cidr, then the address length and bytes
+        match self.addr {
+            IpAddr::V4(addr) => {
+                out.put_slice(&[2, self.bits, self.cidr as u8, 4]);
+                out.put_slice(&addr.octets());
+            }
+            IpAddr::V6(addr) => {
+                out.put_slice(&[3, self.bits, self.cidr as u8, 16]);
+                out.put_slice(&addr.octets());
+            }
+        }
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::INET | Type::CIDR)
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Inet {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        out.put_slice(self.to_string().as_bytes());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<'_> for Inet {
+    fn from_sql(ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let addr = match raw {
+            [2, _, _, 4, addr @ ..] => <[u8; 4]>::try_from(addr).map(IpAddr::from).ok(),
+            [3, _, _, 16, addr @ ..] => <[u8; 16]>::try_from(addr).map(IpAddr::from).ok(),
+            _ => None,
+        }
+        .ok_or_else(|| format!("invalid buffer for {}", ty.name()))?;
+        let bits = raw[1];
+        if bits > max_bits(&addr) {
+            return Err(format!("invalid mask length for {}", ty.name()).into());
+        }
+        Inet::new(ty, addr, bits)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::INET | Type::CIDR)
+    }
+}
+
+impl FromSqlText for Inet {
+    fn from_sql_text(ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Inet::parse(ty, std::str::from_utf8(raw)?)
+    }
+}
+
+/// A `regtype` value, the oid of a type, written as the name of the type in
+/// text format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RegType(pub(crate) u32);
+
+impl ToSql for RegType {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        out.put_u32(self.0);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::REGTYPE
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for RegType {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        // like postgres' regtypeout, the invalid oid 0 is written as `-`
+        // and unknown oids as numbers
+        match (self.0, Type::from_oid(self.0)) {
+            (0, _) => out.put_u8(b'-'),
+            (_, Some(ty)) => out.put_slice(sql_type_name(&ty).as_bytes()),
+            (oid, None) => out.put_slice(oid.to_string().as_bytes()),
+        }
+        Ok(IsNull::No)
+    }
+}
+
+/// A `regclass` or `regproc` value, the oid of a relation or a function.
+///
+/// There is no catalog to name them from, so they are written as numbers
+/// in text format, like postgres does for oids missing from its catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RegOid(pub(crate) u32);
+
+impl ToSql for RegOid {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        out.put_u32(self.0);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::REGCLASS | Type::REGPROC)
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for RegOid {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        // the invalid oid 0 is written as `-`, like postgres does
+        match self.0 {
+            0 => out.put_u8(b'-'),
+            oid => out.put_slice(oid.to_string().as_bytes()),
+        }
+        Ok(IsNull::No)
+    }
+}
+
+/// Like postgres' boolin, accepts `true`, `yes`, `on`, `1` and their
+/// opposites in any case, or an unambiguous prefix of the words, like `t` or
+/// `f`.
+impl FromSqlText for bool {
+    fn from_sql_text(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let text = std::str::from_utf8(raw)?.trim_matches(|c: char| c.is_ascii_whitespace());
+        let word = text.to_lowercase();
+        let prefix_of =
+            |full: &str, shortest: usize| word.len() >= shortest && full.starts_with(word.as_str());
+        if prefix_of("true", 1) || prefix_of("yes", 1) || prefix_of("on", 2) || word == "1" {
+            Ok(true)
+        } else if prefix_of("false", 1) || prefix_of("no", 1) || prefix_of("off", 2) || word == "0"
+        {
+            Ok(false)
+        } else {
+            Err(format!("invalid input syntax for type boolean: \"{text}\"").into())
+        }
+    }
+}
+
+/// Like postgres' oidin, accepts negative values down to -2147483648 as the
+/// oid with the same bits.
+impl FromSqlText for u32 {
+    fn from_sql_text(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let text = std::str::from_utf8(raw)?.trim_matches(|c: char| c.is_ascii_whitespace());
+        text.parse::<u32>()
+            .or_else(|_| text.parse::<i32>().map(|oid| oid as u32))
+            .map_err(|_| format!("invalid input syntax for type oid: \"{text}\"").into())
+    }
+}
+
+/// Dates in text format are read in the ISO style postgres sends them with,
+/// which is also what clients send whatever their `DateStyle`.
+impl FromSqlText for NaiveDate {
+    fn from_sql_text(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let text = std::str::from_utf8(raw)?.trim_matches(|c: char| c.is_ascii_whitespace());
+        NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .map_err(|_| format!("invalid input syntax for type date: \"{text}\"").into())
+    }
+}
+
+/// An `hstore` value. The type comes from an extension, so its oid is the one
+/// it got in the database the client expects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Hstore(Vec<(String, Option<String>)>);
+
+impl Hstore {
+    /// Like postgres, pairs are sorted by the length of their key then by
+    /// its bytes, and the first of the pairs with the same key is kept.
+    pub(crate) fn new(mut pairs: Vec<(String, Option<String>)>) -> Hstore {
+        pairs.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        pairs.dedup_by(|(a, _), (b, _)| a == b);
+        Hstore(pairs)
+    }
+
+    pub(crate) fn pg_type(oid: u32) -> Type {
+        Type::new("hstore".to_owned(), oid, Kind::Simple, "public".to_owned())
+    }
+}
+
+impl ToSql for Hstore {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        out.put_i32(self.0.len() as i32);
+        for (key, value) in &self.0 {
+            out.put_i32(key.len() as i32);
+            out.put_slice(key.as_bytes());
+            match value {
+                Some(value) => {
+                    out.put_i32(value.len() as i32);
+                    out.put_slice(value.as_bytes());
+                }
+                None => out.put_i32(-1),
+            }
+        }
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "hstore"
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Hstore {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        // like hstore_out: "key"=>"value" pairs separated by a comma and a
+        // space, with quotes and backslashes escaped
+        let quoted = |out: &mut BytesMut, text: &str| {
+            out.put_u8(b'"');
+            for c in text.bytes() {
+                if c == b'"' || c == b'\\' {
+                    out.put_u8(b'\\');
+                }
+                out.put_u8(c);
+            }
+            out.put_u8(b'"');
+        };
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                out.put_slice(b", ");
+            }
+            quoted(out, key);
+            out.put_slice(b"=>");
+            match value {
+                Some(value) => quoted(out, value),
+                None => out.put_slice(b"NULL"),
+            }
+        }
+        Ok(IsNull::No)
+    }
+}
+
+/// A `void` value, sent empty in both formats like postgres does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Void;
+
+impl ToSql for Void {
+    fn to_sql(
+        &self,
+        _ty: &Type,
+        _out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::VOID
+    }
+
+    to_sql_checked!();
+}
+
+impl ToSqlText for Void {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        _out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        Ok(IsNull::No)
+    }
+}
+
+/// The name of a type as written by postgres' format_type, which uses the
+/// SQL standard names of built-in types.
+fn sql_type_name(ty: &Type) -> String {
+    if let Kind::Array(item) = ty.kind() {
+        return format!("{}[]", sql_type_name(item));
+    }
+    match *ty {
+        Type::BOOL => "boolean",
+        Type::CHAR => "\"char\"",
+        Type::INT2 => "smallint",
+        Type::INT4 => "integer",
+        Type::INT8 => "bigint",
+        Type::FLOAT4 => "real",
+        Type::FLOAT8 => "double precision",
+        Type::BPCHAR => "character",
+        Type::VARCHAR => "character varying",
+        Type::TIME => "time without time zone",
+        Type::TIMETZ => "time with time zone",
+        Type::TIMESTAMP => "timestamp without time zone",
+        Type::TIMESTAMPTZ => "timestamp with time zone",
+        Type::VARBIT => "bit varying",
+        _ => ty.name(),
+    }
+    .to_owned()
+}
+
+/// A `bit varying` value, from bytes holding 8 bits each, the most
+/// significant bit first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct VarBit<B>(pub(crate) B);
+
+impl<B: AsRef<[u8]> + Debug> ToSql for VarBit<B> {
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        let bytes = self.0.as_ref();
+        let bits = i32::try_from(bytes.len() * 8).map_err(|_| "bit string too long")?;
+        out.put_i32(bits);
+        out.put_slice(bytes);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::VARBIT
+    }
+
+    to_sql_checked!();
+}
+
+impl<B: AsRef<[u8]> + Debug> ToSqlText for VarBit<B> {
+    fn to_sql_text(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        for byte in self.0.as_ref() {
+            for bit in (0..8).rev() {
+                out.put_u8(if byte & (1 << bit) == 0 { b'0' } else { b'1' });
+            }
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<'_> for VarBit<Vec<u8>> {
+    fn from_sql(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let (bits, bytes) = raw
+            .split_first_chunk::<4>()
+            .ok_or("invalid buffer size for bit string")?;
+        let bits = i32::from_be_bytes(*bits);
+        if bits < 0 || bytes.len() != (bits as usize).div_ceil(8) {
+            return Err("invalid buffer size for bit string".into());
+        }
+        if bits % 8 != 0 {
+            return Err(format!("bit string of length {bits} can't be stored as bytes").into());
+        }
+        Ok(VarBit(bytes.to_vec()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::VARBIT
+    }
+}
+
+/// Like postgres' varbit_in, accepts bits, optionally following a `B`, or
+/// hex digits of 4 bits each following an `X`. Like in binary format, only
+/// whole bytes can be stored.
+impl FromSqlText for VarBit<Vec<u8>> {
+    fn from_sql_text(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let (radix, digits) = match raw {
+            [b'x' | b'X', digits @ ..] => (16, digits),
+            [b'b' | b'B', digits @ ..] => (2, digits),
+            digits => (2, digits),
+        };
+        let width = if radix == 16 { 4 } else { 1 };
+        let mut bits = Vec::with_capacity(digits.len() * width);
+        for &c in digits {
+            let digit = (c as char).to_digit(radix).ok_or_else(|| {
+                let kind = if radix == 16 { "hexadecimal" } else { "binary" };
+                format!("\"{}\" is not a valid {kind} digit", c as char)
+            })?;
+            bits.extend((0..width).rev().map(|bit| digit >> bit & 1 == 1));
+        }
+        if bits.len() % 8 != 0 {
+            let length = bits.len();
+            return Err(format!("bit string of length {length} can't be stored as bytes").into());
+        }
+        Ok(VarBit(
+            bits.chunks(8)
+                .map(|byte| byte.iter().fold(0, |acc, bit| acc << 1 | *bit as u8))
+                .collect(),
+        ))
+    }
+}
+
+/// An `xml` value, sent as its text in both formats.
+#[derive(Debug)]
+pub(crate) struct Xml(pub(crate) String);
+
+impl Xml {
+    /// Check that the value is well-formed XML content, which unlike a
+    /// document can hold several elements and text around them, like
+    /// postgres' default `xmloption`. Entities other than the predefined ones
+    /// aren't accepted, nor are document type declarations.
+    pub(crate) fn check_well_formed(&self) -> Result<(), String> {
+        let text = self.0.as_str();
+        let mut open = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find(['<', '&']) {
+            check_text(&rest[..start])?;
+            rest = &rest[start..];
+            if rest.starts_with('&') {
+                rest = skip_reference(rest)?;
+            } else if let Some(comment) = rest.strip_prefix("<!--") {
+                let end = comment.find("-->").ok_or("unterminated comment")?;
+                rest = &comment[end + 3..];
+            } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let end = cdata.find("]]>").ok_or("unterminated CDATA section")?;
+                rest = &cdata[end + 3..];
+            } else if let Some(pi) = rest.strip_prefix("<?") {
+                let end = pi.find("?>").ok_or("unterminated processing instruction")?;
+                rest = &pi[end + 2..];
+            } else if let Some(tag) = rest.strip_prefix("</") {
+                let (name, after) = split_name(tag)?;
+                let after = after.trim_start_matches(is_xml_space);
+                let after = after
+                    .strip_prefix('>')
+                    .ok_or_else(|| format!("malformed end tag for element {name}"))?;
+                match open.pop() {
+                    Some(expected) if expected == name => {}
+                    Some(expected) => {
+                        return Err(format!("end tag {name} doesn't match start tag {expected}"))
+                    }
+                    None => return Err(format!("end tag {name} has no start tag")),
+                }
+                rest = after;
+            } else {
+                let (name, after) = split_name(&rest[1..])?;
+                let (empty, after) = skip_attributes(name, after)?;
+                if !empty {
+                    open.push(name);
+                }
+                rest = after;
+            }
+        }
+        check_text(rest)?;
+        match open.pop() {
+            Some(name) => Err(format!("element {name} is not closed")),
+            None => Ok(()),
+        }
+    }
+}
+
+fn is_xml_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.')
+}
+
+/// Split a name off the start of `text`.
+fn split_name(text: &str) -> Result<(&str, &str), String> {
+    let end = text.find(|c| !is_name_char(c)).unwrap_or(text.len());
+    let name = &text[..end];
+    match name.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == ':' => Ok((name, &text[end..])),
+        _ => Err("expected a name".to_owned()),
+    }
+}
+
+fn check_text(text: &str) -> Result<(), String> {
+    if text.contains("]]>") {
+        return Err("\"]]>\" is not allowed in text".to_owned());
+    }
+    Ok(())
+}
+
+/// Skip an entity or character reference at the start of `text`.
+fn skip_reference(text: &str) -> Result<&str, String> {
+    let end = text.find(';').ok_or("unterminated entity reference")?;
+    let reference = &text[1..end];
+    let valid = match reference.strip_prefix('#') {
+        Some(code) => match code.strip_prefix('x') {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => code.parse().ok(),
+        }
+        .and_then(char::from_u32)
+        .is_some(),
+        None => matches!(reference, "amp" | "lt" | "gt" | "apos" | "quot"),
+    };
+    if !valid {
+        return Err(format!("invalid entity reference &{reference};"));
+    }
+    Ok(&text[end + 1..])
+}
+
+/// Skip the attributes and end of the start tag of element `name`, returning
+/// whether it is an empty-element tag.
+fn skip_attributes<'a>(name: &str, mut text: &'a str) -> Result<(bool, &'a str), String> {
+    let mut attributes = Vec::new();
+    loop {
+        let trimmed = text.trim_start_matches(is_xml_space);
+        if let Some(rest) = trimmed.strip_prefix("/>") {
+            return Ok((true, rest));
+        }
+        if let Some(rest) = trimmed.strip_prefix('>') {
+            return Ok((false, rest));
+        }
+        if trimmed.len() == text.len() {
+            return Err(format!("malformed start tag for element {name}"));
+        }
+
+        let (attribute, rest) = split_name(trimmed)?;
+        if attributes.contains(&attribute) {
+            return Err(format!("attribute {attribute} redefined"));
+        }
+        attributes.push(attribute);
+        let rest = rest
+            .trim_start_matches(is_xml_space)
+            .strip_prefix('=')
+            .ok_or_else(|| format!("attribute {attribute} has no value"))?
+            .trim_start_matches(is_xml_space);
+        let quote = rest
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '"' | '\''))
+            .ok_or_else(|| format!("value of attribute {attribute} is not quoted"))?;
+        let end = rest[1..]
+            .find(quote)
+            .ok_or_else(|| format!("unterminated value of attribute {attribute}"))?;
+        let mut value = &rest[1..end + 1];
+        if value.contains('<') {
+            return Err(format!("'<' in value of attribute {attribute}"));
+        }
+        while let Some(start) = value.find('&') {
+            value = skip_reference(&value[start..])?;
+        }
+        text = &rest[end + 2..];
+    }
+}
+
+impl FromSql<'_> for Xml {
+    fn from_sql(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Ok(Xml(std::str::from_utf8(raw)?.to_owned()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::XML
+    }
+}
+
+impl FromSqlText for Xml {
+    fn from_sql_text(ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Xml::from_sql(ty, raw)
+    }
+}
+
+/// A `jsonpath` value, sent as its text, after a version byte in binary
+/// format. The path itself is passed on unchecked.
+#[derive(Debug)]
+pub(crate) struct JsonPath(pub(crate) String);
+
+impl FromSql<'_> for JsonPath {
+    fn from_sql(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        match raw.split_first() {
+            Some((1, text)) => Ok(JsonPath(std::str::from_utf8(text)?.to_owned())),
+            Some((version, _)) => {
+                Err(format!("unsupported jsonpath version number: {version}").into())
+            }
+            None => Err("jsonpath value without a version number".into()),
+        }
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::JSONPATH
+    }
+}
+
+impl FromSqlText for JsonPath {
+    fn from_sql_text(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Ok(JsonPath(std::str::from_utf8(raw)?.to_owned()))
+    }
+}
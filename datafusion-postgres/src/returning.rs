@@ -0,0 +1,146 @@
+//! `RETURNING` clauses of `INSERT`, which DataFusion doesn't plan.
+
+use std::fmt;
+use std::sync::Arc;
+
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{plan_err, DFSchemaRef};
+use datafusion::datasource::{provider_as_source, MemTable};
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::builder::LogicalTableSource;
+use datafusion::logical_expr::{
+    table_scan, DmlStatement, Expr, Extension, LogicalPlan, LogicalPlanBuilder, TableScan,
+    UserDefinedLogicalNodeCore,
+};
+use datafusion::prelude::{DataFrame, SessionContext};
+use datafusion::sql::parser::Statement as DFStatement;
+use datafusion::sql::sqlparser::ast::{SelectItem, Statement};
+
+/// An `INSERT` with a `RETURNING` clause, planned as the insert and, apart,
+/// the clause's projection of a placeholder scan of the inserted rows.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Hash)]
+pub(crate) struct InsertReturning {
+    insert: LogicalPlan,
+    returning: LogicalPlan,
+}
+
+impl UserDefinedLogicalNodeCore for InsertReturning {
+    fn name(&self) -> &str {
+        "InsertReturning"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.insert, &self.returning]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        self.returning.schema()
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InsertReturning")
+    }
+
+    fn with_exprs_and_inputs(&self, _exprs: Vec<Expr>, inputs: Vec<LogicalPlan>) -> Result<Self> {
+        let [insert, returning] = <[LogicalPlan; 2]>::try_from(inputs)
+            .unwrap_or_else(|_| unreachable!("InsertReturning has two inputs"));
+        Ok(InsertReturning { insert, returning })
+    }
+}
+
+/// Like `SessionState::statement_to_plan`, planning the `RETURNING` clause
+/// of an `INSERT` as an [`InsertReturning`] node.
+pub(crate) async fn statement_to_plan(
+    state: &SessionState,
+    mut statement: DFStatement,
+) -> Result<LogicalPlan> {
+    let returning = match &mut statement {
+        DFStatement::Statement(statement) => match statement.as_mut() {
+            Statement::Insert(insert) => insert.returning.take(),
+            _ => None,
+        },
+        _ => None,
+    };
+    let plan = state.statement_to_plan(statement).await?;
+    let Some(returning) = returning else {
+        return Ok(plan);
+    };
+    let LogicalPlan::Dml(insert) = &plan else {
+        return plan_err!("RETURNING is only supported by INSERT");
+    };
+
+    let rows = table_scan(
+        Some(insert.table_name.clone()),
+        insert.input.schema().as_arrow(),
+        None,
+    )?
+    .build()?;
+    let mut exprs = vec![];
+    for item in returning {
+        match item {
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(_, _) => {
+                exprs.extend(rows.schema().columns().into_iter().map(Expr::Column))
+            }
+            item => exprs.push(state.create_logical_expr(&item.to_string(), rows.schema())?),
+        }
+    }
+    let returning = LogicalPlanBuilder::from(rows).project(exprs)?.build()?;
+
+    Ok(LogicalPlan::Extension(Extension {
+        node: Arc::new(InsertReturning {
+            insert: plan,
+            returning,
+        }),
+    }))
+}
+
+/// The [`InsertReturning`] node of a plan, if it is one.
+pub(crate) fn insert_returning(plan: &LogicalPlan) -> Option<&InsertReturning> {
+    match plan {
+        LogicalPlan::Extension(extension) => extension.node.as_any().downcast_ref(),
+        _ => None,
+    }
+}
+
+/// Insert the rows of `node`, then evaluate its `RETURNING` clause on them.
+#[allow(clippy::result_large_err)]
+pub(crate) async fn execute(ctx: &SessionContext, node: &InsertReturning) -> Result<DataFrame> {
+    let LogicalPlan::Dml(insert) = &node.insert else {
+        unreachable!("InsertReturning inserts with a DML plan")
+    };
+    let rows = ctx
+        .execute_logical_plan(insert.input.as_ref().clone())
+        .await?;
+    let schema = Arc::new(rows.schema().as_arrow().clone());
+    let inserted = provider_as_source(Arc::new(MemTable::try_new(
+        schema,
+        vec![rows.collect().await?],
+    )?));
+
+    let scan = LogicalPlanBuilder::scan(insert.table_name.clone(), inserted.clone(), None)?;
+    let insert = LogicalPlan::Dml(DmlStatement {
+        input: Arc::new(scan.build()?),
+        ..insert.clone()
+    });
+    ctx.execute_logical_plan(insert).await?.collect().await?;
+
+    let returning = node
+        .returning
+        .clone()
+        .transform_up(|plan| match plan {
+            LogicalPlan::TableScan(scan) if scan.source.as_any().is::<LogicalTableSource>() => {
+                Ok(Transformed::yes(LogicalPlan::TableScan(TableScan {
+                    source: inserted.clone(),
+                    ..scan
+                })))
+            }
+            plan => Ok(Transformed::no(plan)),
+        })?
+        .data;
+    ctx.execute_logical_plan(returning).await
+}
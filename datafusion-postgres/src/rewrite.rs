@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::sql::sqlparser::ast::{Expr, Statement, Value};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use pgwire::api::{METADATA_DATABASE, METADATA_USER};
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+
+use crate::roles::ROLE_SETTING;
+use crate::settings::{self, DEFAULT_SETTINGS};
+
+/// Outcome of a [`QueryRewriter`] taking over a query.
+#[derive(Debug, Clone)]
+pub enum Rewrite {
+    /// Plan and execute this SQL instead of the original query.
+    Query(String),
+    /// Respond with rows built in memory, without planning the query.
+    Rows(RecordBatch),
+    /// Respond with no rows and this `CommandComplete` tag, sent as is. Row
+    /// counts are part of the tag, like in `INSERT 0 1`.
+    Command(String),
+    /// Like `Command`, for a statement starting a transaction block. Clients
+    /// are told they are in one by the status of `ReadyForQuery`.
+    TransactionStart(String),
+    /// Like `Command`, for a statement ending a transaction block.
+    TransactionEnd(String),
+}
+
+/// Hook to intercept raw SQL before it is planned by DataFusion.
+///
+/// This is the place to translate or answer postgres specific statements
+/// that DataFusion doesn't understand. `settings` holds the run-time
+/// parameters of the connection, including those sent by the client at
+/// startup, and may be updated by the rewriter.
+///
+/// In the extended query protocol, the rewriter is also called when the
+/// statement is parsed, with a copy of `settings`, to find out whether the
+/// query needs planning. Updates to that copy are discarded, while an error
+/// fails the `Parse` like it would fail the query when it is run.
+pub trait QueryRewriter: Send + Sync {
+    /// Return `None` to leave the query to the next rewriter, or DataFusion.
+    fn rewrite(
+        &self,
+        query: &str,
+        settings: &mut HashMap<String, String>,
+    ) -> PgWireResult<Option<Rewrite>>;
+}
+
+/// Handles `SET name { TO | = } value` for postgres run-time parameters.
+///
+/// Parameters the server doesn't know, like `extra_float_digits`, are stored
+/// for the connection as is, so that `SHOW` returns them. DataFusion's own
+/// `datafusion.*` options are left to DataFusion.
+pub(crate) struct SetRewriter;
+
+impl QueryRewriter for SetRewriter {
+    fn rewrite(
+        &self,
+        query: &str,
+        settings: &mut HashMap<String, String>,
+    ) -> PgWireResult<Option<Rewrite>> {
+        if !starts_with_keyword(query, "set") {
+            return Ok(None);
+        }
+
+        let (name, value) = match parse_single_statement(query) {
+            Some(Statement::SetVariable {
+                variables, value, ..
+            }) => {
+                let name = variables.to_string();
+                if is_datafusion_option(&name) {
+                    return Ok(None);
+                }
+                let value = value.iter().map(expr_to_setting).collect::<Vec<_>>();
+                (name, value.join(", "))
+            }
+            Some(Statement::SetTimeZone { value, .. }) => {
+                ("TimeZone".to_owned(), expr_to_setting(&value))
+            }
+            _ => return Ok(None),
+        };
+
+        match settings::find(&name) {
+            Some(setting) if setting.read_only => {
+                Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                    "ERROR".to_owned(),
+                    "55P02".to_owned(),
+                    format!("parameter \"{name}\" cannot be changed"),
+                ))))
+            }
+            Some(_) => {
+                let value = settings::validate(&name, &value)?;
+                settings.insert(name.to_lowercase(), value);
+                Ok(Some(Rewrite::Command("SET".to_owned())))
+            }
+            None if [METADATA_USER, METADATA_DATABASE]
+                .iter()
+                .any(|key| name.eq_ignore_ascii_case(key)) =>
+            {
+                Err(unrecognized_parameter(&name))
+            }
+            None => {
+                settings.insert(name.to_lowercase(), value);
+                Ok(Some(Rewrite::Command("SET".to_owned())))
+            }
+        }
+    }
+}
+
+/// Handles `SHOW name` and `SHOW ALL` for postgres run-time parameters.
+///
+/// DataFusion's own `datafusion.*` options are left to DataFusion.
+pub(crate) struct ShowRewriter;
+
+impl QueryRewriter for ShowRewriter {
+    fn rewrite(
+        &self,
+        query: &str,
+        settings: &mut HashMap<String, String>,
+    ) -> PgWireResult<Option<Rewrite>> {
+        if !starts_with_keyword(query, "show") {
+            return Ok(None);
+        }
+
+        let Some(Statement::ShowVariable { variable }) = parse_single_statement(query) else {
+            return Ok(None);
+        };
+        let mut name = variable
+            .iter()
+            .map(|ident| ident.value.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if is_datafusion_option(&name) {
+            return Ok(None);
+        }
+        if name.eq_ignore_ascii_case("time zone") {
+            name = "TimeZone".to_owned();
+        }
+
+        if name.eq_ignore_ascii_case("all") {
+            let (names, values): (Vec<_>, Vec<_>) = DEFAULT_SETTINGS
+                .iter()
+                .map(|setting| {
+                    let value = settings::get(settings, setting.name).unwrap_or_default();
+                    (setting.name.to_owned(), value)
+                })
+                .unzip();
+            return Ok(Some(Rewrite::Rows(string_batch(vec![
+                ("name", names),
+                ("setting", values),
+            ])?)));
+        }
+
+        let value = settings::get(settings, &name).ok_or_else(|| unrecognized_parameter(&name))?;
+
+        Ok(Some(Rewrite::Rows(string_batch(vec![(
+            &name.to_lowercase(),
+            vec![value],
+        )])?)))
+    }
+}
+
+/// Handles `SET ROLE name`, `SET ROLE NONE` and `RESET ROLE`, keeping the
+/// role of the connection in its settings for the authorization of its
+/// queries. Roles aren't checked against any catalog, and `SET LOCAL ROLE`
+/// lasts for the whole session, as there are no transactions.
+pub(crate) struct RoleRewriter;
+
+impl QueryRewriter for RoleRewriter {
+    fn rewrite(
+        &self,
+        query: &str,
+        settings: &mut HashMap<String, String>,
+    ) -> PgWireResult<Option<Rewrite>> {
+        if is_reset_role(query) {
+            settings.insert(ROLE_SETTING.to_owned(), "none".to_owned());
+            return Ok(Some(Rewrite::Command("RESET".to_owned())));
+        }
+        if !starts_with_keyword(query, "set") {
+            return Ok(None);
+        }
+
+        let Some(Statement::SetRole { role_name, .. }) = parse_single_statement(query) else {
+            return Ok(None);
+        };
+        let role = role_name.map_or_else(|| "none".to_owned(), |role| role.value);
+        settings.insert(ROLE_SETTING.to_owned(), role);
+        Ok(Some(Rewrite::Command("SET".to_owned())))
+    }
+}
+
+/// Accepts `BEGIN`, `START TRANSACTION`, `COMMIT`, `END`, `ROLLBACK` and
+/// `ABORT` as no-ops, with the tags postgres responds with. DataFusion has no
+/// transactions, but clients and ORMs wrap their statements in one anyway.
+///
+/// The transaction status is still tracked for `ReadyForQuery`: once a
+/// statement of a block fails, the following ones are rejected until the
+/// block ends, like in postgres.
+pub(crate) struct TransactionRewriter;
+
+impl QueryRewriter for TransactionRewriter {
+    fn rewrite(
+        &self,
+        query: &str,
+        _settings: &mut HashMap<String, String>,
+    ) -> PgWireResult<Option<Rewrite>> {
+        Ok(transaction_statement(query))
+    }
+}
+
+/// Whether `query` ends a transaction block, the only statements postgres
+/// runs once the block failed.
+pub(crate) fn ends_transaction(query: &str) -> bool {
+    matches!(
+        transaction_statement(query),
+        Some(Rewrite::TransactionEnd(_))
+    )
+}
+
+fn transaction_statement(query: &str) -> Option<Rewrite> {
+    let keywords = ["begin", "start", "commit", "end", "rollback"];
+    if !keywords
+        .iter()
+        .any(|keyword| starts_with_keyword(query, keyword))
+    {
+        return is_abort(query).then(|| Rewrite::TransactionEnd("ROLLBACK".to_owned()));
+    }
+
+    match parse_single_statement(query)? {
+        Statement::StartTransaction { begin: true, .. } => {
+            Some(Rewrite::TransactionStart("BEGIN".to_owned()))
+        }
+        Statement::StartTransaction { .. } => {
+            Some(Rewrite::TransactionStart("START TRANSACTION".to_owned()))
+        }
+        Statement::Commit { .. } => Some(Rewrite::TransactionEnd("COMMIT".to_owned())),
+        Statement::Rollback {
+            savepoint: None, ..
+        } => Some(Rewrite::TransactionEnd("ROLLBACK".to_owned())),
+        _ => None,
+    }
+}
+
+pub(crate) fn default_rewriters() -> Vec<Arc<dyn QueryRewriter>> {
+    vec![
+        Arc::new(SetRewriter),
+        Arc::new(RoleRewriter),
+        Arc::new(ShowRewriter),
+        Arc::new(TransactionRewriter),
+    ]
+}
+
+fn unrecognized_parameter(name: &str) -> PgWireError {
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_owned(),
+        "42704".to_owned(),
+        format!("unrecognized configuration parameter \"{name}\""),
+    )))
+}
+
+fn is_datafusion_option(name: &str) -> bool {
+    name.to_lowercase().starts_with("datafusion")
+}
+
+/// Whether `query` is `ABORT [ WORK | TRANSACTION ]`, postgres' own
+/// spelling of `ROLLBACK`, which sqlparser doesn't parse.
+fn is_abort(query: &str) -> bool {
+    let mut words = query.trim().trim_end_matches(';').split_whitespace();
+    words
+        .next()
+        .is_some_and(|word| word.eq_ignore_ascii_case("abort"))
+        && words.next().is_none_or(|word| {
+            word.eq_ignore_ascii_case("work") || word.eq_ignore_ascii_case("transaction")
+        })
+        && words.next().is_none()
+}
+
+/// Whether `query` is `RESET ROLE`, which sqlparser doesn't parse.
+fn is_reset_role(query: &str) -> bool {
+    let words = query
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    matches!(words[..], [reset, role]
+        if reset.eq_ignore_ascii_case("reset") && role.eq_ignore_ascii_case("role"))
+}
+
+pub(crate) fn starts_with_keyword(query: &str, keyword: &str) -> bool {
+    query
+        .split_whitespace()
+        .next()
+        .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+}
+
+pub(crate) fn parse_single_statement(query: &str) -> Option<Statement> {
+    let mut statements = Parser::parse_sql(&PostgreSqlDialect {}, query).ok()?;
+    if statements.len() == 1 {
+        statements.pop()
+    } else {
+        None
+    }
+}
+
+fn expr_to_setting(expr: &Expr) -> String {
+    match expr {
+        Expr::Value(Value::SingleQuotedString(s)) => s.clone(),
+        Expr::Identifier(ident) => ident.value.clone(),
+        expr => expr.to_string(),
+    }
+}
+
+/// Build a batch of non-null `Utf8` columns from `(name, values)` pairs.
+fn string_batch(columns: Vec<(&str, Vec<String>)>) -> PgWireResult<RecordBatch> {
+    let fields = columns
+        .iter()
+        .map(|(name, _)| Field::new(*name, DataType::Utf8, false))
+        .collect::<Vec<_>>();
+    let arrays = columns
+        .into_iter()
+        .map(|(_, values)| Arc::new(StringArray::from(values)) as ArrayRef)
+        .collect::<Vec<_>>();
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        .map_err(|e| PgWireError::ApiError(Box::new(e)))
+}
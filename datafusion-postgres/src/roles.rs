@@ -0,0 +1,31 @@
+//! The role a connection runs its queries under, set with `SET ROLE`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use pgwire::api::METADATA_USER;
+use pgwire::error::PgWireResult;
+
+/// Key of the role set by `SET ROLE` in the connection settings, `none`
+/// once reset, like the `role` setting of postgres.
+pub(crate) const ROLE_SETTING: &str = "role";
+
+/// Called with the current role of a connection and the statement before
+/// DataFusion plans it. Returning an error, like `42501
+/// insufficient_privilege`, fails the statement with it.
+///
+/// The role is the one set with `SET ROLE`, or the user the connection
+/// logged in as. Statements answered by a [`QueryRewriter`], like `SET`,
+/// `SHOW` or `RESET ROLE`, aren't authorized.
+///
+/// [`QueryRewriter`]: crate::QueryRewriter
+pub type AuthorizeCallback = Arc<dyn Fn(&str, &str) -> PgWireResult<()> + Send + Sync>;
+
+/// The role the queries of a connection run under.
+pub(crate) fn current_role(settings: &HashMap<String, String>) -> &str {
+    settings
+        .get(ROLE_SETTING)
+        .filter(|role| *role != "none")
+        .or_else(|| settings.get(METADATA_USER))
+        .map_or("", String::as_str)
+}
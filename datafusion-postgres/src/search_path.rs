@@ -0,0 +1,266 @@
+//! Resolution of tables named without a schema along the `search_path`
+//! setting, as DataFusion only knows of a single default schema, and of the
+//! `current_schema()` it makes.
+
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::sql::parser::Statement as DFStatement;
+use datafusion::sql::sqlparser::ast::{
+    CastKind, DataType, Expr, FunctionArguments, Ident, ObjectName, ObjectType, Query, SelectItem,
+    SetExpr, Statement, TableFactor, Value, VisitMut, VisitorMut,
+};
+
+use crate::{returning, settings};
+
+/// Plan `sql` like `SessionState::create_logical_plan`, except that tables
+/// named without a schema are looked up in the schemas of the connection's
+/// `search_path`, in order, like in postgres. Tables found in none of them,
+/// like the ones being created, belong to the first one.
+///
+/// `current_schema()` is the first of these schemas, or NULL when there are
+/// none, like in postgres.
+///
+/// `RETURNING` clauses are planned by [`returning::statement_to_plan`].
+pub(crate) async fn create_logical_plan(
+    state: &SessionState,
+    sql: &str,
+    settings: &HashMap<String, String>,
+) -> Result<LogicalPlan> {
+    let dialect = state.config_options().sql_parser.dialect.clone();
+    let mut statement = state.sql_to_statement(sql, &dialect)?;
+
+    let schemas = search_schemas(state, settings);
+    let default_schema = &state.config_options().catalog.default_schema;
+    replace_current_schema(&mut statement, schemas.first().map(String::as_str));
+    if !schemas.is_empty() && schemas != [default_schema.as_str()] {
+        qualify_tables(
+            &mut statement,
+            &mut Qualifier {
+                state,
+                schemas,
+                ctes_in_scope: vec![],
+                table_function: false,
+            },
+        );
+    }
+    returning::statement_to_plan(state, statement).await
+}
+
+/// The schemas of `search_path` that exist in the default catalog.
+fn search_schemas(state: &SessionState, settings: &HashMap<String, String>) -> Vec<String> {
+    let Some(catalog) = state
+        .catalog_list()
+        .catalog(&state.config_options().catalog.default_catalog)
+    else {
+        return vec![];
+    };
+    let path = settings::get(settings, "search_path").unwrap_or_default();
+
+    path.split(',')
+        .map(str::trim)
+        .filter_map(|name| match name {
+            "\"$user\"" | "$user" => settings.get("user").cloned(),
+            name if name.len() >= 2 && name.starts_with('"') && name.ends_with('"') => {
+                Some(name[1..name.len() - 1].replace("\"\"", "\""))
+            }
+            name => Some(name.to_lowercase()),
+        })
+        .filter(|name| catalog.schema(name).is_some())
+        .collect()
+}
+
+fn qualify_tables(statement: &mut DFStatement, qualifier: &mut Qualifier) {
+    match statement {
+        DFStatement::Statement(statement) => {
+            let _ = statement.visit(qualifier);
+        }
+        DFStatement::Explain(explain) => qualify_tables(&mut explain.statement, qualifier),
+        // external tables are created in the default schema, and `COPY` is
+        // left to DataFusion
+        DFStatement::CreateExternalTable(_) | DFStatement::CopyTo(_) => {}
+    }
+}
+
+fn replace_current_schema(statement: &mut DFStatement, schema: Option<&str>) {
+    match statement {
+        DFStatement::Statement(statement) => {
+            let _ = statement.visit(&mut CurrentSchema { schema });
+        }
+        DFStatement::Explain(explain) => replace_current_schema(&mut explain.statement, schema),
+        DFStatement::CreateExternalTable(_) | DFStatement::CopyTo(_) => {}
+    }
+}
+
+/// Replaces `current_schema`, or `current_schema()`, which DataFusion can't
+/// know as it depends on the connection, with the name of `schema`, and
+/// names the selected `current_database()` like postgres does.
+struct CurrentSchema<'a> {
+    schema: Option<&'a str>,
+}
+
+impl VisitorMut for CurrentSchema<'_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<()> {
+        // selected, the value is named like in postgres, not after itself
+        if let SetExpr::Select(select) = query.body.as_mut() {
+            for item in &mut select.projection {
+                let SelectItem::UnnamedExpr(expr) = item else {
+                    continue;
+                };
+                let name = if is_current_schema(expr) {
+                    "current_schema"
+                } else if is_function(expr, "current_database") {
+                    "current_database"
+                } else {
+                    continue;
+                };
+                *item = SelectItem::ExprWithAlias {
+                    expr: expr.clone(),
+                    alias: Ident::new(name),
+                };
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<()> {
+        if is_current_schema(expr) {
+            *expr = match self.schema {
+                Some(schema) => Expr::Value(Value::SingleQuotedString(schema.to_owned())),
+                None => Expr::Cast {
+                    kind: CastKind::Cast,
+                    expr: Box::new(Expr::Value(Value::Null)),
+                    data_type: DataType::Text,
+                    format: None,
+                },
+            };
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+fn is_current_schema(expr: &Expr) -> bool {
+    match expr {
+        Expr::Identifier(ident) => {
+            ident.quote_style.is_none() && ident.value.eq_ignore_ascii_case("current_schema")
+        }
+        expr => is_function(expr, "current_schema"),
+    }
+}
+
+/// Whether `expr` calls the `pg_catalog` function `name` without arguments.
+fn is_function(expr: &Expr, name: &str) -> bool {
+    match expr {
+        Expr::Function(function) => {
+            let function_name = match function.name.0.as_slice() {
+                [name] => name,
+                [schema, name] if schema.value.eq_ignore_ascii_case("pg_catalog") => name,
+                _ => return false,
+            };
+            let no_args = match &function.args {
+                FunctionArguments::None => true,
+                FunctionArguments::List(list) => list.args.is_empty(),
+                FunctionArguments::Subquery(_) => false,
+            };
+            function_name.value.eq_ignore_ascii_case(name) && no_args
+        }
+        _ => false,
+    }
+}
+
+struct Qualifier<'a> {
+    state: &'a SessionState,
+    schemas: Vec<String>,
+    ctes_in_scope: Vec<String>,
+    /// Whether the next relation is the name of a table function.
+    table_function: bool,
+}
+
+impl Qualifier<'_> {
+    fn qualify(&self, name: &mut ObjectName) {
+        let [table] = name.0.as_slice() else {
+            return;
+        };
+        let table = match table.quote_style {
+            Some(_) => table.value.clone(),
+            None => table.value.to_lowercase(),
+        };
+        if self.ctes_in_scope.contains(&table) {
+            return;
+        }
+
+        let options = self.state.config_options();
+        let catalog = self
+            .state
+            .catalog_list()
+            .catalog(&options.catalog.default_catalog);
+        let schema = self
+            .schemas
+            .iter()
+            .find(|schema| {
+                catalog
+                    .as_ref()
+                    .and_then(|catalog| catalog.schema(schema))
+                    .is_some_and(|schema| schema.table_exist(&table))
+            })
+            .unwrap_or(&self.schemas[0]);
+        if *schema != options.catalog.default_schema {
+            name.0.insert(0, Ident::with_quote('"', schema));
+        }
+    }
+}
+
+impl VisitorMut for Qualifier<'_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<()> {
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                let name = &cte.alias.name;
+                self.ctes_in_scope.push(match name.quote_style {
+                    Some(_) => name.value.clone(),
+                    None => name.value.to_lowercase(),
+                });
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, query: &mut Query) -> ControlFlow<()> {
+        if let Some(with) = &query.with {
+            let in_scope = self.ctes_in_scope.len() - with.cte_tables.len();
+            self.ctes_in_scope.truncate(in_scope);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &mut TableFactor) -> ControlFlow<()> {
+        self.table_function = matches!(table_factor, TableFactor::Table { args: Some(_), .. });
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_relation(&mut self, relation: &mut ObjectName) -> ControlFlow<()> {
+        if !std::mem::take(&mut self.table_function) {
+            self.qualify(relation);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_statement(&mut self, statement: &mut Statement) -> ControlFlow<()> {
+        // the names of dropped tables aren't visited as relations
+        if let Statement::Drop {
+            object_type: ObjectType::Table | ObjectType::View,
+            names,
+            ..
+        } = statement
+        {
+            names.iter_mut().for_each(|name| self.qualify(name));
+        }
+        ControlFlow::Continue(())
+    }
+}
@@ -0,0 +1,589 @@
+//! Accepting connections, over TCP and Unix sockets, with idle timeouts,
+//! a connection limit and graceful shutdown.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::Duration;
+
+use bytes::BytesMut;
+use pgwire::error::ErrorInfo;
+use pgwire::messages::PgWireBackendMessage;
+use pgwire::tokio::process_socket;
+use socket2::{SockRef, TcpKeepalive};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, Interest};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{self, Instant};
+
+use crate::handlers::HandlerFactory;
+
+/// Options of [`serve`].
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    idle_timeout: Option<Duration>,
+    startup_timeout: Duration,
+    shutdown_grace_period: Duration,
+    tcp_nodelay: bool,
+    tcp_keepalive: Option<Duration>,
+    unix_socket_directory: Option<PathBuf>,
+    max_connections: Option<usize>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> ServerOptions {
+        ServerOptions {
+            idle_timeout: None,
+            startup_timeout: Duration::from_secs(60),
+            shutdown_grace_period: Duration::from_secs(30),
+            tcp_nodelay: true,
+            tcp_keepalive: None,
+            unix_socket_directory: None,
+            max_connections: None,
+        }
+    }
+}
+
+impl ServerOptions {
+    /// Close connections waiting for a query for longer than `idle_timeout`,
+    /// like postgres' `idle_session_timeout`. Connections never time out by
+    /// default.
+    pub fn with_idle_timeout(mut self, idle_timeout: Option<Duration>) -> ServerOptions {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Close connections whose clients haven't started up within
+    /// `startup_timeout` of connecting, like postgres'
+    /// `authentication_timeout`, so that silent clients don't hold a
+    /// connection slot. One minute by default.
+    pub fn with_startup_timeout(mut self, startup_timeout: Duration) -> ServerOptions {
+        self.startup_timeout = startup_timeout;
+        self
+    }
+
+    /// How long queries running at shutdown are given to complete, 30
+    /// seconds by default.
+    pub fn with_shutdown_grace_period(mut self, grace_period: Duration) -> ServerOptions {
+        self.shutdown_grace_period = grace_period;
+        self
+    }
+
+    /// Set `TCP_NODELAY` on accepted connections, so small responses are
+    /// sent right away rather than held back by Nagle's algorithm. On by
+    /// default, like postgres.
+    pub fn with_tcp_nodelay(mut self, nodelay: bool) -> ServerOptions {
+        self.tcp_nodelay = nodelay;
+        self
+    }
+
+    /// Send TCP keepalive probes on connections idle for `idle`, like
+    /// postgres' `tcp_keepalives_idle`. The interval and number of probes
+    /// are the system's. Keepalives are off by default.
+    pub fn with_tcp_keepalive(mut self, idle: Option<Duration>) -> ServerOptions {
+        self.tcp_keepalive = idle;
+        self
+    }
+
+    /// Also accept connections on the Unix socket `.s.PGSQL.<port>` of
+    /// `directory`, `<port>` being the one of the TCP listener. This is where
+    /// clients look for the server of a port, like psql does in
+    /// `/var/run/postgresql` by default. Unix sockets are only supported on
+    /// unix.
+    pub fn with_unix_socket_directory(mut self, directory: Option<PathBuf>) -> ServerOptions {
+        self.unix_socket_directory = directory;
+        self
+    }
+
+    /// Refuse connections while `max_connections` are open, like postgres'
+    /// `max_connections`, with a `FATAL` `53300` error. Connections over the
+    /// Unix socket count too. Refused clients are closed like others if
+    /// they don't start up in time. There is no limit by default.
+    pub fn with_max_connections(mut self, max_connections: Option<usize>) -> ServerOptions {
+        self.max_connections = max_connections;
+        self
+    }
+}
+
+/// Serve connections accepted by `listener` until `shutdown` completes.
+///
+/// Clients asking for GSSAPI encryption are told it isn't supported, so they
+/// go on with TLS or in plain text.
+///
+/// On shutdown, no more connections are accepted and open ones are closed as
+/// soon as they are idle, or once the grace period is over. Like postgres,
+/// clients are sent a `FATAL` error telling why their connection is closed,
+/// both on shutdown and on idle timeout.
+///
+/// Connections end once clients close them, usually right after sending
+/// `Terminate`, which stops the query they may be running and drops their
+/// prepared statements and portals, suspended ones included, and the data of
+/// a `COPY ... FROM STDIN` left unfinished.
+pub async fn serve(
+    factory: Arc<HandlerFactory>,
+    listener: TcpListener,
+    options: ServerOptions,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+    let mut connections = JoinSet::new();
+    let slots = options
+        .max_connections
+        .map(|max_connections| Arc::new(Semaphore::new(max_connections)));
+    // completes early only if accepting on the Unix socket fails
+    let mut unix_socket = JoinSet::new();
+    if let Some(directory) = &options.unix_socket_directory {
+        let addr = listener.local_addr()?;
+        let path = directory.join(format!(".s.PGSQL.{}", addr.port()));
+        unix_socket.spawn(unix::serve(path, addr, shutdown_receiver.clone())?);
+    }
+
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (socket, addr) = accepted?;
+                // the slot of a connection is freed once it is closed
+                let slot = match &slots {
+                    Some(slots) => match slots.clone().try_acquire_owned() {
+                        Ok(slot) => Some(slot),
+                        Err(_) => {
+                            connections.spawn(refuse_connection(
+                                socket,
+                                options.startup_timeout,
+                                shutdown_receiver.clone(),
+                            ));
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                let connection = serve_connection(
+                    socket,
+                    factory.clone(),
+                    options.clone(),
+                    shutdown_receiver.clone(),
+                );
+                let service = factory.0.clone();
+                connections.spawn(async move {
+                    let _slot = slot;
+                    let result = connection.await;
+                    // whether the client sent `Terminate` or just went away
+                    service.release_connection(addr);
+                    result
+                });
+            }
+            // reap completed connections, so the set doesn't keep growing
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            Some(result) = unix_socket.join_next(), if !unix_socket.is_empty() => {
+                result.map_err(io::Error::other)??;
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    drop(listener);
+    shutdown_sender.send_replace(true);
+    while connections.join_next().await.is_some() {}
+    while unix_socket.join_next().await.is_some() {}
+    Ok(())
+}
+
+async fn serve_connection(
+    mut socket: TcpStream,
+    factory: Arc<HandlerFactory>,
+    options: ServerOptions,
+    mut shutdown: watch::Receiver<bool>,
+) -> io::Result<()> {
+    if let Some(idle) = options.tcp_keepalive {
+        SockRef::from(&socket).set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+    }
+    let startup_deadline = Instant::now() + options.startup_timeout;
+    let activity = factory.0.connections().register(socket.peer_addr()?);
+    // clients that haven't started up yet are closed like idle ones, and
+    // without a word once they are too late to start up
+    let fatal = tokio::select! {
+        result = decline_gss_encryption(&mut socket) => {
+            result?;
+            None
+        }
+        _ = time::sleep_until(startup_deadline) => return Ok(()),
+        _ = activity.idle_for(options.idle_timeout) => {
+            Some(("57P05", "terminating connection due to idle-session timeout"))
+        }
+        _ = wait_for_shutdown(&mut shutdown) => {
+            Some(("57P01", "terminating connection due to administrator command"))
+        }
+    };
+    if let Some((code, reason)) = fatal {
+        return send_fatal(socket, code, reason).await;
+    }
+
+    // pgwire owns the socket until the connection is dropped, a second handle
+    // is kept to then send the reason it is closed
+    let socket = socket.into_std()?;
+    let closing_socket = socket.try_clone()?;
+    let watched_socket = TcpStream::from_std(socket.try_clone()?)?;
+    let mut connection = Box::pin(process_socket(
+        TcpStream::from_std(socket)?,
+        None,
+        factory.clone(),
+    ));
+    // pgwire (0.28) sets TCP_NODELAY in `process_socket`, once the connection
+    // is first polled, it can only be turned off after that
+    if !options.tcp_nodelay {
+        if let Poll::Ready(result) = futures::poll!(&mut connection) {
+            return result;
+        }
+        closing_socket.set_nodelay(false)?;
+    }
+
+    let fatal = tokio::select! {
+        result = &mut connection => return result,
+        // pgwire doesn't read while a query runs, dropping the connection
+        // drops the query's stream, which stops its execution
+        _ = closed_by_peer(&watched_socket) => None,
+        _ = activity.startup_timeout(startup_deadline) => return Ok(()),
+        _ = activity.idle_for(options.idle_timeout) => {
+            Some(("57P05", "terminating connection due to idle-session timeout"))
+        }
+        _ = wait_for_shutdown(&mut shutdown) => {
+            let grace_period = time::sleep(options.shutdown_grace_period);
+            tokio::select! {
+                result = &mut connection => return result,
+                _ = activity.idle_for(Some(Duration::ZERO)) => {}
+                _ = grace_period => {}
+            }
+            Some(("57P01", "terminating connection due to administrator command"))
+        }
+    };
+
+    drop(connection);
+    let socket = TcpStream::from_std(closing_socket)?;
+    match fatal {
+        Some((code, reason)) => send_fatal(socket, code, reason).await,
+        None => drain(socket).await,
+    }
+}
+
+/// Read what a client sent before closing its end of the connection, like
+/// the `Terminate` message pgwire ignores, so that the connection is closed
+/// without a reset.
+async fn drain(mut socket: TcpStream) -> io::Result<()> {
+    tokio::io::copy(&mut socket, &mut tokio::io::sink()).await?;
+    Ok(())
+}
+
+/// Refuse a connection over the limit like postgres does, answering its
+/// startup message with a `FATAL` error once its requests for encryption
+/// are declined. Clients that don't send one in time are disconnected, on
+/// shutdown too.
+async fn refuse_connection(
+    mut socket: TcpStream,
+    startup_timeout: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) -> io::Result<()> {
+    const SSL_REQUEST: [u8; 4] = [4, 210, 22, 47];
+    const GSSENC_REQUEST: [u8; 4] = [4, 210, 22, 48];
+
+    let startup = async {
+        loop {
+            let length = socket.read_u32().await? as usize;
+            // startup messages are small, larger ones aren't read in full
+            let mut body = vec![0; length.saturating_sub(4).min(10_000)];
+            socket.read_exact(&mut body).await?;
+            if body != SSL_REQUEST && body != GSSENC_REQUEST {
+                return Ok::<_, io::Error>(());
+            }
+            socket.write_all(b"N").await?;
+        }
+    };
+    tokio::select! {
+        result = startup => result?,
+        _ = time::sleep(startup_timeout) => return Ok(()),
+        _ = wait_for_shutdown(&mut shutdown) => return Ok(()),
+    }
+    send_fatal(socket, "53300", "sorry, too many clients already").await
+}
+
+/// Send a `FATAL` error to the client, then close the connection.
+async fn send_fatal(mut socket: TcpStream, code: &str, reason: &str) -> io::Result<()> {
+    let error = ErrorInfo::new("FATAL".to_owned(), code.to_owned(), reason.to_owned());
+    let mut buf = BytesMut::new();
+    PgWireBackendMessage::ErrorResponse(error.into())
+        .encode(&mut buf)
+        .map_err(io::Error::other)?;
+    socket.write_all(&buf).await?;
+    socket.shutdown().await
+}
+
+/// Connections over a Unix socket, relayed to the TCP listener since pgwire
+/// only serves TCP sockets.
+#[cfg(unix)]
+mod unix {
+    use std::fs;
+    use std::future::Future;
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::path::PathBuf;
+
+    use tokio::net::{TcpStream, UnixListener, UnixStream};
+    use tokio::sync::watch;
+    use tokio::task::JoinSet;
+
+    use super::wait_for_shutdown;
+
+    /// Bind the socket at `path`, returning a future relaying the connections
+    /// it accepts to `tcp_addr` until shutdown. The socket file is removed
+    /// once it stops accepting.
+    pub(super) fn serve(
+        path: PathBuf,
+        tcp_addr: SocketAddr,
+        mut shutdown: watch::Receiver<bool>,
+    ) -> io::Result<impl Future<Output = io::Result<()>>> {
+        let listener = match UnixListener::bind(&path) {
+            // left behind by a server that didn't shut down, as nothing
+            // accepts connections on it
+            Err(e)
+                if e.kind() == io::ErrorKind::AddrInUse
+                    && std::os::unix::net::UnixStream::connect(&path).is_err() =>
+            {
+                fs::remove_file(&path)?;
+                UnixListener::bind(&path)?
+            }
+            result => result?,
+        };
+        let tcp_addr = loopback(tcp_addr);
+
+        Ok(async move {
+            let mut relays = JoinSet::new();
+            let result = loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((socket, _)) => {
+                            relays.spawn(relay(socket, tcp_addr));
+                        }
+                        Err(e) => break Err(e),
+                    },
+                    Some(_) = relays.join_next(), if !relays.is_empty() => {}
+                    _ = wait_for_shutdown(&mut shutdown) => break Ok(()),
+                }
+            };
+
+            drop(listener);
+            let _ = fs::remove_file(&path);
+            // relays end with the TCP connections, closed on shutdown
+            while relays.join_next().await.is_some() {}
+            result
+        })
+    }
+
+    /// Copy what is sent both ways between `socket` and a new connection to
+    /// `tcp_addr`, until either side closes.
+    async fn relay(mut socket: UnixStream, tcp_addr: SocketAddr) -> io::Result<()> {
+        let mut tcp_socket = TcpStream::connect(tcp_addr).await?;
+        let (mut unix_read, mut unix_write) = socket.split();
+        let (mut tcp_read, mut tcp_write) = tcp_socket.split();
+        tokio::select! {
+            result = tokio::io::copy(&mut unix_read, &mut tcp_write) => result?,
+            result = tokio::io::copy(&mut tcp_read, &mut unix_write) => result?,
+        };
+        Ok(())
+    }
+
+    /// The address to connect to for a listener bound to `addr`, which may
+    /// be the unspecified address of all interfaces.
+    fn loopback(mut addr: SocketAddr) -> SocketAddr {
+        match addr.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => addr.set_ip(Ipv4Addr::LOCALHOST.into()),
+            IpAddr::V6(ip) if ip.is_unspecified() => addr.set_ip(Ipv6Addr::LOCALHOST.into()),
+            _ => {}
+        }
+        addr
+    }
+}
+
+#[cfg(not(unix))]
+mod unix {
+    use std::future::Ready;
+    use std::io;
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+
+    use tokio::sync::watch;
+
+    pub(super) fn serve(
+        _path: PathBuf,
+        _tcp_addr: SocketAddr,
+        _shutdown: watch::Receiver<bool>,
+    ) -> io::Result<Ready<io::Result<()>>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Unix sockets are only supported on unix",
+        ))
+    }
+}
+
+async fn wait_for_shutdown(shutdown: &mut watch::Receiver<bool>) {
+    // the sender is only dropped once all connections are closed
+    let _ = shutdown.wait_for(|shutdown| *shutdown).await;
+}
+
+/// Answer `N` to a `GSSENCRequest`, like postgres built without GSSAPI
+/// support. pgwire would take it for a startup message and close the
+/// connection.
+async fn decline_gss_encryption(socket: &mut TcpStream) -> io::Result<()> {
+    // length 8 and the request code 80877104
+    const GSSENC_REQUEST: [u8; 8] = [0, 0, 0, 8, 4, 210, 22, 48];
+
+    // other messages are left unread for pgwire, so the request is peeked
+    // at until it has fully arrived, through a handle peeking with std
+    let peeker = std::net::TcpStream::from(SockRef::from(&*socket).try_clone()?);
+    let mut buf = [0; 8];
+    let requested = socket
+        .async_io(Interest::READABLE, || {
+            let n = peeker.peek(&mut buf)?;
+            if n > 0 && n < buf.len() && buf[..n] == GSSENC_REQUEST[..n] {
+                // only the start of the request has arrived, wait for more
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            Ok(buf[..n] == GSSENC_REQUEST)
+        })
+        .await?;
+    if !requested {
+        return Ok(());
+    }
+    socket.read_exact(&mut buf).await?;
+    socket.write_all(b"N").await
+}
+
+/// Wait for the client to close the connection, without reading what it
+/// sent.
+async fn closed_by_peer(socket: &TcpStream) -> io::Result<()> {
+    loop {
+        let ready = socket.ready(Interest::READABLE).await?;
+        if ready.is_read_closed() {
+            return Ok(());
+        }
+        // the messages are left to pgwire, this waits for the next ones or
+        // the end of the connection
+        let _ = socket.try_io(Interest::READABLE, || {
+            Err::<(), _>(io::ErrorKind::WouldBlock.into())
+        });
+    }
+}
+
+/// Whether the open connections have started up and are running a query, by
+/// client address.
+#[derive(Debug, Default)]
+pub(crate) struct Connections {
+    activity: Mutex<HashMap<SocketAddr, Arc<watch::Sender<Activity>>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Activity {
+    started_up: bool,
+    running: usize,
+    since: Instant,
+}
+
+impl Connections {
+    fn register(self: &Arc<Self>, addr: SocketAddr) -> Registration {
+        let sender = Arc::new(watch::Sender::new(Activity {
+            started_up: false,
+            running: 0,
+            since: Instant::now(),
+        }));
+        self.activity.lock().unwrap().insert(addr, sender.clone());
+        Registration {
+            connections: self.clone(),
+            addr,
+            receiver: sender.subscribe(),
+        }
+    }
+
+    /// Mark the connection of `addr` as started up, once its client is
+    /// authenticated.
+    pub(crate) fn started_up(&self, addr: SocketAddr) {
+        if let Some(sender) = self.activity.lock().unwrap().get(&addr) {
+            sender.send_modify(|activity| activity.started_up = true);
+        }
+    }
+
+    /// Mark the connection of `addr` as running a query, until the returned
+    /// guard is dropped. Connections not accepted by [`serve`] are ignored.
+    pub(crate) fn running(&self, addr: SocketAddr) -> Option<Running> {
+        let sender = self.activity.lock().unwrap().get(&addr)?.clone();
+        sender.send_modify(|activity| activity.running += 1);
+        Some(Running(sender))
+    }
+}
+
+pub(crate) struct Running(Arc<watch::Sender<Activity>>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.send_modify(|activity| {
+            activity.running -= 1;
+            activity.since = Instant::now();
+        });
+    }
+}
+
+struct Registration {
+    connections: Arc<Connections>,
+    addr: SocketAddr,
+    receiver: watch::Receiver<Activity>,
+}
+
+impl Registration {
+    /// Wait until `deadline` if the client hasn't started up by then,
+    /// forever otherwise.
+    async fn startup_timeout(&self, deadline: Instant) {
+        let mut receiver = self.receiver.clone();
+        loop {
+            if receiver.borrow_and_update().started_up {
+                return std::future::pending().await;
+            }
+            tokio::select! {
+                _ = time::sleep_until(deadline) => return,
+                _ = receiver.changed() => {}
+            }
+        }
+    }
+
+    /// Wait until the connection has been idle for `timeout`, forever when
+    /// there is no timeout.
+    async fn idle_for(&self, timeout: Option<Duration>) {
+        let Some(timeout) = timeout else {
+            return std::future::pending().await;
+        };
+        let mut receiver = self.receiver.clone();
+        loop {
+            let activity = *receiver.borrow_and_update();
+            if activity.running > 0 {
+                // the sender lives as long as the registration
+                let _ = receiver.changed().await;
+                continue;
+            }
+            tokio::select! {
+                _ = time::sleep_until(activity.since + timeout) => return,
+                _ = receiver.changed() => {}
+            }
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.connections.activity.lock().unwrap().remove(&self.addr);
+    }
+}
+
+#[cfg(test)]
+mod tests;
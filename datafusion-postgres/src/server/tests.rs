@@ -0,0 +1,72 @@
+//! Socket options of accepted connections, read back from the server's end.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use datafusion::prelude::SessionContext;
+use socket2::{SockRef, Socket};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+use super::{serve_connection, ServerOptions};
+use crate::handlers::{DfSessionService, HandlerFactory};
+
+/// The server's end of a connection served with `options`, once its client
+/// has started up, and the client's end.
+async fn accept(options: ServerOptions) -> (Socket, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (socket, _) = listener.accept().await.unwrap();
+    // options are shared with the duplicated descriptor
+    let accepted = SockRef::from(&socket).try_clone().unwrap();
+
+    let factory = Arc::new(HandlerFactory(Arc::new(DfSessionService::new(
+        SessionContext::new(),
+    ))));
+    let (shutdown, shutdown_receiver) = watch::channel(false);
+    tokio::spawn(async move {
+        // the connection is shut down once the sender is dropped
+        let _shutdown = shutdown;
+        serve_connection(socket, factory, options, shutdown_receiver).await
+    });
+
+    let mut startup = 196608i32.to_be_bytes().to_vec();
+    startup.extend_from_slice(b"user\0test\0\0");
+    client
+        .write_all(&[&(startup.len() as i32 + 4).to_be_bytes()[..], &startup].concat())
+        .await
+        .unwrap();
+    // until `ReadyForQuery`
+    loop {
+        let kind = client.read_u8().await.unwrap();
+        let length = client.read_i32().await.unwrap() as usize;
+        client.read_exact(&mut vec![0; length - 4]).await.unwrap();
+        if kind == b'Z' {
+            break;
+        }
+    }
+    (accepted, client)
+}
+
+#[tokio::test]
+async fn default_socket_options() {
+    let (accepted, _client) = accept(ServerOptions::default()).await;
+    assert!(accepted.nodelay().unwrap());
+    assert!(!accepted.keepalive().unwrap());
+}
+
+#[tokio::test]
+async fn configured_socket_options() {
+    let options = ServerOptions::default()
+        .with_tcp_nodelay(false)
+        .with_tcp_keepalive(Some(Duration::from_secs(120)));
+    let (accepted, _client) = accept(options).await;
+    // turned off after pgwire turned it on
+    assert!(!accepted.nodelay().unwrap());
+    assert!(accepted.keepalive().unwrap());
+    #[cfg(target_os = "linux")]
+    assert_eq!(accepted.keepalive_time().unwrap(), Duration::from_secs(120));
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use datafusion::arrow::array::timezone::Tz;
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+
+/// A postgres run-time parameter known to the server.
+pub(crate) struct Setting {
+    pub(crate) name: &'static str,
+    pub(crate) default: &'static str,
+    /// Read-only settings are decided by the server and cannot be `SET`.
+    pub(crate) read_only: bool,
+}
+
+/// Version reported to clients, as `server_version` and in `version()`.
+pub(crate) const SERVER_VERSION: &str = "16.6";
+
+/// Settings reported to clients with `ParameterStatus` at startup.
+pub(crate) const DEFAULT_SETTINGS: &[Setting] = &[
+    Setting {
+        name: "server_version",
+        default: SERVER_VERSION,
+        read_only: true,
+    },
+    Setting {
+        name: "server_encoding",
+        default: "UTF8",
+        read_only: true,
+    },
+    Setting {
+        name: "client_encoding",
+        default: "UTF8",
+        read_only: false,
+    },
+    Setting {
+        name: "DateStyle",
+        default: "ISO, MDY",
+        read_only: false,
+    },
+    Setting {
+        name: "TimeZone",
+        default: "UTC",
+        read_only: false,
+    },
+    Setting {
+        name: "integer_datetimes",
+        default: "on",
+        read_only: true,
+    },
+    Setting {
+        name: "standard_conforming_strings",
+        default: "on",
+        read_only: false,
+    },
+    Setting {
+        name: "application_name",
+        default: "",
+        read_only: false,
+    },
+    Setting {
+        name: "extra_float_digits",
+        default: "1",
+        read_only: false,
+    },
+    Setting {
+        name: "search_path",
+        default: "\"$user\", public",
+        read_only: false,
+    },
+    // in milliseconds, 0 for no timeout, see `statement_timeout`
+    Setting {
+        name: "statement_timeout",
+        default: "0",
+        read_only: false,
+    },
+    // not a postgres setting, see `DfSessionService::copy_arrow`
+    Setting {
+        name: "result_format",
+        default: "text",
+        read_only: false,
+    },
+];
+
+/// Find a known setting by its case-insensitive name.
+pub(crate) fn find(name: &str) -> Option<&'static Setting> {
+    DEFAULT_SETTINGS
+        .iter()
+        .find(|setting| setting.name.eq_ignore_ascii_case(name))
+}
+
+/// Value of a setting for a connection. Connection settings are stored in
+/// the client metadata, keyed by lowercase name.
+pub(crate) fn get(settings: &HashMap<String, String>, name: &str) -> Option<String> {
+    settings
+        .get(&name.to_lowercase())
+        .cloned()
+        .or_else(|| find(name).map(|setting| setting.default.to_owned()))
+}
+
+/// Check the value of a setting before it's stored, returning the value to
+/// store.
+pub(crate) fn validate(name: &str, value: &str) -> PgWireResult<String> {
+    if name.eq_ignore_ascii_case("client_encoding") {
+        // Arrow strings are UTF-8 and sent as is, which is only correct for
+        // UTF8 clients, or SQL_ASCII ones that ask for no conversion at all.
+        match value.to_uppercase().replace(['-', '_'], "").as_str() {
+            "UTF8" | "UNICODE" => Ok("UTF8".to_owned()),
+            "SQLASCII" => Ok("SQL_ASCII".to_owned()),
+            _ => Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "0A000".to_owned(),
+                format!("client_encoding \"{value}\" is not supported, use UTF8"),
+            )))),
+        }
+    } else if name.eq_ignore_ascii_case("result_format") {
+        match value.to_lowercase().as_str() {
+            format @ ("text" | "arrow") => Ok(format.to_owned()),
+            _ => Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "22023".to_owned(),
+                format!("invalid value for parameter \"result_format\": \"{value}\""),
+            )))),
+        }
+    } else if name.eq_ignore_ascii_case("extra_float_digits") {
+        let invalid = |message: String| {
+            PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "22023".to_owned(),
+                message,
+            )))
+        };
+        match value.trim().parse::<i32>() {
+            Ok(digits @ -15..=3) => Ok(digits.to_string()),
+            Ok(digits) => Err(invalid(format!(
+                "{digits} is outside the valid range for parameter \"extra_float_digits\" (-15 .. 3)"
+            ))),
+            Err(_) => Err(invalid(format!(
+                "invalid value for parameter \"extra_float_digits\": \"{value}\""
+            ))),
+        }
+    } else if name.eq_ignore_ascii_case("statement_timeout") {
+        match milliseconds(value) {
+            Some(milliseconds) if milliseconds <= i32::MAX as u64 => Ok(format_milliseconds(milliseconds)),
+            Some(milliseconds) => Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "22023".to_owned(),
+                format!(
+                    "{milliseconds} ms is outside the valid range for parameter \"statement_timeout\" (0 .. {})",
+                    i32::MAX
+                ),
+            )))),
+            None => Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "22023".to_owned(),
+                format!("invalid value for parameter \"statement_timeout\": \"{value}\""),
+            )))),
+        }
+    } else if name.eq_ignore_ascii_case("TimeZone") {
+        // timestamptz values are written in this zone
+        match value.parse::<Tz>() {
+            Ok(_) => Ok(value.to_owned()),
+            Err(_) => Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "22023".to_owned(),
+                format!("invalid value for parameter \"TimeZone\": \"{value}\""),
+            )))),
+        }
+    } else {
+        Ok(value.to_owned())
+    }
+}
+
+/// How long the statements of a connection may run before they are
+/// cancelled, from the time they are received to their last row sent.
+pub(crate) fn statement_timeout(settings: &HashMap<String, String>) -> Option<Duration> {
+    get(settings, "statement_timeout")
+        .and_then(|value| milliseconds(&value))
+        .filter(|milliseconds| *milliseconds > 0)
+        .map(Duration::from_millis)
+}
+
+/// Units of time settings, in milliseconds.
+const TIME_UNITS: &[(&str, u64)] = &[
+    ("d", 86_400_000),
+    ("h", 3_600_000),
+    ("min", 60_000),
+    ("s", 1_000),
+    ("ms", 1),
+];
+
+/// A time setting in milliseconds, a number followed by an optional unit,
+/// rounded like postgres rounds it. Negative numbers are not valid.
+fn milliseconds(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let unit_at = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_at);
+    let number = number.trim().parse::<f64>().ok()?;
+    let scale = match unit {
+        "" => 1.0,
+        "us" => 0.001,
+        unit => TIME_UNITS.iter().find(|(name, _)| *name == unit)?.1 as f64,
+    };
+    let milliseconds = (number * scale).round();
+    (milliseconds >= 0.0 && milliseconds.is_finite()).then_some(milliseconds as u64)
+}
+
+/// Milliseconds in the largest unit they are a whole number of, like
+/// postgres shows time settings.
+fn format_milliseconds(milliseconds: u64) -> String {
+    if milliseconds == 0 {
+        return "0".to_owned();
+    }
+    let (unit, scale) = TIME_UNITS
+        .iter()
+        .find(|(_, scale)| milliseconds % scale == 0)
+        .unwrap_or(&("ms", 1));
+    format!("{}{unit}", milliseconds / scale)
+}
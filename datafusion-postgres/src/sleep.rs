@@ -0,0 +1,223 @@
+//! `pg_sleep`, sleeping without holding the thread running the query, so
+//! that other queries keep running and the sleep stops with its query.
+
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use datafusion::arrow::array::{AsArray, RecordBatch};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Float64Type};
+use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
+use datafusion::common::{not_impl_err, Result, ScalarValue};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::expr::ScalarFunction;
+use datafusion::logical_expr::{
+    ColumnarValue, Expr, LogicalPlan, ScalarFunctionArgs, ScalarUDFImpl, Signature, Volatility,
+};
+use datafusion::physical_expr::{PhysicalExpr, ScalarFunctionExpr};
+use datafusion::physical_plan::filter::FilterExec;
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties};
+use futures::StreamExt;
+
+/// `pg_sleep(seconds)`, also callable as `pg_catalog.pg_sleep`.
+///
+/// Scalar functions can't wait, so the function itself only returns NULL:
+/// the sleep happens in [`sleep_before_calls`], before the rows of the
+/// projection or filter calling it are computed. It can thus only be called
+/// in the select list and in `WHERE`, [`check_calls`] rejects the other
+/// calls, like in `ORDER BY`, a join condition or an aggregate. Postgres
+/// returns `void`, which a function result can't be hinted as, so the NULL
+/// is sent as `unknown`.
+#[derive(Debug)]
+pub(crate) struct PgSleep {
+    aliases: Vec<String>,
+    signature: Signature,
+}
+
+impl PgSleep {
+    pub(crate) fn new() -> PgSleep {
+        PgSleep {
+            aliases: vec!["pg_catalog.pg_sleep".to_owned()],
+            signature: Signature::uniform(1, vec![DataType::Float64], Volatility::Volatile),
+        }
+    }
+}
+
+impl ScalarUDFImpl for PgSleep {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "pg_sleep"
+    }
+
+    fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Null)
+    }
+
+    fn invoke_with_args(&self, _args: ScalarFunctionArgs) -> Result<ColumnarValue> {
+        Ok(ColumnarValue::Scalar(ScalarValue::Null))
+    }
+}
+
+/// Fail for `plan` calling `pg_sleep` elsewhere than in a projection or a
+/// filter, where it wouldn't sleep.
+#[allow(clippy::result_large_err)]
+pub(crate) fn check_calls(plan: &LogicalPlan) -> Result<()> {
+    plan.apply_with_subqueries(|plan| {
+        if matches!(plan, LogicalPlan::Projection(_) | LogicalPlan::Filter(_)) {
+            return Ok(TreeNodeRecursion::Continue);
+        }
+        plan.apply_expressions(|expr| {
+            expr.apply(|expr| match expr {
+                Expr::ScalarFunction(ScalarFunction { func, .. })
+                    if func.inner().as_any().is::<PgSleep>() =>
+                {
+                    not_impl_err!("pg_sleep is only supported in the select list and WHERE")
+                }
+                _ => Ok(TreeNodeRecursion::Continue),
+            })
+        })
+    })
+    .map(|_| ())
+}
+
+/// `plan`, with the projections and filters calling `pg_sleep` reading their
+/// input through a [`Sleep`] waiting for these calls. Each call sleeps once
+/// per row, like in postgres, even in a branch of a `CASE` that isn't taken.
+#[allow(clippy::result_large_err)]
+pub(crate) fn sleep_before_calls(plan: Arc<dyn ExecutionPlan>) -> Result<Arc<dyn ExecutionPlan>> {
+    plan.transform_up(|plan| {
+        let exprs = if let Some(projection) = plan.as_any().downcast_ref::<ProjectionExec>() {
+            projection
+                .expr()
+                .iter()
+                .map(|(expr, _)| expr.clone())
+                .collect()
+        } else if let Some(filter) = plan.as_any().downcast_ref::<FilterExec>() {
+            vec![filter.predicate().clone()]
+        } else {
+            return Ok(Transformed::no(plan));
+        };
+        let mut seconds = vec![];
+        for expr in exprs {
+            expr.apply(|expr| {
+                if let Some(call) = expr.as_any().downcast_ref::<ScalarFunctionExpr>() {
+                    if call.fun().inner().as_any().is::<PgSleep>() {
+                        seconds.extend(call.args().first().cloned());
+                    }
+                }
+                Ok(TreeNodeRecursion::Continue)
+            })?;
+        }
+        if seconds.is_empty() {
+            return Ok(Transformed::no(plan));
+        }
+        let input = Arc::new(Sleep {
+            input: plan.children()[0].clone(),
+            seconds: seconds.into(),
+        });
+        plan.with_new_children(vec![input]).map(Transformed::yes)
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// Passes the batches of its input through, once it slept for the
+/// `pg_sleep` calls of the projection or filter above for each of their
+/// rows.
+#[derive(Debug)]
+struct Sleep {
+    input: Arc<dyn ExecutionPlan>,
+    /// The arguments of the calls, evaluated against the input.
+    seconds: Arc<[Arc<dyn PhysicalExpr>]>,
+}
+
+impl DisplayAs for Sleep {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sleep")
+    }
+}
+
+impl ExecutionPlan for Sleep {
+    fn name(&self) -> &str {
+        "Sleep"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let [input] = <[Arc<dyn ExecutionPlan>; 1]>::try_from(children)
+            .unwrap_or_else(|_| unreachable!("Sleep has one input"));
+        Ok(Arc::new(Sleep {
+            input,
+            seconds: self.seconds.clone(),
+        }))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context)?;
+        let schema = input.schema();
+        let seconds = self.seconds.clone();
+        let batches = input.then(move |batch| {
+            let seconds = seconds.clone();
+            async move {
+                let batch = batch?;
+                let duration = sleep_duration(&seconds, &batch)?;
+                if !duration.is_zero() {
+                    tokio::time::sleep(duration).await;
+                }
+                Ok(batch)
+            }
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, batches)))
+    }
+}
+
+/// How long the calls taking `seconds` sleep for the rows of `batch`, in
+/// total. NULL and negative durations don't sleep.
+#[allow(clippy::result_large_err)]
+fn sleep_duration(seconds: &[Arc<dyn PhysicalExpr>], batch: &RecordBatch) -> Result<Duration> {
+    let mut total = 0.0;
+    for expr in seconds {
+        let values = expr.evaluate(batch)?.into_array(batch.num_rows())?;
+        let values = cast(&values, &DataType::Float64)?;
+        total += values
+            .as_primitive::<Float64Type>()
+            .iter()
+            .flatten()
+            .filter(|seconds| *seconds > 0.0)
+            .sum::<f64>();
+    }
+    // too long to be anything but forever
+    Ok(Duration::try_from_secs_f64(total).unwrap_or(Duration::MAX))
+}
@@ -0,0 +1,82 @@
+//! Execution metrics of queries returning rows.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use pgwire::api::results::QueryResponse;
+use pgwire::messages::data::DataRow;
+use pgwire::messages::Message;
+
+/// Metrics of a query returning rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct QueryStats {
+    /// The statement, as sent by the client.
+    pub query: String,
+    /// Number of rows sent to the client.
+    pub rows: usize,
+    /// Size of the `DataRow` messages sent to the client.
+    pub bytes: usize,
+    /// Wall-clock time from the start of the execution to the last row
+    /// sent, including planning with the simple query protocol. For a portal
+    /// executed with a row limit, this includes the time the client took to
+    /// ask for the next rows.
+    pub duration: Duration,
+}
+
+/// Called with the metrics of each query returning rows, once all its rows
+/// are sent, or when it is abandoned after an error or by the client.
+pub type QueryStatsCallback = Arc<dyn Fn(&QueryStats) + Send + Sync>;
+
+/// Report the metrics of `resp` to `callback` once its rows are consumed.
+pub(crate) fn record<'a>(
+    resp: QueryResponse<'a>,
+    query: &str,
+    started: Instant,
+    callback: QueryStatsCallback,
+) -> QueryResponse<'a> {
+    let command_tag = resp.command_tag().to_owned();
+    let fields = resp.row_schema();
+    let mut recorder = Recorder {
+        stats: QueryStats {
+            query: query.to_owned(),
+            rows: 0,
+            bytes: 0,
+            duration: Duration::ZERO,
+        },
+        started,
+        callback,
+    };
+    let rows = resp.data_rows().inspect(move |row| {
+        if let Ok(row) = row {
+            recorder.add(row);
+        }
+    });
+
+    let mut resp = QueryResponse::new(fields, rows);
+    resp.set_command_tag(&command_tag);
+    resp
+}
+
+/// Reports the metrics once dropped along with the row stream.
+struct Recorder {
+    stats: QueryStats,
+    started: Instant,
+    callback: QueryStatsCallback,
+}
+
+impl Recorder {
+    fn add(&mut self, row: &DataRow) {
+        self.stats.rows += 1;
+        // the message type byte isn't part of the message length
+        self.stats.bytes += 1 + row.message_length();
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stats.duration = self.started.elapsed();
+        (self.callback)(&self.stats);
+    }
+}
@@ -0,0 +1,75 @@
+//! Catalog functions called by introspection tools like pgAdmin and DBeaver,
+//! stubbed to return a constant so that their queries don't fail.
+
+use std::any::Any;
+
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::{Result, ScalarValue};
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarFunctionArgs, ScalarUDFImpl, Signature, Volatility,
+};
+
+/// Functions registered by default, all returning NULL.
+pub(crate) const DEFAULT_STUB_FUNCTIONS: &[&str] = &[
+    "pg_get_constraintdef",
+    "pg_get_expr",
+    "pg_get_function_arguments",
+    "pg_get_function_identity_arguments",
+    "pg_get_function_result",
+    "pg_get_functiondef",
+    "pg_get_indexdef",
+    "pg_get_partkeydef",
+    "pg_get_ruledef",
+    "pg_get_serial_sequence",
+    "pg_get_statisticsobjdef",
+    "pg_get_triggerdef",
+    "pg_get_userbyid",
+    "pg_get_viewdef",
+];
+
+/// A function taking any arguments and returning the same `text`, callable
+/// with and without the `pg_catalog` schema.
+#[derive(Debug)]
+pub(crate) struct StubFunction {
+    name: String,
+    aliases: Vec<String>,
+    signature: Signature,
+    value: Option<String>,
+}
+
+impl StubFunction {
+    pub(crate) fn new(name: &str, value: Option<&str>) -> StubFunction {
+        StubFunction {
+            name: name.to_owned(),
+            aliases: vec![format!("pg_catalog.{name}")],
+            signature: Signature::variadic_any(Volatility::Stable),
+            value: value.map(str::to_owned),
+        }
+    }
+}
+
+impl ScalarUDFImpl for StubFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Utf8)
+    }
+
+    fn invoke_with_args(&self, _args: ScalarFunctionArgs) -> Result<ColumnarValue> {
+        Ok(ColumnarValue::Scalar(ScalarValue::Utf8(self.value.clone())))
+    }
+}
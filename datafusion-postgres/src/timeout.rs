@@ -0,0 +1,52 @@
+//! `statement_timeout`, cancelling statements that run for too long.
+
+use std::future::Future;
+use std::time::Instant;
+
+use futures::{stream, StreamExt};
+use pgwire::api::results::QueryResponse;
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use tokio::time;
+
+fn statement_timeout_error() -> PgWireError {
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_owned(),
+        "57014".to_owned(),
+        "canceling statement due to statement timeout".to_owned(),
+    )))
+}
+
+/// Run `future`, dropping it to fail once `deadline` passes.
+pub(crate) async fn run_until<T>(
+    deadline: Option<Instant>,
+    future: impl Future<Output = PgWireResult<T>>,
+) -> PgWireResult<T> {
+    let Some(deadline) = deadline else {
+        return future.await;
+    };
+    time::timeout_at(deadline.into(), future)
+        .await
+        .unwrap_or_else(|_| Err(statement_timeout_error()))
+}
+
+/// `resp`, its rows ending with an error once `deadline` passes before they
+/// are all sent. The query stops with its row stream, dropped then.
+pub(crate) fn rows_until(resp: QueryResponse<'_>, deadline: Option<Instant>) -> QueryResponse<'_> {
+    let Some(deadline) = deadline else {
+        return resp;
+    };
+    let command_tag = resp.command_tag().to_owned();
+    let fields = resp.row_schema();
+    let timer = Box::pin(time::sleep_until(deadline.into()));
+    let rows = stream::unfold(Some((resp.data_rows(), timer)), |state| async move {
+        let (mut rows, mut timer) = state?;
+        tokio::select! {
+            row = rows.next() => row.map(|row| (row, Some((rows, timer)))),
+            _ = &mut timer => Some((Err(statement_timeout_error()), None)),
+        }
+    });
+
+    let mut resp = QueryResponse::new(fields, rows.boxed());
+    resp.set_command_tag(&command_tag);
+    resp
+}
@@ -0,0 +1,89 @@
+//! Boolean parameters, declared or inferred from a predicate, are decoded
+//! from the words postgres accepts in text format, and from a byte in binary
+//! format.
+
+mod common;
+
+use common::{connect_default, error_code, message, read_until_ready};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// The single text value returned by `sql` with `value` bound to `$1` in
+/// `format`, declared with `oid` or left to be inferred with 0, or the
+/// SQLSTATE of the error it fails with.
+async fn bind(socket: &mut TcpStream, sql: &str, oid: i32, format: i16, value: &[u8]) -> String {
+    let mut parse = [b"\0", sql.as_bytes(), b"\0"].concat();
+    parse.extend_from_slice(&1i16.to_be_bytes());
+    parse.extend_from_slice(&oid.to_be_bytes());
+    let mut bind = b"\0\0".to_vec();
+    bind.extend_from_slice(&1i16.to_be_bytes());
+    bind.extend_from_slice(&format.to_be_bytes());
+    bind.extend_from_slice(&1i16.to_be_bytes());
+    bind.extend_from_slice(&(value.len() as i32).to_be_bytes());
+    bind.extend_from_slice(value);
+    bind.extend_from_slice(&0i16.to_be_bytes());
+    let messages = [
+        message(b'P', &parse),
+        message(b'B', &bind),
+        message(b'E', &[0, 0, 0, 0, 0]),
+        message(b'S', &[]),
+    ]
+    .concat();
+    socket.write_all(&messages).await.unwrap();
+
+    let messages = read_until_ready(socket).await;
+    if let Some(code) = error_code(&messages) {
+        return code;
+    }
+    let (_, row) = messages.iter().find(|(kind, _)| *kind == b'D').unwrap();
+    String::from_utf8(row[6..].to_vec()).unwrap()
+}
+
+const PREDICATE: &str = "SELECT count(*) FROM (VALUES (1), (2)) AS t(v) WHERE $1";
+
+#[tokio::test]
+async fn bind_text_booleans() {
+    let mut socket = connect_default().await;
+    for (value, expected) in [
+        ("t", "2"),
+        ("f", "0"),
+        ("TRUE", "2"),
+        (" false ", "0"),
+        ("yes", "2"),
+        ("n", "0"),
+        ("on", "2"),
+        ("off", "0"),
+        ("1", "2"),
+        ("0", "0"),
+    ] {
+        // inferred from the predicate, and declared as bool
+        for oid in [0, 16] {
+            let count = bind(&mut socket, PREDICATE, oid, 0, value.as_bytes()).await;
+            assert_eq!(count, expected, "{value:?} with oid {oid}");
+        }
+    }
+
+    // `o` could be `on` or `off`
+    for value in ["o", "maybe", "2", ""] {
+        let code = bind(&mut socket, PREDICATE, 16, 0, value.as_bytes()).await;
+        assert_eq!(code, "22P02", "{value:?}");
+    }
+}
+
+#[tokio::test]
+async fn bind_binary_booleans() {
+    let mut socket = connect_default().await;
+    for (value, expected) in [(1, "2"), (0, "0")] {
+        for oid in [0, 16] {
+            let count = bind(&mut socket, PREDICATE, oid, 1, &[value]).await;
+            assert_eq!(count, expected, "{value} with oid {oid}");
+        }
+    }
+
+    for oid in [0, 16] {
+        let text = bind(&mut socket, "SELECT NOT $1", oid, 1, &[1]).await;
+        assert_eq!(text, "f");
+        let text = bind(&mut socket, "SELECT $1 OR false", oid, 1, &[0]).await;
+        assert_eq!(text, "f");
+    }
+}
@@ -0,0 +1,120 @@
+//! Catalogs and schemas registered by embedders are visible to clients, with
+//! their tables named through them.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{columns, connect, simple_query, text_rows};
+use datafusion::arrow::array::Int32Array;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::{
+    CatalogProvider, MemoryCatalogProvider, MemorySchemaProvider, SchemaProvider, TableProvider,
+};
+use datafusion::datasource::MemTable;
+use datafusion::prelude::{SessionConfig, SessionContext};
+use datafusion_postgres::DfSessionService;
+use tokio::net::TcpStream;
+
+/// A table of a single `id` column with `ids`.
+fn table(ids: Vec<i32>) -> Arc<dyn TableProvider> {
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+    let batch =
+        RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(ids))]).unwrap();
+    Arc::new(MemTable::try_new(schema, vec![vec![batch]]).unwrap())
+}
+
+/// The text values of the rows returned by the simple query `sql`.
+async fn query(socket: &mut TcpStream, sql: &str) -> Vec<Vec<String>> {
+    let messages = simple_query(socket, sql).await;
+    assert!(messages.iter().all(|(kind, _)| *kind != b'E'), "{sql}");
+    text_rows(&messages)
+        .into_iter()
+        .map(|row| row.into_iter().map(Option::unwrap).collect())
+        .collect()
+}
+
+#[tokio::test]
+async fn query_registered_catalogs_and_schemas() {
+    let config = SessionConfig::new().with_information_schema(true);
+    let service = DfSessionService::new(SessionContext::new_with_config(config));
+
+    let sales = Arc::new(MemorySchemaProvider::new());
+    sales
+        .register_table("orders".to_owned(), table(vec![1, 2]))
+        .unwrap();
+    assert!(service.register_schema("sales", sales).unwrap().is_none());
+
+    let stock = Arc::new(MemorySchemaProvider::new());
+    stock
+        .register_table("items".to_owned(), table(vec![3]))
+        .unwrap();
+    let warehouse = Arc::new(MemoryCatalogProvider::new());
+    warehouse.register_schema("stock", stock).unwrap();
+    assert!(service.register_catalog("warehouse", warehouse).is_none());
+
+    let mut socket = connect(service).await;
+
+    let tables = query(
+        &mut socket,
+        "SELECT table_catalog, table_schema, table_name FROM information_schema.tables \
+         WHERE table_name IN ('orders', 'items') ORDER BY table_name",
+    )
+    .await;
+    assert_eq!(
+        tables,
+        vec![
+            vec!["warehouse", "stock", "items"],
+            vec!["datafusion", "sales", "orders"],
+        ]
+    );
+
+    let rows = query(&mut socket, "SELECT id FROM sales.orders ORDER BY id").await;
+    assert_eq!(rows, vec![vec!["1"], vec!["2"]]);
+    let rows = query(&mut socket, "SELECT id FROM warehouse.stock.items").await;
+    assert_eq!(rows, vec![vec!["3"]]);
+
+    // tables of registered schemas are found along the search_path
+    query(&mut socket, "SET search_path = sales").await;
+    let rows = query(&mut socket, "SELECT count(*) FROM orders").await;
+    assert_eq!(rows, vec![vec!["2"]]);
+}
+
+#[tokio::test]
+async fn current_schema_and_database() {
+    let service = DfSessionService::new(SessionContext::new()).with_database_name("analytics");
+    service
+        .register_schema("sales", Arc::new(MemorySchemaProvider::new()))
+        .unwrap();
+
+    let mut socket = connect(service).await;
+
+    let messages = simple_query(&mut socket, "SELECT current_schema(), current_database()").await;
+    let (_, description) = messages.iter().find(|(kind, _)| *kind == b'T').unwrap();
+    let names = columns(description)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["current_schema", "current_database"]);
+    let rows = query(
+        &mut socket,
+        "SELECT current_schema(), current_database(), pg_catalog.current_database() AS name",
+    )
+    .await;
+    assert_eq!(rows, vec![vec!["public", "analytics", "analytics"]]);
+
+    // the first schema of the search_path that exists
+    query(&mut socket, "SET search_path = missing, sales, public").await;
+    let rows = query(
+        &mut socket,
+        "SELECT current_schema, pg_catalog.current_schema() AS name WHERE current_schema() = 'sales'",
+    )
+    .await;
+    assert_eq!(rows, vec![vec!["sales", "sales"]]);
+
+    // none of them exists
+    query(&mut socket, "SET search_path = nope").await;
+    let messages = simple_query(&mut socket, "SELECT current_schema()").await;
+    assert_eq!(text_rows(&messages), vec![vec![None]]);
+}
@@ -0,0 +1,146 @@
+//! A minimal client of the postgres wire protocol, for the tests talking to
+//! a server of their own.
+
+// each test uses some of these
+#![allow(dead_code)]
+
+use std::future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::{serve, DfSessionService, HandlerFactory, ServerOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The type byte of a message and its body.
+pub type Message = (u8, Vec<u8>);
+
+pub fn message(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = vec![kind];
+    message.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+    message.extend_from_slice(body);
+    message
+}
+
+pub async fn read_message(socket: &mut TcpStream) -> Message {
+    let kind = socket.read_u8().await.unwrap();
+    let length = socket.read_i32().await.unwrap() as usize;
+    let mut body = vec![0; length - 4];
+    socket.read_exact(&mut body).await.unwrap();
+    (kind, body)
+}
+
+/// Read messages until `ReadyForQuery`.
+pub async fn read_until_ready(socket: &mut TcpStream) -> Vec<Message> {
+    let mut messages = vec![];
+    loop {
+        let (kind, body) = read_message(socket).await;
+        messages.push((kind, body));
+        if kind == b'Z' {
+            return messages;
+        }
+    }
+}
+
+/// Serve `service` on a port of its own, until the test ends.
+pub async fn serve_service(service: DfSessionService) -> SocketAddr {
+    serve_with_options(service, ServerOptions::default()).await
+}
+
+/// Serve `service` with `options` on a port of its own, until the test
+/// ends.
+pub async fn serve_with_options(service: DfSessionService, options: ServerOptions) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(
+        Arc::new(HandlerFactory(Arc::new(service))),
+        listener,
+        options,
+        future::pending(),
+    ));
+    addr
+}
+
+/// A connection to `addr` as user `test`, ready for queries.
+pub async fn start_up(addr: SocketAddr) -> TcpStream {
+    let mut socket = TcpStream::connect(addr).await.unwrap();
+    let mut startup = 196608i32.to_be_bytes().to_vec();
+    startup.extend_from_slice(b"user\0test\0database\0test\0\0");
+    socket
+        .write_all(&[&(startup.len() as i32 + 4).to_be_bytes()[..], &startup].concat())
+        .await
+        .unwrap();
+    read_until_ready(&mut socket).await;
+    socket
+}
+
+/// A connection to a server of its own serving `service`.
+pub async fn connect(service: DfSessionService) -> TcpStream {
+    start_up(serve_service(service).await).await
+}
+
+/// A connection to a server of its own serving an empty session.
+pub async fn connect_default() -> TcpStream {
+    connect(DfSessionService::new(SessionContext::new())).await
+}
+
+/// The messages in response to the simple query `sql`.
+pub async fn simple_query(socket: &mut TcpStream, sql: &str) -> Vec<Message> {
+    let sql = [sql.as_bytes(), b"\0"].concat();
+    socket.write_all(&message(b'Q', &sql)).await.unwrap();
+    read_until_ready(socket).await
+}
+
+/// The SQLSTATE of the error in `messages`, if any.
+pub fn error_code(messages: &[Message]) -> Option<String> {
+    let (_, body) = messages.iter().find(|(kind, _)| *kind == b'E')?;
+    body.split(|b| *b == 0)
+        .find_map(|field| field.strip_prefix(b"C"))
+        .map(|code| String::from_utf8(code.to_vec()).unwrap())
+}
+
+/// The command tag in `messages`, if any.
+pub fn command_tag(messages: &[Message]) -> Option<String> {
+    let (_, body) = messages.iter().find(|(kind, _)| *kind == b'C')?;
+    Some(String::from_utf8(body[..body.len() - 1].to_vec()).unwrap())
+}
+
+/// The names and type oids of the columns of a `RowDescription`.
+pub fn columns(body: &[u8]) -> Vec<(String, u32)> {
+    let count = i16::from_be_bytes([body[0], body[1]]);
+    let mut rest = &body[2..];
+    let mut columns = vec![];
+    for _ in 0..count {
+        let end = rest.iter().position(|b| *b == 0).unwrap();
+        let name = String::from_utf8(rest[..end].to_vec()).unwrap();
+        let oid = u32::from_be_bytes(rest[end + 7..end + 11].try_into().unwrap());
+        columns.push((name, oid));
+        rest = &rest[end + 19..];
+    }
+    columns
+}
+
+/// The text values of the rows in `messages`.
+pub fn text_rows(messages: &[Message]) -> Vec<Vec<Option<String>>> {
+    messages
+        .iter()
+        .filter(|(kind, _)| *kind == b'D')
+        .map(|(_, row)| {
+            let mut data = &row[2..];
+            let mut values = vec![];
+            while !data.is_empty() {
+                let length = i32::from_be_bytes(data[..4].try_into().unwrap());
+                data = &data[4..];
+                if length < 0 {
+                    values.push(None);
+                    continue;
+                }
+                let (value, rest) = data.split_at(length as usize);
+                values.push(Some(String::from_utf8(value.to_vec()).unwrap()));
+                data = rest;
+            }
+            values
+        })
+        .collect()
+}
@@ -0,0 +1,217 @@
+//! `COPY ... FROM STDIN` inserts the rows sent by the client into the
+//! columns it names, in any order, leaving the others to their default.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{
+    command_tag, connect, connect_default, error_code, message, read_message, read_until_ready,
+    serve_with_options, simple_query, start_up, text_rows,
+};
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::{DfSessionService, ServerOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time;
+
+/// The text values of the rows the simple query `sql` returns.
+async fn query(socket: &mut TcpStream, sql: &str) -> Vec<Vec<Option<String>>> {
+    let messages = simple_query(socket, sql).await;
+    assert_eq!(error_code(&messages), None);
+    text_rows(&messages)
+}
+
+/// Run `statement`, sending `data` once the server is ready for it, and
+/// return the messages that complete the statement. An error while
+/// starting the copy is returned as is.
+async fn copy_in(socket: &mut TcpStream, statement: &str, data: &[&[u8]]) -> Vec<(u8, Vec<u8>)> {
+    let sql = [statement.as_bytes(), b"\0"].concat();
+    socket.write_all(&message(b'Q', &sql)).await.unwrap();
+    let (kind, body) = read_message(socket).await;
+    if kind != b'G' {
+        let mut messages = vec![(kind, body)];
+        messages.extend(read_until_ready(socket).await);
+        return messages;
+    }
+    for chunk in data {
+        socket.write_all(&message(b'd', chunk)).await.unwrap();
+    }
+    socket.write_all(&message(b'c', &[])).await.unwrap();
+    read_until_ready(socket).await
+}
+
+fn row(values: &[Option<&str>]) -> Vec<Option<String>> {
+    values
+        .iter()
+        .map(|value| value.map(str::to_owned))
+        .collect()
+}
+
+const CREATE: &str = "CREATE TABLE t (a INT, b INT DEFAULT 5, c VARCHAR, d BOOLEAN)";
+
+#[tokio::test]
+async fn copy_column_subset() {
+    let mut socket = connect_default().await;
+    query(&mut socket, CREATE).await;
+
+    // the data may be split anywhere
+    let messages = copy_in(
+        &mut socket,
+        "COPY t (a, c) FROM STDIN",
+        &[b"1\tone\n2\t\\N\n3\ttab\\there", b" \\\\ \\x41\\101\n\\.\n"],
+    )
+    .await;
+    assert_eq!(error_code(&messages), None);
+    assert_eq!(command_tag(&messages).as_deref(), Some("COPY 3"));
+
+    // in another order, in CSV
+    let messages = copy_in(
+        &mut socket,
+        "COPY t (d, c, a) FROM STDIN WITH (FORMAT csv, HEADER true)",
+        &[b"d,c,a\nt,\"x, \"\"y\"\"\",4\nf,,5\r\nyes,\"\",\n"],
+    )
+    .await;
+    assert_eq!(error_code(&messages), None);
+    assert_eq!(command_tag(&messages).as_deref(), Some("COPY 3"));
+
+    assert_eq!(
+        query(
+            &mut socket,
+            "SELECT a, b, c, d FROM t ORDER BY a NULLS LAST"
+        )
+        .await,
+        vec![
+            row(&[Some("1"), Some("5"), Some("one"), None]),
+            row(&[Some("2"), Some("5"), None, None]),
+            row(&[Some("3"), Some("5"), Some("tab\there \\ AA"), None]),
+            row(&[Some("4"), Some("5"), Some("x, \"y\""), Some("t")]),
+            row(&[Some("5"), Some("5"), None, Some("f")]),
+            row(&[None, Some("5"), Some(""), Some("t")]),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn copy_all_columns() {
+    let mut socket = connect_default().await;
+    query(&mut socket, CREATE).await;
+
+    let messages = copy_in(&mut socket, "COPY t FROM STDIN", &[b"1\t2\tx\tt\n"]).await;
+    assert_eq!(command_tag(&messages).as_deref(), Some("COPY 1"));
+    let messages = copy_in(&mut socket, "COPY t FROM STDIN", &[]).await;
+    assert_eq!(command_tag(&messages).as_deref(), Some("COPY 0"));
+
+    assert_eq!(
+        query(&mut socket, "SELECT * FROM t").await,
+        vec![row(&[Some("1"), Some("2"), Some("x"), Some("t")])]
+    );
+}
+
+#[tokio::test]
+async fn copy_invalid_data() {
+    let mut socket = connect_default().await;
+    query(&mut socket, CREATE).await;
+
+    for (data, code) in [
+        (&b"1\n"[..], "22P04"),
+        (b"1\tx\ty\n", "22P04"),
+        (b"one\tx\n", "22P02"),
+        (b"1\t\xff\n", "22021"),
+    ] {
+        let messages = copy_in(&mut socket, "COPY t (a, c) FROM STDIN", &[data]).await;
+        assert_eq!(error_code(&messages).as_deref(), Some(code), "{data:?}");
+    }
+    let messages = copy_in(&mut socket, "COPY t (a, e) FROM STDIN", &[]).await;
+    assert!(error_code(&messages).is_some());
+    let messages = copy_in(&mut socket, "COPY missing FROM STDIN", &[]).await;
+    assert!(error_code(&messages).is_some());
+
+    // a client giving up
+    let sql = b"COPY t (a) FROM STDIN\0";
+    socket.write_all(&message(b'Q', sql)).await.unwrap();
+    assert_eq!(read_message(&mut socket).await.0, b'G');
+    socket.write_all(&message(b'd', b"1\n")).await.unwrap();
+    socket
+        .write_all(&message(b'f', b"cancelled\0"))
+        .await
+        .unwrap();
+    let messages = read_until_ready(&mut socket).await;
+    assert_eq!(error_code(&messages).as_deref(), Some("57014"));
+
+    // nothing was inserted, and the connection is still usable
+    assert!(query(&mut socket, "SELECT * FROM t").await.is_empty());
+}
+
+#[tokio::test]
+async fn copy_many_rows() {
+    let mut socket = connect_default().await;
+    query(&mut socket, CREATE).await;
+
+    // more rows than a batch holds, split in the middle of rows and quotes
+    let data = (0..20_000)
+        .map(|idx| format!("{idx},\"line\nbreak, {idx}\"\n"))
+        .collect::<String>();
+    let chunks = data.as_bytes().chunks(1000).collect::<Vec<_>>();
+    let messages = copy_in(
+        &mut socket,
+        "COPY t (a, c) FROM STDIN (FORMAT csv)",
+        &chunks,
+    )
+    .await;
+    assert_eq!(error_code(&messages), None);
+    assert_eq!(command_tag(&messages).as_deref(), Some("COPY 20000"));
+
+    assert_eq!(
+        query(
+            &mut socket,
+            "SELECT count(*), sum(a), max(c) FROM t WHERE c LIKE 'line\nbreak, %'"
+        )
+        .await,
+        vec![row(&[
+            Some("20000"),
+            Some("199990000"),
+            Some("line\nbreak, 9999")
+        ])]
+    );
+}
+
+#[tokio::test]
+async fn copy_beyond_memory_limit() {
+    let service = DfSessionService::new(SessionContext::new()).with_copy_memory_limit(64 * 1024);
+    let mut socket = connect(service).await;
+    query(&mut socket, CREATE).await;
+
+    let data = "1\tsome text\n".repeat(10_000);
+    let messages = copy_in(&mut socket, "COPY t (a, c) FROM STDIN", &[data.as_bytes()]).await;
+    assert_eq!(error_code(&messages).as_deref(), Some("54000"));
+
+    // nothing was inserted, and smaller copies still succeed
+    assert!(query(&mut socket, "SELECT * FROM t").await.is_empty());
+    let messages = copy_in(&mut socket, "COPY t (a, c) FROM STDIN", &[b"1\tx\n"]).await;
+    assert_eq!(command_tag(&messages).as_deref(), Some("COPY 1"));
+}
+
+#[tokio::test]
+async fn copy_slower_than_idle_timeout() {
+    let options = ServerOptions::default().with_idle_timeout(Some(Duration::from_millis(200)));
+    let service = DfSessionService::new(SessionContext::new());
+    let mut socket = start_up(serve_with_options(service, options).await).await;
+    query(&mut socket, CREATE).await;
+
+    // the connection isn't idle while its client sends the data
+    let sql = b"COPY t (a) FROM STDIN\0";
+    socket.write_all(&message(b'Q', sql)).await.unwrap();
+    assert_eq!(read_message(&mut socket).await.0, b'G');
+    for idx in 0..5 {
+        time::sleep(Duration::from_millis(100)).await;
+        let row = format!("{idx}\n");
+        socket
+            .write_all(&message(b'd', row.as_bytes()))
+            .await
+            .unwrap();
+    }
+    socket.write_all(&message(b'c', &[])).await.unwrap();
+    let messages = read_until_ready(&mut socket).await;
+    assert_eq!(command_tag(&messages).as_deref(), Some("COPY 5"));
+}
@@ -0,0 +1,118 @@
+//! Errors of DataFusion are sent with the SQLSTATE postgres has for their
+//! kind, with both the simple and the extended query protocols.
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::{
+    connect, connect_default, error_code, message, read_until_ready, simple_query, Message,
+};
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::{DfSessionService, QueryRewriter, Rewrite};
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// The messages in response to preparing `sql` as an unnamed statement.
+async fn prepare(socket: &mut TcpStream, sql: &str) -> Vec<Message> {
+    let parse = [b"\0", sql.as_bytes(), b"\0", &0i16.to_be_bytes()].concat();
+    let messages = [message(b'P', &parse), message(b'S', &[])].concat();
+    socket.write_all(&messages).await.unwrap();
+    read_until_ready(socket).await
+}
+
+#[tokio::test]
+async fn syntax_error() {
+    let mut socket = connect_default().await;
+    let messages = simple_query(&mut socket, "SELEC 1").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("42601"));
+    let messages = prepare(&mut socket, "SELECT 1 +").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("42601"));
+}
+
+#[tokio::test]
+async fn unknown_table() {
+    let mut socket = connect_default().await;
+    let messages = simple_query(&mut socket, "SELECT * FROM missing").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("42P01"));
+    let messages = prepare(&mut socket, "SELECT * FROM public.missing").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("42P01"));
+}
+
+#[tokio::test]
+async fn unknown_column() {
+    let mut socket = connect_default().await;
+    let messages = simple_query(&mut socket, "SELECT b FROM (VALUES (1)) AS t(a)").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("42703"));
+    let messages = prepare(
+        &mut socket,
+        "SELECT a FROM (VALUES (1)) AS t(a), (VALUES (2)) AS u(a)",
+    )
+    .await;
+    assert_eq!(error_code(&messages).as_deref(), Some("42702"));
+}
+
+#[tokio::test]
+async fn invalid_values() {
+    let mut socket = connect_default().await;
+    let messages = simple_query(&mut socket, "SELECT a / 0 FROM (VALUES (1)) AS t(a)").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("22012"));
+    let messages = simple_query(&mut socket, "SELECT a::int FROM (VALUES ('x')) AS t(a)").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("22P02"));
+}
+
+#[tokio::test]
+async fn not_implemented() {
+    let mut socket = connect_default().await;
+    let messages = simple_query(&mut socket, "CREATE TABLE t (a INT) WITH (fillfactor = 70)").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("0A000"));
+}
+
+#[tokio::test]
+async fn rewritten_statement_error() {
+    let mut socket = connect_default().await;
+    // fails when it is prepared, like when it is run
+    let messages = prepare(&mut socket, "SET server_version = '1'").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("55P02"));
+    let kinds = messages.iter().map(|(kind, _)| *kind).collect::<Vec<_>>();
+    assert_eq!(kinds, b"EZ");
+}
+
+/// Answers `SELECT 'guarded'` only for clients that set `application_name`
+/// to `app`.
+struct Guard;
+
+impl QueryRewriter for Guard {
+    fn rewrite(
+        &self,
+        query: &str,
+        settings: &mut HashMap<String, String>,
+    ) -> PgWireResult<Option<Rewrite>> {
+        if query != "SELECT 'guarded'" {
+            return Ok(None);
+        }
+        if settings.get("application_name").map(String::as_str) != Some("app") {
+            return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                "ERROR".to_owned(),
+                "42501".to_owned(),
+                "not for this application".to_owned(),
+            ))));
+        }
+        Ok(Some(Rewrite::Command("GUARDED".to_owned())))
+    }
+}
+
+#[tokio::test]
+async fn rewriter_settings_at_parse() {
+    let service = DfSessionService::new(SessionContext::new()).with_query_rewriter(Arc::new(Guard));
+    let mut socket = connect(service).await;
+    let messages = prepare(&mut socket, "SELECT 'guarded'").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("42501"));
+
+    // rewriters get the settings of the connection when it is parsed too
+    simple_query(&mut socket, "SET application_name = 'app'").await;
+    let messages = prepare(&mut socket, "SELECT 'guarded'").await;
+    assert_eq!(error_code(&messages), None);
+}
@@ -0,0 +1,92 @@
+//! `EXPLAIN` returns a single `QUERY PLAN` text column, with a row per line
+//! of the plans, like postgres.
+
+mod common;
+
+use common::{columns, connect_default, message, read_until_ready, simple_query, Message};
+use tokio::io::AsyncWriteExt;
+
+/// The columns described and the single text value of each row returned.
+fn result(messages: &[Message]) -> (Vec<(String, u32)>, Vec<String>) {
+    assert!(
+        messages.iter().all(|(kind, _)| *kind != b'E'),
+        "{messages:?}"
+    );
+    let (_, description) = messages.iter().find(|(kind, _)| *kind == b'T').unwrap();
+    let rows = messages
+        .iter()
+        .filter(|(kind, _)| *kind == b'D')
+        .map(|(_, row)| {
+            assert_eq!(i16::from_be_bytes([row[0], row[1]]), 1);
+            String::from_utf8(row[6..].to_vec()).unwrap()
+        })
+        .collect();
+    (columns(description), rows)
+}
+
+const EXPLAIN: &str = "EXPLAIN SELECT v + 1 FROM (VALUES (1), (2)) AS t(v) WHERE v > 1";
+
+fn check_plan(lines: &[String]) {
+    let logical = lines.iter().position(|line| line == "logical_plan:");
+    let physical = lines.iter().position(|line| line == "physical_plan:");
+    assert!(logical < physical, "{lines:#?}");
+    // the lines of each plan are indented under its type
+    for line in &lines[logical.unwrap() + 1..physical.unwrap()] {
+        assert!(line.starts_with("  "), "{lines:#?}");
+    }
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.trim_start().starts_with("Projection:")),
+        "{lines:#?}"
+    );
+    assert!(
+        lines.iter().any(|line| line.contains("FilterExec")),
+        "{lines:#?}"
+    );
+}
+
+#[tokio::test]
+async fn explain_simple_query() {
+    let mut socket = connect_default().await;
+    let (columns, lines) = result(&simple_query(&mut socket, EXPLAIN).await);
+    assert_eq!(columns, vec![("QUERY PLAN".to_owned(), 25)]);
+    check_plan(&lines);
+}
+
+#[tokio::test]
+async fn explain_prepared_statement() {
+    let mut socket = connect_default().await;
+    let parse = [b"\0", EXPLAIN.as_bytes(), b"\0\0\0"].concat();
+    let messages = [
+        message(b'P', &parse),
+        message(b'B', b"\0\0\0\0\0\0\0\0"),
+        message(b'D', b"P\0"),
+        message(b'E', &[0, 0, 0, 0, 0]),
+        message(b'S', &[]),
+    ]
+    .concat();
+    socket.write_all(&messages).await.unwrap();
+    // described like it is returned
+    let (columns, lines) = result(&read_until_ready(&mut socket).await);
+    assert_eq!(columns, vec![("QUERY PLAN".to_owned(), 25)]);
+    check_plan(&lines);
+}
+
+#[tokio::test]
+async fn explain_analyze() {
+    // runs the query, with the metrics of each operator
+    let mut socket = connect_default().await;
+    let sql = "EXPLAIN ANALYZE SELECT sum(value) FROM generate_series(1, 10)";
+    let (columns, lines) = result(&simple_query(&mut socket, sql).await);
+    assert_eq!(columns, vec![("QUERY PLAN".to_owned(), 25)]);
+    assert_eq!(lines[0], "Plan with Metrics:");
+    assert!(
+        lines[1..].iter().all(|line| line.starts_with("  ")),
+        "{lines:#?}"
+    );
+    assert!(
+        lines.iter().any(|line| line.contains("metrics=[")),
+        "{lines:#?}"
+    );
+}
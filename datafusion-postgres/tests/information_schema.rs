@@ -0,0 +1,78 @@
+//! The `information_schema` of DataFusion is enabled, whatever the
+//! configuration of the served `SessionContext`, and all its views can be
+//! sent to clients.
+
+mod common;
+
+use common::{columns, connect_default, simple_query, text_rows, Message};
+use tokio::net::TcpStream;
+
+/// The messages in response to the simple query `sql`, which must succeed.
+async fn query(socket: &mut TcpStream, sql: &str) -> Vec<Message> {
+    let messages = simple_query(socket, sql).await;
+    assert!(
+        messages.iter().all(|(kind, _)| *kind != b'E'),
+        "{sql}: {messages:?}"
+    );
+    messages
+}
+
+/// The text values of the rows in `messages`, NULLs as empty strings.
+fn rows(messages: &[Message]) -> Vec<Vec<String>> {
+    text_rows(messages)
+        .into_iter()
+        .map(|row| row.into_iter().map(Option::unwrap_or_default).collect())
+        .collect()
+}
+
+#[tokio::test]
+async fn query_information_schema_columns() {
+    let mut socket = connect_default().await;
+    query(
+        &mut socket,
+        "CREATE TABLE readings (id INT NOT NULL, station VARCHAR, temperature DOUBLE)",
+    )
+    .await;
+
+    let messages = query(
+        &mut socket,
+        "SELECT table_schema, column_name, ordinal_position, is_nullable, data_type \
+         FROM information_schema.columns WHERE table_name = 'readings' \
+         ORDER BY ordinal_position",
+    )
+    .await;
+    let (_, description) = messages.iter().find(|(kind, _)| *kind == b'T').unwrap();
+    let oids = columns(description).into_iter().map(|(_, oid)| oid);
+    assert_eq!(oids.collect::<Vec<_>>(), vec![1043, 1043, 20, 1043, 1043]);
+    assert_eq!(
+        rows(&messages),
+        vec![
+            vec!["public", "id", "0", "NO", "Int32"],
+            vec!["public", "station", "1", "YES", "Utf8"],
+            vec!["public", "temperature", "2", "YES", "Float64"],
+        ]
+    );
+}
+
+#[tokio::test]
+async fn query_all_information_schema_views() {
+    let mut socket = connect_default().await;
+    let messages = query(
+        &mut socket,
+        "SELECT table_name FROM information_schema.tables \
+         WHERE table_schema = 'information_schema' ORDER BY table_name",
+    )
+    .await;
+    let views = rows(&messages);
+    assert!(views.contains(&vec!["columns".to_owned()]), "{views:?}");
+    assert!(views.contains(&vec!["tables".to_owned()]), "{views:?}");
+
+    // every column of every view has a postgres type
+    for view in views {
+        query(
+            &mut socket,
+            &format!("SELECT * FROM information_schema.{} LIMIT 1", view[0]),
+        )
+        .await;
+    }
+}
@@ -0,0 +1,60 @@
+//! Queries needing more memory than the limit of the server fail with the
+//! `out_of_memory` error of postgres, without affecting the next ones.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{connect, error_code, simple_query};
+use datafusion::execution::memory_pool::GreedyMemoryPool;
+use datafusion::execution::runtime_env::RuntimeEnvBuilder;
+use datafusion::prelude::{SessionConfig, SessionContext};
+use datafusion_postgres::DfSessionService;
+use tokio::net::TcpStream;
+
+/// The SQLSTATE of the error the simple query `sql` fails with, if any, and
+/// the number of rows it returned.
+async fn query(socket: &mut TcpStream, sql: &str) -> (Option<String>, usize) {
+    let messages = simple_query(socket, sql).await;
+    let rows = messages.iter().filter(|(kind, _)| *kind == b'D').count();
+    (error_code(&messages), rows)
+}
+
+const SORT: &str = "SELECT value FROM generate_series(1, 100000) ORDER BY value DESC";
+
+#[tokio::test]
+async fn sort_beyond_memory_limit() {
+    let service = DfSessionService::new(SessionContext::new()).with_query_memory_limit(64 * 1024);
+    let mut socket = connect(service).await;
+
+    let (code, _) = query(&mut socket, SORT).await;
+    assert_eq!(code.as_deref(), Some("53200"));
+
+    // the connection is still usable, and each query has a limit of its own
+    let (code, rows) = query(&mut socket, "SELECT 1").await;
+    assert_eq!((code, rows), (None, 1));
+    let (code, _) = query(&mut socket, SORT).await;
+    assert_eq!(code.as_deref(), Some("53200"));
+}
+
+#[tokio::test]
+async fn sort_without_memory_limit() {
+    let mut socket = connect(DfSessionService::new(SessionContext::new())).await;
+    let (code, rows) = query(&mut socket, SORT).await;
+    assert_eq!((code, rows), (None, 100_000));
+}
+
+#[tokio::test]
+async fn sort_beyond_session_memory_pool() {
+    // the memory of the query is still taken from the pool of the session
+    let runtime = RuntimeEnvBuilder::new()
+        .with_memory_pool(Arc::new(GreedyMemoryPool::new(64 * 1024)))
+        .build_arc()
+        .unwrap();
+    let ctx = SessionContext::new_with_config_rt(SessionConfig::new(), runtime);
+    let service = DfSessionService::new(ctx).with_query_memory_limit(1 << 30);
+    let mut socket = connect(service).await;
+
+    let (code, _) = query(&mut socket, SORT).await;
+    assert_eq!(code.as_deref(), Some("53200"));
+}
@@ -0,0 +1,70 @@
+//! `$n` parameters are bounded by the 16 bits `Bind` counts its parameters
+//! with, and portals are bound with as many parameters as their statement
+//! takes.
+
+mod common;
+
+use common::{connect_default, error_code, message, read_until_ready};
+use tokio::io::AsyncWriteExt;
+
+#[tokio::test]
+async fn parse_parameter_beyond_bind() {
+    let mut socket = connect_default().await;
+    for sql in ["SELECT $100000000000", "SELECT $65536::int"] {
+        let parse = [b"\0", sql.as_bytes(), b"\0", &0i16.to_be_bytes()].concat();
+        let messages = [
+            message(b'P', &parse),
+            message(b'D', b"S\0"),
+            message(b'S', &[]),
+        ]
+        .concat();
+        socket.write_all(&messages).await.unwrap();
+        let messages = read_until_ready(&mut socket).await;
+        assert_eq!(error_code(&messages).as_deref(), Some("42P02"), "{sql}");
+    }
+
+    // the last position a client can bind is still described, with the
+    // positions before it
+    let parse = [b"\0SELECT $65535::int\0".as_slice(), &0i16.to_be_bytes()].concat();
+    let messages = [
+        message(b'P', &parse),
+        message(b'D', b"S\0"),
+        message(b'S', &[]),
+    ]
+    .concat();
+    socket.write_all(&messages).await.unwrap();
+    let messages = read_until_ready(&mut socket).await;
+    assert_eq!(error_code(&messages), None);
+    let (_, description) = messages.iter().find(|(kind, _)| *kind == b't').unwrap();
+    assert_eq!(u16::from_be_bytes([description[0], description[1]]), 65535);
+}
+
+#[tokio::test]
+async fn bind_wrong_parameter_count() {
+    let mut socket = connect_default().await;
+    let parse = [
+        b"\0SELECT $1::int + $2::int\0".as_slice(),
+        &0i16.to_be_bytes(),
+    ]
+    .concat();
+    let mut bind = b"\0\0".to_vec();
+    bind.extend_from_slice(&0i16.to_be_bytes());
+    bind.extend_from_slice(&1i16.to_be_bytes());
+    bind.extend_from_slice(&1i32.to_be_bytes());
+    bind.extend_from_slice(b"1");
+    bind.extend_from_slice(&0i16.to_be_bytes());
+    let messages = [
+        message(b'P', &parse),
+        message(b'B', &bind),
+        message(b'E', &[0, 0, 0, 0, 0]),
+        message(b'S', &[]),
+    ]
+    .concat();
+    socket.write_all(&messages).await.unwrap();
+
+    // rejected by Bind, before it completes
+    let messages = read_until_ready(&mut socket).await;
+    let kinds = messages.iter().map(|(kind, _)| *kind).collect::<Vec<_>>();
+    assert_eq!(kinds, b"1EZ");
+    assert_eq!(error_code(&messages).as_deref(), Some("08P01"));
+}
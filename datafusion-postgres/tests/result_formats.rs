@@ -0,0 +1,114 @@
+//! Each result column is sent in the format its client asked for in `Bind`,
+//! which may differ from one column to the next.
+
+mod common;
+
+use common::{connect, error_code, message, read_until_ready, Message};
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::DfSessionService;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// The values of a row, each with its length.
+fn fields(mut data: &[u8]) -> Vec<Option<Vec<u8>>> {
+    let mut fields = vec![];
+    while !data.is_empty() {
+        let length = i32::from_be_bytes(data[..4].try_into().unwrap());
+        data = &data[4..];
+        if length < 0 {
+            fields.push(None);
+            continue;
+        }
+        fields.push(Some(data[..length as usize].to_vec()));
+        data = &data[length as usize..];
+    }
+    fields
+}
+
+fn value(value: impl AsRef<[u8]>) -> Option<Vec<u8>> {
+    Some(value.as_ref().to_vec())
+}
+
+/// Send `sql` bound with the result format codes `formats`, returning the
+/// messages received in response.
+async fn bind(socket: &mut TcpStream, sql: &str, formats: &[i16]) -> Vec<Message> {
+    let parse = [b"\0", sql.as_bytes(), b"\0\0\0"].concat();
+    let mut bind = b"\0\0\0\0\0\0".to_vec();
+    bind.extend_from_slice(&(formats.len() as i16).to_be_bytes());
+    for code in formats {
+        bind.extend_from_slice(&code.to_be_bytes());
+    }
+    let messages = [
+        message(b'P', &parse),
+        message(b'B', &bind),
+        message(b'D', b"P\0"),
+        message(b'E', &[0, 0, 0, 0, 0]),
+        message(b'S', &[]),
+    ]
+    .concat();
+    socket.write_all(&messages).await.unwrap();
+    read_until_ready(socket).await
+}
+
+/// The format codes of the columns of a `RowDescription`.
+fn format_codes(body: &[u8]) -> Vec<i16> {
+    let count = i16::from_be_bytes([body[0], body[1]]) as usize;
+    let mut at = 2;
+    let mut codes = vec![];
+    for _ in 0..count {
+        let end = at + body[at..].iter().position(|b| *b == 0).unwrap();
+        codes.push(i16::from_be_bytes([body[end + 17], body[end + 18]]));
+        at = end + 19;
+    }
+    codes
+}
+
+const SELECT: &str = "SELECT CAST(7 AS INT) AS a, 'seven' AS b, CAST(7 AS INT) AS c";
+
+#[tokio::test]
+async fn bind_mixed_formats() {
+    let mut socket = connect(DfSessionService::new(SessionContext::new())).await;
+    let messages = bind(&mut socket, SELECT, &[1, 0, 0]).await;
+    assert_eq!(error_code(&messages), None);
+    let (_, description) = messages.iter().find(|(kind, _)| *kind == b'T').unwrap();
+    assert_eq!(format_codes(description), vec![1, 0, 0]);
+    let (_, row) = messages.iter().find(|(kind, _)| *kind == b'D').unwrap();
+    assert_eq!(i16::from_be_bytes([row[0], row[1]]), 3);
+    assert_eq!(
+        fields(&row[2..]),
+        vec![value(7i32.to_be_bytes()), value("seven"), value("7")]
+    );
+}
+
+#[tokio::test]
+async fn bind_invalid_formats() {
+    let mut socket = connect(DfSessionService::new(SessionContext::new())).await;
+    for formats in [&[1, 0][..], &[1, 0, 0, 1]] {
+        let messages = bind(&mut socket, SELECT, formats).await;
+        assert_eq!(
+            error_code(&messages).as_deref(),
+            Some("08P01"),
+            "{formats:?}"
+        );
+    }
+    let messages = bind(&mut socket, SELECT, &[1, 2, 0]).await;
+    assert_eq!(error_code(&messages).as_deref(), Some("22023"));
+
+    // the connection is still usable
+    let messages = bind(&mut socket, SELECT, &[0, 1, 1]).await;
+    assert_eq!(error_code(&messages), None);
+}
+
+#[tokio::test]
+async fn bind_mixed_formats_with_text_numbers() {
+    // numbers are sent as text whatever their format, other columns keep
+    // theirs
+    let service = DfSessionService::new(SessionContext::new()).with_text_numbers(true);
+    let mut socket = connect(service).await;
+    let messages = bind(&mut socket, "SELECT 7 AS a, true AS b, 7 AS c", &[1, 1, 0]).await;
+    assert_eq!(error_code(&messages), None);
+    let (_, description) = messages.iter().find(|(kind, _)| *kind == b'T').unwrap();
+    assert_eq!(format_codes(description), vec![0, 1, 0]);
+    let (_, row) = messages.iter().find(|(kind, _)| *kind == b'D').unwrap();
+    assert_eq!(fields(&row[2..]), vec![value("7"), value([1]), value("7")]);
+}
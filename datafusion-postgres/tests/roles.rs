@@ -0,0 +1,77 @@
+//! `SET ROLE` changes the role queries are authorized under, until `RESET
+//! ROLE`.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{connect, error_code, simple_query};
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::DfSessionService;
+use pgwire::error::{ErrorInfo, PgWireError};
+use tokio::net::TcpStream;
+
+/// The SQLSTATE of the error the simple query `sql` fails with, if any, and
+/// the number of rows it returned.
+async fn query(socket: &mut TcpStream, sql: &str) -> (Option<String>, usize) {
+    let messages = simple_query(socket, sql).await;
+    let rows = messages.iter().filter(|(kind, _)| *kind == b'D').count();
+    (error_code(&messages), rows)
+}
+
+#[tokio::test]
+async fn authorize_queries_under_role() {
+    let authorized = Arc::new(Mutex::new(vec![]));
+    let callback_authorized = authorized.clone();
+    let service = DfSessionService::new(SessionContext::new()).with_authorize_callback(Arc::new(
+        move |role: &str, query: &str| {
+            callback_authorized
+                .lock()
+                .unwrap()
+                .push((role.to_owned(), query.to_owned()));
+            if role == "reader" && query.contains("secret") {
+                return Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+                    "ERROR".to_owned(),
+                    "42501".to_owned(),
+                    "permission denied".to_owned(),
+                ))));
+            }
+            Ok(())
+        },
+    ));
+    let mut socket = connect(service).await;
+
+    assert_eq!(query(&mut socket, "SELECT 'secret'").await, (None, 1));
+    assert_eq!(query(&mut socket, "SET ROLE reader").await, (None, 0));
+    assert_eq!(query(&mut socket, "SELECT 'public'").await, (None, 1));
+    let (code, _) = query(&mut socket, "SELECT 'secret'").await;
+    assert_eq!(code.as_deref(), Some("42501"));
+    assert_eq!(query(&mut socket, "RESET ROLE").await, (None, 0));
+    assert_eq!(query(&mut socket, "SELECT 'secret'").await, (None, 1));
+
+    // SET ROLE NONE goes back to the user too
+    assert_eq!(
+        query(&mut socket, "SET SESSION ROLE reader").await,
+        (None, 0)
+    );
+    assert_eq!(query(&mut socket, "SET ROLE NONE").await, (None, 0));
+    assert_eq!(query(&mut socket, "SELECT 'secret'").await, (None, 1));
+
+    // role statements themselves aren't authorized
+    let roles = authorized
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(role, query)| format!("{role}: {query}"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        roles,
+        [
+            "test: SELECT 'secret'",
+            "reader: SELECT 'public'",
+            "reader: SELECT 'secret'",
+            "test: SELECT 'secret'",
+            "test: SELECT 'secret'",
+        ]
+    );
+}
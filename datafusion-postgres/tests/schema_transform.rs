@@ -0,0 +1,79 @@
+//! A schema transform renames columns and attaches type hints to them, in
+//! both the description and the rows of results.
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::{columns, connect, message, read_until_ready, simple_query};
+use datafusion::arrow::datatypes::Field;
+use datafusion::common::DFSchema;
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::DfSessionService;
+use tokio::io::AsyncWriteExt;
+
+const EXTENSION_NAME_KEY: &str = "ARROW:extension:name";
+
+/// Rename `payload` columns to `body`, sent as `json` as the extension type
+/// tells.
+fn rename_payload(schema: &DFSchema) -> DFSchema {
+    let fields = schema.iter().map(|(qualifier, field)| {
+        let field = if field.name() == "payload" {
+            let metadata =
+                HashMap::from([(EXTENSION_NAME_KEY.to_owned(), "arrow.json".to_owned())]);
+            Arc::new(
+                Field::clone(field)
+                    .with_name("body")
+                    .with_metadata(metadata),
+            )
+        } else {
+            field.clone()
+        };
+        (qualifier.cloned(), field)
+    });
+    DFSchema::new_with_metadata(fields.collect(), schema.metadata().clone()).unwrap()
+}
+
+#[tokio::test]
+async fn rename_columns_with_schema_transform() {
+    let service = DfSessionService::new(SessionContext::new())
+        .with_schema_transform(Arc::new(rename_payload));
+    let mut socket = connect(service).await;
+
+    let query = "SELECT 1 AS id, '{\"a\": 1}' AS payload";
+    let messages = simple_query(&mut socket, query).await;
+    let kinds: Vec<_> = messages.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, b"TDCZ");
+    assert_eq!(
+        columns(&messages[0].1),
+        vec![("id".to_owned(), 20), ("body".to_owned(), 114)]
+    );
+    let mut row = 2i16.to_be_bytes().to_vec();
+    row.extend_from_slice(&1i32.to_be_bytes());
+    row.extend_from_slice(b"1");
+    row.extend_from_slice(&8i32.to_be_bytes());
+    row.extend_from_slice(b"{\"a\": 1}");
+    assert_eq!(messages[1].1, row);
+
+    // statements are described with the transformed schema too
+    let parse = [b"\0", query.as_bytes(), b"\0", &0i16.to_be_bytes()].concat();
+    socket
+        .write_all(
+            &[
+                message(b'P', &parse),
+                message(b'D', b"S\0"),
+                message(b'S', &[]),
+            ]
+            .concat(),
+        )
+        .await
+        .unwrap();
+    let messages = read_until_ready(&mut socket).await;
+    let kinds: Vec<_> = messages.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, b"1tTZ");
+    assert_eq!(
+        columns(&messages[2].1),
+        vec![("id".to_owned(), 20), ("body".to_owned(), 114)]
+    );
+}
@@ -0,0 +1,131 @@
+//! Clients that connect without starting up, or stop halfway through a
+//! `GSSENCRequest`, are closed like idle ones, on shutdown or idle timeout,
+//! and once they are too late to start up, over the connection limit too.
+
+mod common;
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{error_code, read_message, simple_query, start_up};
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::{serve, DfSessionService, HandlerFactory, ServerOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+/// Serve an empty session with `options` until `shutdown` completes.
+async fn serve_until(
+    options: ServerOptions,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> (SocketAddr, JoinHandle<std::io::Result<()>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let service = DfSessionService::new(SessionContext::new());
+    let factory = Arc::new(HandlerFactory(Arc::new(service)));
+    (
+        addr,
+        tokio::spawn(serve(factory, listener, options, shutdown)),
+    )
+}
+
+/// A silent client, and one that only sent the length of a `GSSENCRequest`,
+/// once the server of `addr` has accepted them.
+async fn connect_silently(addr: SocketAddr) -> [TcpStream; 2] {
+    let silent = TcpStream::connect(addr).await.unwrap();
+    let mut partial = TcpStream::connect(addr).await.unwrap();
+    partial.write_all(&8i32.to_be_bytes()).await.unwrap();
+    time::sleep(Duration::from_millis(100)).await;
+    [silent, partial]
+}
+
+/// Check `sockets` are sent a `FATAL` error with `code`.
+async fn assert_closed_with(sockets: [TcpStream; 2], code: &str) {
+    for mut socket in sockets {
+        let message = read_message(&mut socket).await;
+        assert_eq!(error_code(&[message]).as_deref(), Some(code));
+    }
+}
+
+#[tokio::test]
+async fn shutdown_closes_silent_clients() {
+    let (stop, stopped) = oneshot::channel::<()>();
+    let (addr, server) = serve_until(ServerOptions::default(), async {
+        let _ = stopped.await;
+    })
+    .await;
+    let sockets = connect_silently(addr).await;
+
+    stop.send(()).unwrap();
+    time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("shutdown waits for silent clients")
+        .unwrap()
+        .unwrap();
+    assert_closed_with(sockets, "57P01").await;
+}
+
+#[tokio::test]
+async fn idle_timeout_closes_silent_clients() {
+    let options = ServerOptions::default().with_idle_timeout(Some(Duration::from_millis(200)));
+    let (addr, _server) = serve_until(options, std::future::pending()).await;
+    let sockets = connect_silently(addr).await;
+
+    time::timeout(Duration::from_secs(5), assert_closed_with(sockets, "57P05"))
+        .await
+        .expect("silent clients time out");
+}
+
+/// Check `socket` is closed without a message, or reset when what its
+/// client sent was left unread.
+async fn assert_closed(mut socket: TcpStream) {
+    let mut buf = [0; 1];
+    let read = time::timeout(Duration::from_secs(5), socket.read(&mut buf))
+        .await
+        .expect("late clients are closed");
+    assert!(matches!(read, Ok(0) | Err(_)), "{read:?}");
+}
+
+#[tokio::test]
+async fn startup_timeout_closes_late_clients() {
+    let options = ServerOptions::default().with_startup_timeout(Duration::from_millis(300));
+    let (addr, _server) = serve_until(options, std::future::pending()).await;
+    let [silent, partial] = connect_silently(addr).await;
+    // one that stops halfway through its startup message
+    let mut halfway = TcpStream::connect(addr).await.unwrap();
+    halfway.write_all(&[0, 0, 0, 40, 0, 3]).await.unwrap();
+    let mut started = start_up(addr).await;
+
+    for socket in [silent, partial, halfway] {
+        assert_closed(socket).await;
+    }
+    // clients that started up in time aren't
+    let messages = simple_query(&mut started, "SELECT 1").await;
+    assert_eq!(error_code(&messages), None);
+}
+
+#[tokio::test]
+async fn startup_timeout_closes_clients_over_the_limit() {
+    let options = ServerOptions::default()
+        .with_max_connections(Some(1))
+        .with_startup_timeout(Duration::from_millis(300));
+    let (addr, _server) = serve_until(options, std::future::pending()).await;
+    let _started = start_up(addr).await;
+
+    // refused clients are sent an error once they start up, or closed
+    let mut refused = TcpStream::connect(addr).await.unwrap();
+    let mut startup = 196608i32.to_be_bytes().to_vec();
+    startup.extend_from_slice(b"user\0test\0\0");
+    let length = (startup.len() as i32 + 4).to_be_bytes();
+    refused
+        .write_all(&[&length[..], &startup].concat())
+        .await
+        .unwrap();
+    let message = read_message(&mut refused).await;
+    assert_eq!(error_code(&[message]).as_deref(), Some("53300"));
+    assert_closed(TcpStream::connect(addr).await.unwrap()).await;
+}
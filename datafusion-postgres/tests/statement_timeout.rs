@@ -0,0 +1,112 @@
+//! `pg_sleep` waits without holding up the server, and is cancelled along
+//! with its statement once `statement_timeout` passes.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{connect_default, error_code, message, read_until_ready, simple_query, Message};
+use tokio::io::AsyncWriteExt;
+
+/// The single value of the single row in `messages`.
+fn value(messages: &[Message]) -> Option<String> {
+    let (_, row) = messages.iter().find(|(kind, _)| *kind == b'D').unwrap();
+    assert_eq!(i16::from_be_bytes([row[0], row[1]]), 1);
+    let length = i32::from_be_bytes(row[2..6].try_into().unwrap());
+    (length >= 0).then(|| String::from_utf8(row[6..].to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn pg_sleep() {
+    let mut socket = connect_default().await;
+    let started = Instant::now();
+    let messages = simple_query(&mut socket, "SELECT pg_sleep(0.2)").await;
+    assert_eq!(error_code(&messages), None);
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    assert_eq!(value(&messages), None);
+
+    // once per row
+    let started = Instant::now();
+    let messages = simple_query(
+        &mut socket,
+        "SELECT pg_catalog.pg_sleep(value / 10.0) FROM generate_series(1, 2)",
+    )
+    .await;
+    assert_eq!(error_code(&messages), None);
+    assert!(started.elapsed() >= Duration::from_millis(300));
+
+    // in WHERE too
+    let started = Instant::now();
+    let messages = simple_query(
+        &mut socket,
+        "SELECT value FROM generate_series(1, 2) WHERE pg_sleep(0.1) IS NULL",
+    )
+    .await;
+    assert_eq!(error_code(&messages), None);
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    // but not where it can't sleep
+    let messages = simple_query(
+        &mut socket,
+        "SELECT value FROM generate_series(1, 2) ORDER BY pg_sleep(0.1)",
+    )
+    .await;
+    assert_eq!(error_code(&messages).as_deref(), Some("0A000"));
+}
+
+#[tokio::test]
+async fn statement_timeout_setting() {
+    let mut socket = connect_default().await;
+    assert_eq!(
+        value(&simple_query(&mut socket, "SHOW statement_timeout").await).as_deref(),
+        Some("0")
+    );
+    for (setting, shown) in [("100", "100ms"), ("'1.5s'", "1500ms"), ("'2min'", "2min")] {
+        let messages =
+            simple_query(&mut socket, &format!("SET statement_timeout = {setting}")).await;
+        assert_eq!(error_code(&messages), None);
+        assert_eq!(
+            value(&simple_query(&mut socket, "SHOW statement_timeout").await).as_deref(),
+            Some(shown)
+        );
+    }
+    for setting in ["-1", "'1 week'", "'soon'"] {
+        let messages =
+            simple_query(&mut socket, &format!("SET statement_timeout = {setting}")).await;
+        assert_eq!(error_code(&messages).as_deref(), Some("22023"), "{setting}");
+    }
+}
+
+#[tokio::test]
+async fn statement_timeout_cancels_sleep() {
+    let mut socket = connect_default().await;
+    simple_query(&mut socket, "SET statement_timeout = 100").await;
+
+    let started = Instant::now();
+    let messages = simple_query(&mut socket, "SELECT pg_sleep(30)").await;
+    assert_eq!(error_code(&messages).as_deref(), Some("57014"));
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    // with the extended query protocol too
+    let parse = b"\0SELECT pg_sleep($1)\0\0\0";
+    let bind = [&b"\0\0\0\0\0\x01\0\0\0\x0230"[..], b"\0\0"].concat();
+    let messages = [
+        message(b'P', parse),
+        message(b'B', &bind),
+        message(b'E', &[0, 0, 0, 0, 0]),
+        message(b'S', &[]),
+    ]
+    .concat();
+    let started = Instant::now();
+    socket.write_all(&messages).await.unwrap();
+    let messages = read_until_ready(&mut socket).await;
+    assert_eq!(error_code(&messages).as_deref(), Some("57014"));
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    // statements finishing in time succeed
+    let messages = simple_query(&mut socket, "SELECT pg_sleep(0.01)").await;
+    assert_eq!(error_code(&messages), None);
+    simple_query(&mut socket, "SET statement_timeout = 0").await;
+    let messages = simple_query(&mut socket, "SELECT pg_sleep(0.2)").await;
+    assert_eq!(error_code(&messages), None);
+}
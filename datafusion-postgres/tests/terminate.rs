@@ -0,0 +1,118 @@
+//! Connections closed with `Terminate` release their session state, and the
+//! executions of queries left in suspended portals.
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{message, read_until_ready, serve_service, start_up};
+use datafusion::arrow::array::Int32Array;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::streaming::StreamingTable;
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::streaming::PartitionStream;
+use datafusion::prelude::SessionContext;
+use datafusion_postgres::DfSessionService;
+use futures::{stream, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Batches of ids, counting the streams reading them that are still open.
+#[derive(Debug)]
+struct CountedStreams {
+    schema: SchemaRef,
+    open: Arc<AtomicUsize>,
+}
+
+/// Decrements the count of open streams once the stream holding it is
+/// dropped.
+struct Open(Arc<AtomicUsize>);
+
+impl Drop for Open {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl PartitionStream for CountedStreams {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        self.open.fetch_add(1, Ordering::SeqCst);
+        let open = Open(self.open.clone());
+        let batches = (0..3)
+            .map(|batch| {
+                let ids = Int32Array::from_iter_values(batch * 10..batch * 10 + 10);
+                RecordBatch::try_new(self.schema.clone(), vec![Arc::new(ids)]).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let batches = stream::iter(batches.into_iter().map(Ok)).inspect(move |_| {
+            let _ = &open;
+        });
+        Box::pin(RecordBatchStreamAdapter::new(self.schema.clone(), batches))
+    }
+}
+
+#[tokio::test]
+async fn terminate_releases_suspended_portals() {
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+    let open = Arc::new(AtomicUsize::new(0));
+    let partition = Arc::new(CountedStreams {
+        schema: schema.clone(),
+        open: open.clone(),
+    });
+    let ctx = SessionContext::new();
+    ctx.register_table(
+        "ids",
+        Arc::new(StreamingTable::try_new(schema, vec![partition]).unwrap()),
+    )
+    .unwrap();
+    let service = DfSessionService::new(ctx);
+
+    let addr = serve_service(service).await;
+
+    for _ in 0..20 {
+        let mut socket = start_up(addr).await;
+
+        // a prepared statement, and a portal suspended after its first row
+        let messages = [
+            message(b'P', b"ids\0SELECT id FROM ids\0\0\0"),
+            message(b'B', b"portal\0ids\0\0\0\0\0\0\0"),
+            message(
+                b'E',
+                &[b"portal\0".as_slice(), &1i32.to_be_bytes()].concat(),
+            ),
+            message(b'S', &[]),
+        ]
+        .concat();
+        socket.write_all(&messages).await.unwrap();
+        let kinds = read_until_ready(&mut socket)
+            .await
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, b"12DsZ");
+        assert_eq!(open.load(Ordering::SeqCst), 1);
+
+        // like libpq, close the connection right after `Terminate`, which
+        // the server doesn't answer
+        socket.write_all(&message(b'X', &[])).await.unwrap();
+        socket.shutdown().await.unwrap();
+        let mut rest = vec![];
+        socket.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+
+        for _ in 0..100 {
+            if open.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(open.load(Ordering::SeqCst), 0);
+    }
+}
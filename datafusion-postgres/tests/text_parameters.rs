@@ -0,0 +1,158 @@
+//! Parameters of types without a text decoder in pgwire, like `numeric`,
+//! `money`, `varbit` and `interval`, are read from the text postgres accepts
+//! for them, the format clients send parameters in by default.
+
+mod common;
+
+use common::{connect_default, error_code, message, read_until_ready};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// The single text value returned by `sql` with `value` bound to `$1` in
+/// text format, declared with `oid`, or the SQLSTATE of the error it fails
+/// with.
+async fn bind(socket: &mut TcpStream, sql: &str, oid: i32, value: &str) -> String {
+    let mut parse = [b"\0", sql.as_bytes(), b"\0"].concat();
+    parse.extend_from_slice(&1i16.to_be_bytes());
+    parse.extend_from_slice(&oid.to_be_bytes());
+    let mut bind = b"\0\0".to_vec();
+    bind.extend_from_slice(&0i16.to_be_bytes());
+    bind.extend_from_slice(&1i16.to_be_bytes());
+    bind.extend_from_slice(&(value.len() as i32).to_be_bytes());
+    bind.extend_from_slice(value.as_bytes());
+    bind.extend_from_slice(&0i16.to_be_bytes());
+    let messages = [
+        message(b'P', &parse),
+        message(b'B', &bind),
+        message(b'E', &[0, 0, 0, 0, 0]),
+        message(b'S', &[]),
+    ]
+    .concat();
+    socket.write_all(&messages).await.unwrap();
+
+    let messages = read_until_ready(socket).await;
+    if let Some(code) = error_code(&messages) {
+        return code;
+    }
+    let (_, row) = messages.iter().find(|(kind, _)| *kind == b'D').unwrap();
+    String::from_utf8(row[6..].to_vec()).unwrap()
+}
+
+/// Check the text of each value, bound with `oid`, is what `sql` returns.
+async fn assert_bound(socket: &mut TcpStream, sql: &str, oid: i32, cases: &[(&str, &str)]) {
+    for (value, expected) in cases {
+        assert_eq!(&bind(socket, sql, oid, value).await, expected, "{value:?}");
+    }
+}
+
+#[tokio::test]
+async fn bind_text_numerics() {
+    let mut socket = connect_default().await;
+    let sql = "SELECT CAST($1 AS VARCHAR)";
+    assert_bound(
+        &mut socket,
+        sql,
+        1700,
+        &[
+            ("-1234.5600", "-1234.5600"),
+            (" +0.001 ", "0.001"),
+            ("1.50e3", "1500"),
+            ("15E-1", "1.5"),
+            ("12345678901234567890.123", "12345678901234567890.123"),
+            ("1e38", "22P02"),
+            ("NaN", "22P02"),
+            ("1.2.3", "22P02"),
+            ("", "22P02"),
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn bind_text_money() {
+    let mut socket = connect_default().await;
+    let sql = "SELECT CAST($1 AS VARCHAR)";
+    assert_bound(
+        &mut socket,
+        sql,
+        790,
+        &[
+            ("$1,234.56", "1234.56"),
+            ("-$1,234.50", "-1234.50"),
+            ("($0.01)", "-0.01"),
+            ("12", "12.00"),
+            ("0.015", "0.02"),
+            ("$", "22P02"),
+            ("12 dollars", "22P02"),
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn bind_text_varbits() {
+    let mut socket = connect_default().await;
+    for (value, bytes) in [
+        ("1111111100000000", "ff00"),
+        ("B10110000", "b0"),
+        ("X0aF1", "0af1"),
+    ] {
+        let sql = format!("SELECT $1 = X'{bytes}'");
+        assert_eq!(bind(&mut socket, &sql, 1562, value).await, "t", "{value:?}");
+    }
+    for value in ["101", "12", "Xg"] {
+        let code = bind(&mut socket, "SELECT $1 = X'00'", 1562, value).await;
+        assert_eq!(code, "22P02", "{value:?}");
+    }
+}
+
+#[tokio::test]
+async fn bind_text_intervals() {
+    let mut socket = connect_default().await;
+    let sql = "SELECT $1 = INTERVAL '1 month 1 day 2 hours 30 minutes'";
+    assert_bound(
+        &mut socket,
+        sql,
+        1186,
+        &[
+            // the postgres style, as sent by libpq and pgjdbc
+            ("1 mon 1 day 02:30:00", "t"),
+            ("0 years 1 mons 1 days 2 hours 30 mins 0.0 secs", "t"),
+            // like str() of a timedelta, as sent by psycopg
+            ("@ 1 month 1 day, 2:30", "t"),
+            ("1mon 1d 2.5h", "t"),
+            ("P1M1DT2H30M", "t"),
+            ("1 mon 1 day 9000", "t"),
+            ("1 mon 26 hours 30 minutes", "f"),
+            ("-1 mon -1 day -02:30:00", "f"),
+            ("1 mon 1 day 02:30:00 ago", "f"),
+            ("soon", "22P02"),
+            ("1 fortnight", "22P02"),
+            ("", "22P02"),
+        ],
+    )
+    .await;
+
+    let sql = "SELECT $1 = INTERVAL '-1 month -1 day -2 hours -30 minutes'";
+    assert_bound(
+        &mut socket,
+        sql,
+        1186,
+        &[
+            ("-1 mon -1 day -02:30:00", "t"),
+            ("1 mon 1 day 02:30:00 ago", "t"),
+            ("-P1M1DT2H30M", "22P02"),
+        ],
+    )
+    .await;
+
+    // fractions of months and days are carried to days and time
+    let sql = "SELECT $1 = INTERVAL '1 month 15 days 12 hours'";
+    assert_bound(
+        &mut socket,
+        sql,
+        1186,
+        &[("1.5 mons 0.5 days", "t"), ("P1.5M0.5D", "t")],
+    )
+    .await;
+}
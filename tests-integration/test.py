@@ -1,4 +1,21 @@
+import csv
+import io
+import os
+import socket
+import struct
+import time
+from datetime import datetime, timedelta, timezone
+from datetime import time as clock_time
+from decimal import Decimal
+from ipaddress import ip_address, ip_interface, ip_network
+from uuid import UUID
+
 import psycopg
+import pyarrow.ipc
+from psycopg.adapt import Dumper
+from psycopg.pq import Format
+from psycopg.types.numeric import Int4, Int8
+from psycopg.types.string import StrBinaryDumper, StrDumper
 
 conn = psycopg.connect("host=127.0.0.1 port=5432 user=tom password=pencil dbname=localdb")
 conn.autocommit = True
@@ -19,3 +36,1435 @@ with conn.cursor() as cur:
     assert len(results) == 527
     assert len(results[0]) == 1
     print(results[0])
+
+with conn.cursor() as cur:
+    cur.execute("SELECT %s IS NULL", [None])
+    results = cur.fetchone()
+    assert results[0] is True
+
+with conn.cursor() as cur:
+    cur.execute("SHOW server_version")
+    results = cur.fetchone()
+    assert results[0] == "16.6"
+
+assert conn.info.parameter_status("server_version") == "16.6"
+
+# drivers parse the version of the server from version()
+with conn.cursor() as cur:
+    cur.execute("SELECT version(), pg_catalog.version()")
+    version, qualified = cur.fetchone()
+    assert version.startswith("PostgreSQL 16.6 on ")
+    assert qualified == version
+
+# catalog functions called by introspection tools are stubbed to return NULL
+with conn.cursor() as cur:
+    cur.execute(
+        "SELECT pg_get_expr('x', 1), pg_catalog.pg_get_constraintdef(1),"
+        " pg_get_viewdef(1, true)"
+    )
+    assert cur.fetchone() == (None, None, None)
+    assert [column.type_code for column in cur.description] == [1043] * 3
+
+assert conn.info.parameter_status("client_encoding") == "UTF8"
+assert conn.info.parameter_status("DateStyle") == "ISO, MDY"
+
+# binary timestamps are integer microseconds since 2000-01-01, as announced
+# by integer_datetimes
+assert conn.info.parameter_status("integer_datetimes") == "on"
+with conn.cursor(binary=True) as cur:
+    moment = datetime(2024, 2, 28, 1, 0, 0, 123456)
+    cur.execute("SELECT CAST(%b AS TIMESTAMP)", [moment])
+    assert cur.fetchone()[0] == moment
+    assert cur.pgresult.get_value(0, 0) == struct.pack("!q", 762397200123456)
+
+with conn.cursor() as cur:
+    try:
+        cur.execute("SET client_encoding = 'LATIN1'")
+        raise AssertionError("unsupported client_encoding accepted")
+    except psycopg.errors.FeatureNotSupported:
+        pass
+    assert conn.info.parameter_status("client_encoding") == "UTF8"
+
+# Compare raw values with what postgres sends for a boolean column
+with conn.cursor() as cur:
+    cur.execute("SELECT true, false, NULL::boolean")
+    assert cur.pgresult.get_value(0, 0) == b"t"
+    assert cur.pgresult.get_value(0, 1) == b"f"
+    assert cur.pgresult.get_value(0, 2) is None
+
+with conn.cursor(binary=True) as cur:
+    cur.execute("SELECT true, false")
+    assert cur.pgresult.get_value(0, 0) == b"\x01"
+    assert cur.pgresult.get_value(0, 1) == b"\x00"
+
+# money, from a Decimal128 column with a pg_type hint
+with conn.cursor() as cur:
+    cur.execute("SELECT price FROM types ORDER BY id")
+    assert [cur.pgresult.get_value(i, 0) for i in range(4)] == [
+        b"$1,234.56",
+        b"-$1,234.50",
+        None,
+        b"$0.01",
+    ]
+
+
+class Cents(int):
+    pass
+
+
+class MoneyBinaryDumper(Dumper):
+    format = Format.BINARY
+    oid = 790
+
+    def dump(self, obj):
+        return struct.pack("!q", obj)
+
+
+with conn.cursor(binary=True) as cur:
+    cur.adapters.register_dumper(Cents, MoneyBinaryDumper)
+    cur.execute("SELECT id, price FROM types WHERE price = %b", [Cents(-123450)])
+    assert cur.fetchone()[0] == 2
+    assert cur.pgresult.get_value(0, 1) == struct.pack("!q", -123450)
+
+
+# and in text format, as postgres writes it
+class MoneyText(str):
+    pass
+
+
+class MoneyTextDumper(Dumper):
+    oid = 790
+
+    def dump(self, obj):
+        return obj.encode()
+
+
+with conn.cursor() as cur:
+    cur.adapters.register_dumper(MoneyText, MoneyTextDumper)
+    cur.execute("SELECT id FROM types WHERE price = %s", [MoneyText("-$1,234.50")])
+    assert cur.fetchone()[0] == 2
+
+# bytes are served as int2, "char" is only used when hinted
+with conn.cursor() as cur:
+    cur.execute("SELECT arrow_cast(65, 'Int8'), arrow_cast(200, 'UInt8')")
+    assert cur.fetchone() == (65, 200)
+    assert [column.type_code for column in cur.description] == [21, 21]
+
+with conn.cursor() as cur:
+    cur.execute("SELECT grade FROM types ORDER BY id")
+    assert cur.description[0].type_code == 18
+    assert [cur.pgresult.get_value(i, 0) for i in range(4)] == [b"A", b"B", None, b"\\310"]
+
+with conn.cursor(binary=True) as cur:
+    cur.execute("SELECT grade FROM types ORDER BY id")
+    assert [cur.pgresult.get_value(i, 0) for i in range(4)] == [b"A", b"B", None, b"\xc8"]
+
+# numeric parameters in binary format, as sent by JDBC
+class JdbcNumeric(bytes):
+    pass
+
+
+class NumericBinaryDumper(Dumper):
+    format = Format.BINARY
+    oid = 1700
+
+    def dump(self, obj):
+        return obj
+
+
+with conn.cursor() as cur:
+    cur.adapters.register_dumper(JdbcNumeric, NumericBinaryDumper)
+    # -1234.5600: ndigits 2, weight 0, negative, dscale 4, digits 1234 5600
+    buffer = struct.pack("!hhHHhh", 2, 0, 0x4000, 4, 1234, 5600)
+    cur.execute("SELECT CAST(%b AS VARCHAR)", [JdbcNumeric(buffer)])
+    assert cur.fetchone()[0] == "-1234.5600"
+    # a zero with a scale beyond the 38 digits of a decimal
+    buffer = struct.pack("!hhHH", 0, 0, 0, 50)
+    cur.execute("SELECT CAST(%b AS VARCHAR)", [JdbcNumeric(buffer)])
+    assert cur.fetchone()[0] == "0." + "0" * 38
+
+with conn.cursor() as cur:
+    for value in ["0", "0.0001", "10000", "12345678901234567890.123"]:
+        cur.execute("SELECT CAST(%b AS VARCHAR)", [Decimal(value)])
+        assert cur.fetchone()[0] == value
+        # in text format too, the default of psycopg
+        cur.execute("SELECT CAST(%s AS VARCHAR)", [Decimal(value)])
+        assert cur.fetchone()[0] == value
+
+    cur.execute("SELECT id FROM types WHERE price = %b", [Decimal("-1234.5")])
+    assert cur.fetchone()[0] == 2
+
+
+# bytea parameters in text format, hex or escaped, and in binary format
+class EscapedBytea(str):
+    pass
+
+
+class ByteaEscapeDumper(Dumper):
+    oid = 17
+
+    def dump(self, obj):
+        return obj.encode()
+
+
+with conn.cursor() as cur:
+    for value in [b"", b"\x00\xff\n", bytes(range(256))]:
+        for placeholder in ["%t", "%b"]:
+            cur.execute(f"SELECT {placeholder}::bytea", [value])
+            assert cur.fetchone()[0] == value
+
+    cur.adapters.register_dumper(EscapedBytea, ByteaEscapeDumper)
+    cur.execute("SELECT %t::bytea", [EscapedBytea("a\\\\b\\000")])
+    assert cur.fetchone()[0] == b"a\\b\x00"
+
+# empty strings and bytes are values, distinct from NULL, whether the type
+# of the parameter is sent, inferred or neither
+with conn.cursor() as cur:
+    for placeholder in ["%t", "%b"]:
+        for value, length in [("", 0), (b"", 0), (None, None)]:
+            cur.execute(f"SELECT octet_length({placeholder})", [value])
+            assert cur.fetchone()[0] == length
+        for value, count in [("", 1), (None, 0)]:
+            cur.execute(f"SELECT count(*) FROM (VALUES ('a'), ('')) AS t(x) WHERE x = {placeholder}", [value])
+            assert cur.fetchone()[0] == count
+        for value, count in [(b"", 1), (None, 0)]:
+            cur.execute(f"SELECT count(*) FROM (VALUES (X'01'), (X'')) AS t(x) WHERE x = {placeholder}", [value])
+            assert cur.fetchone()[0] == count
+
+# empty results still describe their columns and complete
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT * FROM delhi WHERE false")
+        assert [column.name for column in cur.description] == [
+            "date",
+            "meantemp",
+            "humidity",
+            "winspeed",
+            "meanpressure",
+        ]
+        assert cur.fetchall() == []
+        assert cur.statusmessage == "SELECT 0"
+
+        cur.execute("SELECT date FROM delhi WHERE meantemp > %b", [1000.0])
+        assert cur.description[0].type_code == 1082
+        assert cur.fetchall() == []
+        assert cur.statusmessage == "SELECT 0"
+
+# the count of CommandComplete is the number of rows sent
+with conn.cursor() as cur:
+    cur.execute("SELECT * FROM delhi")
+    assert len(cur.fetchall()) == 1462
+    assert cur.statusmessage == "SELECT 1462"
+
+
+# portals stop at the row limit of Execute and resume from there. psycopg
+# never sets a limit, so messages are exchanged by hand.
+def pg_message(kind, body=b""):
+    return kind + struct.pack("!i", len(body) + 4) + body
+
+
+def read_messages(sock, until=b"Z"):
+    messages = []
+    buffer = b""
+    while not messages or messages[-1][0] != until:
+        while len(buffer) < 5 or len(buffer) < 1 + struct.unpack("!i", buffer[1:5])[0]:
+            buffer += sock.recv(4096)
+        length = struct.unpack("!i", buffer[1:5])[0]
+        messages.append((buffer[:1], buffer[5 : 1 + length]))
+        buffer = buffer[1 + length :]
+    return messages
+
+
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    execute = pg_message(b"E", b"\0" + struct.pack("!i", 3))
+    sock.sendall(
+        pg_message(b"P", b"\0SELECT id FROM types ORDER BY id\0" + struct.pack("!h", 0))
+        + pg_message(b"B", b"\0\0" + struct.pack("!hhh", 0, 0, 0))
+        + execute * 3
+        + pg_message(b"S")
+    )
+    messages = read_messages(sock)
+    assert [kind for kind, _ in messages] == [b"1", b"2", b"D", b"D", b"D", b"s", b"D", b"C", b"C", b"Z"]
+    assert [body for kind, body in messages if kind == b"C"] == [b"SELECT 1\0", b"SELECT 0\0"]
+
+# GSSAPI encryption is declined, and the client goes on in plain text
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    sock.sendall(struct.pack("!ii", 8, 80877104))
+    assert sock.recv(1) == b"N"
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    assert read_messages(sock)[0] == (b"R", struct.pack("!i", 0))
+
+with psycopg.connect(
+    "host=127.0.0.1 port=5432 user=tom password=pencil dbname=localdb gssencmode=prefer"
+) as gss_conn:
+    assert gss_conn.execute("SELECT 1").fetchone() == (1,)
+
+# several statements in one simple query
+with conn.cursor() as cur:
+    cur.execute("SELECT count(*) FROM delhi; SELECT 'a;b' AS s, 2 AS n")
+    assert cur.fetchone() == (1462,)
+    assert cur.nextset()
+    assert cur.fetchone() == ("a;b", 2)
+    assert not cur.nextset()
+
+with conn.cursor() as cur:
+    try:
+        cur.execute("SELECT 1; SELECT * FROM missing_table; SELECT 3")
+        raise AssertionError("failing statement ignored")
+    except psycopg.errors.InternalError:
+        pass
+
+# each statement sends its own rows and CommandComplete, in order, like
+# postgres, with a single ReadyForQuery at the end
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    sock.sendall(pg_message(b"Q", b"SELECT 1; CREATE TABLE t(a int); SELECT 2; DROP TABLE t\0"))
+    messages = read_messages(sock)
+    assert [kind for kind, _ in messages] == [b"T", b"D", b"C", b"C", b"T", b"D", b"C", b"C", b"Z"]
+    assert [body for kind, body in messages if kind in (b"D", b"C")] == [
+        b"\0\x01\0\0\0\x011",
+        b"SELECT 1\0",
+        b"CREATE TABLE\0",
+        b"\0\x01\0\0\0\x012",
+        b"SELECT 1\0",
+        b"DROP TABLE\0",
+    ]
+
+# the transaction status of ReadyForQuery follows the transaction block, and
+# once a statement of the block fails, the others are rejected until it ends
+with psycopg.connect("host=127.0.0.1 port=5432 user=tom password=pencil dbname=localdb") as tx_conn:
+    with tx_conn.cursor() as cur:
+        cur.execute("SELECT 1")
+        assert tx_conn.info.transaction_status == psycopg.pq.TransactionStatus.INTRANS
+        try:
+            cur.execute("SELECT * FROM missing_table")
+            raise AssertionError("failing statement ignored")
+        except psycopg.errors.InternalError:
+            pass
+        assert tx_conn.info.transaction_status == psycopg.pq.TransactionStatus.INERROR
+        try:
+            cur.execute("SELECT 1")
+            raise AssertionError("statement of a failed transaction run")
+        except psycopg.errors.InFailedSqlTransaction:
+            pass
+        tx_conn.rollback()
+        assert tx_conn.info.transaction_status == psycopg.pq.TransactionStatus.IDLE
+        assert cur.execute("SELECT 2").fetchone() == (2,)
+
+# in the extended protocol, messages following an error are skipped until
+# Sync, and COMMIT ends a failed block with ROLLBACK
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    def extended_query(query):
+        return (
+            pg_message(b"P", b"\0" + query + b"\0" + struct.pack("!h", 0))
+            + pg_message(b"B", b"\0\0" + struct.pack("!hhh", 0, 0, 0))
+            + pg_message(b"E", b"\0" + struct.pack("!i", 0))
+        )
+
+    sock.sendall(extended_query(b"BEGIN") + pg_message(b"S"))
+    assert read_messages(sock)[-2:] == [(b"C", b"BEGIN\0"), (b"Z", b"T")]
+
+    sock.sendall(extended_query(b"SELECT * FROM missing_table") + extended_query(b"SELECT 1") + pg_message(b"S"))
+    assert [kind for kind, _ in read_messages(sock)] == [b"E", b"Z"]
+
+    sock.sendall(extended_query(b"SELECT 1") + pg_message(b"S"))
+    messages = read_messages(sock)
+    assert [kind for kind, _ in messages] == [b"1", b"2", b"E", b"Z"]
+    assert b"C25P02\0" in messages[2][1]
+    assert messages[3] == (b"Z", b"E")
+
+    sock.sendall(extended_query(b"COMMIT") + pg_message(b"S"))
+    assert read_messages(sock)[-2:] == [(b"C", b"ROLLBACK\0"), (b"Z", b"I")]
+
+    # the status after each simple query of a transaction lifecycle
+    for query, status in [
+        (b"SELECT 1", b"I"),
+        (b"START TRANSACTION", b"T"),
+        (b"SELECT 1", b"T"),
+        (b"END", b"I"),
+        (b"BEGIN; SELECT 1", b"T"),
+        (b"SELECT * FROM missing_table", b"E"),
+        (b"ABORT", b"I"),
+        (b"SELECT * FROM missing_table", b"I"),
+        (b"BEGIN; SELECT 1; COMMIT", b"I"),
+    ]:
+        sock.sendall(pg_message(b"Q", query + b"\0"))
+        assert read_messages(sock)[-1] == (b"Z", status), query
+
+# FixedSizeBinary as bytea, and lists of it as bytea[]
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT token, tokens FROM types ORDER BY id")
+        results = cur.fetchall()
+        assert results[0] == (bytes(range(16)), [b"\xca\xfe", None])
+        assert results[1] == (b"\xff" * 16, [])
+        assert results[2] == (None, None)
+        assert results[3] == (bytes(16), [b"\x00\xff"])
+
+with conn.cursor() as cur:
+    cur.execute("SELECT tokens FROM types WHERE id = 1")
+    assert cur.pgresult.get_value(0, 0) == b'{"\\\\xcafe",NULL}'
+
+# the portal is described with the types of its bound parameters
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT %b, %b", [Int8(42), "hi"])
+        assert [column.type_code for column in cur.description] == [20, 1043]
+        assert cur.pgresult.fformat(0) == int(binary)
+        assert cur.fetchone() == (42, "hi")
+
+# a parameter used several times is bound once, and applies everywhere
+with conn.cursor() as cur:
+    query = "SELECT id FROM types WHERE id = %(v)s OR typoid = %(v)s ORDER BY id"
+    cur.execute(query, {"v": Int4(3)})
+    assert cur.fetchall() == [(3,)]
+    cur.execute(query, {"v": Int4(23)})
+    assert cur.fetchall() == [(1,)]
+
+# binding more parameters than the statement takes fails, like in postgres
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    param = struct.pack("!i", 4) + struct.pack("!i", 3)
+    sock.sendall(
+        pg_message(b"P", b"\0SELECT id FROM types WHERE id = $1 OR typoid = $1\0" + struct.pack("!hi", 1, 23))
+        + pg_message(b"B", b"\0\0" + struct.pack("!hh", 1, 1) + struct.pack("!h", 2) + param * 2 + struct.pack("!h", 0))
+        + pg_message(b"E", b"\0" + struct.pack("!i", 0))
+        + pg_message(b"S")
+    )
+    messages = read_messages(sock)
+    assert [kind for kind, _ in messages][-2:] == [b"E", b"Z"]
+    assert b"C08P01\0" in messages[-2][1]
+    assert b"bind message supplies 2 parameters, but prepared statement \"\" requires 1" in messages[-2][1]
+
+# `$name` parameters are described and bound in the order they first appear
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    params = struct.pack("!ii", 4, 3) + struct.pack("!ii", 4, 23)
+    sock.sendall(
+        pg_message(b"P", b"\0SELECT id FROM types WHERE id = $id OR typoid = $oid OR id = $id ORDER BY id\0\0\0")
+        + pg_message(b"D", b"S\0")
+        + pg_message(b"B", b"\0\0" + struct.pack("!hh", 1, 1) + struct.pack("!h", 2) + params + struct.pack("!h", 0))
+        + pg_message(b"E", b"\0" + struct.pack("!i", 0))
+        + pg_message(b"S")
+    )
+    messages = read_messages(sock)
+    assert [kind for kind, _ in messages] == [b"1", b"t", b"T", b"2", b"D", b"D", b"C", b"Z"]
+    assert messages[1][1] == struct.pack("!hii", 2, 23, 23)
+    assert [body for kind, body in messages if kind == b"D"] == [
+        struct.pack("!hi", 1, 1) + b"1",
+        struct.pack("!hi", 1, 1) + b"3",
+    ]
+
+# jsonpath parameters are passed on as their text, which follows a version
+# byte in binary format
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    path = b"$.a[*] ? (@ > 1)"
+    for value, param_format in [(path, 0), (b"\x01" + path, 1)]:
+        sock.sendall(
+            pg_message(b"P", b"\0SELECT $1 || ''\0" + struct.pack("!hi", 1, 4072))
+            + pg_message(b"B", b"\0\0" + struct.pack("!hhhi", 1, param_format, 1, len(value)) + value + struct.pack("!h", 0))
+            + pg_message(b"E", b"\0" + struct.pack("!i", 0))
+            + pg_message(b"S")
+        )
+        messages = read_messages(sock)
+        assert [kind for kind, _ in messages] == [b"1", b"2", b"D", b"C", b"Z"]
+        assert messages[2][1] == struct.pack("!hi", 1, len(path)) + path
+
+    value = b"\x02" + path
+    sock.sendall(
+        pg_message(b"P", b"\0SELECT $1 || ''\0" + struct.pack("!hi", 1, 4072))
+        + pg_message(b"B", b"\0\0" + struct.pack("!hhhi", 1, 1, 1, len(value)) + value + struct.pack("!h", 0))
+        + pg_message(b"E", b"\0" + struct.pack("!i", 0))
+        + pg_message(b"S")
+    )
+    messages = read_messages(sock)
+    assert [kind for kind, _ in messages] == [b"1", b"2", b"E", b"Z"]
+    assert b"unsupported jsonpath version number: 2" in messages[2][1]
+
+# date parameters are days since 2000-01-01 in binary format, ISO dates in
+# text format
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    for value, param_format in [(struct.pack("!i", 8825), 1), (b"2024-02-29", 0), (b"2024-02-30", 0)]:
+        sock.sendall(
+            pg_message(b"P", b"\0SELECT $1 = DATE '2024-02-29'\0" + struct.pack("!hi", 1, 1082))
+            + pg_message(b"B", b"\0\0" + struct.pack("!hhhi", 1, param_format, 1, len(value)) + value + struct.pack("!h", 0))
+            + pg_message(b"E", b"\0" + struct.pack("!i", 0))
+            + pg_message(b"S")
+        )
+        messages = read_messages(sock)
+        if value == b"2024-02-30":
+            assert [kind for kind, _ in messages] == [b"1", b"2", b"E", b"Z"]
+            assert b"C22P02\0" in messages[2][1]
+        else:
+            assert [kind for kind, _ in messages] == [b"1", b"2", b"D", b"C", b"Z"]
+            assert messages[2][1] == struct.pack("!hi", 1, 1) + b"t"
+
+# interval parameters are bound in the arrow layout they are inferred with:
+# year to month intervals drop days and time, day to second ones round time
+# to milliseconds and can't hold months
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    for unit, text, (microseconds, days, months), expected in [
+        (b"YearMonth", b"1 year 2 months", (0, 3, 14), b"t"),
+        (b"DayTime", b"3 days 1.5 seconds", (1500400, 3, 0), b"t"),
+        (b"DayTime", b"1 day", (0, 0, 1), None),
+        (b"DayTime", b"1 day", (2**45, 0, 0), None),
+        (b"MonthDayNano", b"1 month 2 days 0.000001 seconds", (1, 2, 1), b"t"),
+    ]:
+        value = struct.pack("!qii", microseconds, days, months)
+        sock.sendall(
+            pg_message(b"P", b"\0SELECT arrow_cast('" + text + b"', 'Interval(" + unit + b")') = $1\0\0\0")
+            + pg_message(b"D", b"S\0")
+            + pg_message(b"B", b"\0\0" + struct.pack("!hhhi", 1, 1, 1, len(value)) + value + struct.pack("!h", 0))
+            + pg_message(b"E", b"\0" + struct.pack("!i", 0))
+            + pg_message(b"S")
+        )
+        messages = read_messages(sock)
+        assert messages[1] == (b"t", struct.pack("!hi", 1, 1186))
+        if expected is None:
+            assert [kind for kind, _ in messages] == [b"1", b"t", b"T", b"2", b"E", b"Z"]
+            assert b"C22008\0" in messages[4][1]
+        else:
+            assert [kind for kind, _ in messages] == [b"1", b"t", b"T", b"2", b"D", b"C", b"Z"]
+            assert messages[4][1] == struct.pack("!hi", 1, 1) + expected
+
+# like postgres, responses to pipelined extended queries are only written on
+# Flush or Sync, all at once
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    def query(sql):
+        return (
+            pg_message(b"P", b"\0" + sql + b"\0" + struct.pack("!h", 0))
+            + pg_message(b"B", b"\0\0" + struct.pack("!hhh", 0, 0, 0))
+            + pg_message(b"D", b"P\0")
+            + pg_message(b"E", b"\0" + struct.pack("!i", 0))
+        )
+
+    sock.sendall(query(b"SELECT 1"))
+    sock.settimeout(0.5)
+    try:
+        sock.recv(1)
+        assert False, "responses were sent before Flush"
+    except socket.timeout:
+        pass
+    sock.settimeout(None)
+    sock.sendall(pg_message(b"H"))
+    messages = read_messages(sock, until=b"C")
+    assert [kind for kind, _ in messages] == [b"1", b"2", b"T", b"D", b"C"]
+
+    sock.sendall(
+        query(b"SELECT 2")
+        + query(b"SELECT id FROM types ORDER BY id LIMIT 2")
+        + pg_message(b"C", b"S\0")
+        + pg_message(b"S")
+    )
+    messages = read_messages(sock)
+    assert [kind for kind, _ in messages] == [
+        b"1", b"2", b"T", b"D", b"C", b"1", b"2", b"T", b"D", b"D", b"C", b"3", b"Z"
+    ]
+    assert [body for kind, body in messages if kind == b"C"] == [b"SELECT 1\0", b"SELECT 2\0"]
+
+# Flush sends what is queued without a Sync, so that clients can wait for
+# the description of a statement in the middle of a pipeline, or for the
+# error that ends it
+with socket.create_connection(("127.0.0.1", 5432)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+
+    sock.settimeout(5)
+    sock.sendall(
+        pg_message(b"P", b"s\0SELECT id FROM types WHERE id = $1\0" + struct.pack("!hi", 1, 23))
+        + pg_message(b"B", b"\0s\0" + struct.pack("!hhhi", 1, 1, 1, 4) + struct.pack("!i", 1) + struct.pack("!h", 0))
+        + pg_message(b"D", b"Ss\0")
+        + pg_message(b"H")
+    )
+    messages = read_messages(sock, until=b"T")
+    assert [kind for kind, _ in messages] == [b"1", b"2", b"t", b"T"]
+    assert messages[2][1] == struct.pack("!hi", 1, 23)
+
+    sock.sendall(pg_message(b"P", b"\0SELECT FROM WHERE\0\0\0") + pg_message(b"H"))
+    messages = read_messages(sock, until=b"E")
+    assert [kind for kind, _ in messages] == [b"E"]
+    sock.settimeout(None)
+
+    sock.sendall(pg_message(b"S"))
+    assert [kind for kind, _ in read_messages(sock)] == [b"Z"]
+
+# intervals of the day-time unit, with millisecond seconds
+day_time = "arrow_cast('{}', 'Interval(DayTime)')"
+intervals = (
+    f"SELECT {day_time.format('1 day 2 hours 0.25 seconds')}, {day_time.format('-3 days 4 hours')}, "
+    f"{day_time.format('0 seconds')}, make_array({day_time.format('1 day')}, NULL)"
+)
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute(intervals)
+        assert [column.type_code for column in cur.description] == [1186, 1186, 1186, 1187]
+        assert cur.fetchone() == (
+            timedelta(days=1, hours=2, milliseconds=250),
+            timedelta(days=-3, hours=4),
+            timedelta(0),
+            [timedelta(days=1), None],
+        )
+
+with conn.cursor() as cur:
+    cur.execute(intervals)
+    assert [cur.pgresult.get_value(0, i) for i in range(4)] == [
+        b"1 day 02:00:00.25",
+        b"-3 days +04:00:00",
+        b"00:00:00",
+        b'{"1 day",NULL}',
+    ]
+
+# interval parameters in text format, as psycopg writes timedeltas, and in
+# binary format
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute(
+            "SELECT %s = arrow_cast('1 day 2 hours', 'Interval(MonthDayNano)')",
+            [timedelta(days=1, hours=2)],
+        )
+        assert cur.fetchone() == (True,)
+
+# arrow extension types
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT uid, doc, uids FROM types ORDER BY id")
+        assert [column.type_code for column in cur.description] == [2950, 114, 2951]
+        first = UUID("00010203-0405-0607-0809-0a0b0c0d0e0f")
+        last = UUID("ffffffff-ffff-ffff-ffff-ffffffffffff")
+        assert cur.fetchall() == [
+            (first, {"a": [1, 2]}, [first, None]),
+            (None, None, None),
+            (last, None, []),
+            (UUID(int=0), "x", [last]),
+        ]
+
+# bytes hinted as varbit, 8 bits each
+class Bits(bytes):
+    pass
+
+
+class VarbitBinaryDumper(Dumper):
+    format = Format.BINARY
+    oid = 1562
+
+    def dump(self, obj):
+        return struct.pack("!i", len(obj) * 8) + obj
+
+
+with conn.cursor() as cur:
+    cur.execute("SELECT flags FROM types ORDER BY id")
+    assert cur.description[0].type_code == 1562
+    assert cur.fetchall() == [("10110000",), ("1111111100000000",), (None,), ("",)]
+
+with conn.cursor(binary=True) as cur:
+    cur.adapters.register_dumper(Bits, VarbitBinaryDumper)
+    cur.execute("SELECT id, flags FROM types WHERE flags = %b", [Bits(b"\xff\x00")])
+    assert cur.fetchone()[0] == 2
+    assert cur.pgresult.get_value(0, 1) == struct.pack("!i", 16) + b"\xff\x00"
+
+
+class BitsText(str):
+    pass
+
+
+class VarbitTextDumper(Dumper):
+    oid = 1562
+
+    def dump(self, obj):
+        return obj.encode()
+
+
+with conn.cursor() as cur:
+    cur.adapters.register_dumper(BitsText, VarbitTextDumper)
+    cur.execute("SELECT id FROM types WHERE flags = %s", [BitsText("1111111100000000")])
+    assert cur.fetchone()[0] == 2
+
+# catalog types: name, and oid and regtype from integers
+with conn.cursor() as cur:
+    cur.execute("SELECT typname, typoid, typ FROM types ORDER BY id")
+    assert [column.type_code for column in cur.description] == [19, 26, 2206]
+    assert cur.fetchall() == [
+        ("int4", 23, "integer"),
+        ("varchar", 1043, "character varying"),
+        (None, None, None),
+        ("_int4", 4294967295, "integer[]"),
+    ]
+
+with conn.cursor(binary=True) as cur:
+    cur.execute("SELECT typname, typoid, typ FROM types WHERE id = 4")
+    assert [cur.pgresult.get_value(0, i) for i in range(3)] == [
+        b"_int4",
+        struct.pack("!I", 4294967295),
+        struct.pack("!I", 1007),
+    ]
+
+# regclass and regproc oids, written as numbers in text format as there is
+# no catalog to name them from
+with conn.cursor() as cur:
+    cur.execute("SELECT rel, func FROM types ORDER BY id")
+    assert [column.type_code for column in cur.description] == [2205, 24]
+    assert [(cur.pgresult.get_value(i, 0), cur.pgresult.get_value(i, 1)) for i in range(4)] == [
+        (b"1259", b"-"),
+        (b"16384", b"1242"),
+        (None, None),
+        (b"-", b"16500"),
+    ]
+
+with conn.cursor(binary=True) as cur:
+    cur.execute("SELECT rel, func FROM types WHERE id = 2")
+    assert [cur.pgresult.get_value(0, i) for i in range(2)] == [
+        struct.pack("!I", 16384),
+        struct.pack("!I", 1242),
+    ]
+
+
+# oid parameters, as bound by catalog queries, in both formats
+class Oid(int):
+    pass
+
+
+class OidDumper(Dumper):
+    oid = 26
+
+    def dump(self, obj):
+        return str(obj).encode()
+
+
+class OidBinaryDumper(Dumper):
+    format = Format.BINARY
+    oid = 26
+
+    def dump(self, obj):
+        return struct.pack("!I", obj)
+
+
+with conn.cursor() as cur:
+    cur.adapters.register_dumper(Oid, OidDumper)
+    cur.adapters.register_dumper(Oid, OidBinaryDumper)
+    for placeholder in ["%t", "%b"]:
+        cur.execute(f"SELECT id FROM types WHERE typoid = {placeholder}", [Oid(1043)])
+        assert cur.fetchall() == [(2,)]
+
+    try:
+        cur.execute("SELECT %t", [Oid(4294967296)])
+        raise AssertionError("out of range oid accepted")
+    except psycopg.errors.InvalidTextRepresentation:
+        pass
+
+# void, sent empty in both formats
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT done FROM types ORDER BY id")
+        assert [column.type_code for column in cur.description] == [2278]
+        assert [cur.pgresult.get_value(row, 0) for row in range(4)] == [b""] * 4
+
+# statements not returning rows complete with the tag postgres sends
+with conn.cursor() as cur:
+    for statement, params, tag in [
+        ("BEGIN", None, "BEGIN"),
+        ("CREATE TABLE tags (a INT)", None, "CREATE TABLE"),
+        ("INSERT INTO tags VALUES (1), (2)", None, "INSERT 0 2"),
+        ("INSERT INTO tags VALUES (%b)", [Int4(3)], "INSERT 0 1"),
+        ("CREATE VIEW tags_view AS SELECT a FROM tags", None, "CREATE VIEW"),
+        ("SELECT a FROM tags_view", None, "SELECT 3"),
+        ("DROP VIEW tags_view", None, "DROP VIEW"),
+        ("DROP TABLE tags", None, "DROP TABLE"),
+        ("SET datestyle = 'ISO, MDY'", None, "SET"),
+        ("START TRANSACTION", None, "START TRANSACTION"),
+        ("ROLLBACK", None, "ROLLBACK"),
+        ("COMMIT", None, "COMMIT"),
+    ]:
+        cur.execute(statement, params)
+        assert cur.statusmessage == tag, (statement, cur.statusmessage)
+        assert cur.description is None or tag.startswith("SELECT")
+
+# INSERT ... RETURNING returns the rows inserted, and their count in its tag
+with conn.cursor() as cur:
+    cur.execute("CREATE TABLE scores (id INT, name VARCHAR, score DOUBLE)")
+    cur.execute(
+        "INSERT INTO scores (id, name) VALUES (%b, %s), (2, 'b') RETURNING id * 10 AS ten, name, score",
+        [Int4(1), "a"],
+    )
+    assert [column.name for column in cur.description] == ["ten", "name", "score"]
+    assert cur.fetchall() == [(10, "a", None), (20, "b", None)]
+    assert cur.statusmessage == "INSERT 0 2"
+
+    cur.execute("INSERT INTO scores SELECT id + 2, name, 1.5 FROM scores RETURNING *")
+    assert cur.fetchall() == [(3, "a", 1.5), (4, "b", 1.5)]
+    assert cur.statusmessage == "INSERT 0 2"
+
+    cur.execute("SELECT count(*) FROM scores")
+    assert cur.fetchone() == (4,)
+    cur.execute("DROP TABLE scores")
+
+# tables named without a schema are looked up along the search_path
+with psycopg.connect("host=127.0.0.1 port=5432 user=tom password=pencil dbname=localdb") as path_conn:
+    path_conn.autocommit = True
+    with path_conn.cursor() as cur:
+        cur.execute("SHOW search_path")
+        assert cur.fetchone() == ('"$user", public',)
+        cur.execute("CREATE SCHEMA reports")
+        cur.execute("CREATE TABLE reports.totals AS VALUES (1), (2)")
+
+        cur.execute("SET search_path TO reports, public")
+        cur.execute("SELECT count(*) FROM totals")
+        assert cur.fetchone() == (2,)
+        cur.execute("SELECT count(*) FROM delhi WHERE meantemp > %b", [Int8(40)])
+        assert cur.fetchone() == (0,)
+        cur.execute("CREATE TABLE created (a INT)")
+        cur.execute("SELECT count(*) FROM reports.created")
+        assert cur.fetchone() == (0,)
+
+        cur.execute("SET search_path TO public")
+        try:
+            cur.execute("SELECT count(*) FROM totals")
+            raise AssertionError("table found outside of the search_path")
+        except psycopg.errors.InternalError:
+            pass
+
+# roles set by ORMs and poolers are kept for the session
+with psycopg.connect("host=127.0.0.1 port=5432 user=tom password=pencil dbname=localdb") as role_conn:
+    role_conn.autocommit = True
+    with role_conn.cursor() as cur:
+        cur.execute("SET ROLE reporting")
+        assert cur.statusmessage == "SET"
+        cur.execute("SHOW role")
+        assert cur.fetchone() == ("reporting",)
+        cur.execute("SELECT count(*) FROM delhi WHERE meantemp > %s", [40])
+        assert cur.fetchone() == (0,)
+        cur.execute("RESET ROLE")
+        assert cur.statusmessage == "RESET"
+        cur.execute("SHOW role")
+        assert cur.fetchone() == ("none",)
+
+# the same server, over the Unix socket it listens to in target
+with psycopg.connect(f"host={os.path.abspath('target')} port=5432 user=tom password=pencil dbname=localdb") as unix_conn:
+    with unix_conn.cursor() as cur:
+        cur.execute("SELECT count(*) FROM delhi")
+        assert cur.fetchone() == (1462,)
+
+# parameters the server doesn't know are kept for the session, like known ones
+with psycopg.connect("host=127.0.0.1 port=5432 user=tom password=pencil dbname=localdb") as guc_conn:
+    guc_conn.autocommit = True
+    with guc_conn.cursor() as cur:
+        cur.execute("SET application_name = 'reporting'")
+        assert cur.statusmessage == "SET"
+        cur.execute("SHOW application_name")
+        assert cur.fetchone() == ("reporting",)
+        cur.execute("SET lock_timeout = '5s'")
+        assert cur.statusmessage == "SET"
+        cur.execute("SHOW LOCK_TIMEOUT")
+        assert cur.fetchone() == ("5s",)
+        try:
+            cur.execute("SHOW never_set")
+            raise AssertionError("unset parameter shown")
+        except psycopg.errors.UndefinedObject:
+            pass
+
+# timestamptz values are written in the TimeZone of the session, whatever the
+# zone of their column, in arrays too
+with psycopg.connect("host=127.0.0.1 port=5432 user=tom password=pencil dbname=localdb") as tz_conn:
+    tz_conn.autocommit = True
+    with tz_conn.cursor() as cur:
+        instant = "arrow_cast(CAST('2024-01-02T03:04:05' AS TIMESTAMP), 'Timestamp(Microsecond, Some(\"+01:00\"))')"
+        query = f"SELECT {instant}, make_array({instant}, NULL)"
+        for zone, offset in [("UTC", timedelta(0)), ("America/New_York", timedelta(hours=-5))]:
+            cur.execute(f"SET TimeZone = '{zone}'")
+            cur.execute(query)
+            value, values = cur.fetchone()
+            assert value == values[0] == datetime(2024, 1, 2, 2, 4, 5, tzinfo=timezone.utc)
+            assert values[1] is None
+            assert value.utcoffset() == values[0].utcoffset() == offset
+        # offsets are written with minutes only when they have some
+        cur.execute("SET TimeZone = 'Asia/Kolkata'")
+        cur.execute(query)
+        assert cur.pgresult.get_value(0, 0) == b"2024-01-02 07:34:05+05:30"
+        assert cur.fetchone()[0].utcoffset() == timedelta(hours=5, minutes=30)
+        try:
+            cur.execute("SET TimeZone = 'Mars/Olympus'")
+            raise AssertionError("unknown time zone accepted")
+        except psycopg.errors.InvalidParameterValue:
+            pass
+
+# floats are written in text with the precision extra_float_digits asks for
+with psycopg.connect("host=127.0.0.1 port=5432 user=tom password=pencil dbname=localdb") as digits_conn:
+    digits_conn.autocommit = True
+    with digits_conn.cursor() as cur:
+        query = """SELECT CAST(0.1 AS DOUBLE) + CAST(0.2 AS DOUBLE),
+            CAST(1 AS REAL) / CAST(3 AS REAL),
+            CAST(1e20 AS DOUBLE),
+            make_array(CAST(0.1 AS DOUBLE) + CAST(0.2 AS DOUBLE))"""
+        cur.execute("SET extra_float_digits = 3")
+        cur.execute(query)
+        assert cur.fetchone() == (0.30000000000000004, 0.33333334, 1e20, [0.30000000000000004])
+        cur.execute("SET extra_float_digits = 0")
+        cur.execute(query)
+        assert cur.fetchone() == (0.3, 0.333333, 1e20, [0.3])
+        cur.execute("SET extra_float_digits = -14")
+        cur.execute(query)
+        assert cur.fetchone() == (0.3, 0.3, 1e20, [0.3])
+        try:
+            cur.execute("SET extra_float_digits = 4")
+            raise AssertionError("extra_float_digits out of range accepted")
+        except psycopg.errors.InvalidParameterValue:
+            pass
+
+# strings hinted as inet and cidr, for IPv4 and IPv6 addresses
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT ip, net FROM types ORDER BY id")
+        assert [column.type_code for column in cur.description] == [869, 650]
+        assert cur.fetchall() == [
+            (ip_address("192.168.0.1"), ip_network("10.0.0.0/8")),
+            (ip_interface("2001:db8::1:0:0:1/64"), ip_network("2001:db8::/32")),
+            (None, None),
+            (ip_address("::ffff:1.2.3.4"), ip_network("1.2.3.4/32")),
+        ]
+
+        for value, expected in [(ip_address("192.168.0.1"), 1), (ip_network("2001:db8::/32"), 2)]:
+            for placeholder in ["%t", "%b"]:
+                cur.execute(
+                    f"SELECT id FROM types WHERE ip = {placeholder} OR net = {placeholder}",
+                    [value, value],
+                )
+                assert cur.fetchall() == [(expected,)]
+
+# strings hinted as xml, sent as is
+class Xml(str):
+    pass
+
+
+class XmlDumper(StrDumper):
+    oid = 142
+
+
+class XmlBinaryDumper(StrBinaryDumper):
+    oid = 142
+
+
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.adapters.register_dumper(Xml, XmlDumper)
+        cur.adapters.register_dumper(Xml, XmlBinaryDumper)
+        cur.execute("SELECT xdoc FROM types ORDER BY id")
+        assert cur.description[0].type_code == 142
+        assert [cur.pgresult.get_value(i, 0) for i in range(4)] == [
+            b'<a href="x">1 &amp; 2</a>',
+            b'<?xml version="1.0"?><b/>',
+            None,
+            b"text <c>&#233;</c>",
+        ]
+
+        for placeholder in ["%t", "%b"]:
+            cur.execute(f"SELECT id FROM types WHERE xdoc = {placeholder}", [Xml("text <c>&#233;</c>")])
+            assert cur.fetchall() == [(4,)]
+
+# bpchar(10) strings are padded with spaces to 10 characters, like the
+# values of a postgres char(10) column
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT code FROM types ORDER BY id")
+        assert cur.description[0].type_code == 1042
+        assert cur.fetchall() == [("ab        ",), ("héllo     ",), (None,), (" " * 10,)]
+        assert cur.pgresult.get_value(1, 0) == "héllo     ".encode()
+
+# lists of dictionary encoded strings, as text arrays
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT tags FROM types ORDER BY id")
+        assert cur.description[0].type_code == 1009
+        assert cur.fetchall() == [
+            (["red", "blue", "red"],),
+            ([None, 'a "quoted" tag'],),
+            (None,),
+            ([],),
+        ]
+
+# lists of decimals, as numeric arrays keeping the scale
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT amounts FROM types ORDER BY id")
+        assert cur.description[0].type_code == 1231
+        assert cur.fetchall() == [
+            ([Decimal("1.500"), None, Decimal("-12345.678")],),
+            ([],),
+            (None,),
+            ([Decimal("0.000"), Decimal("100000.001")],),
+        ]
+
+with conn.cursor() as cur:
+    cur.execute("SELECT amounts FROM types ORDER BY id")
+    assert cur.pgresult.get_value(0, 0) == b"{1.500,NULL,-12345.678}"
+    assert cur.pgresult.get_value(3, 0) == b"{0.000,100000.001}"
+
+    cur.execute("SELECT CAST(-0.5 AS DECIMAL(10, 2))")
+    assert cur.description[0].type_code == 1700
+    assert cur.pgresult.get_value(0, 0) == b"-0.50"
+
+# binary numerics are decoded by the client from their base-10000 digits
+with conn.cursor(binary=True) as cur:
+    cur.execute(
+        "SELECT CAST('-99999999999999999999999999999999999999' AS DECIMAL(38, 0)),"
+        " CAST('0.99999999999999999999999999999999999999' AS DECIMAL(38, 38)),"
+        " CAST('10000.00' AS DECIMAL(10, 2)), CAST('-0.00123' AS DECIMAL(5, 5)),"
+        " CAST('0' AS DECIMAL(10, 3))"
+    )
+    assert cur.fetchone() == (
+        Decimal("-99999999999999999999999999999999999999"),
+        Decimal("0.99999999999999999999999999999999999999"),
+        Decimal("10000.00"),
+        Decimal("-0.00123"),
+        Decimal("0.000"),
+    )
+
+# nanosecond timestamps are truncated to microseconds
+with conn.cursor() as cur:
+    cur.execute("SELECT created FROM types ORDER BY id")
+    assert cur.fetchall() == [
+        (datetime(2024, 1, 2, 3, 4, 5, 123456),),
+        (datetime(2024, 1, 2, 3, 4, 5, 123456),),
+        (None,),
+        (datetime(1970, 1, 1),),
+    ]
+
+# like postgres, timestamps are written with at most six fractional digits,
+# and without their trailing zeros
+with conn.cursor() as cur:
+    cur.execute(
+        "SELECT CAST('2024-01-02 03:04:05.100000' AS TIMESTAMP), "
+        "CAST('2024-01-02 03:04:05.123456' AS TIMESTAMP), "
+        "CAST('2024-01-02 03:04:05.000000' AS TIMESTAMP), "
+        "CAST('2024-01-02 03:04:05.1234567' AS TIMESTAMP), "
+        "make_array(CAST('2024-01-02 03:04:05.100000' AS TIMESTAMP), CAST('2024-01-02 03:04:05' AS TIMESTAMP)), "
+        "arrow_cast(CAST('2024-01-02 03:04:05.5' AS TIMESTAMP), 'Timestamp(Millisecond, Some(\"UTC\"))')"
+    )
+    assert [cur.pgresult.get_value(0, i) for i in range(6)] == [
+        b"2024-01-02 03:04:05.1",
+        b"2024-01-02 03:04:05.123456",
+        b"2024-01-02 03:04:05",
+        b"2024-01-02 03:04:05.123456",
+        b"{2024-01-02 03:04:05.1,2024-01-02 03:04:05}",
+        b"2024-01-02 03:04:05.5+00",
+    ]
+    assert cur.fetchone()[:3] == (
+        datetime(2024, 1, 2, 3, 4, 5, 100000),
+        datetime(2024, 1, 2, 3, 4, 5, 123456),
+        datetime(2024, 1, 2, 3, 4, 5),
+    )
+
+# special float values are written as postgres does
+with conn.cursor() as cur:
+    cur.execute(
+        "SELECT CAST('NaN' AS DOUBLE) AS n, CAST('inf' AS DOUBLE) AS i, CAST('-inf' AS REAL) AS m, "
+        "[CAST('NaN' AS DOUBLE), CAST('inf' AS DOUBLE), NULL] AS d, [CAST('-inf' AS REAL), 1.5] AS r"
+    )
+    assert [cur.pgresult.get_value(0, i) for i in range(5)] == [
+        b"NaN",
+        b"Infinity",
+        b"-Infinity",
+        b"{NaN,Infinity,NULL}",
+        b"{-Infinity,1.5}",
+    ]
+
+with conn.cursor(binary=True) as cur:
+    cur.execute("SELECT CAST('-inf' AS DOUBLE) AS m, [CAST('inf' AS REAL)] AS r")
+    assert cur.fetchone() == (float("-inf"), [float("inf")])
+
+# boolean arrays in binary format: dimensions, whether there are NULLs and
+# the element type, then the length and lower bound of the dimension, then
+# each element as its length, -1 for NULL, and a byte
+with conn.cursor(binary=True) as cur:
+    cur.execute(
+        "SELECT [true, NULL, false] AS b, arrow_cast([false, NULL], 'LargeList(Boolean)') AS l, "
+        "arrow_cast([true, false], 'FixedSizeList(2, Boolean)') AS f"
+    )
+    assert [column.type_code for column in cur.description] == [1000, 1000, 1000]
+    assert cur.pgresult.get_value(0, 0) == struct.pack("!iiiii", 1, 1, 16, 3, 1) + (
+        struct.pack("!ib", 1, 1) + struct.pack("!i", -1) + struct.pack("!ib", 1, 0)
+    )
+    assert cur.fetchone() == ([True, None, False], [False, None], [True, False])
+
+# times of every arrow unit, and lists of them
+for binary in [False, True]:
+    with conn.cursor(binary=binary) as cur:
+        cur.execute(
+            "SELECT arrow_cast('01:02:03', 'Time32(Second)') AS s, "
+            "[arrow_cast('01:02:03.5', 'Time32(Millisecond)'), NULL] AS ms, "
+            "[arrow_cast('01:02:03.25', 'Time64(Microsecond)')] AS us, "
+            "[CAST('01:02:03.125' AS TIME)] AS ns"
+        )
+        assert [column.type_code for column in cur.description] == [1083, 1183, 1183, 1183]
+        assert cur.fetchone() == (
+            clock_time(1, 2, 3),
+            [clock_time(1, 2, 3, 500000), None],
+            [clock_time(1, 2, 3, 250000)],
+            [clock_time(1, 2, 3, 125000)],
+        )
+
+# COPY TO STDOUT, with the NULL string applying to fields but not to the
+# elements of arrays
+def copy_out(cur, statement):
+    with cur.copy(statement) as copy:
+        return b"".join(bytes(data) for data in copy)
+
+
+with conn.cursor() as cur:
+    assert copy_out(cur, "COPY (SELECT id, price, tokens FROM types ORDER BY id) TO STDOUT") == (
+        b'1\t$1,234.56\t{"\\\\\\\\xcafe",NULL}\n'
+        b"2\t-$1,234.50\t{}\n"
+        b"3\t\\N\t\\N\n"
+        b'4\t$0.01\t{"\\\\\\\\x00ff"}\n'
+    )
+    assert cur.rowcount == 4
+
+    assert copy_out(cur, "COPY (SELECT id, price FROM types ORDER BY id) TO STDOUT WITH (NULL 'NA')") == (
+        b"1\t$1,234.56\n2\t-$1,234.50\n3\tNA\n4\t$0.01\n"
+    )
+
+    assert copy_out(
+        cur,
+        "COPY (SELECT id, tokens, 'NA' AS s, '' AS e FROM types WHERE id > 2 ORDER BY id) "
+        "TO STDOUT WITH (FORMAT csv, NULL 'NA', HEADER)",
+    ) == b'id,tokens,s,e\n3,NA,"NA",\n4,"{""\\\\x00ff""}","NA",\n'
+
+    assert copy_out(cur, "COPY (SELECT ['a', NULL] AS a, NULL AS n) TO STDOUT CSV") == b'"{a,NULL}",\n'
+
+    # binary COPY data, between the PGCOPY header and the -1 trailer, read
+    # back with psycopg's binary COPY parser
+    binary_copy = "COPY (SELECT id, tokens, CAST(id AS DOUBLE) / 2 AS half FROM types ORDER BY id) TO STDOUT (FORMAT binary)"
+    data = copy_out(cur, binary_copy)
+    assert data.startswith(b"PGCOPY\n\xff\r\n\x00" + bytes(8))
+    assert data.endswith(b"\xff\xff")
+    with cur.copy(binary_copy) as copy:
+        copy.set_types(["int4", "bytea[]", "float8"])
+        assert list(copy.rows()) == [
+            (1, [b"\xca\xfe", None], 0.5),
+            (2, [], 1.0),
+            (3, None, 1.5),
+            (4, [b"\x00\xff"], 2.0),
+        ]
+    assert cur.rowcount == 4
+
+# CSV COPY data holds the same text as text results, quoted where needed
+with conn.cursor() as cur:
+    query = (
+        "SELECT id, price, grade, token, tokens, created, uid, doc, uids, flags, typname, typoid, typ, "
+        "ip, net, xdoc, done, tags, amounts, code, rel, func FROM types ORDER BY id"
+    )
+    cur.execute(query)
+    results = [
+        [cur.pgresult.get_value(row, col) for col in range(cur.pgresult.nfields)]
+        for row in range(cur.pgresult.ntuples)
+    ]
+    data = copy_out(cur, f"COPY ({query}) TO STDOUT CSV")
+    # CSV has no NULL string, NULL and empty values both read as empty
+    assert list(csv.reader(io.StringIO(data.decode()))) == [
+        [(value or b"").decode() for value in row] for row in results
+    ]
+
+# with result_format set to arrow, results are sent as an Arrow IPC stream
+# in the data of a binary COPY
+with psycopg.connect("host=127.0.0.1 port=5432 user=tom password=pencil dbname=localdb") as arrow_conn:
+    arrow_conn.autocommit = True
+    with arrow_conn.cursor() as cur:
+        cur.execute("SET result_format = 'arrow'")
+        table = pyarrow.ipc.open_stream(copy_out(cur, "SELECT id, price FROM types ORDER BY id")).read_all()
+        assert cur.rowcount == 4
+        assert table.column_names == ["id", "price"]
+        assert table.column("id").to_pylist() == [1, 2, 3, 4]
+        assert table.column("price").to_pylist() == [Decimal("1234.56"), Decimal("-1234.50"), None, Decimal("0.01")]
+        assert table.schema.field("price").metadata == {b"pg_type": b"money"}
+
+        try:
+            cur.execute("SET result_format = 'csv'")
+            raise AssertionError("unknown result format accepted")
+        except psycopg.errors.InvalidParameterValue:
+            pass
+
+options_conn = psycopg.connect("host=127.0.0.1 port=5433 user=tom password=pencil dbname=localdb")
+options_conn.autocommit = True
+
+with options_conn.cursor(binary=True) as cur:
+    cur.execute("SELECT count(*), %s::bigint FROM delhi", [7])
+    results = cur.fetchone()
+    assert results == (1462, 7)
+    assert cur.pgresult.fformat(0) == 0
+
+with options_conn.cursor() as cur:
+    cur.execute("SELECT * FROM delhi a JOIN delhi b ON a.date = b.date LIMIT 1")
+    names = [column.name for column in cur.description]
+    assert names == [
+        "date",
+        "meantemp",
+        "humidity",
+        "winspeed",
+        "meanpressure",
+        "date_1",
+        "meantemp_1",
+        "humidity_1",
+        "winspeed_1",
+        "meanpressure_1",
+    ]
+
+# unless timestamps are strict
+with options_conn.cursor() as cur:
+    cur.execute("SELECT created FROM types WHERE id = 2")
+    assert cur.fetchone() == (datetime(2024, 1, 2, 3, 4, 5, 123456),)
+
+    for query in ["SELECT created FROM types", "SELECT [created] FROM types WHERE id = 1"]:
+        try:
+            cur.execute(query)
+            raise AssertionError("nanoseconds truncated in strict mode")
+        except psycopg.errors.DatetimeFieldOverflow:
+            pass
+
+# and limits the size of values to 1024 bytes
+with options_conn.cursor() as cur:
+    cur.execute("SELECT CAST(repeat('x', 1024) AS BYTEA) AS big")
+    assert cur.fetchone() == (b"x" * 1024,)
+
+    try:
+        cur.execute("SELECT CAST(repeat('x', 1025) AS BYTEA) AS big")
+        raise AssertionError("oversized value sent")
+    except psycopg.errors.ProgramLimitExceeded as e:
+        assert str(e) == (
+            'value of column "big" is 1025 bytes, more than the maximum field size of 1024 bytes'
+        )
+
+# and converts parameters sent as text to the type inferred from the query,
+# which the default server leaves as strings
+query = "SELECT meantemp + %s AS t FROM delhi ORDER BY date LIMIT 1"
+with conn.cursor() as cur:
+    cur.adapters.register_dumper(str, StrDumper)
+    try:
+        cur.execute(query, ["2.5"])
+        raise AssertionError("text parameter used as a number")
+    except psycopg.errors.InternalError:
+        pass
+
+with options_conn.cursor() as cur:
+    cur.adapters.register_dumper(str, StrDumper)
+    cur.execute(query, ["2.5"])
+    assert cur.fetchone() == (12.5,)
+
+with options_conn.cursor() as cur:
+    cur.adapters.register_dumper(str, StrDumper)
+    try:
+        cur.execute("SELECT meantemp + %s AS t FROM delhi", ["abc"])
+        raise AssertionError("invalid number accepted")
+    except psycopg.errors.InvalidTextRepresentation:
+        pass
+
+# and bind at most 4 parameters
+with options_conn.cursor() as cur:
+    cur.execute("SELECT count(*) FROM delhi WHERE meantemp IN (%b, %b, %b, %b)", [Int8(40)] * 4)
+    assert cur.fetchone() == (0,)
+
+    try:
+        cur.execute("SELECT count(*) FROM delhi WHERE meantemp IN (%b, %b, %b, %b, %b)", [Int8(40)] * 5)
+        raise AssertionError("too many parameters bound")
+    except psycopg.errors.ProgramLimitExceeded as e:
+        assert str(e) == "bind message has 5 parameters, more than the maximum of 4"
+
+# and rejects xml parameters that aren't well-formed
+with options_conn.cursor() as cur:
+    cur.adapters.register_dumper(Xml, XmlDumper)
+    cur.execute("SELECT id FROM types WHERE xdoc = %s", [Xml('<a href="x">1 &amp; 2</a>')])
+    assert cur.fetchall() == [(1,)]
+
+    for value in ["<a><b></a>", "<a href=x/>", "&nbsp;"]:
+        try:
+            cur.execute("SELECT id FROM types WHERE xdoc = %s", [Xml(value)])
+            raise AssertionError(f"malformed xml {value} accepted")
+        except psycopg.errors.InvalidXmlContent:
+            pass
+
+# and sends maps of strings as hstore, with the oid it is given
+for binary in [False, True]:
+    with options_conn.cursor(binary=binary) as cur:
+        cur.execute("SELECT attrs FROM types ORDER BY id")
+        assert cur.description[0].type_code == 16400
+        values = [cur.pgresult.get_value(i, 0) for i in range(4)]
+        if binary:
+            assert values == [
+                struct.pack("!ii1si1si1si", 2, 1, b"a", 1, b"1", 1, b"b", -1),
+                struct.pack("!i", 0),
+                None,
+                struct.pack("!ii1si1si3si10s", 2, 1, b"a", 1, b"2", 3, b'q"x', 10, b"back\\slash"),
+            ]
+        else:
+            # duplicate keys keep their first value, like in postgres
+            assert values == [b'"a"=>"1", "b"=>NULL', b"", None, b'"a"=>"2", "q\\"x"=>"back\\\\slash"']
+
+# and logs query stats
+with options_conn.cursor() as cur:
+    cur.execute("SELECT date, meantemp FROM delhi ORDER BY date LIMIT 3")
+    assert len(cur.fetchall()) == 3
+with open("target/query-stats.log") as log:
+    query = "SELECT date, meantemp FROM delhi ORDER BY date LIMIT 3"
+    stats = [line for line in log if line.endswith(f"query={query}\n")]
+    assert len(stats) == 1
+    # 7 bytes of DataRow header, then each value in text with its length:
+    # 2013-01-01 and 10, 2013-01-02 and 7.4, 2013-01-03 and 7.166666666666667
+    assert stats[0].startswith("rows=3 bytes=97 duration=")
+
+# and counts the columns of unsupported types, printed by test.sh once the
+# server shuts down
+with options_conn.cursor() as cur:
+    for _ in range(2):
+        try:
+            cur.execute("SELECT arrow_cast(1, 'Duration(Second)') AS elapsed")
+            raise AssertionError("duration column returned")
+        except psycopg.errors.InternalError as e:
+            assert str(e) == "Unsupported Datatype Duration(Second)"
+
+# the options server follows Describe with the number of rows DataFusion
+# estimates, exact here as the rows are given in the query
+with socket.create_connection(("127.0.0.1", 5433)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+    sock.sendall(
+        pg_message(b"P", b"\0SELECT * FROM (VALUES (1), (2), (3)) AS t(a)\0" + struct.pack("!h", 0))
+        + pg_message(b"D", b"S\0")
+        + pg_message(b"B", b"\0\0" + struct.pack("!hhh", 0, 0, 0))
+        + pg_message(b"E", b"\0" + struct.pack("!i", 0))
+        + pg_message(b"S")
+    )
+    messages = read_messages(sock)
+    assert [kind for kind, _ in messages] == [b"1", b"t", b"T", b"N", b"2", b"D", b"D", b"D", b"C", b"Z"]
+    notice = dict((field[:1], field[1:]) for field in messages[3][1].split(b"\0") if field)
+    assert notice[b"S"] == b"NOTICE"
+    assert notice[b"M"] == b"estimated rows: 3"
+    assert notice[b"D"] == b"The estimate is exact."
+
+# a query still computing its first row stops when the client goes away,
+# the stats being logged once its stream is dropped
+query = "SELECT sum(value) FROM generate_series(1, 100000000000)"
+with socket.create_connection(("127.0.0.1", 5433)) as sock:
+    startup = struct.pack("!i", 196608) + b"user\0tom\0database\0localdb\0\0"
+    sock.sendall(struct.pack("!i", len(startup) + 4) + startup)
+    read_messages(sock)
+    sock.sendall(pg_message(b"Q", query.encode() + b"\0"))
+    time.sleep(1)
+for _ in range(50):
+    with open("target/query-stats.log") as log:
+        stats = [line for line in log if line.endswith(f"query={query}\n")]
+    if stats:
+        break
+    time.sleep(0.1)
+assert len(stats) == 1 and stats[0].startswith("rows=0 bytes=0 duration=")
+
+# a server denying binary columns, and lists of them, but not binary values
+# turned into other types
+policy_conn = psycopg.connect("host=127.0.0.1 port=5434 user=tom password=pencil dbname=localdb")
+policy_conn.autocommit = True
+with policy_conn.cursor() as cur:
+    cur.execute("SELECT id, encode(flags, 'hex') AS flags FROM types WHERE id = 1")
+    assert cur.fetchone() == (1, "b0")
+
+    for query in ["SELECT id, flags AS payload FROM types", "SELECT [flags] AS payload FROM types"]:
+        try:
+            cur.execute(query)
+            raise AssertionError(f"binary column returned by {query}")
+        except psycopg.errors.FeatureNotSupported as e:
+            assert str(e) == 'column "payload" has type Binary, which is not allowed'
+
+    try:
+        copy_out(cur, "COPY (SELECT flags AS payload FROM types) TO STDOUT")
+        raise AssertionError("binary column copied")
+    except psycopg.errors.FeatureNotSupported:
+        pass
+
+# the policy server warns about values sent despite a loss, once per column
+# of a result, without failing the query
+notices = []
+policy_conn.add_notice_handler(lambda diagnostic: notices.append((diagnostic.severity, diagnostic.message_primary)))
+with policy_conn.cursor() as cur:
+    cur.execute(
+        "SELECT arrow_cast(v, 'UInt64') AS big FROM "
+        "(VALUES ('1'), ('18446744073709551615'), ('18446744073709551614')) AS t(v)"
+    )
+    assert cur.fetchall() == [(1,), (-1,), (-2,)]
+    assert notices == [
+        ("WARNING", 'value 18446744073709551615 of column "big" is out of range for its type, sent as -1'),
+    ]
+    # 32 bits ones too, unless they are hinted as oids
+    notices.clear()
+    cur.execute("SELECT arrow_cast(v, 'UInt32') AS mid FROM (VALUES ('3000000000')) AS t(v)")
+    assert cur.fetchall() == [(-1294967296,)]
+    assert notices == [
+        ("WARNING", 'value 3000000000 of column "mid" is out of range for its type, sent as -1294967296'),
+    ]
+
+# the policy server takes 2 connections at most, and refuses others like
+# postgres does until one is closed
+policy_dsn = "host=127.0.0.1 port=5434 user=tom password=pencil dbname=localdb"
+second_conn = psycopg.connect(policy_dsn)
+try:
+    psycopg.connect(policy_dsn)
+    raise AssertionError("connection over the limit accepted")
+except psycopg.OperationalError as e:
+    assert "sorry, too many clients already" in str(e)
+# clients over the limit that don't start up are closed after a second
+with socket.create_connection(("127.0.0.1", 5434)) as sock:
+    sock.settimeout(5)
+    try:
+        assert sock.recv(1) == b""
+    except ConnectionResetError:
+        pass
+second_conn.close()
+for _ in range(50):
+    try:
+        with psycopg.connect(policy_dsn) as third_conn:
+            assert third_conn.execute("SELECT 1").fetchone() == (1,)
+        break
+    except psycopg.OperationalError:
+        time.sleep(0.1)
+else:
+    raise AssertionError("closed connection not freed")
+
+# the options server closes connections idle for 2 seconds
+idle_conn = psycopg.connect("host=127.0.0.1 port=5433 user=tom password=pencil dbname=localdb")
+idle_conn.execute("SELECT 1")
+time.sleep(3)
+try:
+    idle_conn.execute("SELECT 1")
+    raise AssertionError("idle connection not closed")
+except psycopg.OperationalError as e:
+    assert "idle-session timeout" in str(e)
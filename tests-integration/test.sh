@@ -3,8 +3,16 @@
 set -e
 
 cargo build
-./target/debug/datafusion-postgres-cli --csv delhi:tests-integration/delhiclimate.csv &
+cargo run --example write_types_fixture -- target/types.arrow
+./target/debug/datafusion-postgres-cli --csv delhi:tests-integration/delhiclimate.csv --arrow types:target/types.arrow --unix-socket-dir target &
 PID=$!
+./target/debug/datafusion-postgres-cli --csv delhi:tests-integration/delhiclimate.csv --arrow types:target/types.arrow -p 5433 --text-numbers --unique-column-names --strict-timestamps --inferred-parameter-types --idle-timeout 2 --max-field-size 1024 --max-parameters 4 --validate-xml --hstore-oid 16400 --tcp-keepalive 60 --describe-estimates --log-query-stats 2> target/query-stats.log &
+OPTIONS_PID=$!
+./target/debug/datafusion-postgres-cli --csv delhi:tests-integration/delhiclimate.csv --arrow types:target/types.arrow -p 5434 --deny-type Binary --max-connections 2 --startup-timeout 1 --encode-warnings &
+POLICY_PID=$!
 sleep 3
 python tests-integration/test.py
-kill -9 $PID 2>/dev/null
+kill -9 $PID $POLICY_PID 2>/dev/null
+kill $OPTIONS_PID
+wait $OPTIONS_PID
+grep -qx "unsupported type Duration(Second): 2" target/query-stats.log